@@ -1,17 +1,33 @@
-use crate::core::clip::{preprocess::preprocess_clip_image, ClipEngine, ClipEngineOptions};
-use crate::core::events::STREAM_EVENT;
-use crate::core::model::{AnalysisEngine, CategoryKey, Scores, Settings, StreamChunk};
-use crate::core::ollama::{classify_image_streaming_with_options, classify_image_with_options};
-use anyhow::Result;
+use crate::core::clip::preprocess::{preprocess_clip_image, PreprocessOutput};
+use crate::core::clip::prompts::default_tag_prompts;
+use crate::core::clip::{ClipEngine, ClipEngineOptions, SubcategoryPrompts};
+use crate::core::debug_capture::capture_path;
+use crate::core::events::{STREAM_EVENT, WARMUP_EVENT};
+use crate::core::model::{
+    AnalysisEngine, CategoryKey, ClipEngineStatus, ClipPromptTestResult, ClipWarmupEvent,
+    ClipWarmupPhase, OllamaBackend, Scores, Settings, StreamChunk,
+};
+use crate::core::ollama::{
+    classify_image_streaming_with_options, classify_image_with_options, normalize_tags,
+    score_repairs_log, ChatOptions,
+};
+use crate::core::openai;
+use anyhow::{anyhow, Result};
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::future::Future;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
 use tokio_util::sync::CancellationToken;
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ClassificationOutput {
     pub model: String,
     pub scores: Scores,
@@ -22,14 +38,22 @@ pub struct ClassificationOutput {
     pub analysis_log: String,
     pub is_valuable: Option<bool>,
     pub valuable_score: Option<f32>,
+    pub subcategory: Option<String>,
+    pub value_bucket: Option<String>,
 }
 
 pub struct ClassifyInput<'a> {
     pub app: &'a AppHandle,
     pub job_id: &'a str,
+    // Names the raw response capture; the row id when there is one.
+    pub photo_id: &'a str,
     pub file_name: &'a str,
     pub path: &'a Path,
     pub base64_jpeg: Option<&'a str>,
+    // CLIP input already decoded by the pipeline's decode stage; None decodes `path` here.
+    pub preprocessed: Option<PreprocessOutput>,
+    // Worker slot within the job; tags stream chunks so parallel streams stay apart.
+    pub slot: usize,
     pub cancel: &'a CancellationToken,
 }
 
@@ -40,10 +64,49 @@ pub trait Classifier: Send + Sync {
     ) -> Pin<Box<dyn Future<Output = Result<ClassificationOutput>> + Send + 'a>>;
 }
 
+// Talks to `ollama_base_url` with either the Ollama or the OpenAI-compatible protocol, depending
+// on `ollama_backend`.
 pub struct OllamaClassifier {
     pub settings: Settings,
 }
 
+impl OllamaClassifier {
+    fn normalize_tags(&self, tags: Vec<String>) -> Vec<String> {
+        normalize_tags(
+            tags,
+            self.settings.ollama_max_tags as usize,
+            self.settings.ollama_max_tag_chars as usize,
+            self.settings.output_language.fallback_tag(),
+        )
+    }
+}
+
+// Shared by every Ollama request in the app and replaced when `ollama_max_parallel` changes;
+// requests holding a permit from the old semaphore still finish.
+static OLLAMA_PERMITS: Lazy<Mutex<(u32, Arc<Semaphore>)>> =
+    Lazy::new(|| Mutex::new((0, Arc::new(Semaphore::new(0)))));
+
+async fn acquire_ollama_permit(
+    max_parallel: u32,
+    cancel: &CancellationToken,
+) -> Result<OwnedSemaphorePermit> {
+    let max_parallel = max_parallel.max(1);
+    let semaphore = {
+        let mut guard = OLLAMA_PERMITS.lock();
+        if guard.0 != max_parallel {
+            *guard = (
+                max_parallel,
+                Arc::new(Semaphore::new(max_parallel as usize)),
+            );
+        }
+        guard.1.clone()
+    };
+    tokio::select! {
+        _ = cancel.cancelled() => Err(anyhow::anyhow!("canceled")),
+        permit = semaphore.acquire_owned() => Ok(permit?),
+    }
+}
+
 impl Classifier for OllamaClassifier {
     fn classify<'a>(
         &'a self,
@@ -53,6 +116,13 @@ impl Classifier for OllamaClassifier {
             let b64 = input
                 .base64_jpeg
                 .ok_or_else(|| anyhow::anyhow!("missing base64 jpeg"))?;
+            // Held until the response is parsed; waiting here doesn't count toward the timeout.
+            let _permit =
+                acquire_ollama_permit(self.settings.ollama_max_parallel, input.cancel).await?;
+            let mut chat = ChatOptions::from_settings(&self.settings);
+            if self.settings.debug_capture_raw_responses {
+                chat.raw_capture = capture_path(input.app, input.job_id, input.photo_id).ok();
+            }
 
             if self.settings.ollama_stream {
                 let app = input.app.clone();
@@ -65,39 +135,60 @@ impl Classifier for OllamaClassifier {
                     StreamChunk {
                         job_id: job_id.clone(),
                         file_name: file_name.clone(),
+                        slot: input.slot,
                         delta: String::new(),
                         done: false,
                         reset: true,
                     },
                 );
 
-            let (model_out, analysis_log) = classify_image_streaming_with_options(
-                    &self.settings.ollama_base_url,
-                    &self.settings.ollama_model,
-                    self.settings.ollama_think,
-                    b64,
-                    input.cancel,
-                    |delta| {
-                        stream_text.push_str(delta);
-                        let _ = app.emit(
-                            STREAM_EVENT,
-                            StreamChunk {
-                                job_id: job_id.clone(),
-                                file_name: file_name.clone(),
-                                delta: delta.to_string(),
-                                done: false,
-                                reset: false,
-                            },
-                        );
-                    },
-                )
-                .await?;
+                let on_delta = |delta: &str| {
+                    stream_text.push_str(delta);
+                    let _ = app.emit(
+                        STREAM_EVENT,
+                        StreamChunk {
+                            job_id: job_id.clone(),
+                            file_name: file_name.clone(),
+                            slot: input.slot,
+                            delta: delta.to_string(),
+                            done: false,
+                            reset: false,
+                        },
+                    );
+                };
+                let (model_out, analysis_log) = match self.settings.ollama_backend {
+                    OllamaBackend::Ollama => {
+                        classify_image_streaming_with_options(
+                            &self.settings.ollama_base_url,
+                            &self.settings.ollama_model,
+                            self.settings.ollama_think,
+                            &chat,
+                            b64,
+                            input.cancel,
+                            on_delta,
+                        )
+                        .await?
+                    }
+                    OllamaBackend::OpenaiCompatible => {
+                        openai::classify_image_streaming(
+                            &self.settings.ollama_base_url,
+                            &self.settings.ollama_model,
+                            &chat,
+                            b64,
+                            input.cancel,
+                            on_delta,
+                        )
+                        .await?
+                    }
+                };
+                let analysis_log = analysis_log + &score_repairs_log(&model_out);
 
                 let _ = app.emit(
                     STREAM_EVENT,
                     StreamChunk {
                         job_id,
                         file_name,
+                        slot: input.slot,
                         delta: String::new(),
                         done: true,
                         reset: false,
@@ -108,34 +199,54 @@ impl Classifier for OllamaClassifier {
                     model: self.settings.ollama_model.clone(),
                     scores: model_out.scores,
                     category: model_out.category,
-                    tags: model_out.tags_ko,
-                    caption: Some(model_out.caption_ko),
-                    text_in_image: Some(model_out.text_in_image_ko),
+                    tags: self.normalize_tags(model_out.tags),
+                    caption: Some(model_out.caption),
+                    text_in_image: Some(model_out.text_in_image),
                     analysis_log,
                     is_valuable: None,
                     valuable_score: None,
+                    subcategory: None,
+                    value_bucket: None,
                 });
             }
 
-            let (model_out, analysis_log) = classify_image_with_options(
-                &self.settings.ollama_base_url,
-                &self.settings.ollama_model,
-                self.settings.ollama_think,
-                b64,
-                input.cancel,
-            )
-            .await?;
+            let (model_out, analysis_log) = match self.settings.ollama_backend {
+                OllamaBackend::Ollama => {
+                    classify_image_with_options(
+                        &self.settings.ollama_base_url,
+                        &self.settings.ollama_model,
+                        self.settings.ollama_think,
+                        &chat,
+                        b64,
+                        input.cancel,
+                    )
+                    .await?
+                }
+                OllamaBackend::OpenaiCompatible => {
+                    openai::classify_image(
+                        &self.settings.ollama_base_url,
+                        &self.settings.ollama_model,
+                        &chat,
+                        b64,
+                        input.cancel,
+                    )
+                    .await?
+                }
+            };
+            let analysis_log = analysis_log + &score_repairs_log(&model_out);
 
             Ok(ClassificationOutput {
                 model: self.settings.ollama_model.clone(),
                 scores: model_out.scores,
                 category: model_out.category,
-                tags: model_out.tags_ko,
-                caption: Some(model_out.caption_ko),
-                text_in_image: Some(model_out.text_in_image_ko),
+                tags: self.normalize_tags(model_out.tags),
+                caption: Some(model_out.caption),
+                text_in_image: Some(model_out.text_in_image),
                 analysis_log,
                 is_valuable: None,
                 valuable_score: None,
+                subcategory: None,
+                value_bucket: None,
             })
         })
     }
@@ -143,34 +254,255 @@ impl Classifier for OllamaClassifier {
 
 pub struct ClipClassifier {
     pub opts: ClipEngineOptions,
+    pub max_loaded_models: usize,
+    pub tag_top_n: usize,
+    pub tag_threshold: f32,
+    pub screenshot_heuristic: bool,
+    pub alpha_background: [u8; 3],
 }
 
-static CLIP_ENGINE: Lazy<Mutex<Option<(String, Arc<ClipEngine>)>>> = Lazy::new(|| Mutex::new(None));
+// Logit added to screenshot_document when the preprocess cues say the image looks like a UI
+// capture; on the scale of the manual category bias.
+const SCREENSHOT_HEURISTIC_BOOST: f32 = 0.03;
 
-fn get_clip_engine(app: &AppHandle, opts: &ClipEngineOptions) -> Result<Arc<ClipEngine>> {
-    let key = format!(
-        "dir={:?};file={};pool={};intra={};value={};auto={};coreml={};cuda={};rocm={};directml={};openvino={}",
+struct CachedEngine<E = ClipEngine> {
+    model: String,
+    key: String,
+    engine: Arc<E>,
+}
+
+// Loaded engines, least recently used first. Entries are keyed by model identity (dir + file) so
+// switching between bundled models keeps the others warm; changing any other option replaces that
+// model's entry rather than piling up stale engines.
+struct EngineCache<E = ClipEngine> {
+    engines: Vec<CachedEngine<E>>,
+    // Key and error of the last failed load. That key fails fast until the cache is cleared, so a
+    // broken model isn't reloaded for every photo.
+    failed: Option<(String, String)>,
+}
+
+impl<E> EngineCache<E> {
+    const fn new() -> Self {
+        Self {
+            engines: Vec::new(),
+            failed: None,
+        }
+    }
+
+    // Moves a hit to the most recently used end; `None` when `key` needs loading.
+    fn lookup(&mut self, key: &str) -> Option<Result<Arc<E>>> {
+        if let Some((failed, error)) = &self.failed {
+            if failed == key {
+                return Some(Err(anyhow!("{error}")));
+            }
+        }
+        let pos = self.engines.iter().position(|c| c.key == key)?;
+        let entry = self.engines.remove(pos);
+        let engine = Arc::clone(&entry.engine);
+        self.engines.push(entry);
+        Some(Ok(engine))
+    }
+
+    fn clear(&mut self) -> bool {
+        let loaded = !self.engines.is_empty();
+        self.engines.clear();
+        self.failed = None;
+        loaded
+    }
+}
+
+static CLIP_ENGINES: Lazy<Mutex<EngineCache>> = Lazy::new(|| Mutex::new(EngineCache::new()));
+
+// Held while a model loads so loads run one at a time; `CLIP_ENGINES` stays free meanwhile.
+static CLIP_ENGINE_LOAD: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+// Latest warmup progress; `None` until a warmup has been requested.
+static CLIP_WARMUP: Lazy<watch::Sender<Option<ClipWarmupEvent>>> =
+    Lazy::new(|| watch::Sender::new(None));
+
+// Loads (or reuses) the engine and publishes warmup events.
+fn get_clip_engine_reporting(
+    app: &AppHandle,
+    opts: &ClipEngineOptions,
+    max_loaded_models: usize,
+    always_report: bool,
+) -> Result<Arc<ClipEngine>> {
+    report_warmup(
+        always_report,
+        |event| {
+            CLIP_WARMUP.send_replace(Some(event.clone()));
+            let _ = app.emit(WARMUP_EVENT, event);
+        },
+        |on_phase| get_clip_engine_with_progress(app, opts, max_loaded_models, on_phase),
+    )
+}
+
+// Runs `load`, publishing each phase it reports followed by `ready` or `failed`. Cache hits report
+// no phase and stay silent unless `always_report` is set, so per-photo lookups don't spam `ready`.
+fn report_warmup<T>(
+    always_report: bool,
+    mut publish: impl FnMut(ClipWarmupEvent),
+    load: impl FnOnce(&mut dyn FnMut(ClipWarmupPhase, Option<(usize, usize)>)) -> Result<T>,
+) -> Result<T> {
+    let started = std::time::Instant::now();
+    let mut emit = |phase: ClipWarmupPhase, pool: Option<(usize, usize)>, error: Option<String>| {
+        publish(ClipWarmupEvent {
+            phase,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            error,
+            pool_index: pool.map(|(i, _)| i),
+            pool_size: pool.map(|(_, n)| n),
+        })
+    };
+    let mut loaded = false;
+    let result = load(&mut |phase, pool| {
+        loaded = true;
+        emit(phase, pool, None);
+    });
+    if loaded || always_report {
+        match &result {
+            Ok(_) => emit(ClipWarmupPhase::Ready, None, None),
+            Err(e) => emit(ClipWarmupPhase::Failed, None, Some(e.to_string())),
+        }
+    }
+    result
+}
+
+fn prompts_hash<T: Hash + ?Sized>(prompts: &T) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    prompts.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn get_clip_engine_with_progress(
+    app: &AppHandle,
+    opts: &ClipEngineOptions,
+    max_loaded_models: usize,
+    on_phase: &mut dyn FnMut(ClipWarmupPhase, Option<(usize, usize)>),
+) -> Result<Arc<ClipEngine>> {
+    let model = format!(
+        "dir={:?};file={}",
         opts.model_dir.as_deref().unwrap_or("<auto>"),
-        opts.model_file,
+        opts.model_file
+    );
+    let key = format!(
+        "{};pool={};intra={};inter={};parallel={};arena={};value={};auto={};coreml={};tensorrt={};cuda={};rocm={};directml={};openvino={};gpu={};tags={:016x};subs={:016x};value={:016x}",
+        model,
         opts.session_pool_size,
         opts.intra_threads,
+        opts.inter_threads,
+        opts.parallel_execution,
+        opts.cpu_arena,
         opts.enable_value,
         opts.ep_auto,
         opts.ep_coreml,
+        opts.ep_tensorrt,
         opts.ep_cuda,
         opts.ep_rocm,
         opts.ep_directml,
-        opts.ep_openvino
+        opts.ep_openvino,
+        opts.gpu_device_id,
+        prompts_hash(&opts.tag_prompts),
+        prompts_hash(&opts.subcategories),
+        prompts_hash(&opts.value_buckets)
     );
-    let mut guard = CLIP_ENGINE.lock();
-    if let Some((k, eng)) = guard.as_ref() {
-        if k == &key {
-            return Ok(Arc::clone(eng));
+    let eng = cached_or_load(
+        &CLIP_ENGINES,
+        &CLIP_ENGINE_LOAD,
+        model,
+        key,
+        max_loaded_models,
+        || {
+            Ok(Arc::new(ClipEngine::new_with_progress(
+                app,
+                opts.clone(),
+                on_phase,
+            )?))
+        },
+    )?;
+    eng.set_category_bias(&opts.category_bias);
+    Ok(eng)
+}
+
+// Moves the entry for `key` to the most recently used end, loading it when missing, then drops
+// the least recently used entries past `max_loaded_models`. The load runs under `loading` only,
+// so classifies on other cached engines, the status view and unload don't wait for it. A stale
+// engine for the same model is evicted only once its replacement has loaded.
+fn cached_or_load<E>(
+    cache: &Mutex<EngineCache<E>>,
+    loading: &Mutex<()>,
+    model: String,
+    key: String,
+    max_loaded_models: usize,
+    load: impl FnOnce() -> Result<Arc<E>>,
+) -> Result<Arc<E>> {
+    if let Some(hit) = cache.lock().lookup(&key) {
+        return hit;
+    }
+    let _loading = loading.lock();
+    // Another caller may have loaded (or failed) this key while we waited.
+    if let Some(hit) = cache.lock().lookup(&key) {
+        return hit;
+    }
+    let loaded = load();
+    let mut cache = cache.lock();
+    let engine = match loaded {
+        Ok(engine) => engine,
+        Err(e) => {
+            cache.failed = Some((key, format!("{e:#}")));
+            return Err(e);
         }
+    };
+    cache.engines.retain(|c| c.model != model);
+    cache.engines.push(CachedEngine {
+        engine: Arc::clone(&engine),
+        model,
+        key,
+    });
+    let excess = cache.engines.len().saturating_sub(max_loaded_models.max(1));
+    cache.engines.drain(..excess);
+    Ok(engine)
+}
+
+// Read-only view of the cached engine and the warmup in progress; never triggers a load or waits
+// for one.
+pub fn clip_engine_status() -> ClipEngineStatus {
+    let warmup = clip_warmup_status();
+    let cache = CLIP_ENGINES.lock();
+    let Some(CachedEngine {
+        key, engine: eng, ..
+    }) = cache.engines.last()
+    else {
+        return ClipEngineStatus {
+            warmup,
+            ..ClipEngineStatus::default()
+        };
+    };
+    let (input_ids, attention_mask, pixel_values) = eng.input_names();
+    let (image_embeds, text_embeds) = eng.output_names();
+    ClipEngineStatus {
+        loaded: true,
+        loaded_models: cache
+            .engines
+            .iter()
+            .rev()
+            .map(|c| c.engine.model_path().display().to_string())
+            .collect(),
+        cache_key: Some(key.clone()),
+        model_path: Some(eng.model_path().display().to_string()),
+        eps_log: Some(eng.eps_log().to_string()),
+        effective_eps: Some(eng.effective_eps().to_string()),
+        session_pool_size: Some(eng.session_pool_size()),
+        intra_threads: Some(eng.intra_threads()),
+        model_load_ms: Some(eng.model_load_ms() as u64),
+        text_cache_ms: Some(eng.text_cache_ms() as u64),
+        input_ids_name: Some(input_ids.to_string()),
+        attention_mask_name: Some(attention_mask.to_string()),
+        pixel_values_name: Some(pixel_values.to_string()),
+        output_image_embeds: Some(image_embeds.to_string()),
+        output_text_embeds: Some(text_embeds.to_string()),
+        warmup,
     }
-    let eng = Arc::new(ClipEngine::new(app, opts.clone())?);
-    *guard = Some((key, Arc::clone(&eng)));
-    Ok(eng)
 }
 
 fn derive_clip_threads(settings: &Settings) -> (usize, usize) {
@@ -179,29 +511,246 @@ fn derive_clip_threads(settings: &Settings) -> (usize, usize) {
         .unwrap_or(4)
         .max(1);
     let requested = settings.analysis_concurrency.max(1) as usize;
-    let pool = requested.min(cores).max(1);
-    let intra = ((cores + pool - 1) / pool).max(1);
+    let pool = settings
+        .clip_session_pool_size
+        .map(|n| n as usize)
+        .unwrap_or_else(|| requested.min(cores))
+        .max(1);
+    let intra = settings
+        .clip_intra_threads
+        .map(|n| n as usize)
+        .unwrap_or_else(|| (cores + pool - 1) / pool)
+        .max(1);
     (pool, intra)
 }
 
-pub fn warmup_clip_engine(app: &AppHandle, settings: &Settings) -> Result<()> {
+// Drops every cached engine and forgets a failed load, so the next use retries. Returns false
+// when nothing was loaded.
+pub fn unload_clip_engine() -> bool {
+    CLIP_ENGINES.lock().clear()
+}
+
+pub fn reload_clip_engine(app: &AppHandle, settings: &Settings) -> Result<u128> {
+    let started = std::time::Instant::now();
+    unload_clip_engine();
+    warmup_clip_engine(app, settings)?;
+    Ok(started.elapsed().as_millis())
+}
+
+fn max_loaded_models(settings: &Settings) -> usize {
+    settings.clip_max_loaded_models.max(1) as usize
+}
+
+fn clip_engine_options(settings: &Settings) -> ClipEngineOptions {
     let (pool, intra) = derive_clip_threads(settings);
-    let opts = ClipEngineOptions {
+    let tag_prompts = if settings.clip_tags_enabled {
+        default_tag_prompts()
+            .iter()
+            .map(|(p, l)| (p.to_string(), l.to_string()))
+            .chain(
+                settings
+                    .clip_tag_prompts
+                    .iter()
+                    .filter(|t| !t.prompt.trim().is_empty() && !t.label.trim().is_empty())
+                    .map(|t| (t.prompt.trim().to_string(), t.label.trim().to_string())),
+            )
+            .collect()
+    } else {
+        Vec::new()
+    };
+    // Sorted by parent so the cache key doesn't depend on HashMap iteration order.
+    let mut subcategories: SubcategoryPrompts = if settings.clip_subcategories_enabled {
+        settings
+            .clip_subcategories
+            .iter()
+            .map(|(parent, subs)| {
+                let subs = subs
+                    .iter()
+                    .filter(|s| !s.label.trim().is_empty())
+                    .map(|s| {
+                        let prompts = s
+                            .prompts
+                            .iter()
+                            .map(|p| p.trim().to_string())
+                            .filter(|p| !p.is_empty())
+                            .collect();
+                        (s.label.trim().to_string(), prompts)
+                    })
+                    .collect();
+                (*parent, subs)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    subcategories.sort_by_key(|(parent, _)| parent.as_str());
+    // Buckets without a name or prompts are dropped; fewer than two left means nothing to compare,
+    // so the built-in keep/drop pair is used instead.
+    let mut value_buckets: Vec<(String, bool, Vec<String>)> = settings
+        .clip_value_buckets
+        .iter()
+        .map(|b| {
+            let prompts: Vec<String> = b
+                .prompts
+                .iter()
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect();
+            (b.name.trim().to_string(), b.keep, prompts)
+        })
+        .filter(|(name, _, prompts)| !name.is_empty() && !prompts.is_empty())
+        .collect();
+    if value_buckets.len() < 2 {
+        value_buckets = ClipEngineOptions::default().value_buckets;
+    }
+    ClipEngineOptions {
         model_dir: settings.clip_model_dir.clone(),
         model_file: settings.clip_model_file.clone(),
         session_pool_size: pool,
         intra_threads: intra,
+        inter_threads: settings.clip_inter_threads.unwrap_or(1).max(1) as usize,
+        parallel_execution: settings.clip_parallel_execution,
+        cpu_arena: !settings.clip_disable_cpu_arena,
         enable_value: settings.analysis_value_enabled,
         ep_auto: settings.clip_ep_auto,
         ep_coreml: settings.clip_ep_coreml,
+        ep_tensorrt: settings.clip_ep_tensorrt,
         ep_cuda: settings.clip_ep_cuda,
         ep_rocm: settings.clip_ep_rocm,
         ep_directml: settings.clip_ep_directml,
         ep_openvino: settings.clip_ep_openvino,
+        gpu_device_id: settings.clip_gpu_device_id,
+        tag_prompts,
+        subcategories,
+        value_buckets,
+        category_bias: settings.category_bias.clone(),
         ..ClipEngineOptions::default()
+    }
+}
+
+pub fn warmup_clip_engine(app: &AppHandle, settings: &Settings) -> Result<()> {
+    let opts = clip_engine_options(settings);
+    get_clip_engine_reporting(app, &opts, max_loaded_models(settings), true).map(|_| ())
+}
+
+// Starts the warmup on the blocking pool so app startup isn't held up by model loading.
+pub fn spawn_clip_warmup(app: AppHandle, settings: Settings) {
+    // Mark the warmup as pending before returning so `wait_clip_warmup` can't race past it.
+    CLIP_WARMUP.send_replace(Some(ClipWarmupEvent {
+        phase: ClipWarmupPhase::LoadingModel,
+        elapsed_ms: 0,
+        error: None,
+        pool_index: None,
+        pool_size: None,
+    }));
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = warmup_clip_engine(&app, &settings) {
+            eprintln!("clip warmup failed: {}", e);
+        }
+    });
+}
+
+pub fn clip_warmup_status() -> Option<ClipWarmupEvent> {
+    CLIP_WARMUP.borrow().clone()
+}
+
+// Resolves once no warmup is in flight (finished, failed or never started).
+pub async fn wait_clip_warmup() {
+    let mut rx = CLIP_WARMUP.subscribe();
+    let _ = rx
+        .wait_for(|event| {
+            event
+                .as_ref()
+                .is_none_or(|e| matches!(e.phase, ClipWarmupPhase::Ready | ClipWarmupPhase::Failed))
+        })
+        .await;
+}
+
+// Classifies one image with the configured CLIP engine outside of any job. Returns a short
+// summary of the result and the engine's execution-provider log.
+pub fn clip_self_test(
+    app: &AppHandle,
+    settings: &Settings,
+    path: &Path,
+) -> Result<(String, String)> {
+    let engine = get_clip_engine_reporting(
+        app,
+        &clip_engine_options(settings),
+        max_loaded_models(settings),
+        false,
+    )?;
+    let pre = preprocess_clip_image(
+        path,
+        engine.preprocess_config(),
+        settings.alpha_background,
+        None,
+    )?;
+    let result = engine.classify(&pre.nchw, &[])?;
+    let summary = format!(
+        "category: {} (model: {})",
+        result.category.as_str(),
+        engine.model_path().display()
+    );
+    Ok((summary, engine.eps_log().to_string()))
+}
+
+// The decode and preprocess half of a CLIP classification, run ahead of inference by batch jobs.
+// Blocking; loads the engine if needed for its preprocess config.
+pub fn prepare_clip_input(
+    app: &AppHandle,
+    settings: &Settings,
+    path: &Path,
+    cancel: &CancellationToken,
+) -> Result<Option<PreprocessOutput>> {
+    // The engine error is left to the classify call, which reports it or falls back to Ollama. A
+    // failed load is remembered by the cache, so this doesn't retry it for every photo.
+    let Ok(engine) = get_clip_engine_reporting(
+        app,
+        &clip_engine_options(settings),
+        max_loaded_models(settings),
+        false,
+    ) else {
+        return Ok(None);
     };
-    let _ = get_clip_engine(app, &opts)?;
-    Ok(())
+    preprocess_clip_image(
+        path,
+        engine.preprocess_config(),
+        settings.alpha_background,
+        Some(cancel),
+    )
+    .map(Some)
+}
+
+// Scores one image against a trial category prompt set for prompt tuning. The cached engine and its
+// embeddings are left untouched, so running jobs are unaffected.
+pub fn clip_prompt_test(
+    app: &AppHandle,
+    settings: &Settings,
+    path: &Path,
+    prompts: &HashMap<CategoryKey, Vec<(String, f32)>>,
+) -> Result<ClipPromptTestResult> {
+    let engine = get_clip_engine_reporting(
+        app,
+        &clip_engine_options(settings),
+        max_loaded_models(settings),
+        false,
+    )?;
+    let started = std::time::Instant::now();
+    let embeds = engine.category_embeds_for(prompts)?;
+    let embed_ms = started.elapsed().as_millis() as u64;
+    let pre = preprocess_clip_image(
+        path,
+        engine.preprocess_config(),
+        settings.alpha_background,
+        None,
+    )?;
+    let result = engine.classify_with_embeds(&pre.nchw, &[], &embeds)?;
+    Ok(ClipPromptTestResult {
+        scores: result.scores,
+        category: result.category,
+        embed_ms,
+        infer_ms: result.inference_ms as u64,
+    })
 }
 
 impl Classifier for ClipClassifier {
@@ -210,23 +759,91 @@ impl Classifier for ClipClassifier {
         input: ClassifyInput<'a>,
     ) -> Pin<Box<dyn Future<Output = Result<ClassificationOutput>> + Send + 'a>> {
         Box::pin(async move {
-            let pre = preprocess_clip_image(input.path)?;
-            let engine = get_clip_engine(input.app, &self.opts)?;
-            let (scores, category, valuable, analysis_log, _infer_ms) = engine.classify(&pre.nchw)?;
-            let (is_valuable, valuable_score) = valuable
+            // Decode, preprocess and inference are all CPU-bound; run them on the blocking pool so
+            // concurrent tasks don't starve the async workers and each can hold its own session.
+            let app = input.app.clone();
+            let path = input.path.to_path_buf();
+            let opts = self.opts.clone();
+            let max_loaded = self.max_loaded_models;
+            let screenshot_heuristic = self.screenshot_heuristic;
+            let alpha_background = self.alpha_background;
+            let cancel = input.cancel.clone();
+            let preprocessed = input.preprocessed;
+            let task = tauri::async_runtime::spawn_blocking(move || {
+                // The first photo after a settings change can trigger a load here; report it like a warmup.
+                let engine = get_clip_engine_reporting(&app, &opts, max_loaded, false)?;
+                let pre = match preprocessed {
+                    Some(pre) => pre,
+                    None => preprocess_clip_image(
+                        &path,
+                        engine.preprocess_config(),
+                        alpha_background,
+                        Some(&cancel),
+                    )?,
+                };
+                let boost = if screenshot_heuristic && pre.screenshot.detected {
+                    vec![(CategoryKey::ScreenshotDocument, SCREENSHOT_HEURISTIC_BOOST)]
+                } else {
+                    Vec::new()
+                };
+                let result = engine.classify(&pre.nchw, &boost)?;
+                anyhow::Ok((result, pre.screenshot, pre.geometry))
+            });
+            // A decode that can't stop keeps its blocking thread, but the task returns right away.
+            let (result, screenshot, geometry) = tokio::select! {
+                _ = input.cancel.cancelled() => return Err(anyhow::anyhow!("canceled")),
+                res = task => res??,
+            };
+            let (is_valuable, valuable_score) = result
+                .valuable
                 .map(|(b, p)| (Some(b), Some(p)))
                 .unwrap_or((None, None));
+            let category = result.category;
+
+            // Category name first, then the best tag concepts that clear the threshold.
+            let mut tags = vec![category.dir_name_ko().to_string()];
+            for (label, _) in result
+                .tag_scores
+                .iter()
+                .filter(|(_, sim)| *sim >= self.tag_threshold)
+                .take(self.tag_top_n)
+            {
+                if !tags.contains(label) {
+                    tags.push(label.clone());
+                }
+            }
+            let mut analysis_log = format!(
+                "{}preprocess_input: {}\ntags: {}\n",
+                result.log,
+                geometry.describe(),
+                tags[1..].join(", ")
+            );
+            if let Some(warning) = geometry.warning() {
+                analysis_log.push_str(&format!("preprocess_warning: {}\n", warning));
+            }
+            if self.screenshot_heuristic {
+                analysis_log.push_str(&format!(
+                    "screenshot_heuristic: {}\n",
+                    screenshot.describe()
+                ));
+            }
 
             Ok(ClassificationOutput {
                 model: "clip-vit-b32-onnx".to_string(),
-                scores,
+                scores: result.scores,
                 category,
-                tags: vec![category.dir_name_ko().to_string()],
+                tags,
                 caption: Some("".to_string()),
                 text_in_image: Some("".to_string()),
                 analysis_log,
                 is_valuable: if self.opts.enable_value { is_valuable } else { None },
                 valuable_score: if self.opts.enable_value { valuable_score } else { None },
+                subcategory: result.subcategory.map(|(label, _)| label),
+                value_bucket: if self.opts.enable_value {
+                    result.value_bucket.map(|(name, _)| name)
+                } else {
+                    None
+                },
             })
         })
     }
@@ -240,27 +857,281 @@ pub fn build_classifier(settings: &Settings) -> (AnalysisEngine, Box<dyn Classif
                 settings: settings.clone(),
             }),
         ),
-        AnalysisEngine::Clip => (
-            AnalysisEngine::Clip,
+        // Hybrid's Ollama stage runs separately; see `pipeline::describe_with_ollama`.
+        engine @ (AnalysisEngine::Clip | AnalysisEngine::Hybrid) => (
+            engine,
             Box::new(ClipClassifier {
-                opts: {
-                    let (pool, intra) = derive_clip_threads(settings);
-                    ClipEngineOptions {
-                        model_dir: settings.clip_model_dir.clone(),
-                        model_file: settings.clip_model_file.clone(),
-                        session_pool_size: pool,
-                        intra_threads: intra,
-                        enable_value: settings.analysis_value_enabled,
-                        ep_auto: settings.clip_ep_auto,
-                        ep_coreml: settings.clip_ep_coreml,
-                        ep_cuda: settings.clip_ep_cuda,
-                        ep_rocm: settings.clip_ep_rocm,
-                        ep_directml: settings.clip_ep_directml,
-                        ep_openvino: settings.clip_ep_openvino,
-                        ..ClipEngineOptions::default()
-                    }
-                },
+                opts: clip_engine_options(settings),
+                max_loaded_models: max_loaded_models(settings),
+                tag_top_n: settings.clip_tag_top_n as usize,
+                tag_threshold: settings.clip_tag_threshold,
+                screenshot_heuristic: settings.screenshot_heuristic,
+                alpha_background: settings.alpha_background,
             }),
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(cache: &Mutex<EngineCache<String>>) -> Vec<String> {
+        cache.lock().engines.iter().map(|c| c.key.clone()).collect()
+    }
+
+    fn load_with(
+        cache: &Mutex<EngineCache<String>>,
+        model: &str,
+        key: &str,
+        max_loaded_models: usize,
+        load: impl FnOnce() -> Result<Arc<String>>,
+    ) -> Result<Arc<String>> {
+        let loading = Mutex::new(());
+        cached_or_load(
+            cache,
+            &loading,
+            model.into(),
+            key.into(),
+            max_loaded_models,
+            load,
+        )
+    }
+
+    fn load(
+        cache: &Mutex<EngineCache<String>>,
+        model: &str,
+        key: &str,
+        max_loaded_models: usize,
+    ) -> Result<Arc<String>> {
+        load_with(cache, model, key, max_loaded_models, || {
+            Ok(Arc::new(key.to_string()))
+        })
+    }
+
+    #[test]
+    fn least_recently_used_engine_is_evicted() {
+        let cache = Mutex::new(EngineCache::new());
+        load(&cache, "a", "a1", 2).unwrap();
+        load(&cache, "b", "b1", 2).unwrap();
+        // A hit makes `a1` the most recently used, so `b1` goes when `c1` loads.
+        let hit = load_with(&cache, "a", "a1", 2, || panic!("cached engine reloaded")).unwrap();
+        assert_eq!(*hit, "a1");
+        assert_eq!(keys(&cache), ["b1", "a1"]);
+        load(&cache, "c", "c1", 2).unwrap();
+        assert_eq!(keys(&cache), ["a1", "c1"]);
+        load(&cache, "d", "d1", 0).unwrap();
+        assert_eq!(keys(&cache), ["d1"]);
+    }
+
+    #[test]
+    fn concurrent_callers_acquire_distinct_sessions() {
+        use crate::core::clip::engine::acquire_pooled;
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::Barrier;
+
+        const N: usize = 4;
+        let pool: Vec<Mutex<usize>> = (0..=N).map(Mutex::new).collect();
+        // Slot 0 is busy, so the first round-robin start must move on to an idle session.
+        let busy = pool[0].lock();
+        let rr = AtomicUsize::new(0);
+        let all_held = Barrier::new(N);
+        let mut acquired: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..N)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let session = acquire_pooled(&pool, &rr).unwrap();
+                        // Hold the session until every caller has one.
+                        all_held.wait();
+                        *session
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        drop(busy);
+        acquired.sort();
+        assert_eq!(acquired, (1..=N).collect::<Vec<_>>());
+        assert!(acquire_pooled::<usize>(&[], &rr).is_err());
+    }
+
+    #[test]
+    fn stale_engine_is_replaced_only_after_the_new_one_loads() {
+        let cache = Mutex::new(EngineCache::new());
+        load(&cache, "a", "a1", 3).unwrap();
+        load(&cache, "b", "b1", 3).unwrap();
+        let failed = load_with(&cache, "a", "a2", 3, || Err(anyhow!("load failed")));
+        assert!(failed.is_err());
+        assert_eq!(keys(&cache), ["a1", "b1"]);
+        cache.lock().clear();
+        load(&cache, "a", "a1", 3).unwrap();
+        load(&cache, "a", "a2", 3).unwrap();
+        assert_eq!(keys(&cache), ["a2"]);
+    }
+
+    #[test]
+    fn failed_load_is_not_retried_until_the_cache_is_cleared() {
+        let cache = Mutex::new(EngineCache::new());
+        let failed = load_with(&cache, "a", "a1", 2, || Err(anyhow!("model is corrupt")));
+        assert!(failed.is_err());
+        let again = load_with(&cache, "a", "a1", 2, || panic!("failed engine reloaded"));
+        assert!(again.unwrap_err().to_string().contains("model is corrupt"));
+        // Other settings still load.
+        load(&cache, "b", "b1", 2).unwrap();
+        assert!(cache.lock().clear());
+        load(&cache, "a", "a1", 2).unwrap();
+        assert_eq!(keys(&cache), ["a1"]);
+    }
+
+    #[test]
+    fn cache_stays_usable_while_a_model_loads() {
+        let cache = Mutex::new(EngineCache::new());
+        load(&cache, "a", "a1", 2).unwrap();
+        load_with(&cache, "b", "b1", 2, || {
+            // A classify on the cached engine, or an unload, must not wait for this load.
+            let mut cache = cache.try_lock().expect("cache locked during load");
+            assert_eq!(*cache.lookup("a1").unwrap().unwrap(), "a1");
+            Ok(Arc::new("b1".to_string()))
+        })
+        .unwrap();
+        assert_eq!(keys(&cache), ["a1", "b1"]);
+    }
+
+    #[test]
+    fn warmup_phases_are_published_in_order() {
+        let mut events = Vec::new();
+        report_warmup(
+            false,
+            |e| events.push(e),
+            |on_phase| {
+                on_phase(ClipWarmupPhase::LoadingModel, None);
+                on_phase(ClipWarmupPhase::CachingText, None);
+                on_phase(ClipWarmupPhase::SmokeTest, None);
+                on_phase(ClipWarmupPhase::BuildingPool, Some((1, 2)));
+                on_phase(ClipWarmupPhase::BuildingPool, Some((2, 2)));
+                Ok(())
+            },
+        )
+        .unwrap();
+        let phases: Vec<_> = events.iter().map(|e| e.phase).collect();
+        assert_eq!(
+            phases,
+            [
+                ClipWarmupPhase::LoadingModel,
+                ClipWarmupPhase::CachingText,
+                ClipWarmupPhase::SmokeTest,
+                ClipWarmupPhase::BuildingPool,
+                ClipWarmupPhase::BuildingPool,
+                ClipWarmupPhase::Ready,
+            ]
+        );
+        assert_eq!(
+            (events[4].pool_index, events[4].pool_size),
+            (Some(2), Some(2))
+        );
+        assert!(events
+            .windows(2)
+            .all(|w| w[0].elapsed_ms <= w[1].elapsed_ms));
+    }
+
+    #[test]
+    fn cache_hit_is_silent_and_failed_load_ends_with_failed() {
+        let mut events = Vec::new();
+        report_warmup(false, |e| events.push(e), |_| Ok(())).unwrap();
+        assert!(events.is_empty());
+        let failed = report_warmup::<()>(
+            false,
+            |e| events.push(e),
+            |on_phase| {
+                on_phase(ClipWarmupPhase::LoadingModel, None);
+                Err(anyhow!("model missing"))
+            },
+        );
+        assert!(failed.is_err());
+        let phases: Vec<_> = events.iter().map(|e| e.phase).collect();
+        assert_eq!(
+            phases,
+            [ClipWarmupPhase::LoadingModel, ClipWarmupPhase::Failed]
+        );
+        assert_eq!(events[1].error.as_deref(), Some("model missing"));
+    }
+
+    #[test]
+    fn three_value_buckets_reach_the_engine_options() {
+        use crate::core::model::ValueBucket;
+
+        let bucket = |name: &str, keep: bool, prompts: &[&str]| ValueBucket {
+            name: name.to_string(),
+            prompts: prompts.iter().map(|p| p.to_string()).collect(),
+            keep,
+        };
+        let settings = Settings {
+            clip_value_buckets: vec![
+                bucket("keep", true, &["a great photo"]),
+                bucket(" maybe ", false, &["an ordinary photo", "  "]),
+                bucket("drop", false, &["a blurry photo"]),
+                bucket("empty", false, &[" "]),
+            ],
+            ..Settings::default()
+        };
+        let buckets = clip_engine_options(&settings).value_buckets;
+        assert_eq!(
+            buckets,
+            [
+                ("keep".to_string(), true, vec!["a great photo".to_string()]),
+                (
+                    "maybe".to_string(),
+                    false,
+                    vec!["an ordinary photo".to_string()]
+                ),
+                (
+                    "drop".to_string(),
+                    false,
+                    vec!["a blurry photo".to_string()]
+                ),
+            ]
+        );
+
+        // A single usable bucket can't be compared, so the built-in pair stays.
+        let settings = Settings {
+            clip_value_buckets: vec![bucket("keep", true, &["a great photo"])],
+            ..Settings::default()
+        };
+        assert_eq!(
+            clip_engine_options(&settings).value_buckets,
+            ClipEngineOptions::default().value_buckets
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn ollama_permits_cap_requests_in_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let cancel = CancellationToken::new();
+        let tasks = (0..8)
+            .map(|_| {
+                let (in_flight, peak, cancel) = (in_flight.clone(), peak.clone(), cancel.clone());
+                tokio::spawn(async move {
+                    let _permit = acquire_ollama_permit(2, &cancel).await.unwrap();
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        // A waiter gives up when the job is canceled instead of holding the queue.
+        let _held = acquire_ollama_permit(2, &cancel).await.unwrap();
+        let _held2 = acquire_ollama_permit(2, &cancel).await.unwrap();
+        cancel.cancel();
+        let err = acquire_ollama_permit(2, &cancel).await.unwrap_err();
+        assert_eq!(err.to_string(), "canceled");
+    }
+}
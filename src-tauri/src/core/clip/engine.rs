@@ -1,38 +1,67 @@
 use crate::core::clip::math::{cosine_similarity, l2_normalize, softmax};
-use crate::core::clip::prompts::{all_category_prompts, value_drop_prompts, value_keep_prompts};
-use crate::core::model::{CategoryKey, Scores, CATEGORY_KEYS};
+use crate::core::clip::preprocess::PreprocessConfig;
+use crate::core::clip::prompts::all_category_prompts;
+use crate::core::model::{
+    default_clip_value_buckets, CategoryKey, ClipGpuDevice, ClipWarmupPhase, Scores, CATEGORY_KEYS,
+};
 use anyhow::{anyhow, Result};
 use ort::execution_providers::{
     CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider,
     DirectMLExecutionProvider, ExecutionProvider, ExecutionProviderDispatch,
-    OpenVINOExecutionProvider, ROCmExecutionProvider,
+    OpenVINOExecutionProvider, ROCmExecutionProvider, TensorRTExecutionProvider,
 };
 use ort::execution_providers::coreml::CoreMLModelFormat;
-use ort::session::builder::GraphOptimizationLevel;
+use ort::session::builder::{GraphOptimizationLevel, SessionBuilder};
 use ort::session::run_options::{OutputSelector, RunOptions};
-use ort::session::Session;
+use ort::session::{Input, Session};
 use ort::value::Tensor;
-use parking_lot::Mutex;
-use std::collections::HashMap;
+use parking_lot::{Mutex, MutexGuard};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tauri::{AppHandle, Manager};
 use tokenizers::Tokenizer;
 
+// Parent category -> (label, prompts) per sub-category.
+pub type SubcategoryPrompts = Vec<(CategoryKey, Vec<(String, Vec<String>)>)>;
+// Parent category -> (label, averaged embedding) per sub-category.
+type SubcategoryEmbeds = HashMap<CategoryKey, Vec<(String, Vec<f32>)>>;
+// One averaged embedding per category.
+type CategoryEmbeds = HashMap<CategoryKey, Vec<f32>>;
+// Weighted prompts per category.
+type PromptSets<'a> = Vec<(CategoryKey, Vec<(&'a str, f32)>)>;
+
 #[derive(Debug, Clone)]
 pub struct ClipEngineOptions {
     pub model_dir: Option<String>,
     pub model_file: String,
     pub session_pool_size: usize,
     pub intra_threads: usize,
+    // Inter-op threads only matter with `parallel_execution`; the defaults (1, sequential) match
+    // ONNX Runtime's own.
+    pub inter_threads: usize,
+    pub parallel_execution: bool,
+    pub cpu_arena: bool,
     pub enable_value: bool,
     pub allow_ep_fallback: bool,
     pub ep_auto: bool,
     pub ep_coreml: bool,
+    pub ep_tensorrt: bool,
     pub ep_cuda: bool,
     pub ep_rocm: bool,
     pub ep_directml: bool,
     pub ep_openvino: bool,
+    pub tensorrt_cache_dir: Option<PathBuf>,
+    pub gpu_device_id: u32,
+    // (prompt, label) pairs; empty disables tagging.
+    pub tag_prompts: Vec<(String, String)>,
+    // Parent category -> (label, prompts) per sub-category; empty disables the second stage.
+    pub subcategories: SubcategoryPrompts,
+    // (name, keep, prompts) per value bucket; softmax picks one when `enable_value` is set.
+    pub value_buckets: Vec<(String, bool, Vec<String>)>,
+    // Added to the category logits before softmax. Not part of the cache key: a cached engine
+    // picks up new values through `set_category_bias`.
+    pub category_bias: HashMap<CategoryKey, f32>,
 }
 
 impl Default for ClipEngineOptions {
@@ -46,36 +75,125 @@ impl Default for ClipEngineOptions {
                 .unwrap_or(4)
                 .max(1)
                 .min(4),
+            inter_threads: 1,
+            parallel_execution: false,
+            cpu_arena: true,
             enable_value: false,
             allow_ep_fallback: true,
             ep_auto: true,
             ep_coreml: cfg!(target_vendor = "apple"),
+            ep_tensorrt: false,
             ep_cuda: false,
             ep_rocm: false,
             ep_directml: false,
             ep_openvino: false,
+            tensorrt_cache_dir: None,
+            gpu_device_id: 0,
+            tag_prompts: Vec::new(),
+            subcategories: Vec::new(),
+            value_buckets: default_clip_value_buckets()
+                .into_iter()
+                .map(|b| (b.name, b.keep, b.prompts))
+                .collect(),
+            category_bias: HashMap::new(),
         }
     }
 }
 
+pub struct ClipClassification {
+    pub scores: Scores,
+    pub category: CategoryKey,
+    // (in a keep bucket, summed probability of the keep buckets)
+    pub valuable: Option<(bool, f32)>,
+    // Most likely value bucket and its probability.
+    pub value_bucket: Option<(String, f32)>,
+    // Every tag label with its cosine similarity, best first.
+    pub tag_scores: Vec<(String, f32)>,
+    // Best sub-category label and its probability among the parent's sub-categories.
+    pub subcategory: Option<(String, f32)>,
+    pub log: String,
+    pub inference_ms: u128,
+}
+
+// A session plus its image-only run inputs. The text inputs never change and the pixel buffer is
+// overwritten in place, so `classify` doesn't allocate input tensors per call.
+struct PooledSession {
+    session: Session,
+    pixel_values: Tensor<f32>,
+    input_ids: Tensor<i64>,
+    attention_mask: Tensor<i64>,
+}
+
+impl PooledSession {
+    fn new(
+        session: Session,
+        image_size: usize,
+        dummy_input_ids: &[i64],
+        dummy_attention_mask: &[i64],
+    ) -> Result<Self> {
+        let pixel = ndarray::Array4::<f32>::zeros((1, 3, image_size, image_size));
+        let ids = ndarray::Array2::<i64>::from_shape_vec(
+            (1, dummy_input_ids.len()),
+            dummy_input_ids.to_vec(),
+        )?;
+        let mask = ndarray::Array2::<i64>::from_shape_vec(
+            (1, dummy_attention_mask.len()),
+            dummy_attention_mask.to_vec(),
+        )?;
+        Ok(Self {
+            session,
+            pixel_values: Tensor::from_array(pixel)?,
+            input_ids: Tensor::from_array(ids)?,
+            attention_mask: Tensor::from_array(mask)?,
+        })
+    }
+}
+
 pub struct ClipEngine {
     model_path: PathBuf,
     tokenizer_path: PathBuf,
-    sessions: Vec<Mutex<Session>>,
+    sessions: Vec<Mutex<PooledSession>>,
     rr: AtomicUsize,
     input_ids_name: String,
     attention_mask_name: String,
     pixel_values_name: String,
     output_image_embeds: String,
     output_text_embeds: String,
-    dummy_input_ids: Vec<i64>,
-    dummy_attention_mask: Vec<i64>,
+    preprocess: PreprocessConfig,
     category_text_embeds: HashMap<CategoryKey, Vec<f32>>,
-    value_keep_embed: Vec<f32>,
-    value_drop_embed: Vec<f32>,
+    value_buckets: Vec<(String, bool, Vec<f32>)>,
+    tag_labels: Vec<String>,
+    tag_embeds: Vec<Vec<f32>>,
+    subcategory_embeds: SubcategoryEmbeds,
+    category_bias: Mutex<HashMap<CategoryKey, f32>>,
+    // Kept after init so prompts can be embedded on demand (`category_embeds_for`).
+    text_encoder: TextEncoder,
     model_load_ms: u128,
     text_cache_ms: u128,
     eps_log: String,
+    // Providers ORT actually assigned nodes to during warmup; `eps_log` only lists the requested
+    // ones, and a requested EP may still leave nodes on the CPU.
+    effective_eps: String,
+    intra_threads: usize,
+}
+
+// Start at the round-robin slot but prefer any idle session, so N concurrent callers spread over
+// N pooled sessions instead of queueing behind a busy one.
+pub(crate) fn acquire_pooled<'a, T>(
+    pool: &'a [Mutex<T>],
+    rr: &AtomicUsize,
+) -> Result<MutexGuard<'a, T>> {
+    let n = pool.len();
+    if n == 0 {
+        return Err(anyhow!("clip session pool is empty"));
+    }
+    let start = rr.fetch_add(1, Ordering::Relaxed) % n;
+    for offset in 0..n {
+        if let Some(guard) = pool[(start + offset) % n].try_lock() {
+            return Ok(guard);
+        }
+    }
+    Ok(pool[start].lock())
 }
 
 impl ClipEngine {
@@ -120,6 +238,15 @@ impl ClipEngine {
     }
 
     pub fn new(app: &AppHandle, opts: ClipEngineOptions) -> Result<Self> {
+        Self::new_with_progress(app, opts, &mut |_, _| {})
+    }
+
+    pub fn new_with_progress(
+        app: &AppHandle,
+        opts: ClipEngineOptions,
+        on_phase: &mut dyn FnMut(ClipWarmupPhase, Option<(usize, usize)>),
+    ) -> Result<Self> {
+        on_phase(ClipWarmupPhase::LoadingModel, None);
         let dir = Self::resolve_model_dir(app, opts.model_dir.as_deref())?;
         let model_path = dir.join(Path::new(&opts.model_file));
         if !model_path.exists() {
@@ -135,51 +262,52 @@ impl ClipEngine {
                 tokenizer_path.display()
             ));
         }
+        let preprocess = PreprocessConfig::load(&dir);
+        let image_size = preprocess.size as usize;
 
         let mut opts_try = opts.clone();
+        if opts_try.ep_tensorrt && opts_try.tensorrt_cache_dir.is_none() {
+            // Keep built TensorRT engines across launches; building one takes minutes.
+            if let Ok(data_dir) = app.path().app_data_dir() {
+                let cache_dir = data_dir.join("tensorrt_cache");
+                if std::fs::create_dir_all(&cache_dir).is_ok() {
+                    opts_try.tensorrt_cache_dir = Some(cache_dir);
+                }
+            }
+        }
+        if opts_try.gpu_device_id != 0 {
+            let devices = list_gpu_devices();
+            if !devices.is_empty() && opts_try.gpu_device_id as usize >= devices.len() {
+                eprintln!(
+                    "clip: gpu device {} not found ({} visible), using device 0",
+                    opts_try.gpu_device_id,
+                    devices.len()
+                );
+                opts_try.gpu_device_id = 0;
+            }
+        }
         let intra_threads = opts_try.intra_threads.max(1);
 
         let started = std::time::Instant::now();
-        let (mut first_session, eps_log) = loop {
-            let builder = Session::builder()?
-                .with_optimization_level(GraphOptimizationLevel::Level3)?
-                .with_intra_threads(intra_threads)?;
-
-            let (eps, eps_log) = build_execution_providers(&opts_try);
-            let builder = match builder.with_execution_providers(eps) {
-                Ok(b) => b,
-                Err(e) => {
-                    eprintln!(
-                        "clip: failed to apply execution providers (fallback to CPU). err={}",
-                        e
-                    );
-                    Session::builder()?
-                        .with_optimization_level(GraphOptimizationLevel::Level3)?
-                        .with_intra_threads(intra_threads)?
-                }
-            };
-
-            let first_session = builder.commit_from_file(&model_path);
-            match first_session {
-                Ok(s) => break (s, eps_log),
-                Err(e) => {
-                    if opts_try.allow_ep_fallback && opts_try.ep_auto && opts_try.ep_coreml {
-                        eprintln!(
-                            "clip: session build failed with CoreML enabled, retrying without CoreML. err={}",
-                            e
-                        );
-                        opts_try.ep_coreml = false;
-                        continue;
-                    }
-                    return Err(e.into());
-                }
-            }
-        };
+        let mut failed_eps: Vec<&'static str> = Vec::new();
+        // Only the first session is profiled, and only until warmup ends.
+        let profile_prefix =
+            std::env::temp_dir().join(format!("img-sort-clip-profile-{}", std::process::id()));
+        let (mut first_session, mut active_eps) =
+            with_ep_fallback(&mut opts_try, &mut failed_eps, |o| {
+                open_session(&model_path, intra_threads, o, Some(&profile_prefix))
+            })?;
 
         let model_load_ms = started.elapsed().as_millis();
 
         let (input_ids_name, attention_mask_name, pixel_values_name) =
             resolve_input_names(&first_session)?;
+        let inputs = ModelInputs {
+            input_ids_name: &input_ids_name,
+            attention_mask_name: &attention_mask_name,
+            pixel_values_name: &pixel_values_name,
+            image_size,
+        };
 
         let output_image_embeds = pick_output_name(
             first_session
@@ -205,97 +333,122 @@ impl ClipEngine {
 
         let tokenizer =
             Tokenizer::from_file(&tokenizer_path).map_err(|e| anyhow!(e.to_string()))?;
-        let pad_id = tokenizer
-            .token_to_id("<|endoftext|>")
-            .ok_or_else(|| anyhow!("tokenizer missing <|endoftext|>"))? as i64;
+        let encoder = TextEncoder {
+            pad_id: resolve_pad_id(&tokenizer),
+            seq_len: resolve_seq_len(&first_session.inputs, &input_ids_name),
+            tokenizer,
+        };
 
         // Prepare dummy text input (will be used when we only need image embeddings).
-        let dummy = encode_fixed_77(&tokenizer, "", pad_id)?;
+        let dummy = encoder.encode("")?;
 
         // Cache text embeddings at init (will also validate EP compatibility).
         let started_cache = std::time::Instant::now();
-        let (category_text_embeds, value_keep_embed, value_drop_embed) = loop {
-            let category = cache_category_text_embeds(
+        let (category_text_embeds, value_embeds) = loop {
+            on_phase(ClipWarmupPhase::CachingText, None);
+            let warmup = cache_text_embeds(
                 &mut first_session,
-                &tokenizer,
-                pad_id,
-                &dummy.0,
-                &dummy.1,
-                &input_ids_name,
-                &attention_mask_name,
-                &pixel_values_name,
+                &encoder,
+                &inputs,
                 &output_text_embeds,
-            );
-            let keep = cache_text_embed_for_prompts(
+                &opts_try.value_buckets,
+            )
+            .and_then(|embeds| {
+                // Smoke-test vision path as well. Some EPs can compile/load but fail at runtime.
+                on_phase(ClipWarmupPhase::SmokeTest, None);
+                smoke_test_vision(
+                    &mut first_session,
+                    &inputs,
+                    &output_image_embeds,
+                    &dummy.0,
+                    &dummy.1,
+                )?;
+                Ok(embeds)
+            });
+            match warmup {
+                Ok(embeds) => break embeds,
+                Err(e) => {
+                    let Some(ep) = disable_fallible_ep(&mut opts_try, &active_eps) else {
+                        return Err(e);
+                    };
+                    eprintln!(
+                        "clip: {} failed during warmup, retrying without it. err={}",
+                        ep, e
+                    );
+                    failed_eps.push(ep);
+                    if let Ok(path) = first_session.end_profiling() {
+                        let _ = std::fs::remove_file(path);
+                    }
+                    (first_session, active_eps) =
+                        with_ep_fallback(&mut opts_try, &mut failed_eps, |o| {
+                            open_session(&model_path, intra_threads, o, Some(&profile_prefix))
+                        })?;
+                }
+            }
+        };
+        let eps_log = format_eps_log(&active_eps, &failed_eps);
+        let effective_eps = effective_providers(&mut first_session).unwrap_or_else(|e| {
+            eprintln!("clip: could not read effective providers. err={}", e);
+            "unknown".to_string()
+        });
+        let value_buckets = opts_try
+            .value_buckets
+            .iter()
+            .zip(value_embeds)
+            .map(|((name, keep, _), embed)| (name.clone(), *keep, embed))
+            .collect();
+
+        // Tags are optional; a failure here only disables them.
+        let tag_prompts: Vec<&str> = opts_try
+            .tag_prompts
+            .iter()
+            .map(|(p, _)| p.as_str())
+            .collect();
+        let (tag_labels, tag_embeds) = if tag_prompts.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            match cache_tag_embeds(
                 &mut first_session,
-                &tokenizer,
-                pad_id,
-                &input_ids_name,
-                &attention_mask_name,
-                &pixel_values_name,
+                &encoder,
+                &inputs,
                 &output_text_embeds,
-                value_keep_prompts(),
-            );
-            let drop = cache_text_embed_for_prompts(
+                &tag_prompts,
+            ) {
+                Ok(embeds) => (
+                    opts_try
+                        .tag_prompts
+                        .iter()
+                        .map(|(_, l)| l.clone())
+                        .collect(),
+                    embeds,
+                ),
+                Err(e) => {
+                    eprintln!(
+                        "clip: failed to cache tag embeddings, tags disabled. err={}",
+                        e
+                    );
+                    (Vec::new(), Vec::new())
+                }
+            }
+        };
+
+        let subcategory_embeds = if opts_try.subcategories.is_empty() {
+            HashMap::new()
+        } else {
+            match cache_subcategory_embeds(
                 &mut first_session,
-                &tokenizer,
-                pad_id,
-                &input_ids_name,
-                &attention_mask_name,
-                &pixel_values_name,
+                &encoder,
+                &inputs,
                 &output_text_embeds,
-                value_drop_prompts(),
-            );
-
-            match (category, keep, drop) {
-                (Ok(c), Ok(k), Ok(d)) => {
-                    // Smoke-test vision path as well. Some EPs can compile/load but fail at runtime.
-                    if let Err(e) = smoke_test_vision(
-                        &mut first_session,
-                        &input_ids_name,
-                        &attention_mask_name,
-                        &pixel_values_name,
-                        &output_image_embeds,
-                        &dummy.0,
-                        &dummy.1,
-                    ) {
-                        if opts_try.allow_ep_fallback && opts_try.ep_auto && opts_try.ep_coreml {
-                            eprintln!(
-                                "clip: CoreML failed during vision smoke test, retrying without CoreML. err={}",
-                                e
-                            );
-                            opts_try.ep_coreml = false;
-                            let builder = Session::builder()?
-                                .with_optimization_level(GraphOptimizationLevel::Level3)?
-                                .with_intra_threads(intra_threads)?;
-                            let (eps, _eps_log2) = build_execution_providers(&opts_try);
-                            let builder = builder.with_execution_providers(eps)?;
-                            first_session = builder.commit_from_file(&model_path)?;
-                            continue;
-                        }
-                        return Err(e);
-                    }
-                    break (c, k, d);
-                }
-                (cat_err, keep_err, drop_err) => {
-                    let err = cat_err.err().or_else(|| keep_err.err()).or_else(|| drop_err.err());
-                    let err = err.unwrap_or_else(|| anyhow!("unknown cache error"));
-                    if opts_try.allow_ep_fallback && opts_try.ep_auto && opts_try.ep_coreml {
-                        eprintln!(
-                            "clip: CoreML failed during warmup, retrying without CoreML. err={}",
-                            err
-                        );
-                        opts_try.ep_coreml = false;
-                        // rebuild session without CoreML
-                        let builder = Session::builder()?
-                            .with_optimization_level(GraphOptimizationLevel::Level3)?
-                            .with_intra_threads(intra_threads)?;
-                        let (eps, _eps_log2) = build_execution_providers(&opts_try);
-                        let builder = builder.with_execution_providers(eps)?;
-                        first_session = builder.commit_from_file(&model_path)?;
-                        continue;
-                    }
-                    return Err(err);
+                &opts_try.subcategories,
+            ) {
+                Ok(map) => map,
+                Err(e) => {
+                    eprintln!(
+                        "clip: failed to cache sub-category embeddings, sub-categories disabled. err={}",
+                        e
+                    );
+                    HashMap::new()
                 }
             }
         };
@@ -303,22 +456,21 @@ impl ClipEngine {
         let text_cache_ms = started_cache.elapsed().as_millis();
 
         let session_pool_size = opts_try.session_pool_size.max(1);
-        let mut sessions: Vec<Mutex<Session>> = Vec::with_capacity(session_pool_size);
-        sessions.push(Mutex::new(first_session));
+        let mut sessions: Vec<Mutex<PooledSession>> = Vec::with_capacity(session_pool_size);
+        sessions.push(Mutex::new(PooledSession::new(
+            first_session,
+            image_size,
+            &dummy.0,
+            &dummy.1,
+        )?));
         for _ in 1..session_pool_size {
+            on_phase(
+                ClipWarmupPhase::BuildingPool,
+                Some((sessions.len() + 1, session_pool_size)),
+            );
             let started = std::time::Instant::now();
-            let builder = Session::builder()?
-                .with_optimization_level(GraphOptimizationLevel::Level3)?
-                .with_intra_threads(intra_threads)?;
-
-            let (eps, _eps_log2) = build_execution_providers(&opts_try);
-            let builder = match builder.with_execution_providers(eps) {
-                Ok(b) => b,
-                Err(_) => Session::builder()?
-                    .with_optimization_level(GraphOptimizationLevel::Level3)?
-                    .with_intra_threads(intra_threads)?,
-            };
-            let session = builder.commit_from_file(&model_path)?;
+            let (session, _) = open_session(&model_path, intra_threads, &opts_try, None);
+            let session = session?;
             let extra_ms = started.elapsed().as_millis();
             eprintln!(
                 "clip: session pooled ({} of {}) loaded in {}ms",
@@ -326,17 +478,24 @@ impl ClipEngine {
                 session_pool_size,
                 extra_ms
             );
-            sessions.push(Mutex::new(session));
+            sessions.push(Mutex::new(PooledSession::new(
+                session, image_size, &dummy.0, &dummy.1,
+            )?));
         }
 
         eprintln!(
-            "clip: loaded model in {}ms, cached text embeds in {}ms (model={}) eps={} pool={} intra_threads={}",
+            "clip: loaded model in {}ms, cached text embeds in {}ms (model={}) eps={} effective_eps={} pool={} intra_threads={} inter_threads={} parallel={} cpu_arena={} seq_len={}",
             model_load_ms,
             text_cache_ms,
             model_path.display(),
             eps_log,
+            effective_eps,
             session_pool_size,
-            intra_threads
+            intra_threads,
+            opts_try.inter_threads,
+            opts_try.parallel_execution,
+            opts_try.cpu_arena,
+            encoder.seq_len
         );
 
         Ok(Self {
@@ -349,45 +508,134 @@ impl ClipEngine {
             pixel_values_name,
             output_image_embeds,
             output_text_embeds,
-            dummy_input_ids: dummy.0,
-            dummy_attention_mask: dummy.1,
+            preprocess,
             category_text_embeds,
-            value_keep_embed,
-            value_drop_embed,
+            value_buckets,
+            tag_labels,
+            tag_embeds,
+            subcategory_embeds,
+            category_bias: Mutex::new(opts.category_bias.clone()),
+            text_encoder: encoder,
             model_load_ms,
             text_cache_ms,
             eps_log,
+            effective_eps,
+            intra_threads,
         })
     }
 
+    pub fn model_path(&self) -> &Path {
+        &self.model_path
+    }
+
+    pub fn eps_log(&self) -> &str {
+        &self.eps_log
+    }
+
+    pub fn effective_eps(&self) -> &str {
+        &self.effective_eps
+    }
+
+    pub fn session_pool_size(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn intra_threads(&self) -> usize {
+        self.intra_threads
+    }
+
+    pub fn model_load_ms(&self) -> u128 {
+        self.model_load_ms
+    }
+
+    pub fn text_cache_ms(&self) -> u128 {
+        self.text_cache_ms
+    }
+
+    pub fn preprocess_config(&self) -> &PreprocessConfig {
+        &self.preprocess
+    }
+
+    pub fn input_names(&self) -> (&str, &str, &str) {
+        (
+            &self.input_ids_name,
+            &self.attention_mask_name,
+            &self.pixel_values_name,
+        )
+    }
+
+    pub fn output_names(&self) -> (&str, &str) {
+        (&self.output_image_embeds, &self.output_text_embeds)
+    }
+
+    fn model_inputs(&self) -> ModelInputs<'_> {
+        ModelInputs {
+            input_ids_name: &self.input_ids_name,
+            attention_mask_name: &self.attention_mask_name,
+            pixel_values_name: &self.pixel_values_name,
+            image_size: self.preprocess.size as usize,
+        }
+    }
+
+    fn acquire_session(&self) -> Result<MutexGuard<'_, PooledSession>> {
+        acquire_pooled(&self.sessions, &self.rr)
+    }
+
+    pub fn set_category_bias(&self, bias: &HashMap<CategoryKey, f32>) {
+        *self.category_bias.lock() = bias.clone();
+    }
+
+    // Embeds a category prompt set without touching the cached one. Each prompt carries a weight
+    // in the category average. Categories missing from `prompts` (or with only blank prompts)
+    // keep the built-in prompts at weight 1.0.
+    pub fn category_embeds_for(
+        &self,
+        prompts: &HashMap<CategoryKey, Vec<(String, f32)>>,
+    ) -> Result<HashMap<CategoryKey, Vec<f32>>> {
+        let prompt_sets = custom_prompt_sets(prompts)?;
+        let dummy = self.text_encoder.encode("")?;
+        let mut guard = self.acquire_session()?;
+        cache_category_text_embeds(
+            &mut guard.session,
+            &self.text_encoder,
+            &dummy.0,
+            &dummy.1,
+            &self.model_inputs(),
+            &self.output_text_embeds,
+            &prompt_sets,
+        )
+    }
+
+    // `extra_bias` is added on top of the configured category bias for this image only.
     pub fn classify(
         &self,
         image_nchw: &[f32],
-    ) -> Result<(Scores, CategoryKey, Option<(bool, f32)>, String, u128)> {
-        let started = std::time::Instant::now();
-        let pixel = ndarray::Array4::<f32>::from_shape_vec((1, 3, 224, 224), image_nchw.to_vec())?;
-        let pixel_tensor = Tensor::from_array(pixel)?;
-
-        let ids = ndarray::Array2::<i64>::from_shape_vec((1, 77), self.dummy_input_ids.clone())?;
-        let mask =
-            ndarray::Array2::<i64>::from_shape_vec((1, 77), self.dummy_attention_mask.clone())?;
-        let ids_tensor = Tensor::from_array(ids)?;
-        let mask_tensor = Tensor::from_array(mask)?;
+        extra_bias: &[(CategoryKey, f32)],
+    ) -> Result<ClipClassification> {
+        self.classify_with_embeds(image_nchw, extra_bias, &self.category_text_embeds)
+    }
 
+    // `classify` against another set of category embeddings, e.g. from `category_embeds_for`.
+    pub fn classify_with_embeds(
+        &self,
+        image_nchw: &[f32],
+        extra_bias: &[(CategoryKey, f32)],
+        category_embeds: &HashMap<CategoryKey, Vec<f32>>,
+    ) -> Result<ClipClassification> {
+        let started = std::time::Instant::now();
         let run_image_only = RunOptions::new()?
             .with_outputs(OutputSelector::no_default().with(self.output_image_embeds.as_str()));
 
-        let idx = self.rr.fetch_add(1, Ordering::Relaxed) % self.sessions.len().max(1);
-        let mut session = self
-            .sessions
-            .get(idx)
-            .ok_or_else(|| anyhow!("clip session pool is empty"))?
-            .lock();
-        let outputs = session.run_with_options(
+        let mut guard = self.acquire_session()?;
+        let pooled = &mut *guard;
+        let (_shape, pixel) = pooled.pixel_values.extract_tensor_mut();
+        copy_pixels(pixel, image_nchw)?;
+
+        let outputs = pooled.session.run_with_options(
             ort::inputs![
-                self.input_ids_name.as_str() => &ids_tensor,
-                self.attention_mask_name.as_str() => &mask_tensor,
-                self.pixel_values_name.as_str() => &pixel_tensor,
+                self.input_ids_name.as_str() => &pooled.input_ids,
+                self.attention_mask_name.as_str() => &pooled.attention_mask,
+                self.pixel_values_name.as_str() => &pooled.pixel_values,
             ],
             &run_image_only,
         )?;
@@ -404,26 +652,27 @@ impl ClipEngine {
         let mut image_embed = data.to_vec();
         l2_normalize(&mut image_embed);
 
-        let value_logits = vec![
-            cosine_similarity(&image_embed, &self.value_keep_embed),
-            cosine_similarity(&image_embed, &self.value_drop_embed),
-        ];
-        let value_probs = softmax(&value_logits);
-        let keep_prob = value_probs.get(0).copied().unwrap_or(0.0);
-        let is_valuable = keep_prob >= 0.5;
+        let (value_probs, keep_prob, best_bucket) =
+            score_value_buckets(&self.value_buckets, &image_embed);
+        let value_log = self
+            .value_buckets
+            .iter()
+            .zip(value_probs.iter())
+            .map(|((name, _, _), p)| format!("{}={:.4}", name, p))
+            .collect::<Vec<_>>()
+            .join(", ");
 
-        let mut logits = Vec::<f32>::with_capacity(CATEGORY_KEYS.len());
-        for k in CATEGORY_KEYS {
-            let t = self
-                .category_text_embeds
-                .get(k)
-                .ok_or_else(|| anyhow!("missing text embedding for {}", k.as_str()))?;
-            logits.push(cosine_similarity(&image_embed, t));
-        }
-        let probs = softmax(&logits);
-        if probs.len() != CATEGORY_KEYS.len() {
-            return Err(anyhow!("softmax length mismatch"));
+        let mut bias = self.category_bias.lock().clone();
+        for (k, b) in extra_bias {
+            *bias.entry(*k).or_insert(0.0) += b;
         }
+        let bias_log = CATEGORY_KEYS
+            .iter()
+            .filter_map(|k| bias.get(k).filter(|b| **b != 0.0).map(|b| (k, b)))
+            .map(|(k, b)| format!("{}={:+.3}", k.as_str(), b))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let probs = category_probs(&image_embed, category_embeds, &bias)?;
         let scores = Scores {
             screenshot_document: probs[0],
             people: probs[1],
@@ -436,21 +685,131 @@ impl ClipEngine {
         };
         let (category, _top) = scores.top();
 
+        let mut tag_scores: Vec<(String, f32)> = self
+            .tag_labels
+            .iter()
+            .zip(self.tag_embeds.iter())
+            .map(|(label, t)| (label.clone(), cosine_similarity(&image_embed, t)))
+            .collect();
+        tag_scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let tag_log = tag_scores
+            .iter()
+            .take(10)
+            .map(|(label, sim)| format!("{}={:.4}", label, sim))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut subcategory = None;
+        let mut sub_log = String::new();
+        if let Some(subs) = self.subcategory_embeds.get(&category) {
+            let sub_logits: Vec<f32> = subs
+                .iter()
+                .map(|(_, t)| cosine_similarity(&image_embed, t))
+                .collect();
+            let sub_probs = softmax(&sub_logits);
+            sub_log = subs
+                .iter()
+                .zip(sub_probs.iter())
+                .map(|((label, _), p)| format!("{}={:.4}", label, p))
+                .collect::<Vec<_>>()
+                .join(", ");
+            subcategory = subs
+                .iter()
+                .zip(sub_probs.iter())
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|((label, _), p)| (label.clone(), *p));
+        }
+
         let inference_ms = started.elapsed().as_millis();
         let log = format!(
-            "engine: clip\nmodel_path: {model}\ntokenizer_path: {tok}\nmodel_load_ms: {load}\ntext_cache_ms: {cache}\nexecution_providers: {eps}\noutput_image_embeds: {oimg}\noutput_text_embeds: {otxt}\nvision_infer_ms: {infer}\nvalue_keep_prob: {keep_prob:.4}\n",
+            "engine: clip\nmodel_path: {model}\ntokenizer_path: {tok}\nmodel_load_ms: {load}\ntext_cache_ms: {cache}\nexecution_providers: {eps}\neffective_providers: {eff}\noutput_image_embeds: {oimg}\noutput_text_embeds: {otxt}\nvision_infer_ms: {infer}\nvalue_keep_prob: {keep_prob:.4}\nvalue_probs: {value_log}\npreprocess: {pre}\ncategory_bias: {bias_log}\ntag_similarities: {tag_log}\nsubcategory_probs: {sub_log}\n",
             model = self.model_path.display(),
             tok = self.tokenizer_path.display(),
             load = self.model_load_ms,
             cache = self.text_cache_ms,
             eps = self.eps_log,
+            eff = self.effective_eps,
             oimg = self.output_image_embeds,
             otxt = self.output_text_embeds,
             infer = inference_ms,
             keep_prob = keep_prob,
+            value_log = value_log,
+            pre = self.preprocess.describe(),
+            bias_log = bias_log,
+            tag_log = tag_log,
+            sub_log = sub_log,
         );
-        Ok((scores, category, Some((is_valuable, keep_prob)), log, inference_ms))
+        Ok(ClipClassification {
+            scores,
+            category,
+            valuable: best_bucket.map(|i| (self.value_buckets[i].1, keep_prob)),
+            value_bucket: best_bucket.map(|i| (self.value_buckets[i].0.clone(), value_probs[i])),
+            tag_scores,
+            subcategory,
+            log,
+            inference_ms,
+        })
+    }
+}
+
+// Softmax over the value buckets' similarity to the image. Returns every bucket's probability, the
+// summed probability of the `keep` buckets and the index of the most likely bucket.
+fn score_value_buckets(
+    buckets: &[(String, bool, Vec<f32>)],
+    image_embed: &[f32],
+) -> (Vec<f32>, f32, Option<usize>) {
+    let logits: Vec<f32> = buckets
+        .iter()
+        .map(|(_, _, t)| cosine_similarity(image_embed, t))
+        .collect();
+    let probs = softmax(&logits);
+    let keep_prob = buckets
+        .iter()
+        .zip(probs.iter())
+        .filter(|((_, keep, _), _)| *keep)
+        .map(|(_, p)| *p)
+        .sum();
+    let best = (0..probs.len()).max_by(|&a, &b| probs[a].total_cmp(&probs[b]));
+    (probs, keep_prob, best)
+}
+
+// Softmax over the image/category cosine similarities, in `CATEGORY_KEYS` order. `bias` is added
+// to each category's logit before the softmax; non-finite values are ignored.
+fn category_probs(
+    image_embed: &[f32],
+    category_embeds: &HashMap<CategoryKey, Vec<f32>>,
+    bias: &HashMap<CategoryKey, f32>,
+) -> Result<Vec<f32>> {
+    let mut logits = Vec::<f32>::with_capacity(CATEGORY_KEYS.len());
+    for k in CATEGORY_KEYS {
+        let t = category_embeds
+            .get(k)
+            .ok_or_else(|| anyhow!("missing text embedding for {}", k.as_str()))?;
+        let b = bias
+            .get(k)
+            .copied()
+            .filter(|b| b.is_finite())
+            .unwrap_or(0.0);
+        logits.push(cosine_similarity(image_embed, t) + b);
     }
+    let probs = softmax(&logits);
+    if probs.len() != CATEGORY_KEYS.len() {
+        return Err(anyhow!("softmax length mismatch"));
+    }
+    Ok(probs)
+}
+
+// Refills a session's preallocated pixel buffer in place instead of building a new tensor.
+fn copy_pixels(pixel: &mut [f32], image_nchw: &[f32]) -> Result<()> {
+    if pixel.len() != image_nchw.len() {
+        return Err(anyhow!(
+            "expected {} pixel values, got {}",
+            pixel.len(),
+            image_nchw.len()
+        ));
+    }
+    pixel.copy_from_slice(image_nchw);
+    Ok(())
 }
 
 fn pick_output_name<'a>(outputs: Vec<&'a str>, priorities: &[&str]) -> Result<String> {
@@ -468,113 +827,207 @@ fn pick_output_name<'a>(outputs: Vec<&'a str>, priorities: &[&str]) -> Result<St
     ))
 }
 
-fn encode_fixed_77(tokenizer: &Tokenizer, text: &str, pad_id: i64) -> Result<(Vec<i64>, Vec<i64>)> {
-    let encoding = tokenizer
-        .encode(text, true)
-        .map_err(|e| anyhow!(e.to_string()))?;
-    let mut ids: Vec<i64> = encoding.get_ids().iter().map(|v| *v as i64).collect();
-    let mut mask: Vec<i64> = encoding
-        .get_attention_mask()
-        .iter()
-        .map(|v| *v as i64)
-        .collect();
+// OpenAI CLIP pads with `<|endoftext|>`; other exports use their own EOS/pad token. Padding
+// positions are masked out, so the exact id matters less than having one.
+const PAD_TOKEN_CANDIDATES: [&str; 4] = ["<|endoftext|>", "</s>", "[PAD]", "<pad>"];
 
-    const MAX_LEN: usize = 77;
-    if ids.len() > MAX_LEN {
-        ids.truncate(MAX_LEN);
-        mask.truncate(MAX_LEN);
+fn resolve_pad_id(tokenizer: &Tokenizer) -> i64 {
+    if let Some(id) = PAD_TOKEN_CANDIDATES
+        .iter()
+        .find_map(|t| tokenizer.token_to_id(t))
+    {
+        return id as i64;
+    }
+    if let Some(padding) = tokenizer.get_padding() {
+        eprintln!(
+            "warning: CLIP tokenizer has none of {:?}; padding with configured pad_token {:?} (id {})",
+            PAD_TOKEN_CANDIDATES, padding.pad_token, padding.pad_id
+        );
+        return padding.pad_id as i64;
     }
-    while ids.len() < MAX_LEN {
-        ids.push(pad_id);
-        mask.push(0);
+    eprintln!(
+        "warning: CLIP tokenizer has none of {:?} and no pad_token; padding with id 0",
+        PAD_TOKEN_CANDIDATES
+    );
+    0
+}
+
+// OpenAI CLIP's context length; used when the model's `input_ids` length is dynamic.
+const DEFAULT_SEQ_LEN: usize = 77;
+
+// Long-context CLIP variants are exported with other fixed lengths (e.g. 64 or 248).
+fn resolve_seq_len(inputs: &[Input], input_ids_name: &str) -> usize {
+    inputs
+        .iter()
+        .find(|i| i.name == input_ids_name)
+        .and_then(|i| i.input_type.tensor_shape())
+        .and_then(|shape| shape.get(1).copied())
+        .filter(|&len| len > 0)
+        .map_or(DEFAULT_SEQ_LEN, |len| len as usize)
+}
+
+// Tokenizer plus the padding the model expects: every prompt becomes exactly `seq_len` ids.
+struct TextEncoder {
+    tokenizer: Tokenizer,
+    pad_id: i64,
+    seq_len: usize,
+}
+
+impl TextEncoder {
+    fn encode(&self, text: &str) -> Result<(Vec<i64>, Vec<i64>)> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let mut ids: Vec<i64> = encoding.get_ids().iter().map(|v| *v as i64).collect();
+        let mut mask: Vec<i64> = encoding
+            .get_attention_mask()
+            .iter()
+            .map(|v| *v as i64)
+            .collect();
+
+        let max_len = self.seq_len;
+        if ids.len() > max_len {
+            ids.truncate(max_len);
+            mask.truncate(max_len);
+        }
+        while ids.len() < max_len {
+            ids.push(self.pad_id);
+            mask.push(0);
+        }
+        while mask.len() < max_len {
+            mask.push(0);
+        }
+        Ok((ids, mask))
     }
-    while mask.len() < MAX_LEN {
-        mask.push(0);
+}
+
+// The model's input names and image size, needed by every run of the full model. Text rows still
+// carry a dummy image and image rows dummy text.
+struct ModelInputs<'a> {
+    input_ids_name: &'a str,
+    attention_mask_name: &'a str,
+    pixel_values_name: &'a str,
+    image_size: usize,
+}
+
+// `prompts` over the built-in set: blank prompts are dropped and a category left with none keeps
+// the built-in prompts at weight 1.0.
+fn custom_prompt_sets(
+    prompts: &HashMap<CategoryKey, Vec<(String, f32)>>,
+) -> Result<PromptSets<'_>> {
+    let mut prompt_sets = Vec::new();
+    for (k, builtin) in all_category_prompts() {
+        let custom: Vec<(&str, f32)> = prompts
+            .get(&k)
+            .into_iter()
+            .flatten()
+            .map(|(p, w)| (p.trim(), *w))
+            .filter(|(p, _)| !p.is_empty())
+            .collect();
+        if let Some((p, w)) = custom.iter().find(|(_, w)| !(w.is_finite() && *w > 0.0)) {
+            return Err(anyhow!("prompt weight must be positive: {} ({})", p, w));
+        }
+        if custom.is_empty() {
+            prompt_sets.push((k, builtin.iter().map(|p| (*p, 1.0)).collect()));
+        } else {
+            prompt_sets.push((k, custom));
+        }
     }
-    Ok((ids, mask))
+    Ok(prompt_sets)
 }
 
 fn cache_category_text_embeds(
     session: &mut Session,
-    tokenizer: &Tokenizer,
-    pad_id: i64,
+    encoder: &TextEncoder,
     dummy_ids: &[i64],
     dummy_mask: &[i64],
-    input_ids_name: &str,
-    attention_mask_name: &str,
-    pixel_values_name: &str,
+    inputs: &ModelInputs,
     output_text_embeds: &str,
+    prompt_sets: &[(CategoryKey, Vec<(&str, f32)>)],
+) -> Result<HashMap<CategoryKey, Vec<f32>>> {
+    let out_map = weighted_category_embeds(prompt_sets, |prompts| {
+        embed_text_batch(session, encoder, inputs, output_text_embeds, prompts)
+    })?;
+
+    // Sanity: ensure dummy ids/mask length is correct (avoid unused vars).
+    if dummy_ids.len() != encoder.seq_len || dummy_mask.len() != encoder.seq_len {
+        return Err(anyhow!(
+            "dummy text input must be length {}",
+            encoder.seq_len
+        ));
+    }
+
+    Ok(out_map)
+}
+
+// `embed` returns one raw row per prompt, in order.
+fn weighted_category_embeds(
+    prompt_sets: &[(CategoryKey, Vec<(&str, f32)>)],
+    embed: impl FnOnce(&[&str]) -> Result<Vec<Vec<f32>>>,
 ) -> Result<HashMap<CategoryKey, Vec<f32>>> {
     // Flatten prompts
-    let prompt_sets = all_category_prompts();
-    let mut flat_prompts: Vec<(CategoryKey, String)> = Vec::new();
+    let mut flat_prompts: Vec<(CategoryKey, String, f32)> = Vec::new();
     for (k, arr) in prompt_sets {
-        for s in arr {
-            flat_prompts.push((k, s.to_string()));
+        for (s, w) in arr {
+            flat_prompts.push((*k, s.to_string(), *w));
         }
     }
     if flat_prompts.is_empty() {
         return Err(anyhow!("no prompts"));
     }
 
-    let mut ids_all: Vec<i64> = Vec::with_capacity(flat_prompts.len() * 77);
-    let mut mask_all: Vec<i64> = Vec::with_capacity(flat_prompts.len() * 77);
-    for (_, p) in flat_prompts.iter() {
-        let (ids, mask) = encode_fixed_77(tokenizer, p, pad_id)?;
-        ids_all.extend_from_slice(&ids);
-        mask_all.extend_from_slice(&mask);
-    }
-    let n = flat_prompts.len();
-    let ids = ndarray::Array2::<i64>::from_shape_vec((n, 77), ids_all)?;
-    let mask = ndarray::Array2::<i64>::from_shape_vec((n, 77), mask_all)?;
-    let ids_tensor = Tensor::from_array(ids)?;
-    let mask_tensor = Tensor::from_array(mask)?;
+    let prompts: Vec<&str> = flat_prompts.iter().map(|(_, p, _)| p.as_str()).collect();
+    let rows = embed(&prompts)?;
+    let weights: Vec<(CategoryKey, f32)> = flat_prompts.iter().map(|(k, _, w)| (*k, *w)).collect();
+    average_category_embeds(&weights, &rows)
+}
 
-    // Dummy pixel values (text-only run, only outputs text embeddings)
-    // Some exported CLIP ONNX graphs require matching batch sizes for all inputs,
-    // so we size pixel_values to the same batch as text.
-    let dummy_pixel = ndarray::Array4::<f32>::zeros((n, 3, 224, 224));
-    let pixel_tensor = Tensor::from_array(dummy_pixel)?;
+fn cache_text_embed_for_prompts(
+    session: &mut Session,
+    encoder: &TextEncoder,
+    inputs: &ModelInputs,
+    output_text_embeds: &str,
+    prompts: &[&str],
+) -> Result<Vec<f32>> {
+    let rows = embed_text_batch(session, encoder, inputs, output_text_embeds, prompts)?;
+    Ok(average_embeds(&rows))
+}
 
-    let run_text_only =
-        RunOptions::new()?.with_outputs(OutputSelector::no_default().with(output_text_embeds));
+// Plain mean of one prompt group's rows, L2-normalized.
+fn average_embeds(rows: &[Vec<f32>]) -> Vec<f32> {
+    let n = rows.len();
+    let d = rows[0].len();
 
-    let outputs = session.run_with_options(
-        ort::inputs![
-            input_ids_name => &ids_tensor,
-            attention_mask_name => &mask_tensor,
-            pixel_values_name => &pixel_tensor,
-        ],
-        &run_text_only,
-    )?;
-    let out = outputs
-        .iter()
-        .next()
-        .map(|(_, v)| v)
-        .ok_or_else(|| anyhow!("missing text embeddings output"))?;
-    let (_shape, data) = out.try_extract_tensor::<f32>()?;
-    if data.is_empty() {
-        return Err(anyhow!("empty text embeddings"));
+    let mut avg = vec![0.0f32; d];
+    for row in rows.iter() {
+        for j in 0..d {
+            avg[j] += row[j];
+        }
     }
-
-    // shape is expected [n, d]
-    let d = data.len() / n;
-    if d == 0 {
-        return Err(anyhow!("invalid text embeddings shape"));
+    let denom = (n as f32).max(1.0);
+    for x in avg.iter_mut() {
+        *x /= denom;
     }
+    l2_normalize(&mut avg);
+    avg
+}
 
-    // Aggregate embeddings per category: average across prompts, then L2 normalize.
+// Aggregate embeddings per category: weighted average across prompts, then L2 normalize.
+// `weights[i]` tags `rows[i]` with its category and prompt weight.
+fn average_category_embeds(
+    weights: &[(CategoryKey, f32)],
+    rows: &[Vec<f32>],
+) -> Result<HashMap<CategoryKey, Vec<f32>>> {
+    let d = rows.first().map(|r| r.len()).unwrap_or(0);
     let mut sums: HashMap<CategoryKey, Vec<f32>> = HashMap::new();
-    let mut counts: HashMap<CategoryKey, usize> = HashMap::new();
-    for (i, (k, _)) in flat_prompts.into_iter().enumerate() {
-        let start = i * d;
-        let end = start + d;
-        let vec = &data[start..end];
-        let entry = sums.entry(k).or_insert_with(|| vec![0.0f32; d]);
+    let mut counts: HashMap<CategoryKey, f32> = HashMap::new();
+    for ((k, w), vec) in weights.iter().zip(rows) {
+        let entry = sums.entry(*k).or_insert_with(|| vec![0.0f32; d]);
         for j in 0..d {
-            entry[j] += vec[j];
+            entry[j] += w * vec[j];
         }
-        *counts.entry(k).or_insert(0) += 1;
+        *counts.entry(*k).or_insert(0.0) += w;
     }
 
     let mut out_map: HashMap<CategoryKey, Vec<f32>> = HashMap::new();
@@ -582,61 +1035,107 @@ fn cache_category_text_embeds(
         let mut v = sums
             .remove(k)
             .ok_or_else(|| anyhow!("missing text sum for {}", k.as_str()))?;
-        let c = *counts.get(k).unwrap_or(&1) as f32;
+        let c = *counts.get(k).unwrap_or(&1.0);
         for x in v.iter_mut() {
             *x /= c;
         }
         l2_normalize(&mut v);
         out_map.insert(*k, v);
     }
+    Ok(out_map)
+}
 
-    // Sanity: ensure dummy ids/mask length is correct (avoid unused vars).
-    if dummy_ids.len() != 77 || dummy_mask.len() != 77 {
-        return Err(anyhow!("dummy text input must be length 77"));
+// One normalized embedding per prompt. Runs in small batches because every text row also needs a
+// dummy image.
+fn cache_tag_embeds(
+    session: &mut Session,
+    encoder: &TextEncoder,
+    inputs: &ModelInputs,
+    output_text_embeds: &str,
+    prompts: &[&str],
+) -> Result<Vec<Vec<f32>>> {
+    let mut out = Vec::with_capacity(prompts.len());
+    for chunk in prompts.chunks(16) {
+        let rows = embed_text_batch(session, encoder, inputs, output_text_embeds, chunk)?;
+        for mut row in rows {
+            l2_normalize(&mut row);
+            out.push(row);
+        }
     }
+    Ok(out)
+}
 
-    Ok(out_map)
+// One averaged embedding per sub-category, grouped by parent. Parents without any usable
+// sub-category are left out so `classify` skips the second stage for them.
+fn cache_subcategory_embeds(
+    session: &mut Session,
+    encoder: &TextEncoder,
+    inputs: &ModelInputs,
+    output_text_embeds: &str,
+    subcategories: &SubcategoryPrompts,
+) -> Result<SubcategoryEmbeds> {
+    let mut out = HashMap::new();
+    for (parent, subs) in subcategories {
+        let mut embeds = Vec::with_capacity(subs.len());
+        for (label, prompts) in subs {
+            let prompts: Vec<&str> = prompts.iter().map(|p| p.as_str()).collect();
+            if prompts.is_empty() {
+                continue;
+            }
+            let embed = cache_text_embed_for_prompts(
+                session,
+                encoder,
+                inputs,
+                output_text_embeds,
+                &prompts,
+            )?;
+            embeds.push((label.clone(), embed));
+        }
+        if !embeds.is_empty() {
+            out.insert(*parent, embeds);
+        }
+    }
+    Ok(out)
 }
 
-fn cache_text_embed_for_prompts(
+// Raw (unnormalized) text embeddings, one row per prompt.
+fn embed_text_batch(
     session: &mut Session,
-    tokenizer: &Tokenizer,
-    pad_id: i64,
-    input_ids_name: &str,
-    attention_mask_name: &str,
-    pixel_values_name: &str,
+    encoder: &TextEncoder,
+    inputs: &ModelInputs,
     output_text_embeds: &str,
     prompts: &[&str],
-) -> Result<Vec<f32>> {
+) -> Result<Vec<Vec<f32>>> {
     if prompts.is_empty() {
         return Err(anyhow!("no prompts for embed cache"));
     }
 
     let n = prompts.len();
-    let mut ids_all: Vec<i64> = Vec::with_capacity(n * 77);
-    let mut mask_all: Vec<i64> = Vec::with_capacity(n * 77);
+    let mut ids_all: Vec<i64> = Vec::with_capacity(n * encoder.seq_len);
+    let mut mask_all: Vec<i64> = Vec::with_capacity(n * encoder.seq_len);
     for p in prompts.iter() {
-        let (ids, mask) = encode_fixed_77(tokenizer, p, pad_id)?;
+        let (ids, mask) = encoder.encode(p)?;
         ids_all.extend_from_slice(&ids);
         mask_all.extend_from_slice(&mask);
     }
-    let ids = ndarray::Array2::<i64>::from_shape_vec((n, 77), ids_all)?;
-    let mask = ndarray::Array2::<i64>::from_shape_vec((n, 77), mask_all)?;
+    let ids = ndarray::Array2::<i64>::from_shape_vec((n, encoder.seq_len), ids_all)?;
+    let mask = ndarray::Array2::<i64>::from_shape_vec((n, encoder.seq_len), mask_all)?;
     let ids_tensor = Tensor::from_array(ids)?;
     let mask_tensor = Tensor::from_array(mask)?;
 
     // Some exported CLIP ONNX graphs require matching batch sizes for all inputs,
     // so we size pixel_values to the same batch as text.
-    let dummy_pixel = ndarray::Array4::<f32>::zeros((n, 3, 224, 224));
+    let size = inputs.image_size;
+    let dummy_pixel = ndarray::Array4::<f32>::zeros((n, 3, size, size));
     let pixel_tensor = Tensor::from_array(dummy_pixel)?;
 
     let run_text_only =
         RunOptions::new()?.with_outputs(OutputSelector::no_default().with(output_text_embeds));
     let outputs = session.run_with_options(
         ort::inputs![
-            input_ids_name => &ids_tensor,
-            attention_mask_name => &mask_tensor,
-            pixel_values_name => &pixel_tensor,
+            inputs.input_ids_name => &ids_tensor,
+            inputs.attention_mask_name => &mask_tensor,
+            inputs.pixel_values_name => &pixel_tensor,
         ],
         &run_text_only,
     )?;
@@ -654,36 +1153,28 @@ fn cache_text_embed_for_prompts(
     if d == 0 {
         return Err(anyhow!("invalid text embeddings shape"));
     }
-
-    let mut avg = vec![0.0f32; d];
-    for i in 0..n {
-        let start = i * d;
-        for j in 0..d {
-            avg[j] += data[start + j];
-        }
-    }
-    let denom = (n as f32).max(1.0);
-    for x in avg.iter_mut() {
-        *x /= denom;
-    }
-    l2_normalize(&mut avg);
-    Ok(avg)
+    Ok(data.chunks(d).take(n).map(|row| row.to_vec()).collect())
 }
 
 fn smoke_test_vision(
     session: &mut Session,
-    input_ids_name: &str,
-    attention_mask_name: &str,
-    pixel_values_name: &str,
+    inputs: &ModelInputs,
     output_image_embeds: &str,
     dummy_input_ids: &[i64],
     dummy_attention_mask: &[i64],
 ) -> Result<()> {
-    let pixel = ndarray::Array4::<f32>::zeros((1, 3, 224, 224));
+    let size = inputs.image_size;
+    let pixel = ndarray::Array4::<f32>::zeros((1, 3, size, size));
     let pixel_tensor = Tensor::from_array(pixel)?;
 
-    let ids = ndarray::Array2::<i64>::from_shape_vec((1, 77), dummy_input_ids.to_vec())?;
-    let mask = ndarray::Array2::<i64>::from_shape_vec((1, 77), dummy_attention_mask.to_vec())?;
+    let ids = ndarray::Array2::<i64>::from_shape_vec(
+        (1, dummy_input_ids.len()),
+        dummy_input_ids.to_vec(),
+    )?;
+    let mask = ndarray::Array2::<i64>::from_shape_vec(
+        (1, dummy_attention_mask.len()),
+        dummy_attention_mask.to_vec(),
+    )?;
     let ids_tensor = Tensor::from_array(ids)?;
     let mask_tensor = Tensor::from_array(mask)?;
 
@@ -692,9 +1183,9 @@ fn smoke_test_vision(
 
     let outputs = session.run_with_options(
         ort::inputs![
-            input_ids_name => &ids_tensor,
-            attention_mask_name => &mask_tensor,
-            pixel_values_name => &pixel_tensor,
+            inputs.input_ids_name => &ids_tensor,
+            inputs.attention_mask_name => &mask_tensor,
+            inputs.pixel_values_name => &pixel_tensor,
         ],
         &run_image_only,
     )?;
@@ -733,6 +1224,30 @@ fn resolve_input_names(session: &Session) -> Result<(String, String, String)> {
     ))
 }
 
+// Best effort: only NVIDIA GPUs can be enumerated (via `nvidia-smi`). Returns an empty list when
+// the tool is missing, in which case device ids are passed through unchecked.
+pub fn list_gpu_devices() -> Vec<ClipGpuDevice> {
+    let output = std::process::Command::new("nvidia-smi")
+        .args(["--query-gpu=index,name", "--format=csv,noheader"])
+        .output();
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (id, name) = line.split_once(',')?;
+            Some(ClipGpuDevice {
+                id: id.trim().parse().ok()?,
+                name: name.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
 fn provider_cap(ep: &impl ExecutionProvider) -> (bool, bool) {
     let supported = ep.supported_by_platform();
     let available = if supported {
@@ -743,9 +1258,232 @@ fn provider_cap(ep: &impl ExecutionProvider) -> (bool, bool) {
     (supported, available)
 }
 
-fn build_execution_providers(opts: &ClipEngineOptions) -> (Vec<ExecutionProviderDispatch>, String) {
+// Category and value prompts share one model run; each group's rows are then averaged exactly as
+// `cache_category_text_embeds` and `cache_text_embed_for_prompts` would.
+fn cache_text_embeds(
+    session: &mut Session,
+    encoder: &TextEncoder,
+    inputs: &ModelInputs,
+    output_text_embeds: &str,
+    value_buckets: &[(String, bool, Vec<String>)],
+) -> Result<(CategoryEmbeds, Vec<Vec<f32>>)> {
+    combined_text_embeds(value_buckets, |prompts| {
+        embed_text_batch(session, encoder, inputs, output_text_embeds, prompts)
+    })
+}
+
+// `embed` returns one raw row per prompt, in order.
+fn combined_text_embeds(
+    value_buckets: &[(String, bool, Vec<String>)],
+    embed: impl FnOnce(&[&str]) -> Result<Vec<Vec<f32>>>,
+) -> Result<(CategoryEmbeds, Vec<Vec<f32>>)> {
+    let mut prompts: Vec<&str> = Vec::new();
+    let mut weights: Vec<(CategoryKey, f32)> = Vec::new();
+    for (k, category_prompts) in all_category_prompts() {
+        for p in category_prompts {
+            prompts.push(p);
+            weights.push((k, 1.0));
+        }
+    }
+    let mut value_ranges = Vec::with_capacity(value_buckets.len());
+    for (name, _, bucket_prompts) in value_buckets {
+        if bucket_prompts.is_empty() {
+            return Err(anyhow!("no prompts for value bucket {}", name));
+        }
+        let start = prompts.len();
+        prompts.extend(bucket_prompts.iter().map(|p| p.as_str()));
+        value_ranges.push(start..prompts.len());
+    }
+
+    let rows = embed(&prompts)?;
+    if rows.len() != prompts.len() {
+        return Err(anyhow!(
+            "expected {} text embeddings, got {}",
+            prompts.len(),
+            rows.len()
+        ));
+    }
+    let category = average_category_embeds(&weights, &rows[..weights.len()])?;
+    let value = value_ranges
+        .into_iter()
+        .map(|range| average_embeds(&rows[range]))
+        .collect();
+    Ok((category, value))
+}
+
+// Returns the providers actually registered alongside the result so a failure can be pinned on
+// the right one.
+fn open_session(
+    model_path: &Path,
+    intra_threads: usize,
+    opts: &ClipEngineOptions,
+    profile_prefix: Option<&Path>,
+) -> (Result<Session>, Vec<&'static str>) {
+    let new_builder = || -> Result<_> {
+        let builder =
+            Session::builder()?.with_optimization_level(GraphOptimizationLevel::Level3)?;
+        let builder = apply_threading(builder, intra_threads, opts)?;
+        Ok(match profile_prefix {
+            Some(prefix) => builder.with_profiling(prefix)?,
+            None => builder,
+        })
+    };
+    let builder = match new_builder() {
+        Ok(b) => b,
+        Err(e) => return (Err(e), Vec::new()),
+    };
+    let (eps, active) = build_execution_providers(opts);
+    let (builder, active) = match builder.with_execution_providers(eps) {
+        Ok(b) => (b, active),
+        Err(e) => {
+            eprintln!(
+                "clip: failed to apply execution providers (fallback to CPU). err={}",
+                e
+            );
+            match new_builder() {
+                Ok(b) => (b, Vec::new()),
+                Err(e) => return (Err(e), Vec::new()),
+            }
+        }
+    };
+    let session = builder.commit_from_file(model_path).map_err(Into::into);
+    (session, active)
+}
+
+// Retries `attempt`, dropping the highest-priority registered provider after each failure until
+// it succeeds or only CPU is left. Disabled providers are appended to `failed`.
+// The thread settings `open_session` passes to ORT, behind a trait so tests can record them
+// without loading a model.
+trait SessionThreading: Sized {
+    fn intra_threads(self, threads: usize) -> Result<Self>;
+    fn inter_threads(self, threads: usize) -> Result<Self>;
+    fn parallel_execution(self, parallel: bool) -> Result<Self>;
+}
+
+impl SessionThreading for SessionBuilder {
+    fn intra_threads(self, threads: usize) -> Result<Self> {
+        Ok(self.with_intra_threads(threads)?)
+    }
+
+    fn inter_threads(self, threads: usize) -> Result<Self> {
+        Ok(self.with_inter_threads(threads)?)
+    }
+
+    fn parallel_execution(self, parallel: bool) -> Result<Self> {
+        Ok(self.with_parallel_execution(parallel)?)
+    }
+}
+
+fn apply_threading<B: SessionThreading>(
+    builder: B,
+    intra_threads: usize,
+    opts: &ClipEngineOptions,
+) -> Result<B> {
+    builder
+        .intra_threads(intra_threads)?
+        .inter_threads(opts.inter_threads.max(1))?
+        .parallel_execution(opts.parallel_execution)
+}
+
+fn with_ep_fallback<T>(
+    opts: &mut ClipEngineOptions,
+    failed: &mut Vec<&'static str>,
+    mut attempt: impl FnMut(&ClipEngineOptions) -> (Result<T>, Vec<&'static str>),
+) -> Result<(T, Vec<&'static str>)> {
+    loop {
+        let (result, active) = attempt(opts);
+        match result {
+            Ok(v) => return Ok((v, active)),
+            Err(e) => {
+                let Some(ep) = disable_fallible_ep(opts, &active) else {
+                    return Err(e);
+                };
+                eprintln!(
+                    "clip: session build failed with {} enabled, retrying without it. err={}",
+                    ep, e
+                );
+                failed.push(ep);
+            }
+        }
+    }
+}
+
+// `active` is in priority order (as registered by `build_execution_providers`), so the first entry
+// is the one to give up on.
+fn disable_fallible_ep(
+    opts: &mut ClipEngineOptions,
+    active: &[&'static str],
+) -> Option<&'static str> {
+    if !opts.allow_ep_fallback || !opts.ep_auto {
+        return None;
+    }
+    let ep = *active.first()?;
+    match ep {
+        "coreml" => opts.ep_coreml = false,
+        "tensorrt" => opts.ep_tensorrt = false,
+        "cuda" => opts.ep_cuda = false,
+        "rocm" => opts.ep_rocm = false,
+        "directml" => opts.ep_directml = false,
+        "openvino" => opts.ep_openvino = false,
+        _ => return None,
+    }
+    Some(ep)
+}
+
+// Stops the session's profiling and reads which provider ran each node, e.g.
+// "coreml(3 nodes)+cpu(12 nodes)", busiest first. Names match `format_eps_log`.
+fn effective_providers(session: &mut Session) -> Result<String> {
+    let path = session.end_profiling()?;
+    let raw = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    providers_from_profile(&raw?)
+}
+
+fn providers_from_profile(raw: &str) -> Result<String> {
+    let events: Vec<serde_json::Value> = serde_json::from_str(raw)?;
+    let mut nodes: HashMap<String, HashSet<&str>> = HashMap::new();
+    for event in &events {
+        if event["cat"] != "Node" {
+            continue;
+        }
+        let (Some(name), Some(provider)) =
+            (event["name"].as_str(), event["args"]["provider"].as_str())
+        else {
+            continue;
+        };
+        if let Some(node) = name.strip_suffix("_kernel_time") {
+            let ep = match provider.trim_end_matches("ExecutionProvider") {
+                "Dml" => "directml".to_string(),
+                other => other.to_lowercase(),
+            };
+            nodes.entry(ep).or_default().insert(node);
+        }
+    }
+    if nodes.is_empty() {
+        return Err(anyhow!("profile has no node events"));
+    }
+    let mut counts: Vec<(String, usize)> = nodes.into_iter().map(|(ep, n)| (ep, n.len())).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(counts
+        .iter()
+        .map(|(ep, n)| format!("{}({} nodes)", ep, n))
+        .collect::<Vec<_>>()
+        .join("+"))
+}
+
+fn format_eps_log(active: &[&str], failed: &[&str]) -> String {
+    let mut parts: Vec<String> = failed.iter().map(|ep| format!("{}(failed)", ep)).collect();
+    parts.extend(active.iter().map(|ep| ep.to_string()));
+    parts.push("cpu".to_string());
+    parts.join("+")
+}
+
+fn build_execution_providers(
+    opts: &ClipEngineOptions,
+) -> (Vec<ExecutionProviderDispatch>, Vec<&'static str>) {
     let mut eps: Vec<ExecutionProviderDispatch> = Vec::new();
     let mut enabled: Vec<&'static str> = Vec::new();
+    let device_id = opts.gpu_device_id as i32;
 
     if opts.ep_auto {
         if opts.ep_coreml {
@@ -760,8 +1498,23 @@ fn build_execution_providers(opts: &ClipEngineOptions) -> (Vec<ExecutionProvider
                 enabled.push("coreml");
             }
         }
+        if opts.ep_tensorrt {
+            let mut ep = TensorRTExecutionProvider::default().with_device_id(device_id);
+            if let Some(dir) = &opts.tensorrt_cache_dir {
+                ep = ep
+                    .with_engine_cache(true)
+                    .with_engine_cache_path(dir.display())
+                    .with_timing_cache(true)
+                    .with_timing_cache_path(dir.display());
+            }
+            let (supported, available) = provider_cap(&ep);
+            if supported && available {
+                eps.push(ep.build());
+                enabled.push("tensorrt");
+            }
+        }
         if opts.ep_cuda {
-            let ep = CUDAExecutionProvider::default();
+            let ep = CUDAExecutionProvider::default().with_device_id(device_id);
             let (supported, available) = provider_cap(&ep);
             if supported && available {
                 eps.push(ep.build());
@@ -777,7 +1530,7 @@ fn build_execution_providers(opts: &ClipEngineOptions) -> (Vec<ExecutionProvider
             }
         }
         if opts.ep_directml {
-            let ep = DirectMLExecutionProvider::default();
+            let ep = DirectMLExecutionProvider::default().with_device_id(device_id);
             let (supported, available) = provider_cap(&ep);
             if supported && available {
                 eps.push(ep.build());
@@ -795,10 +1548,450 @@ fn build_execution_providers(opts: &ClipEngineOptions) -> (Vec<ExecutionProvider
     }
 
     // Always include CPU as last fallback.
-    eps.push(CPUExecutionProvider::default().build());
-    if enabled.is_empty() {
-        (eps, "cpu".to_string())
-    } else {
-        (eps, format!("{}+cpu", enabled.join("+")))
+    eps.push(
+        CPUExecutionProvider::default()
+            .with_arena_allocator(opts.cpu_arena)
+            .build(),
+    );
+    (eps, enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ort::tensor::{Shape, SymbolicDimensions, TensorElementType};
+    use ort::value::ValueType;
+    use std::str::FromStr;
+
+    fn ids_input(name: &str, dims: &[i64]) -> Input {
+        Input {
+            name: name.to_string(),
+            input_type: ValueType::Tensor {
+                ty: TensorElementType::Int64,
+                shape: Shape::new(dims.iter().copied()),
+                dimension_symbols: SymbolicDimensions::empty(dims.len()),
+            },
+        }
+    }
+
+    fn seq_len_for(dims: &[i64]) -> usize {
+        resolve_seq_len(&[ids_input("input_ids", dims)], "input_ids")
+    }
+
+    fn encoder(seq_len: usize) -> TextEncoder {
+        let tokenizer = Tokenizer::from_str(
+            r#"{
+                "version": "1.0",
+                "truncation": null,
+                "padding": null,
+                "added_tokens": [],
+                "normalizer": null,
+                "pre_tokenizer": {"type": "Whitespace"},
+                "post_processor": null,
+                "decoder": null,
+                "model": {
+                    "type": "WordLevel",
+                    "vocab": {"<unk>": 0, "<pad>": 1, "a": 2, "photo": 3, "of": 4, "cat": 5},
+                    "unk_token": "<unk>"
+                }
+            }"#,
+        )
+        .unwrap();
+        TextEncoder {
+            tokenizer,
+            pad_id: 1,
+            seq_len,
+        }
+    }
+
+    #[test]
+    fn seq_len_comes_from_a_static_input_ids_shape() {
+        let inputs = [
+            ids_input("pixel_values", &[1, 3, 224, 224]),
+            ids_input("input_ids", &[1, 64]),
+        ];
+        assert_eq!(resolve_seq_len(&inputs, "input_ids"), 64);
+        assert_eq!(seq_len_for(&[-1, 248]), 248);
+    }
+
+    #[test]
+    fn dynamic_or_missing_seq_len_falls_back_to_77() {
+        assert_eq!(seq_len_for(&[-1, -1]), 77);
+        assert_eq!(seq_len_for(&[-1]), 77);
+        assert_eq!(
+            resolve_seq_len(&[ids_input("text", &[1, 64])], "input_ids"),
+            77
+        );
+    }
+
+    #[test]
+    fn encode_pads_and_truncates_to_the_model_seq_len() {
+        let (ids, mask) = encoder(8).encode("a photo of a cat").unwrap();
+        assert_eq!(ids, [2, 3, 4, 2, 5, 1, 1, 1]);
+        assert_eq!(mask, [1, 1, 1, 1, 1, 0, 0, 0]);
+
+        let (ids, mask) = encoder(3).encode("a photo of a cat").unwrap();
+        assert_eq!(ids, [2, 3, 4]);
+        assert_eq!(mask, [1, 1, 1]);
+
+        let long = vec!["cat"; 100].join(" ");
+        let (ids, mask) = encoder(64).encode(&long).unwrap();
+        assert_eq!((ids.len(), mask.len()), (64, 64));
+    }
+
+    #[test]
+    fn three_value_buckets_pick_the_closest_one() {
+        // Unit embeddings along separate axes; the image leans towards `maybe`.
+        let buckets = vec![
+            ("keep".to_string(), true, vec![1.0, 0.0, 0.0]),
+            ("maybe".to_string(), false, vec![0.0, 1.0, 0.0]),
+            ("drop".to_string(), false, vec![0.0, 0.0, 1.0]),
+        ];
+        let mut image = vec![0.3, 0.9, 0.1];
+        l2_normalize(&mut image);
+        let (probs, keep_prob, best) = score_value_buckets(&buckets, &image);
+
+        assert_eq!(probs.len(), 3);
+        assert!((probs.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert_eq!(best, Some(1));
+        assert!(probs[1] > probs[0] && probs[0] > probs[2], "{probs:?}");
+        assert!((keep_prob - probs[0]).abs() < 1e-6);
+
+        // Two keep buckets add up.
+        let mut two_keep = buckets.clone();
+        two_keep[1].1 = true;
+        let (_, keep_prob, _) = score_value_buckets(&two_keep, &image);
+        assert!((keep_prob - probs[0] - probs[1]).abs() < 1e-5);
+    }
+
+    // Deterministic stand-in for the text tower: each prompt maps to its own row.
+    fn fake_embed(prompts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(prompts
+            .iter()
+            .map(|p| {
+                let bytes = p.as_bytes();
+                vec![
+                    bytes.len() as f32,
+                    bytes.iter().map(|&b| b as f32).sum::<f32>() / 100.0,
+                    bytes[0] as f32,
+                    bytes[bytes.len() - 1] as f32 / 10.0,
+                ]
+            })
+            .collect())
+    }
+
+    #[test]
+    fn combined_text_embeds_match_separate_calls() {
+        let buckets = vec![
+            (
+                "keep".to_string(),
+                true,
+                vec!["a memorable photo".to_string()],
+            ),
+            (
+                "drop".to_string(),
+                false,
+                vec![
+                    "a blurry photo".to_string(),
+                    "an accidental shot".to_string(),
+                ],
+            ),
+        ];
+        let (category, value) = combined_text_embeds(&buckets, fake_embed).unwrap();
+
+        let mut prompts = Vec::new();
+        let mut weights = Vec::new();
+        for (k, category_prompts) in all_category_prompts() {
+            for p in category_prompts {
+                prompts.push(*p);
+                weights.push((k, 1.0));
+            }
+        }
+        let separate = average_category_embeds(&weights, &fake_embed(&prompts).unwrap()).unwrap();
+        let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-6);
+        for k in CATEGORY_KEYS {
+            assert!(close(&category[k], &separate[k]), "{}", k.as_str());
+        }
+        assert_eq!(value.len(), 2);
+        for ((_, _, bucket_prompts), combined) in buckets.iter().zip(&value) {
+            let bucket_prompts: Vec<&str> = bucket_prompts.iter().map(|p| p.as_str()).collect();
+            let separate = average_embeds(&fake_embed(&bucket_prompts).unwrap());
+            assert!(close(combined, &separate));
+        }
+    }
+
+    #[test]
+    fn effective_providers_are_read_from_the_profile() {
+        // Trimmed ORT profile: two CoreML nodes (one run twice), one CPU node and a non-node event.
+        let profile = r#"[
+            {"cat": "Session", "name": "model_run", "args": {}},
+            {"cat": "Node", "name": "Conv_0_kernel_time", "args": {"provider": "CoreMLExecutionProvider"}},
+            {"cat": "Node", "name": "Conv_0_kernel_time", "args": {"provider": "CoreMLExecutionProvider"}},
+            {"cat": "Node", "name": "MatMul_1_kernel_time", "args": {"provider": "CoreMLExecutionProvider"}},
+            {"cat": "Node", "name": "Gather_2_kernel_time", "args": {"provider": "CPUExecutionProvider"}},
+            {"cat": "Node", "name": "Gather_2_fence_before", "args": {"provider": "CPUExecutionProvider"}}
+        ]"#;
+        assert_eq!(
+            providers_from_profile(profile).unwrap(),
+            "coreml(2 nodes)+cpu(1 nodes)"
+        );
+
+        let dml = r#"[{"cat": "Node", "name": "Add_kernel_time", "args": {"provider": "DmlExecutionProvider"}}]"#;
+        assert_eq!(providers_from_profile(dml).unwrap(), "directml(1 nodes)");
+        assert!(providers_from_profile(r#"[{"cat": "Session", "name": "x"}]"#).is_err());
+    }
+
+    #[test]
+    fn preallocated_pixel_buffer_matches_a_fresh_copy() {
+        let size = 224;
+        let len = 3 * size * size;
+        let images: Vec<Vec<f32>> = (0..4)
+            .map(|n| {
+                (0..len)
+                    .map(|i| ((i * 31 + n * 7) % 255) as f32 / 255.0)
+                    .collect()
+            })
+            .collect();
+
+        // Old path: a new array from `to_vec()` per call.
+        let started = std::time::Instant::now();
+        for i in 0..1000 {
+            let image = &images[i % images.len()];
+            let fresh = ndarray::Array4::from_shape_vec((1, 3, size, size), image.to_vec());
+            std::hint::black_box(fresh.unwrap());
+        }
+        let fresh_time = started.elapsed();
+
+        let mut pixel = ndarray::Array4::<f32>::zeros((1, 3, size, size));
+        let started = std::time::Instant::now();
+        for i in 0..1000 {
+            copy_pixels(pixel.as_slice_mut().unwrap(), &images[i % images.len()]).unwrap();
+            std::hint::black_box(&pixel);
+        }
+        let reused_time = started.elapsed();
+        eprintln!(
+            "1000 calls: fresh {:?}, reused {:?}",
+            fresh_time, reused_time
+        );
+
+        for image in &images {
+            copy_pixels(pixel.as_slice_mut().unwrap(), image).unwrap();
+            let fresh = ndarray::Array4::from_shape_vec((1, 3, size, size), image.to_vec());
+            assert_eq!(pixel, fresh.unwrap());
+        }
+
+        let err = copy_pixels(pixel.as_slice_mut().unwrap(), &images[0][..10]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("expected {} pixel values, got 10", len)
+        );
+    }
+
+    fn word_level(vocab: &str, padding: &str) -> Tokenizer {
+        Tokenizer::from_str(&format!(
+            r#"{{
+                "version": "1.0",
+                "truncation": null,
+                "padding": {padding},
+                "added_tokens": [],
+                "normalizer": null,
+                "pre_tokenizer": {{"type": "Whitespace"}},
+                "post_processor": null,
+                "decoder": null,
+                "model": {{"type": "WordLevel", "vocab": {vocab}, "unk_token": "<unk>"}}
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn pad_id_falls_back_to_other_pad_tokens() {
+        // An HF-style export that pads with `</s>`.
+        let eos = word_level(r#"{"<unk>": 0, "<s>": 1, "</s>": 2, "cat": 3}"#, "null");
+        assert_eq!(resolve_pad_id(&eos), 2);
+
+        // The OpenAI token still wins when it is there.
+        let both = word_level(r#"{"<unk>": 0, "</s>": 1, "<|endoftext|>": 9}"#, "null");
+        assert_eq!(resolve_pad_id(&both), 9);
+
+        let configured = word_level(
+            r#"{"<unk>": 0, "[X]": 7}"#,
+            r#"{"strategy": "BatchLongest", "direction": "Right", "pad_to_multiple_of": null,
+                "pad_id": 7, "pad_type_id": 0, "pad_token": "[X]"}"#,
+        );
+        assert_eq!(resolve_pad_id(&configured), 7);
+
+        let bare = word_level(r#"{"<unk>": 0, "cat": 1}"#, "null");
+        assert_eq!(resolve_pad_id(&bare), 0);
+    }
+
+    #[test]
+    fn positive_people_bias_raises_its_probability() {
+        // Each category gets its own axis; the image sits closest to `other`.
+        let dims = CATEGORY_KEYS.len();
+        let embeds: HashMap<CategoryKey, Vec<f32>> = CATEGORY_KEYS
+            .iter()
+            .enumerate()
+            .map(|(i, k)| {
+                let mut v = vec![0.0; dims];
+                v[i] = 1.0;
+                (*k, v)
+            })
+            .collect();
+        let people = CATEGORY_KEYS
+            .iter()
+            .position(|k| *k == CategoryKey::People)
+            .unwrap();
+        let mut image = vec![0.1; dims];
+        image[dims - 1] = 0.9;
+        l2_normalize(&mut image);
+
+        let plain = category_probs(&image, &embeds, &HashMap::new()).unwrap();
+        let zero = HashMap::from([(CategoryKey::People, 0.0)]);
+        assert_eq!(category_probs(&image, &embeds, &zero).unwrap(), plain);
+
+        let boost = HashMap::from([(CategoryKey::People, 0.5)]);
+        let boosted = category_probs(&image, &embeds, &boost).unwrap();
+        assert!(
+            boosted[people] > plain[people],
+            "{} <= {}",
+            boosted[people],
+            plain[people]
+        );
+        assert!((boosted.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        // Every other category gives up some probability.
+        for (i, (b, p)) in boosted.iter().zip(&plain).enumerate() {
+            if i != people {
+                assert!(b < p);
+            }
+        }
+
+        let ignored = HashMap::from([(CategoryKey::People, f32::INFINITY)]);
+        assert_eq!(category_probs(&image, &embeds, &ignored).unwrap(), plain);
+    }
+
+    #[derive(Default)]
+    struct RecordedThreading(Vec<String>);
+
+    impl SessionThreading for RecordedThreading {
+        fn intra_threads(mut self, threads: usize) -> Result<Self> {
+            self.0.push(format!("intra={}", threads));
+            Ok(self)
+        }
+
+        fn inter_threads(mut self, threads: usize) -> Result<Self> {
+            self.0.push(format!("inter={}", threads));
+            Ok(self)
+        }
+
+        fn parallel_execution(mut self, parallel: bool) -> Result<Self> {
+            self.0.push(format!("parallel={}", parallel));
+            Ok(self)
+        }
+    }
+
+    #[test]
+    fn session_builder_gets_the_configured_inter_threads() {
+        let opts = ClipEngineOptions {
+            inter_threads: 3,
+            parallel_execution: true,
+            ..ClipEngineOptions::default()
+        };
+        let applied = apply_threading(RecordedThreading::default(), 4, &opts).unwrap();
+        assert_eq!(applied.0, ["intra=4", "inter=3", "parallel=true"]);
+
+        // Defaults keep the old single inter-op thread, sequential; 0 is bumped to 1.
+        let applied = apply_threading(
+            RecordedThreading::default(),
+            2,
+            &ClipEngineOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(applied.0, ["intra=2", "inter=1", "parallel=false"]);
+        let zero = ClipEngineOptions {
+            inter_threads: 0,
+            ..ClipEngineOptions::default()
+        };
+        let applied = apply_threading(RecordedThreading::default(), 2, &zero).unwrap();
+        assert_eq!(applied.0[1], "inter=1");
+    }
+
+    #[test]
+    fn two_prompt_sets_score_the_same_image_differently() {
+        let image = {
+            let mut v = fake_embed(&["a plate of pasta"]).unwrap().remove(0);
+            l2_normalize(&mut v);
+            v
+        };
+        let probs = |prompts: &HashMap<CategoryKey, Vec<(String, f32)>>| {
+            let sets = custom_prompt_sets(prompts).unwrap();
+            let embeds = weighted_category_embeds(&sets, fake_embed).unwrap();
+            category_probs(&image, &embeds, &HashMap::new()).unwrap()
+        };
+        let food = CATEGORY_KEYS
+            .iter()
+            .position(|k| *k == CategoryKey::FoodCafe)
+            .unwrap();
+
+        let builtin = probs(&HashMap::new());
+        let tuned = probs(&HashMap::from([(
+            CategoryKey::FoodCafe,
+            vec![("a plate of pasta".to_string(), 1.0)],
+        )]));
+        assert_ne!(builtin, tuned);
+        assert!(tuned[food] > builtin[food]);
+
+        // Blank prompts fall back to the built-in set; bad weights are rejected.
+        let blank = probs(&HashMap::from([(
+            CategoryKey::FoodCafe,
+            vec![("  ".to_string(), 1.0)],
+        )]));
+        assert_eq!(blank, builtin);
+        let bad = HashMap::from([(CategoryKey::People, vec![("a face".to_string(), 0.0)])]);
+        assert!(custom_prompt_sets(&bad).is_err());
+    }
+
+    #[test]
+    fn heavily_weighted_prompt_dominates_the_category_embedding() {
+        // The two People prompts get orthogonal rows; every built-in prompt shares a third axis.
+        let axes = |prompts: &[&str]| -> Result<Vec<Vec<f32>>> {
+            Ok(prompts
+                .iter()
+                .map(|p| match *p {
+                    "a portrait of a person" => vec![1.0, 0.0, 0.0],
+                    "a crowd at a concert" => vec![0.0, 1.0, 0.0],
+                    _ => vec![0.0, 0.0, 1.0],
+                })
+                .collect())
+        };
+        let people = |portrait: f32, crowd: f32| {
+            let prompts = HashMap::from([(
+                CategoryKey::People,
+                vec![
+                    ("a portrait of a person".to_string(), portrait),
+                    ("a crowd at a concert".to_string(), crowd),
+                ],
+            )]);
+            let sets = custom_prompt_sets(&prompts).unwrap();
+            weighted_category_embeds(&sets, axes)
+                .unwrap()
+                .remove(&CategoryKey::People)
+                .unwrap()
+        };
+
+        let even = people(1.0, 1.0);
+        assert!((even[0] - even[1]).abs() < 1e-6);
+        // 9:1 is the weighted mean (0.9, 0.1) before normalizing.
+        let weighted = people(9.0, 1.0);
+        let norm = (0.9f32 * 0.9 + 0.1 * 0.1).sqrt();
+        assert!((weighted[0] - 0.9 / norm).abs() < 1e-6);
+        assert!((weighted[1] - 0.1 / norm).abs() < 1e-6);
+        assert!(weighted[0] > 0.99 && weighted[0] > even[0]);
+        // Only the relative weights matter.
+        let scaled = people(90.0, 10.0);
+        assert!(weighted
+            .iter()
+            .zip(&scaled)
+            .all(|(a, b)| (a - b).abs() < 1e-6));
     }
 }
@@ -3,4 +3,4 @@ pub mod math;
 pub mod preprocess;
 pub mod prompts;
 
-pub use engine::{ClipEngine, ClipEngineOptions};
+pub use engine::{list_gpu_devices, ClipEngine, ClipEngineOptions, SubcategoryPrompts};
@@ -1,7 +1,10 @@
-use crate::core::decode::decode_dynamic_image;
-use anyhow::Result;
+use crate::core::decode::{check_canceled, decode_dynamic_image, flatten_alpha};
+use anyhow::{anyhow, Result};
 use image::imageops::FilterType;
+use image::RgbImage;
+use serde_json::Value;
 use std::path::Path;
+use tokio_util::sync::CancellationToken;
 
 const SIZE: u32 = 224;
 
@@ -9,15 +12,272 @@ const SIZE: u32 = 224;
 const MEAN: [f32; 3] = [0.48145466, 0.4578275, 0.40821073];
 const STD: [f32; 3] = [0.26862954, 0.26130258, 0.27577711];
 
+const CONFIG_FILE: &str = "preprocessor_config.json";
+
+// Input size and normalization for the vision tower. Checkpoints differ (OpenAI vs OpenCLIP
+// LAION), so HuggingFace exports ship these in `preprocessor_config.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreprocessConfig {
+    pub size: u32,
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+    // Where the values came from, for the analysis log.
+    pub source: String,
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        Self {
+            size: SIZE,
+            mean: MEAN,
+            std: STD,
+            source: "builtin".to_string(),
+        }
+    }
+}
+
+impl PreprocessConfig {
+    // Reads `preprocessor_config.json` from the model dir. Missing fields keep the builtin
+    // values; a missing or unreadable file falls back entirely.
+    pub fn load(model_dir: &Path) -> Self {
+        let path = model_dir.join(CONFIG_FILE);
+        if !path.exists() {
+            return Self::default();
+        }
+        match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|raw| Self::parse(&raw))
+        {
+            Ok(mut cfg) => {
+                cfg.source = path.display().to_string();
+                cfg
+            }
+            Err(e) => {
+                eprintln!(
+                    "clip: ignoring {} ({}), using builtin preprocessing",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    fn parse(raw: &str) -> Result<Self> {
+        let v: Value = serde_json::from_str(raw)?;
+        let mut cfg = Self::default();
+        if let Some(mean) = v.get("image_mean") {
+            cfg.mean = parse_triplet(mean).ok_or_else(|| anyhow!("invalid image_mean"))?;
+        }
+        if let Some(std) = v.get("image_std") {
+            cfg.std = parse_triplet(std).ok_or_else(|| anyhow!("invalid image_std"))?;
+            if cfg.std.iter().any(|s| *s <= 0.0) {
+                return Err(anyhow!("image_std must be positive"));
+            }
+        }
+        // We resize straight to a square, so the crop size (what the model sees) wins over the
+        // resize target when both are present.
+        if let Some(size) = v
+            .get("crop_size")
+            .and_then(parse_size)
+            .or_else(|| v.get("size").and_then(parse_size))
+        {
+            if size == 0 {
+                return Err(anyhow!("invalid image size"));
+            }
+            cfg.size = size;
+        }
+        Ok(cfg)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "size={} mean=[{:.4}, {:.4}, {:.4}] std=[{:.4}, {:.4}, {:.4}] source={}",
+            self.size,
+            self.mean[0],
+            self.mean[1],
+            self.mean[2],
+            self.std[0],
+            self.std[1],
+            self.std[2],
+            self.source
+        )
+    }
+}
+
+fn parse_triplet(v: &Value) -> Option<[f32; 3]> {
+    let arr = v.as_array()?;
+    if arr.len() != 3 {
+        return None;
+    }
+    Some([
+        arr[0].as_f64()? as f32,
+        arr[1].as_f64()? as f32,
+        arr[2].as_f64()? as f32,
+    ])
+}
+
+// `size`/`crop_size` is either a bare number or an object with `shortest_edge` or `height`/`width`.
+fn parse_size(v: &Value) -> Option<u32> {
+    if let Some(n) = v.as_u64() {
+        return Some(n as u32);
+    }
+    if let Some(n) = v.get("shortest_edge").and_then(|n| n.as_u64()) {
+        return Some(n as u32);
+    }
+    let h = v.get("height").and_then(|n| n.as_u64())?;
+    let w = v.get("width").and_then(|n| n.as_u64())?;
+    (h == w).then_some(h as u32)
+}
+
 pub struct PreprocessOutput {
     pub nchw: Vec<f32>,
+    pub screenshot: ScreenshotCues,
+    pub geometry: PreprocessGeometry,
+}
+
+// Long side / short side past which squashing to a square distorts enough to mention in the log.
+const SQUASH_WARN_ASPECT: f32 = 2.5;
+
+// What part of the decoded image the model actually saw, for debugging misclassifications.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreprocessGeometry {
+    pub orig_width: u32,
+    pub orig_height: u32,
+    // Region of the original fed to the resize as (x, y, width, height). We squash the whole
+    // frame to a square instead of center-cropping, so this currently always covers the image.
+    pub crop: (u32, u32, u32, u32),
+    pub size: u32,
+}
+
+impl PreprocessGeometry {
+    fn new(orig_width: u32, orig_height: u32, size: u32) -> Self {
+        Self {
+            orig_width,
+            orig_height,
+            crop: (0, 0, orig_width, orig_height),
+            size,
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        let long = self.orig_width.max(self.orig_height) as f32;
+        let short = self.orig_width.min(self.orig_height).max(1) as f32;
+        long / short
+    }
+
+    pub fn describe(&self) -> String {
+        let (x, y, w, h) = self.crop;
+        format!(
+            "{}x{} crop=(x={}, y={}, w={}, h={}) resized={}x{}",
+            self.orig_width, self.orig_height, x, y, w, h, self.size, self.size
+        )
+    }
+
+    // Set when the aspect ratio is extreme enough that thin content gets badly squashed.
+    pub fn warning(&self) -> Option<String> {
+        let aspect = self.aspect();
+        (aspect >= SQUASH_WARN_ASPECT).then(|| {
+            format!(
+                "aspect {:.2}:1 squashed to a square; narrow details may be lost",
+                aspect
+            )
+        })
+    }
+}
+
+// Long side / short side of common phone and monitor screens: 16:10, 16:9, 18:9, 19:9, 19.5:9,
+// 20:9 and 21:9.
+const SCREEN_ASPECTS: [f32; 7] = [
+    1.6,
+    16.0 / 9.0,
+    2.0,
+    19.0 / 9.0,
+    19.5 / 9.0,
+    20.0 / 9.0,
+    21.0 / 9.0,
+];
+// Summed per-channel difference up to which neighbouring pixels count as one flat fill.
+const FLAT_DIFF: u32 = 6;
+
+// Cheap screenshot cues, taken from the already resized image. UI captures are mostly flat fills
+// around one background colour; photos carry noise and texture almost everywhere.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScreenshotCues {
+    // Share of pixels nearly identical to their right and lower neighbours.
+    pub flat_ratio: f32,
+    // Share of pixels in the most common colour (4 bits per channel).
+    pub dominant_ratio: f32,
+    // The original aspect ratio matches a common screen.
+    pub screen_aspect: bool,
+    pub detected: bool,
+}
+
+impl ScreenshotCues {
+    pub fn describe(&self) -> String {
+        format!(
+            "detected={} flat={:.3} dominant={:.3} screen_aspect={}",
+            self.detected, self.flat_ratio, self.dominant_ratio, self.screen_aspect
+        )
+    }
+}
+
+pub fn screenshot_cues(img: &RgbImage, orig_width: u32, orig_height: u32) -> ScreenshotCues {
+    let (w, h) = img.dimensions();
+    if w < 2 || h < 2 {
+        return ScreenshotCues::default();
+    }
+    let diff = |a: [u8; 3], b: [u8; 3]| -> u32 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| x.abs_diff(*y) as u32)
+            .sum()
+    };
+    let mut bins = vec![0u32; 4096];
+    let mut flat = 0u32;
+    for y in 0..h {
+        for x in 0..w {
+            let p = img.get_pixel(x, y).0;
+            let bin =
+                ((p[0] >> 4) as usize) << 8 | ((p[1] >> 4) as usize) << 4 | (p[2] >> 4) as usize;
+            bins[bin] += 1;
+            if x + 1 < w && y + 1 < h {
+                let right = img.get_pixel(x + 1, y).0;
+                let below = img.get_pixel(x, y + 1).0;
+                if diff(p, right) <= FLAT_DIFF && diff(p, below) <= FLAT_DIFF {
+                    flat += 1;
+                }
+            }
+        }
+    }
+    let flat_ratio = flat as f32 / ((w - 1) * (h - 1)) as f32;
+    let dominant_ratio = bins.iter().copied().max().unwrap_or(0) as f32 / (w * h) as f32;
+    let aspect = PreprocessGeometry::new(orig_width, orig_height, 0).aspect();
+    let screen_aspect = SCREEN_ASPECTS.iter().any(|a| (aspect - a).abs() < 0.02);
+    // A screen-shaped image needs weaker pixel evidence; anything else has to be clearly flat.
+    let detected = (flat_ratio >= 0.6 && dominant_ratio >= 0.25)
+        || (screen_aspect && flat_ratio >= 0.45 && dominant_ratio >= 0.2);
+    ScreenshotCues {
+        flat_ratio,
+        dominant_ratio,
+        screen_aspect,
+        detected,
+    }
 }
 
-pub fn preprocess_clip_image(path: &Path) -> Result<PreprocessOutput> {
-    let img = decode_dynamic_image(path)?;
-    let rgb = img.to_rgb8();
-    let resized = image::imageops::resize(&rgb, SIZE, SIZE, FilterType::Triangle);
+pub fn preprocess_clip_image(
+    path: &Path,
+    cfg: &PreprocessConfig,
+    alpha_background: [u8; 3],
+    cancel: Option<&CancellationToken>,
+) -> Result<PreprocessOutput> {
+    let img = decode_dynamic_image(path, cancel)?;
+    check_canceled(cancel)?;
+    let rgb = flatten_alpha(&img, alpha_background);
+    let resized = image::imageops::resize(&rgb, cfg.size, cfg.size, FilterType::Triangle);
     let (w, h) = resized.dimensions();
+    let screenshot = screenshot_cues(&resized, img.width(), img.height());
+    let geometry = PreprocessGeometry::new(img.width(), img.height(), cfg.size);
 
     let mut nchw = vec![0.0f32; (3 * w * h) as usize];
     // NCHW with channel-first
@@ -28,9 +288,9 @@ pub fn preprocess_clip_image(path: &Path) -> Result<PreprocessOutput> {
             let g = (p[1] as f32) / 255.0;
             let b = (p[2] as f32) / 255.0;
 
-            let r = (r - MEAN[0]) / STD[0];
-            let g = (g - MEAN[1]) / STD[1];
-            let b = (b - MEAN[2]) / STD[2];
+            let r = (r - cfg.mean[0]) / cfg.std[0];
+            let g = (g - cfg.mean[1]) / cfg.std[1];
+            let b = (b - cfg.mean[2]) / cfg.std[2];
 
             let idx = (y * w + x) as usize;
             nchw[idx] = r;
@@ -40,5 +300,108 @@ pub fn preprocess_clip_image(path: &Path) -> Result<PreprocessOutput> {
     }
 
     let _ = (w, h);
-    Ok(PreprocessOutput { nchw })
+    Ok(PreprocessOutput {
+        nchw,
+        screenshot,
+        geometry,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn fill(img: &mut RgbImage, x0: u32, y0: u32, w: u32, h: u32, colour: [u8; 3]) {
+        for y in y0..y0 + h {
+            for x in x0..x0 + w {
+                img.put_pixel(x, y, Rgb(colour));
+            }
+        }
+    }
+
+    // A chat-like screen: status and tab bars, two bubbles and rows of glyph-sized "text" on a
+    // white background.
+    fn ui_capture() -> RgbImage {
+        let mut img = RgbImage::from_pixel(224, 224, Rgb([255, 255, 255]));
+        fill(&mut img, 0, 0, 224, 14, [30, 30, 30]);
+        fill(&mut img, 0, 200, 224, 24, [242, 242, 247]);
+        fill(&mut img, 12, 40, 140, 40, [0, 122, 255]);
+        fill(&mut img, 72, 100, 140, 52, [229, 229, 234]);
+        for (row, y) in [160u32, 172, 184].into_iter().enumerate() {
+            for i in 0..(18 - row as u32 * 4) {
+                fill(&mut img, 12 + i * 10, y, 6, 8, [20, 20, 20]);
+            }
+        }
+        img
+    }
+
+    // Smooth gradients with per-pixel sensor noise, like a resized camera photo.
+    fn photo() -> RgbImage {
+        let mut state = 0x2545_f491u32;
+        RgbImage::from_fn(224, 224, |x, y| {
+            let mut noise = || {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                ((state >> 16) % 24) as i32 - 12
+            };
+            let base = [
+                60 + x as i32 / 2,
+                90 + y as i32 / 3,
+                140 - (x + y) as i32 / 4,
+            ];
+            Rgb(base.map(|c| (c + noise()).clamp(0, 255) as u8))
+        })
+    }
+
+    #[test]
+    fn flat_ui_capture_is_detected_as_a_screenshot() {
+        let cues = screenshot_cues(&ui_capture(), 1170, 2532);
+        assert!(cues.screen_aspect);
+        assert!(cues.flat_ratio > 0.8, "{}", cues.describe());
+        assert!(cues.dominant_ratio > 0.4, "{}", cues.describe());
+        assert!(cues.detected);
+        // Clearly flat enough to count even when cropped to a non-screen shape.
+        assert!(screenshot_cues(&ui_capture(), 1000, 1000).detected);
+    }
+
+    #[test]
+    fn noisy_photo_is_not_a_screenshot() {
+        let cues = screenshot_cues(&photo(), 4032, 3024);
+        assert!(!cues.screen_aspect);
+        assert!(cues.flat_ratio < 0.2, "{}", cues.describe());
+        assert!(!cues.detected);
+        // A phone-shaped photo still needs the pixel evidence.
+        assert!(!screenshot_cues(&photo(), 1170, 2532).detected);
+    }
+
+    #[test]
+    fn crop_covers_the_whole_400x200_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wide.png");
+        RgbImage::from_pixel(400, 200, Rgb([90, 120, 150]))
+            .save(&path)
+            .unwrap();
+        let cfg = PreprocessConfig::default();
+        let out = preprocess_clip_image(&path, &cfg, [255, 255, 255], None).unwrap();
+
+        let geometry = out.geometry;
+        assert_eq!((geometry.orig_width, geometry.orig_height), (400, 200));
+        assert_eq!(geometry.crop, (0, 0, 400, 200));
+        assert_eq!(geometry.size, cfg.size);
+        assert_eq!(out.nchw.len(), (3 * cfg.size * cfg.size) as usize);
+        assert_eq!(
+            geometry.describe(),
+            format!(
+                "400x200 crop=(x=0, y=0, w=400, h=200) resized={0}x{0}",
+                cfg.size
+            )
+        );
+        // 2:1 is squashed without comment; a panorama gets a warning line.
+        assert!(geometry.warning().is_none());
+        let panorama = PreprocessGeometry::new(1000, 200, cfg.size);
+        assert_eq!(
+            panorama.warning().unwrap(),
+            "aspect 5.00:1 squashed to a square; narrow details may be lost"
+        );
+    }
 }
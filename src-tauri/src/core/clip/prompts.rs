@@ -70,3 +70,85 @@ pub fn value_drop_prompts() -> &'static [&'static str] {
         "a meaningless image to delete",
     ]
 }
+
+// (prompt, Korean tag) pairs used to tag CLIP results. Users can append more via
+// `Settings::clip_tag_prompts`.
+pub fn default_tag_prompts() -> &'static [(&'static str, &'static str)] {
+    &[
+        ("a photo of a sunset", "노을"),
+        ("a photo of a sunrise", "일출"),
+        ("a photo of the night sky", "밤하늘"),
+        ("a photo of a beach", "해변"),
+        ("a photo of the sea", "바다"),
+        ("a photo of mountains", "산"),
+        ("a photo of a forest", "숲"),
+        ("a photo of flowers", "꽃"),
+        ("a photo of snow", "눈"),
+        ("a photo of a rainy day", "비"),
+        ("a photo of a river or lake", "강/호수"),
+        ("a photo of a park", "공원"),
+        ("a selfie", "셀카"),
+        ("a group photo of friends", "단체사진"),
+        ("a photo of a baby", "아기"),
+        ("a photo of a child", "아이"),
+        ("a photo of a couple", "커플"),
+        ("a wedding photo", "결혼식"),
+        ("a photo of a birthday party", "생일"),
+        ("a photo of a concert", "공연"),
+        ("a photo of people playing sports", "스포츠"),
+        ("a photo of a dog", "강아지"),
+        ("a photo of a cat", "고양이"),
+        ("a photo of a bird", "새"),
+        ("a photo of fish or an aquarium", "물고기"),
+        ("a photo of coffee", "커피"),
+        ("a photo of latte art", "라떼아트"),
+        ("a photo of a dessert or cake", "디저트"),
+        ("a photo of bread or a bakery", "빵"),
+        ("a photo of a meal on a plate", "식사"),
+        ("a photo of korean food", "한식"),
+        ("a photo of noodles", "면요리"),
+        ("a photo of meat or barbecue", "고기"),
+        ("a photo of sushi", "초밥"),
+        ("a photo of pizza or burgers", "패스트푸드"),
+        ("a photo of fruit", "과일"),
+        ("a photo of a drink or cocktail", "음료"),
+        ("a photo of a restaurant interior", "식당"),
+        ("a photo of a cafe interior", "카페"),
+        ("a photo of a receipt", "영수증"),
+        ("a photo of a ticket or boarding pass", "티켓"),
+        ("a photo of a business card", "명함"),
+        ("a photo of handwritten notes", "손글씨"),
+        ("a photo of a whiteboard", "화이트보드"),
+        ("a photo of a book page", "책"),
+        ("a screenshot of a chat conversation", "채팅"),
+        ("a screenshot of a website", "웹페이지"),
+        ("a screenshot of a map", "지도"),
+        ("a screenshot of a spreadsheet or chart", "차트"),
+        ("a screenshot of code", "코드"),
+        ("a meme image", "밈"),
+        ("a photo of a car", "자동차"),
+        ("a photo of a train or subway", "기차/지하철"),
+        ("a photo of an airplane or airport", "비행기/공항"),
+        ("a photo of a bridge", "다리"),
+        ("a photo of a temple or palace", "고궁/사찰"),
+        ("a photo of a skyscraper", "고층건물"),
+        ("a photo of a city at night", "야경"),
+        ("a photo of a street market", "시장"),
+        ("a photo of a museum or exhibition", "전시"),
+        ("a photo of a room interior", "실내"),
+        ("a photo of a bedroom", "침실"),
+        ("a photo of a kitchen", "주방"),
+        ("a photo of clothes or fashion", "패션"),
+        ("a photo of shoes", "신발"),
+        ("a photo of a bag", "가방"),
+        ("a photo of cosmetics", "화장품"),
+        ("a photo of a phone or laptop", "전자기기"),
+        ("a photo of furniture", "가구"),
+        ("a photo of a plant in a pot", "화분"),
+        ("a photo of toys or figures", "장난감"),
+        ("a photo of artwork or a painting", "미술"),
+        ("a blurry photo", "흐림"),
+        ("a dark underexposed photo", "어두움"),
+        ("a black and white photo", "흑백"),
+    ]
+}
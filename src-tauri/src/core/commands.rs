@@ -1,41 +1,62 @@
-use crate::core::classifier::warmup_clip_engine;
-use crate::core::clip::ClipEngine;
+use crate::core::classifier::{
+    clip_engine_status, clip_prompt_test, clip_warmup_status, reload_clip_engine as reload_engine,
+    spawn_clip_warmup, unload_clip_engine as unload_engine, wait_clip_warmup,
+};
+use crate::core::clip::{list_gpu_devices, ClipEngine};
 use crate::core::config::{load_settings, save_settings};
-use crate::core::db::Db;
+use crate::core::db::{Db, PhotoFiles, PhotoFilter};
+use crate::core::error_log::{clear_error_log as clear_log, error_log_path};
+use crate::core::events::MODEL_PULL_EVENT;
 use crate::core::model::{
-    AnalysisEngine, ClipAccelCapabilities, ClipProviderCapability, Distribution, DistributionMode,
-    Progress, Settings, StartAnalysisInput, StartAnalysisResult, ValueStats, CATEGORY_KEYS,
+    CategoryKey, ClipAccelCapabilities, ClipEngineStatus, ClipPromptTestResult,
+    ClipProviderCapability, ClipReloadResult, ClipWarmupEvent, DeletePhotoResult, Distribution,
+    DistributionMode, FailureKind, JobRecord, JobSummary, ModelPullEvent, OllamaBackend,
+    OllamaModelCheck, OllamaModelInfo, Progress, QueuedJobInfo, RecategorizeResult, SelfTestReport,
+    Settings, StartAnalysisInput, StartAnalysisResult, TagCount, UndoExportReport, ValueStats,
+    CATEGORY_KEYS,
+};
+use crate::core::ollama::{self, OllamaAuth};
+use crate::core::openai;
+use crate::core::pipeline::{
+    classify_bytes as classify_bytes_one, preflight, reanalyze_photo as reanalyze_one,
+    recategorize_photo, test_ollama_connection, undo_export, JobMeta, Pipeline,
 };
-use crate::core::ollama;
-use crate::core::pipeline::{test_ollama_connection, Pipeline};
-use anyhow::Result;
+use crate::core::self_test::run_self_test;
+use crate::core::thumbnail::{clear_thumbnails, generate_thumbnail};
+use anyhow::{anyhow, Result};
 use ort::execution_providers::{
     CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider,
     DirectMLExecutionProvider, ExecutionProvider, OpenVINOExecutionProvider, ROCmExecutionProvider,
+    TensorRTExecutionProvider,
 };
 use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Emitter, State};
+use tokio_util::sync::CancellationToken;
 
 pub struct AppState {
     pub db: Arc<Mutex<Db>>,
     pub pipeline: Mutex<Pipeline>,
     pub settings: Mutex<Settings>,
+    // Set while `pull_ollama_model` runs; one pull at a time.
+    pub model_pull: Mutex<Option<CancellationToken>>,
 }
 
 impl AppState {
     pub fn new(app: &AppHandle) -> Result<Self> {
         let settings = load_settings(app);
-        if settings.analysis_engine == crate::core::model::AnalysisEngine::Clip {
-            if let Err(e) = warmup_clip_engine(app, &settings) {
-                eprintln!("clip warmup failed: {}", e);
-            }
+        if settings.analysis_engine.uses_clip() {
+            spawn_clip_warmup(app.clone(), settings.clone());
         }
-        let db = Db::init(app)?;
+        let db = Db::open(app, settings.ephemeral_db)?;
+        db.abort_running_jobs()?;
         Ok(AppState {
             db: Arc::new(Mutex::new(db)),
-            pipeline: Mutex::new(Pipeline::new()),
+            pipeline: Mutex::new(Pipeline::new(app)),
             settings: Mutex::new(settings),
+            model_pull: Mutex::new(None),
         })
     }
 }
@@ -51,33 +72,171 @@ pub async fn set_settings(
     state: State<'_, AppState>,
     mut settings: Settings,
 ) -> Result<(), String> {
-    let max = std::thread::available_parallelism()
-        .map(|n| n.get() as u32)
-        .unwrap_or(4)
-        .max(1);
-    settings.analysis_concurrency = settings.analysis_concurrency.clamp(1, max);
-    if settings.analysis_concurrency > 1 {
-        settings.ollama_stream = false;
+    settings.clamp_concurrency();
+    ollama::validate_prompt_templates(
+        settings.ollama_system_prompt.as_deref(),
+        settings.ollama_user_prompt.as_deref(),
+    )
+    .map_err(|e| e.to_string())?;
+    // Held through the save and the database swap so a job can't start in between.
+    let pipeline = state.pipeline.lock();
+    let db = if state.settings.lock().ephemeral_db != settings.ephemeral_db {
+        if pipeline.is_running() {
+            return Err("cannot switch the results database while a job is running".to_string());
+        }
+        Some(Db::open(&app, settings.ephemeral_db).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    save_settings(&app, &settings).map_err(|e| e.to_string())?;
+    if let Some(db) = db {
+        // Swapped in place, so everything holding the shared handle follows.
+        *state.db.lock() = db;
     }
-    {
+    let previous_engine = {
         let mut guard = state.settings.lock();
+        let previous = guard.analysis_engine;
         *guard = settings.clone();
+        previous
+    };
+    if settings.clip_auto_unload
+        && previous_engine.uses_clip()
+        && !settings.analysis_engine.uses_clip()
+        && !pipeline.is_running()
+    {
+        unload_engine();
+    }
+    Ok(())
+}
+
+// The base URL comes from the (possibly unsaved) form; credentials and the backend always come from
+// saved settings.
+fn ollama_auth(state: &State<'_, AppState>) -> OllamaAuth {
+    OllamaAuth::from_settings(&state.settings.lock())
+}
+
+fn ollama_backend(state: &State<'_, AppState>) -> OllamaBackend {
+    state.settings.lock().ollama_backend
+}
+
+// Model inspection and pulls use Ollama-only endpoints.
+fn require_ollama_backend(state: &State<'_, AppState>) -> Result<(), String> {
+    match ollama_backend(state) {
+        OllamaBackend::Ollama => Ok(()),
+        OllamaBackend::OpenaiCompatible => {
+            Err("this is only available with the Ollama backend".to_string())
+        }
     }
-    save_settings(&app, &settings).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn test_ollama(base_url: String) -> Result<String, String> {
-    test_ollama_connection(&base_url)
+pub async fn test_ollama(state: State<'_, AppState>, base_url: String) -> Result<String, String> {
+    let auth = ollama_auth(&state);
+    test_ollama_connection(&base_url, ollama_backend(&state), &auth)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| auth.redact(&e.to_string()))
 }
 
 #[tauri::command]
-pub async fn list_ollama_models(base_url: String) -> Result<Vec<String>, String> {
-    ollama::list_models(&base_url)
+pub async fn list_ollama_models(
+    state: State<'_, AppState>,
+    base_url: String,
+) -> Result<Vec<String>, String> {
+    let auth = ollama_auth(&state);
+    let models = match ollama_backend(&state) {
+        OllamaBackend::Ollama => ollama::list_models(&base_url, &auth).await,
+        OllamaBackend::OpenaiCompatible => openai::list_models(&base_url, &auth).await,
+    };
+    models.map_err(|e| auth.redact(&e.to_string()))
+}
+
+#[tauri::command]
+pub async fn list_ollama_models_detailed(
+    state: State<'_, AppState>,
+    base_url: String,
+) -> Result<Vec<OllamaModelInfo>, String> {
+    let auth = ollama_auth(&state);
+    let models = match ollama_backend(&state) {
+        OllamaBackend::Ollama => ollama::list_models_detailed(&base_url, &auth).await,
+        OllamaBackend::OpenaiCompatible => openai::list_models_detailed(&base_url, &auth).await,
+    };
+    models.map_err(|e| auth.redact(&e.to_string()))
+}
+
+#[tauri::command]
+pub async fn check_ollama_model(
+    state: State<'_, AppState>,
+    base_url: String,
+    model: String,
+) -> Result<OllamaModelCheck, String> {
+    require_ollama_backend(&state)?;
+    let auth = ollama_auth(&state);
+    ollama::check_model(&base_url, &auth, &model)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| auth.redact(&e.to_string()))
+}
+
+#[tauri::command]
+pub async fn pull_ollama_model(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    base_url: String,
+    model: String,
+) -> Result<(), String> {
+    require_ollama_backend(&state)?;
+    let cancel = {
+        let mut guard = state.model_pull.lock();
+        if guard.is_some() {
+            return Err("a model pull is already running".to_string());
+        }
+        let token = CancellationToken::new();
+        *guard = Some(token.clone());
+        token
+    };
+    let auth = ollama_auth(&state);
+    let event = |status: &str, completed, total, done, error: Option<String>| ModelPullEvent {
+        model: model.clone(),
+        status: status.to_string(),
+        completed,
+        total,
+        done,
+        error,
+    };
+    let result = ollama::pull_model(
+        &base_url,
+        &auth,
+        &model,
+        &cancel,
+        |status, completed, total| {
+            let _ = app.emit(
+                MODEL_PULL_EVENT,
+                event(status, completed, total, status == "success", None),
+            );
+        },
+    )
+    .await
+    .map_err(|e| auth.redact_error(e));
+    *state.model_pull.lock() = None;
+    if let Err(e) = &result {
+        let status = if cancel.is_cancelled() {
+            "canceled"
+        } else {
+            "error"
+        };
+        let _ = app.emit(
+            MODEL_PULL_EVENT,
+            event(status, None, None, true, Some(e.to_string())),
+        );
+    }
+    result.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn cancel_model_pull(state: State<'_, AppState>) -> Result<(), String> {
+    if let Some(token) = state.model_pull.lock().as_ref() {
+        token.cancel();
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -110,28 +269,155 @@ pub async fn start_analysis(
     state: State<'_, AppState>,
     input: StartAnalysisInput,
 ) -> Result<StartAnalysisResult, String> {
-    let settings = state.settings.lock().clone();
+    wait_clip_warmup().await;
+    // Merged here rather than in the pipeline so preflight checks the engine and model the job
+    // will actually use.
+    let settings = state
+        .settings
+        .lock()
+        .clone()
+        .with_override(input.settings_override.as_ref());
+    preflight(&settings).await.map_err(|e| e.to_string())?;
     let mut pipeline = state.pipeline.lock();
-    let job_id = pipeline
+    pipeline
         .start(app, state.db.clone(), settings, input)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn resume_job(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    job_id: String,
+) -> Result<StartAnalysisResult, String> {
+    let (input, settings) = state
+        .db
+        .lock()
+        .job_snapshot(&job_id)
+        .map_err(|e| e.to_string())?;
+    wait_clip_warmup().await;
+    preflight(&settings).await.map_err(|e| e.to_string())?;
+    let mut pipeline = state.pipeline.lock();
+    pipeline
+        .resume(app, state.db.clone(), job_id, input, settings)
+        .map_err(|e| e.to_string())
+}
+
+// Analyzes errored photos again with the current settings, all of them or only one job's, and
+// optionally only some failure kinds (e.g. network failures after the server came back).
+#[tauri::command]
+pub async fn retry_failed(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    job_id: Option<String>,
+    kinds: Option<Vec<FailureKind>>,
+) -> Result<Vec<StartAnalysisResult>, String> {
+    let failed = state
+        .db
+        .lock()
+        .failed_photos(job_id.as_deref(), kinds.as_deref())
         .map_err(|e| e.to_string())?;
-    Ok(StartAnalysisResult { job_id })
+    wait_clip_warmup().await;
+    let settings = state.settings.lock().clone();
+    preflight(&settings).await.map_err(|e| e.to_string())?;
+    let mut pipeline = state.pipeline.lock();
+    pipeline
+        .retry_failed(app, state.db.clone(), settings, failed)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_job_queue(state: State<'_, AppState>) -> Result<Vec<QueuedJobInfo>, String> {
+    Ok(state.pipeline.lock().job_queue())
 }
 
 #[tauri::command]
-pub async fn cancel_analysis(state: State<'_, AppState>, job_id: String) -> Result<(), String> {
+pub async fn remove_queued_job(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    job_id: String,
+) -> Result<(), String> {
     state
         .pipeline
         .lock()
-        .cancel(&job_id)
+        .remove_queued(&app, &job_id)
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn list_jobs(state: State<'_, AppState>) -> Result<Vec<JobRecord>, String> {
+    state.db.lock().list_jobs().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_last_job_summary(
+    state: State<'_, AppState>,
+) -> Result<Option<JobSummary>, String> {
+    Ok(state.pipeline.lock().last_job_summary())
+}
+
+#[tauri::command]
+pub async fn cancel_analysis(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    job_id: String,
+    cancel_all: Option<bool>,
+) -> Result<(), String> {
+    state
+        .pipeline
+        .lock()
+        .cancel(&app, &job_id, cancel_all.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+// Rows per `list_photos` call when no limit is given; serializing every row of a large library
+// takes seconds.
+const DEFAULT_PHOTO_PAGE: usize = 500;
+
+// One page of rows, newest first. An explicit `limit` of 0 returns every row.
 #[tauri::command]
 pub async fn list_photos(
     state: State<'_, AppState>,
+    filter: Option<PhotoFilter>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<crate::core::model::PhotoRow>, String> {
+    let filter = filter.unwrap_or_default();
+    let limit = match limit.unwrap_or(DEFAULT_PHOTO_PAGE) {
+        0 => None,
+        n => Some(n),
+    };
+    state
+        .db
+        .lock()
+        .list_photos(&filter, limit, offset.unwrap_or(0))
+        .map_err(|e| e.to_string())
+}
+
+// Rows matching the same filters as `list_photos`, for paging controls.
+#[tauri::command]
+pub async fn count_photos(
+    state: State<'_, AppState>,
+    filter: Option<PhotoFilter>,
+) -> Result<usize, String> {
+    let filter = filter.unwrap_or_default();
+    state
+        .db
+        .lock()
+        .count_photos(&filter)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_photos_since(
+    state: State<'_, AppState>,
+    iso_timestamp: String,
 ) -> Result<Vec<crate::core::model::PhotoRow>, String> {
-    state.db.lock().list_photos().map_err(|e| e.to_string())
+    state
+        .db
+        .lock()
+        .list_photos_since(&iso_timestamp)
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -146,32 +432,234 @@ pub async fn get_photo_detail(
         .map_err(|e| e.to_string())
 }
 
+// Hand-picked category for one photo; its exported file moves to the new category folder.
+#[tauri::command]
+pub async fn set_photo_category(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    category: CategoryKey,
+) -> Result<RecategorizeResult, String> {
+    recategorize_photo(&app, &state.db, &id, category).map_err(|e| e.to_string())
+}
+
+// Rolls back the last job: its exported files go to the trash and their rows are removed. After a
+// retry that is the retried job, which owns the rows. A dry run only reports the counts, for the
+// confirmation prompt.
+#[tauri::command]
+pub async fn undo_last_export(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    dry_run: Option<bool>,
+) -> Result<UndoExportReport, String> {
+    // Hold the pipeline lock so a job can't start while its predecessor is being undone.
+    let pipeline = state.pipeline.lock();
+    if pipeline.is_running() {
+        return Err("analysis job is running".to_string());
+    }
+    let job_id = pipeline
+        .last_job_meta()
+        .and_then(|meta| meta.job_id)
+        .ok_or_else(|| "no job to undo".to_string())?;
+    undo_export(&app, &state.db, &job_id, dry_run.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+// Deletes result rows and their thumbnails. With `delete_exported_file` the exported copy goes too,
+// but only when it lies inside the row's export root and is not the source file itself.
+#[tauri::command]
+pub async fn delete_photos(
+    state: State<'_, AppState>,
+    ids: Vec<String>,
+    delete_exported_file: bool,
+) -> Result<Vec<DeletePhotoResult>, String> {
+    let deleted = state
+        .db
+        .lock()
+        .delete_photos(&ids)
+        .map_err(|e| e.to_string())?;
+    let mut by_id: HashMap<String, PhotoFiles> =
+        deleted.into_iter().map(|d| (d.id.clone(), d)).collect();
+    let results = ids
+        .into_iter()
+        .map(|id| {
+            let Some(photo) = by_id.remove(&id) else {
+                return DeletePhotoResult {
+                    id,
+                    deleted: false,
+                    file_removed: false,
+                    error: Some("not found".to_string()),
+                };
+            };
+            if let Some(thumbnail) = &photo.thumbnail_path {
+                let _ = std::fs::remove_file(thumbnail);
+            }
+            let removal = if delete_exported_file {
+                remove_exported_file(&photo).map(|_| true)
+            } else {
+                Ok(false)
+            };
+            DeletePhotoResult {
+                id,
+                deleted: true,
+                file_removed: removal.as_ref().is_ok_and(|removed| *removed),
+                error: removal.err().map(|e| e.to_string()),
+            }
+        })
+        .collect();
+    Ok(results)
+}
+
+fn remove_exported_file(photo: &PhotoFiles) -> Result<()> {
+    let root = photo
+        .export_root
+        .as_deref()
+        .ok_or_else(|| anyhow!("no export root recorded"))?;
+    let path = Path::new(&photo.path).canonicalize()?;
+    let root = Path::new(root).canonicalize()?;
+    let is_source = photo
+        .source_path
+        .as_deref()
+        .and_then(|s| Path::new(s).canonicalize().ok())
+        .is_some_and(|source| source == path);
+    if !path.starts_with(&root) || is_source {
+        return Err(anyhow!(
+            "refusing to delete {}: not an exported copy",
+            path.display()
+        ));
+    }
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+// Opens the OS file manager on the folder holding the photo's file, with the file selected.
+#[tauri::command]
+pub async fn reveal_in_file_manager(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    let path = file_to_reveal(&state.db.lock(), &id).map_err(|e| e.to_string())?;
+    tauri_plugin_opener::reveal_item_in_dir(path).map_err(|e| e.to_string())
+}
+
+// The photo's current file, which must still be on disk; the file manager opens its parent.
+fn file_to_reveal(db: &Db, id: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(db.get_photo_detail(id)?.path);
+    if !path.is_file() {
+        return Err(anyhow!("file no longer exists: {}", path.display()));
+    }
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn classify_bytes(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    bytes: Vec<u8>,
+    ext: String,
+) -> Result<crate::core::classifier::ClassificationOutput, String> {
+    wait_clip_warmup().await;
+    let settings = state.settings.lock().clone();
+    classify_bytes_one(&app, &settings, &bytes, &ext)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn reanalyze_photo(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<crate::core::model::PhotoDetail, String> {
+    wait_clip_warmup().await;
+    let settings = state.settings.lock().clone();
+    let fallback_root = state
+        .pipeline
+        .lock()
+        .last_job_meta()
+        .map(|meta| meta.export_root);
+    reanalyze_one(
+        &app,
+        state.db.clone(),
+        &settings,
+        fallback_root.as_deref(),
+        &id,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// Diagnostic for support requests: checks decode, CLIP and (if configured) Ollama in one go.
+#[tauri::command]
+pub async fn self_test(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SelfTestReport, String> {
+    wait_clip_warmup().await;
+    let settings = state.settings.lock().clone();
+    run_self_test(&app, &settings)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_error_log_path(app: AppHandle) -> Result<String, String> {
+    error_log_path(&app)
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn clear_error_log(app: AppHandle) -> Result<(), String> {
+    clear_log(&app).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn clear_debug_captures(app: AppHandle) -> Result<(), String> {
+    crate::core::debug_capture::clear_debug_captures(&app).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_distribution(
     state: State<'_, AppState>,
     mode: DistributionMode,
+    job_id: Option<String>,
 ) -> Result<Distribution, String> {
-    if let Some(meta) = state.pipeline.lock().last_job_meta() {
-        if meta.engine == AnalysisEngine::Clip {
-            if let Ok(dist) = get_folder_distribution(&meta.export_root, mode.clone()) {
+    // The export folder only reflects the last job, so a specific job always reads the db.
+    let last_job_meta = match job_id {
+        Some(_) => None,
+        None => state.pipeline.lock().last_job_meta(),
+    };
+    distribution(&state.db, last_job_meta, mode, job_id.as_deref()).map_err(|e| e.to_string())
+}
+
+// A CLIP run's export folders are counted directly; anything else falls back to the db.
+fn distribution(
+    db: &Mutex<Db>,
+    last_job_meta: Option<JobMeta>,
+    mode: DistributionMode,
+    job_id: Option<&str>,
+) -> Result<Distribution> {
+    if let Some(meta) = last_job_meta {
+        if meta.engine.uses_clip() {
+            if let Ok(dist) =
+                get_folder_distribution(&meta.export_root, meta.flat_value_export, mode.clone())
+            {
                 return Ok(dist);
             }
         }
     }
-    state
-        .db
-        .lock()
-        .get_distribution(mode)
-        .map_err(|e| e.to_string())
+    db.lock().get_distribution(mode, job_id)
 }
 
-fn get_folder_distribution(export_root: &str, mode: DistributionMode) -> Result<Distribution> {
+fn get_folder_distribution(
+    export_root: &str,
+    flat_value_export: bool,
+    mode: DistributionMode,
+) -> Result<Distribution> {
     let export_root = std::path::Path::new(export_root);
     let mut counts: std::collections::HashMap<String, f32> = CATEGORY_KEYS
         .iter()
         .map(|c| (c.as_str().to_string(), 0.0f32))
         .collect();
 
+    // Recursive so sub-category folders (e.g. 스크린샷_문서/채팅/) count toward their parent.
     fn count_files(dir: &std::path::Path) -> f32 {
         let Ok(rd) = std::fs::read_dir(dir) else {
             return 0.0;
@@ -181,6 +669,8 @@ fn get_folder_distribution(export_root: &str, mode: DistributionMode) -> Result<
             if let Ok(ft) = entry.file_type() {
                 if ft.is_file() {
                     n += 1.0;
+                } else if ft.is_dir() {
+                    n += count_files(&entry.path());
                 }
             }
         }
@@ -193,9 +683,13 @@ fn get_folder_distribution(export_root: &str, mode: DistributionMode) -> Result<
         // Support both layouts:
         // 1) export_root/<카테고리>/
         // 2) export_root/가치있음/<카테고리>/ and export_root/가치없음/<카테고리>/
-        let n = count_files(&export_root.join(leaf))
-            + count_files(&export_root.join("가치있음").join(leaf))
-            + count_files(&export_root.join("가치없음").join(leaf));
+        // A flat export skips (2) so leftovers from earlier nested runs into the same root
+        // don't count.
+        let mut n = count_files(&export_root.join(leaf));
+        if !flat_value_export {
+            n += count_files(&export_root.join("가치있음").join(leaf))
+                + count_files(&export_root.join("가치없음").join(leaf));
+        }
         total += n;
         if let Some(v) = counts.get_mut(k.as_str()) {
             *v = n;
@@ -226,17 +720,100 @@ pub async fn get_progress(state: State<'_, AppState>) -> Result<Option<Progress>
 }
 
 #[tauri::command]
-pub async fn get_value_stats(state: State<'_, AppState>) -> Result<ValueStats, String> {
+pub async fn get_value_stats(
+    state: State<'_, AppState>,
+    job_id: Option<String>,
+) -> Result<ValueStats, String> {
+    state
+        .db
+        .lock()
+        .get_value_stats(job_id.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+// Top tags for a tag cloud, 50 unless `limit` says otherwise.
+#[tauri::command]
+pub async fn get_tag_stats(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+    category: Option<CategoryKey>,
+    job_id: Option<String>,
+) -> Result<Vec<TagCount>, String> {
     state
         .db
         .lock()
-        .get_value_stats()
+        .get_tag_stats(limit.unwrap_or(50), category, job_id.as_deref())
         .map_err(|e| e.to_string())
 }
 
+// With `job_id` only that job's rows and thumbnails are removed.
+#[tauri::command]
+pub async fn clear_results(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    job_id: Option<String>,
+) -> Result<(), String> {
+    let Some(job_id) = job_id else {
+        state
+            .db
+            .lock()
+            .clear_photos(None)
+            .map_err(|e| e.to_string())?;
+        return clear_thumbnails(&app).map_err(|e| e.to_string());
+    };
+    let thumbnails = {
+        let db = state.db.lock();
+        let thumbnails = db.job_thumbnail_paths(&job_id).map_err(|e| e.to_string())?;
+        db.clear_photos(Some(&job_id)).map_err(|e| e.to_string())?;
+        thumbnails
+    };
+    // Thumbnails are regenerated on demand, so a leftover file is harmless.
+    for path in thumbnails {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
+
+// Returns the cached thumbnail, regenerating it from the source (or the exported copy) when the
+// file was removed or the row predates thumbnails.
 #[tauri::command]
-pub async fn clear_results(state: State<'_, AppState>) -> Result<(), String> {
-    state.db.lock().clear_photos().map_err(|e| e.to_string())
+pub async fn get_thumbnail(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<String, String> {
+    let detail = state
+        .db
+        .lock()
+        .get_photo_detail(&id)
+        .map_err(|e| e.to_string())?;
+    if let Some(existing) = detail.thumbnail_path.as_deref() {
+        if std::path::Path::new(existing).exists() {
+            return Ok(existing.to_string());
+        }
+    }
+    let source = [detail.source_path.as_deref(), Some(detail.path.as_str())]
+        .into_iter()
+        .flatten()
+        .map(std::path::PathBuf::from)
+        .find(|p| p.exists())
+        .ok_or_else(|| "source file not found (moved or deleted)".to_string())?;
+    let thumb_app = app.clone();
+    let thumb_id = id.clone();
+    let path = tauri::async_runtime::spawn_blocking(move || {
+        generate_thumbnail(&thumb_app, &thumb_id, &source)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?
+    .to_string_lossy()
+    .to_string();
+    state
+        .db
+        .lock()
+        .set_thumbnail_path(&id, &path)
+        .map_err(|e| e.to_string())?;
+    Ok(path)
 }
 
 #[tauri::command]
@@ -258,9 +835,162 @@ pub async fn get_clip_accel_capabilities() -> Result<ClipAccelCapabilities, Stri
     Ok(ClipAccelCapabilities {
         cpu: cap("CPU", &CPUExecutionProvider::default()),
         coreml: cap("CoreML (Apple)", &CoreMLExecutionProvider::default()),
+        tensorrt: cap("TensorRT (NVIDIA)", &TensorRTExecutionProvider::default()),
         cuda: cap("CUDA (NVIDIA)", &CUDAExecutionProvider::default()),
         rocm: cap("ROCm (AMD)", &ROCmExecutionProvider::default()),
         directml: cap("DirectML (Windows)", &DirectMLExecutionProvider::default()),
         openvino: cap("OpenVINO (Intel)", &OpenVINOExecutionProvider::default()),
+        gpu_devices: list_gpu_devices(),
+    })
+}
+
+#[tauri::command]
+pub async fn get_clip_engine_status() -> Result<ClipEngineStatus, String> {
+    Ok(clip_engine_status())
+}
+
+#[tauri::command]
+pub async fn get_clip_warmup_status() -> Result<Option<ClipWarmupEvent>, String> {
+    Ok(clip_warmup_status())
+}
+
+#[tauri::command]
+pub async fn unload_clip_engine(state: State<'_, AppState>) -> Result<bool, String> {
+    wait_clip_warmup().await;
+    // Hold the pipeline lock so a job can't start while the engine is being dropped.
+    let pipeline = state.pipeline.lock();
+    if pipeline.is_running() {
+        return Err("analysis job is running".to_string());
+    }
+    Ok(unload_engine())
+}
+
+#[tauri::command]
+pub async fn test_clip_prompts(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    image_path: String,
+    prompts: HashMap<CategoryKey, Vec<(String, f32)>>,
+) -> Result<ClipPromptTestResult, String> {
+    wait_clip_warmup().await;
+    let settings = state.settings.lock().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        clip_prompt_test(&app, &settings, Path::new(&image_path), &prompts)
     })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn reload_clip_engine(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ClipReloadResult, String> {
+    wait_clip_warmup().await;
+    // Held until the reload ends so a job can't start on the engine being replaced.
+    let _reload = state
+        .pipeline
+        .lock()
+        .begin_engine_reload()
+        .map_err(|e| e.to_string())?;
+    let settings = state.settings.lock().clone();
+    let elapsed_ms = tauri::async_runtime::spawn_blocking(move || reload_engine(&app, &settings))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(ClipReloadResult {
+        elapsed_ms: elapsed_ms as u64,
+        status: clip_engine_status(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::model::{AnalysisEngine, PhotoDetail, Scores};
+
+    fn exported(id: &str, path: &Path) -> PhotoDetail {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "fileName": path.file_name().unwrap().to_string_lossy(),
+            "path": path.to_string_lossy(),
+            "category": "people",
+            "topScore": 1.0,
+            "scores": Scores::default(),
+            "tags": [],
+            "exportStatus": "success",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reveal_resolves_the_exported_files_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("export").join("people");
+        std::fs::create_dir_all(&folder).unwrap();
+        let file = folder.join("a.jpg");
+        std::fs::write(&file, b"img").unwrap();
+        let db = Db::init_in_memory().unwrap();
+        db.insert_photo(&exported("a", &file)).unwrap();
+
+        let revealed = file_to_reveal(&db, "a").unwrap();
+        assert_eq!(revealed, file);
+        assert_eq!(revealed.parent(), Some(folder.as_path()));
+    }
+
+    #[test]
+    fn reveal_reports_unknown_ids_and_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let gone = dir.path().join("gone.jpg");
+        let db = Db::init_in_memory().unwrap();
+        db.insert_photo(&exported("gone", &gone)).unwrap();
+
+        let unknown = file_to_reveal(&db, "nope").unwrap_err().to_string();
+        assert_eq!(unknown, "not found");
+        let missing = file_to_reveal(&db, "gone").unwrap_err().to_string();
+        assert!(missing.starts_with("file no longer exists"), "{missing}");
+        assert!(missing.contains("gone.jpg"));
+        // A folder at the recorded path isn't a file to reveal either.
+        std::fs::create_dir(&gone).unwrap();
+        assert!(file_to_reveal(&db, "gone").is_err());
+    }
+
+    #[test]
+    fn persisted_job_meta_drives_the_distribution_after_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let export_root = dir.path().join("export");
+        let people = export_root.join(CategoryKey::People.dir_name_ko());
+        let food = export_root.join(CategoryKey::FoodCafe.dir_name_ko());
+        std::fs::create_dir_all(&people).unwrap();
+        std::fs::create_dir_all(&food).unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg"] {
+            std::fs::write(people.join(name), b"img").unwrap();
+        }
+        std::fs::write(food.join("d.jpg"), b"img").unwrap();
+
+        let meta_path = dir.path().join("last_job.json");
+        let meta = JobMeta {
+            job_id: Some("job1".to_string()),
+            export_root: export_root.to_string_lossy().into_owned(),
+            engine: AnalysisEngine::Clip,
+            flat_value_export: false,
+            summary: None,
+        };
+        crate::core::pipeline::save_last_job_to(&meta_path, &meta).unwrap();
+
+        // As after a restart: the meta comes back from disk and the db has no rows.
+        let reloaded = crate::core::pipeline::load_last_job_from(&meta_path).unwrap();
+        assert_eq!(reloaded.job_id.as_deref(), Some("job1"));
+        let db = Mutex::new(Db::init_in_memory().unwrap());
+        let dist = distribution(&db, Some(reloaded), DistributionMode::CountRatio, None).unwrap();
+        assert_eq!(dist.by_category["people"], 0.75);
+        assert_eq!(dist.by_category["food_cafe"], 0.25);
+
+        let from_db = distribution(&db, None, DistributionMode::CountRatio, None).unwrap();
+        assert_eq!(
+            from_db.by_category.get("people").copied().unwrap_or(0.0),
+            0.0
+        );
+    }
 }
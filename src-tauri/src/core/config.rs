@@ -1,10 +1,13 @@
 use crate::core::model::Settings;
 use anyhow::Result;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Manager};
+use tempfile::NamedTempFile;
 
 const SETTINGS_FILE: &str = "settings.json";
+const SETTINGS_BACKUP_FILE: &str = "settings.json.bak";
 
 fn settings_path(app: &AppHandle) -> Result<PathBuf> {
     let dir = app
@@ -15,23 +18,103 @@ fn settings_path(app: &AppHandle) -> Result<PathBuf> {
     Ok(dir.join(SETTINGS_FILE))
 }
 
+fn backup_path(path: &Path) -> PathBuf {
+    path.with_file_name(SETTINGS_BACKUP_FILE)
+}
+
+fn read_settings(path: &Path) -> Result<Settings> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
 pub fn load_settings(app: &AppHandle) -> Settings {
-    let path = match settings_path(app) {
-        Ok(p) => p,
-        Err(_) => return Settings::default(),
-    };
-    if !Path::new(&path).exists() {
+    match settings_path(app) {
+        Ok(path) => load_settings_from(&path),
+        Err(_) => Settings::default(),
+    }
+}
+
+// A corrupt file falls back to the backup from the previous save before giving up on defaults.
+fn load_settings_from(path: &Path) -> Settings {
+    if !path.exists() {
         return Settings::default();
     }
-    match fs::read_to_string(&path) {
-        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-        Err(_) => Settings::default(),
+    match read_settings(path) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", path.display(), e);
+            let backup = backup_path(path);
+            match read_settings(&backup) {
+                Ok(settings) => {
+                    eprintln!("restored settings from {}", backup.display());
+                    settings
+                }
+                Err(e) => {
+                    eprintln!("no usable settings backup ({}), using defaults", e);
+                    Settings::default()
+                }
+            }
+        }
     }
 }
 
 pub fn save_settings(app: &AppHandle, settings: &Settings) -> Result<()> {
     let path = settings_path(app)?;
-    let content = serde_json::to_string_pretty(settings)?;
-    fs::write(path, content)?;
+    write_settings(&path, settings)
+}
+
+// The new file is written next to the old one and renamed over it, so a crash leaves either the
+// old or the new settings. The old file only becomes the backup if it still parses.
+fn write_settings(path: &Path, settings: &Settings) -> Result<()> {
+    if read_settings(path).is_ok() {
+        fs::copy(path, backup_path(path))?;
+    }
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("settings path has no parent"))?;
+    let mut tmp = NamedTempFile::new_in(dir)?;
+    tmp.write_all(serde_json::to_string_pretty(settings)?.as_bytes())?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(path)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_model(model: &str) -> Settings {
+        Settings {
+            ollama_model: model.to_string(),
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn truncated_settings_are_restored_from_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        write_settings(&path, &with_model("first")).unwrap();
+        write_settings(&path, &with_model("second")).unwrap();
+        assert_eq!(load_settings_from(&path).ollama_model, "second");
+
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() / 2]).unwrap();
+        assert_eq!(load_settings_from(&path).ollama_model, "first");
+
+        // A corrupt file never replaces a good backup.
+        write_settings(&path, &with_model("third")).unwrap();
+        fs::write(&path, b"{").unwrap();
+        assert_eq!(load_settings_from(&path).ollama_model, "first");
+    }
+
+    #[test]
+    fn missing_or_unusable_settings_use_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        let defaults = Settings::default().ollama_model;
+        assert_eq!(load_settings_from(&path).ollama_model, defaults);
+        fs::write(&path, b"not json").unwrap();
+        assert_eq!(load_settings_from(&path).ollama_model, defaults);
+    }
+}
@@ -1,17 +1,76 @@
+use crate::core::error_log::append_error;
 use crate::core::model::{
-    CategoryKey, Distribution, DistributionMode, ExportStatus, PhotoDetail, PhotoRow, Scores,
-    ValueStats, CATEGORY_KEYS,
+    CategoryKey, Distribution, DistributionMode, ExportStatus, FailureKind, JobRecord, JobStatus,
+    JobSummary, PhotoDetail, PhotoRow, Scores, Settings, StartAnalysisInput, TagCount, ValueCounts,
+    ValueStats, CATEGORY_KEYS, DEFAULT_TOP_K,
 };
+use crate::core::ollama::tag_key;
 use anyhow::{anyhow, Result};
-use rusqlite::{params, Connection};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, ErrorCode};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Manager};
 
 pub struct Db {
     conn: Connection,
 }
 
+// How long a statement waits on another connection's lock before failing with "database is
+// locked".
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Bound parameters per statement stay under SQLite's limit (999 on older builds).
+const SQL_PARAM_CHUNK: usize = 500;
+
+// Schema steps in order; `user_version` records how many have run. Append new steps, never edit or
+// reorder applied ones.
+const MIGRATIONS: &[fn(&Db) -> Result<()>] = &[
+    migrate_base_tables,
+    migrate_export_columns,
+    migrate_token_counts,
+    migrate_job_tracking,
+    migrate_manual_override,
+    migrate_rfc3339_timestamps,
+    migrate_photo_tags,
+];
+
+// Files a row points at, so the caller can clean them up after deleting it.
+#[derive(Debug, Clone)]
+pub struct PhotoFiles {
+    pub id: String,
+    pub path: String,
+    pub source_path: Option<String>,
+    pub export_root: Option<String>,
+    pub thumbnail_path: Option<String>,
+}
+
+// An errored row `retry_failed` can analyze again.
+#[derive(Debug, Clone)]
+pub struct FailedPhoto {
+    pub id: String,
+    pub source_path: String,
+    pub export_root: String,
+    pub job_id: Option<String>,
+}
+
+// `list_photos` filters, combined with AND; None leaves that column unfiltered.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PhotoFilter {
+    pub status: Option<ExportStatus>,
+    pub category: Option<CategoryKey>,
+    pub valuable: Option<bool>,
+    // Substring of the file name, tags or caption.
+    pub search: Option<String>,
+    pub job_id: Option<String>,
+    // Inclusive bounds on `created_at`, in any form `list_photos_since` accepts.
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
 impl Db {
     pub fn init(app: &AppHandle) -> Result<Self> {
         let path = app
@@ -20,40 +79,81 @@ impl Db {
             .map_err(|e| anyhow!("app data dir: {}", e))?;
         std::fs::create_dir_all(&path)?;
         let db_path = PathBuf::from(path).join("images.db");
+        match Self::open_file(&db_path) {
+            Err(e) if is_corruption(&e) => {
+                let aside = move_aside_corrupt(&db_path)?;
+                let notice = format!(
+                    "results database was unreadable ({}); moved it to {} and started a fresh one",
+                    e,
+                    aside.display()
+                );
+                eprintln!("db: {}", notice);
+                if let Err(log_err) = append_error(app, &db_path, &notice) {
+                    eprintln!("failed to write error log: {}", log_err);
+                }
+                Self::open_file(&db_path)
+            }
+            res => res,
+        }
+    }
+
+    fn open_file(db_path: &Path) -> Result<Self> {
         let conn = Connection::open(db_path)?;
+        // WAL keeps readers from blocking a job's write transactions and lets commits skip most
+        // fsyncs; with WAL, NORMAL sync can lose the last commits on power loss but never
+        // corrupts the file.
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let mode: String =
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        if !mode.eq_ignore_ascii_case("wal") {
+            // Some network file systems can't do WAL; the default journal still works.
+            eprintln!("db: WAL unavailable, using journal_mode={}", mode);
+        }
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
         let db = Db { conn };
         db.migrate()?;
         Ok(db)
     }
 
+    // Same schema as `init`, but nothing touches disk; the rows go away with the `Db`.
+    pub fn init_in_memory() -> Result<Self> {
+        let db = Db {
+            conn: Connection::open_in_memory()?,
+        };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    pub fn open(app: &AppHandle, ephemeral: bool) -> Result<Self> {
+        if ephemeral {
+            Self::init_in_memory()
+        } else {
+            Self::init(app)
+        }
+    }
+
+    // Applies the migrations past the stored `user_version`, each in its own transaction with the
+    // version bump. Databases from before versioning start at 0 with some columns already added,
+    // so every step has to tolerate work that is already done.
     fn migrate(&self) -> Result<()> {
-        self.conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS photos (
-                id TEXT PRIMARY KEY,
-                path TEXT NOT NULL,
-                file_name TEXT NOT NULL,
-                category TEXT NOT NULL,
-                scores TEXT NOT NULL,
-                tags TEXT,
-                caption TEXT,
-                text_in_image TEXT,
-                model TEXT,
-                is_valuable INTEGER,
-                valuable_score REAL,
-                export_status TEXT NOT NULL,
-                error_message TEXT,
-                analysis_log TEXT,
-                analysis_duration_ms INTEGER,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        let current: usize = self
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if current > MIGRATIONS.len() {
+            // Written by a newer build; its extra columns are ignored here.
+            eprintln!(
+                "db: schema version {} is newer than this build ({})",
+                current,
+                MIGRATIONS.len()
             );
-        ",
-        )?;
-        self.ensure_column("photos", "analysis_log", "TEXT")?;
-        self.ensure_column("photos", "analysis_duration_ms", "INTEGER")?;
-        self.ensure_column("photos", "model", "TEXT")?;
-        self.ensure_column("photos", "is_valuable", "INTEGER")?;
-        self.ensure_column("photos", "valuable_score", "REAL")?;
+            return Ok(());
+        }
+        for (i, step) in MIGRATIONS.iter().enumerate().skip(current) {
+            let tx = self.conn.unchecked_transaction()?;
+            step(self).map_err(|e| anyhow!("db migration {}: {}", i + 1, e))?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
         Ok(())
     }
 
@@ -79,69 +179,142 @@ impl Db {
     }
 
     pub fn insert_photo(&self, row: &PhotoDetail) -> Result<()> {
-        let scores_json = serde_json::to_string(&row.scores.to_map())?;
-        let tags_json = serde_json::to_string(&row.tags)?;
-        self.conn.execute(
-            "INSERT OR REPLACE INTO photos
-            (id, path, file_name, category, scores, tags, caption, text_in_image, model, is_valuable, valuable_score, export_status, error_message, analysis_log, analysis_duration_ms)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
-            params![
-                row.id,
-                row.path,
-                row.file_name,
-                row.category.as_str(),
-                scores_json,
-                tags_json,
-                row.caption,
-                row.text_in_image,
-                row.model,
-                row.is_valuable.map(|b| if b { 1 } else { 0 }),
-                row.valuable_score,
-                export_status_to_str(&row.export_status),
-                row.error_message,
-                row.analysis_log,
-                row.analysis_duration_ms,
-            ],
-        )?;
+        let tx = self.conn.unchecked_transaction()?;
+        insert_photo_row(&tx, row)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // One transaction for the whole batch instead of one per row.
+    pub fn insert_photos(&self, rows: &[PhotoDetail]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for row in rows {
+            insert_photo_row(&tx, row)?;
+        }
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn list_photos(&self) -> Result<Vec<PhotoRow>> {
+    // Deletes the rows for `ids` in one transaction. Ids without a row are left out of the result.
+    pub fn delete_photos(&self, ids: &[String]) -> Result<Vec<PhotoFiles>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut deleted = Vec::new();
+        for chunk in ids.chunks(SQL_PARAM_CHUNK) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut stmt = tx.prepare(&format!(
+                "SELECT id, path, source_path, export_root, thumbnail_path FROM photos WHERE id IN ({})",
+                placeholders
+            ))?;
+            let rows = stmt
+                .query_map(params_from_iter(chunk), |row| {
+                    Ok(PhotoFiles {
+                        id: row.get(0)?,
+                        path: row.get(1)?,
+                        source_path: row.get(2)?,
+                        export_root: row.get(3)?,
+                        thumbnail_path: row.get(4)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            deleted.extend(rows);
+            tx.execute(
+                &format!(
+                    "DELETE FROM photo_tags WHERE photo_id IN ({})",
+                    placeholders
+                ),
+                params_from_iter(chunk),
+            )?;
+            tx.execute(
+                &format!("DELETE FROM photos WHERE id IN ({})", placeholders),
+                params_from_iter(chunk),
+            )?;
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    // Successful rows of a job whose exported file no row of another job (or a reanalysis)
+    // also points at.
+    pub fn job_exported_files(&self, job_id: &str) -> Result<Vec<PhotoFiles>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, path, file_name, category, scores, tags, export_status, error_message, analysis_duration_ms, model, is_valuable, valuable_score FROM photos ORDER BY created_at DESC",
+            "SELECT id, path, source_path, export_root, thumbnail_path FROM photos p
+            WHERE job_id=?1 AND export_status=?2 AND NOT EXISTS (
+              SELECT 1 FROM photos o WHERE o.path = p.path AND o.id != p.id
+                AND (o.job_id IS NULL OR o.job_id != ?1)
+            )",
         )?;
         let rows = stmt
-            .query_map([], |row| {
-                let scores_map: HashMap<String, f32> =
-                    serde_json::from_str(row.get::<_, String>(4)?.as_str()).unwrap_or_default();
-                let scores = Scores::from_map(&scores_map);
-                let top = scores.top();
-                Ok(PhotoRow {
-                    id: row.get(0)?,
-                    path: row.get(1)?,
-                    file_name: row.get(2)?,
-                    category: CategoryKey::from(row.get::<_, String>(3)?.as_str()),
-                    scores: scores.clone(),
-                    top_score: top.1,
-                    tags: serde_json::from_str(row.get::<_, String>(5)?.as_str())
-                        .unwrap_or_default(),
-                    export_status: str_to_export_status(row.get::<_, String>(6)?.as_str()),
-                    error_message: row.get(7)?,
-                    analysis_duration_ms: row.get(8)?,
-                    model: row.get(9)?,
-                    is_valuable: row
-                        .get::<_, Option<i64>>(10)?
-                        .map(|v| v != 0),
-                    valuable_score: row.get(11)?,
-                })
-            })?
+            .query_map(
+                params![job_id, export_status_to_str(&ExportStatus::Success)],
+                |row| {
+                    Ok(PhotoFiles {
+                        id: row.get(0)?,
+                        path: row.get(1)?,
+                        source_path: row.get(2)?,
+                        export_root: row.get(3)?,
+                        thumbnail_path: row.get(4)?,
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    // Newest first; `id` breaks ties so pages don't overlap when a batch shares one timestamp.
+    // `limit` None returns every matching row.
+    pub fn list_photos(
+        &self,
+        filter: &PhotoFilter,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Result<Vec<PhotoRow>> {
+        let (where_sql, mut values) = filter_clause(&self.conn, filter)?;
+        let page_sql = match limit {
+            Some(limit) => {
+                values.push(Value::Integer(limit as i64));
+                values.push(Value::Integer(offset as i64));
+                "LIMIT ? OFFSET ?"
+            }
+            None => "",
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos {} ORDER BY created_at DESC, id {}",
+            PHOTO_ROW_COLUMNS, where_sql, page_sql
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(values), photo_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn count_photos(&self, filter: &PhotoFilter) -> Result<usize> {
+        let (where_sql, values) = filter_clause(&self.conn, filter)?;
+        let count: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM photos {}", where_sql),
+            params_from_iter(values),
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    // `since` goes through SQLite's datetime(), so dates, `T`-separated times, fractional
+    // seconds and `Z`/`+HH:MM` offsets all work; anything it can't read is rejected instead of
+    // silently matching nothing.
+    pub fn list_photos_since(&self, since: &str) -> Result<Vec<PhotoRow>> {
+        let normalized = normalize_timestamp(&self.conn, since)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos WHERE datetime(created_at) >= ?1 ORDER BY created_at DESC",
+            PHOTO_ROW_COLUMNS
+        ))?;
+        let rows = stmt
+            .query_map([normalized], photo_row)?
             .collect::<Result<Vec<_>, _>>()?;
         Ok(rows)
     }
 
     pub fn get_photo_detail(&self, id: &str) -> Result<PhotoDetail> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, path, file_name, category, scores, tags, export_status, error_message, caption, text_in_image, analysis_log, analysis_duration_ms, model, is_valuable, valuable_score
+            "SELECT id, path, file_name, category, scores, tags, export_status, error_message, caption, text_in_image, analysis_log, analysis_duration_ms, model, is_valuable, valuable_score, source_path, export_root, subcategory, value_bucket, thumbnail_path, prompt_tokens, eval_tokens, strftime('%Y-%m-%dT%H:%M:%SZ', created_at), job_id, exif, failure_kind, manual_override
             FROM photos WHERE id=?1",
         )?;
         let mut rows = stmt.query([id])?;
@@ -157,6 +330,7 @@ impl Db {
                 category: CategoryKey::from(row.get::<_, String>(3)?.as_str()),
                 scores: scores.clone(),
                 top_score: top.1,
+                top_k: scores.top_k(DEFAULT_TOP_K),
                 tags: serde_json::from_str(row.get::<_, String>(5)?.as_str()).unwrap_or_default(),
                 export_status: str_to_export_status(row.get::<_, String>(6)?.as_str()),
                 error_message: row.get(7)?,
@@ -167,74 +341,325 @@ impl Db {
                 model: row.get(12)?,
                 is_valuable: row.get::<_, Option<i64>>(13)?.map(|v| v != 0),
                 valuable_score: row.get(14)?,
+                source_path: row.get(15)?,
+                export_root: row.get(16)?,
+                subcategory: row.get(17)?,
+                value_bucket: row.get(18)?,
+                thumbnail_path: row.get(19)?,
+                prompt_tokens: row.get(20)?,
+                eval_tokens: row.get(21)?,
+                created_at: row.get(22)?,
+                job_id: row.get(23)?,
+                exif: row
+                    .get::<_, Option<String>>(24)?
+                    .and_then(|s| serde_json::from_str(&s).ok()),
+                failure_kind: row
+                    .get::<_, Option<String>>(25)?
+                    .as_deref()
+                    .map(str_to_failure_kind),
+                manual_override: row.get::<_, i64>(26)? != 0,
             });
         }
         Err(anyhow!("not found"))
     }
 
-    pub fn get_value_stats(&self) -> Result<ValueStats> {
+    // `job_id` None counts every row.
+    pub fn get_value_stats(&self, job_id: Option<&str>) -> Result<ValueStats> {
         let mut stmt = self.conn.prepare(
-            "SELECT
+            "SELECT category,
               SUM(CASE WHEN is_valuable = 1 THEN 1 ELSE 0 END) AS valuable,
               SUM(CASE WHEN is_valuable = 0 THEN 1 ELSE 0 END) AS not_valuable,
               SUM(CASE WHEN is_valuable IS NULL THEN 1 ELSE 0 END) AS unknown
-            FROM photos",
+            FROM photos WHERE ?1 IS NULL OR job_id = ?1
+            GROUP BY category",
         )?;
-        let mut rows = stmt.query([])?;
-        if let Some(row) = rows.next()? {
-            let valuable: i64 = row.get(0)?;
-            let not_valuable: i64 = row.get(1)?;
-            let unknown: i64 = row.get(2)?;
-            return Ok(ValueStats {
-                valuable: valuable.max(0) as usize,
-                not_valuable: not_valuable.max(0) as usize,
-                unknown: unknown.max(0) as usize,
-            });
+        let mut by_category: HashMap<String, ValueCounts> = CATEGORY_KEYS
+            .iter()
+            .map(|c| (c.as_str().to_string(), ValueCounts::default()))
+            .collect();
+        let mut rows = stmt.query([job_id])?;
+        while let Some(row) = rows.next()? {
+            let counts = by_category.entry(row.get::<_, String>(0)?).or_default();
+            counts.valuable += row.get::<_, i64>(1)?.max(0) as usize;
+            counts.not_valuable += row.get::<_, i64>(2)?.max(0) as usize;
+            counts.unknown += row.get::<_, i64>(3)?.max(0) as usize;
         }
         Ok(ValueStats {
-            valuable: 0,
-            not_valuable: 0,
-            unknown: 0,
+            valuable: by_category.values().map(|c| c.valuable).sum(),
+            not_valuable: by_category.values().map(|c| c.not_valuable).sum(),
+            unknown: by_category.values().map(|c| c.unknown).sum(),
+            by_category,
         })
     }
 
-    pub fn clear_photos(&self) -> Result<()> {
-        self.conn.execute("DELETE FROM photos", [])?;
+    // A hand-picked category; the subcategory belonged to the old one and is cleared.
+    pub fn set_photo_category(&self, id: &str, category: CategoryKey, path: &str) -> Result<()> {
+        let changed = self.conn.execute(
+            "UPDATE photos SET category=?2, subcategory=NULL, path=?3, manual_override=1 WHERE id=?1",
+            params![id, category.as_str(), path],
+        )?;
+        if changed == 0 {
+            return Err(anyhow!("not found"));
+        }
+        Ok(())
+    }
+
+    pub fn set_thumbnail_path(&self, id: &str, thumbnail_path: &str) -> Result<()> {
+        let changed = self.conn.execute(
+            "UPDATE photos SET thumbnail_path=?1 WHERE id=?2",
+            params![thumbnail_path, id],
+        )?;
+        if changed == 0 {
+            return Err(anyhow!("not found"));
+        }
+        Ok(())
+    }
+
+    // Settings and input are kept as JSON so a resumed job runs exactly as it started.
+    pub fn insert_job(
+        &self,
+        id: &str,
+        input: &StartAnalysisInput,
+        settings: &Settings,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO jobs (id, source_root, export_root, settings, input, status, started_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
+            params![
+                id,
+                input.source_root,
+                input.export_root,
+                serde_json::to_string(settings)?,
+                serde_json::to_string(input)?,
+                job_status_to_str(&JobStatus::Running),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_job_status(&self, id: &str, status: &JobStatus) -> Result<()> {
+        self.conn.execute(
+            "UPDATE jobs SET status=?1 WHERE id=?2",
+            params![job_status_to_str(status), id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_job_summary(&self, id: &str, summary: &JobSummary) -> Result<()> {
+        self.conn.execute(
+            "UPDATE jobs SET summary=?1 WHERE id=?2",
+            params![serde_json::to_string(summary)?, id],
+        )?;
+        Ok(())
+    }
+
+    // Every errored photo, optionally for one job and only some failure kinds. Rows without an
+    // export root can't be exported again and are left out; rows from before kinds were recorded
+    // only match when no kinds are given.
+    pub fn failed_photos(
+        &self,
+        job_id: Option<&str>,
+        kinds: Option<&[FailureKind]>,
+    ) -> Result<Vec<FailedPhoto>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, COALESCE(source_path, path), export_root, job_id, failure_kind FROM photos
+            WHERE export_status=?1 AND export_root IS NOT NULL AND (?2 IS NULL OR job_id=?2)",
+        )?;
+        let rows = stmt
+            .query_map(
+                params![export_status_to_str(&ExportStatus::Error), job_id],
+                |row| {
+                    Ok((
+                        FailedPhoto {
+                            id: row.get(0)?,
+                            source_path: row.get(1)?,
+                            export_root: row.get(2)?,
+                            job_id: row.get(3)?,
+                        },
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows
+            .into_iter()
+            .filter(|(_, kind)| match kinds {
+                None => true,
+                Some(kinds) => kind
+                    .as_deref()
+                    .is_some_and(|k| kinds.contains(&str_to_failure_kind(k))),
+            })
+            .map(|(photo, _)| photo)
+            .collect())
+    }
+
+    // Nothing is running at startup, so any job still marked running was cut off.
+    pub fn abort_running_jobs(&self) -> Result<usize> {
+        let changed = self.conn.execute(
+            "UPDATE jobs SET status=?1 WHERE status=?2",
+            params![
+                job_status_to_str(&JobStatus::Aborted),
+                job_status_to_str(&JobStatus::Running)
+            ],
+        )?;
+        Ok(changed)
+    }
+
+    pub fn list_jobs(&self) -> Result<Vec<JobRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, source_root, export_root, status, strftime('%Y-%m-%dT%H:%M:%SZ', started_at),
+              COALESCE(p.count, 0), summary,
+              strftime('%Y-%m-%dT%H:%M:%SZ', p.first_at), strftime('%Y-%m-%dT%H:%M:%SZ', p.last_at)
+            FROM jobs
+            LEFT JOIN (
+              SELECT job_id, COUNT(*) AS count, MIN(created_at) AS first_at, MAX(created_at) AS last_at
+              FROM photos GROUP BY job_id
+            ) p ON p.job_id = jobs.id
+            ORDER BY started_at DESC",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(JobRecord {
+                    id: row.get(0)?,
+                    source_root: row.get(1)?,
+                    export_root: row.get(2)?,
+                    status: str_to_job_status(row.get::<_, String>(3)?.as_str()),
+                    started_at: row.get(4)?,
+                    done: row.get::<_, i64>(5)?.max(0) as usize,
+                    summary: row
+                        .get::<_, Option<String>>(6)?
+                        .and_then(|s| serde_json::from_str(&s).ok()),
+                    first_photo_at: row.get(7)?,
+                    last_photo_at: row.get(8)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn job_snapshot(&self, id: &str) -> Result<(StartAnalysisInput, Settings)> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT input, settings FROM jobs WHERE id=?1")?;
+        let mut rows = stmt.query([id])?;
+        let Some(row) = rows.next()? else {
+            return Err(anyhow!("job not found"));
+        };
+        let input: String = row.get(0)?;
+        let settings: String = row.get(1)?;
+        Ok((
+            serde_json::from_str(&input)?,
+            serde_json::from_str(&settings)?,
+        ))
+    }
+
+    // Source paths that already have a row for the job, successful or not.
+    pub fn job_source_paths(&self, id: &str) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT source_path FROM photos WHERE job_id=?1 AND source_path IS NOT NULL",
+        )?;
+        let paths = stmt
+            .query_map([id], |row| row.get::<_, String>(0))?
+            .collect::<Result<HashSet<_>, _>>()?;
+        Ok(paths)
+    }
+
+    pub fn job_thumbnail_paths(&self, id: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT thumbnail_path FROM photos WHERE job_id=?1 AND thumbnail_path IS NOT NULL",
+        )?;
+        let paths = stmt
+            .query_map([id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(paths)
+    }
+
+    // `job_id` None deletes every row.
+    pub fn clear_photos(&self, job_id: Option<&str>) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM photo_tags WHERE photo_id IN (SELECT id FROM photos WHERE ?1 IS NULL OR job_id = ?1)",
+            [job_id],
+        )?;
+        tx.execute(
+            "DELETE FROM photos WHERE ?1 IS NULL OR job_id = ?1",
+            [job_id],
+        )?;
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn get_distribution(&self, mode: DistributionMode) -> Result<Distribution> {
-        let rows = self.list_photos()?;
+    // Most used tags first, counted once per photo; ties go alphabetically by key. `category`
+    // and `job_id` narrow the photos counted.
+    pub fn get_tag_stats(
+        &self,
+        limit: usize,
+        category: Option<CategoryKey>,
+        job_id: Option<&str>,
+    ) -> Result<Vec<TagCount>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT MIN(t.tag), COUNT(*) AS n FROM photo_tags t JOIN photos p ON p.id = t.photo_id
+            WHERE (?1 IS NULL OR p.category = ?1) AND (?2 IS NULL OR p.job_id = ?2)
+            GROUP BY t.tag_key ORDER BY n DESC, t.tag_key LIMIT ?3",
+        )?;
+        let rows = stmt
+            .query_map(
+                params![category.map(|c| c.as_str()), job_id, limit as i64],
+                |row| {
+                    Ok(TagCount {
+                        tag: row.get(0)?,
+                        count: row.get::<_, i64>(1)?.max(0) as usize,
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    // Both modes aggregate in SQLite instead of loading and re-parsing every row. `job_id` None
+    // covers every row.
+    pub fn get_distribution(
+        &self,
+        mode: DistributionMode,
+        job_id: Option<&str>,
+    ) -> Result<Distribution> {
         let mut by_category: HashMap<String, f32> = CATEGORY_KEYS
             .iter()
             .map(|c| (c.as_str().to_string(), 0.0f32))
             .collect();
 
-        if rows.is_empty() {
-            return Ok(Distribution { mode, by_category });
-        }
-
         match mode {
             DistributionMode::CountRatio => {
-                for row in rows.iter() {
-                    *by_category.get_mut(row.category.as_str()).unwrap() += 1.0;
+                let mut stmt = self
+                    .conn
+                    .prepare(
+                    "SELECT category, COUNT(*) FROM photos WHERE ?1 IS NULL OR job_id = ?1 GROUP BY category",
+                )?;
+                let mut rows = stmt.query([job_id])?;
+                let mut total = 0.0f32;
+                while let Some(row) = rows.next()? {
+                    let category = CategoryKey::from(row.get::<_, String>(0)?.as_str());
+                    let count = row.get::<_, i64>(1)? as f32;
+                    *by_category.get_mut(category.as_str()).unwrap() += count;
+                    total += count;
                 }
-                let total = rows.len() as f32;
-                for val in by_category.values_mut() {
-                    *val = (*val / total).round_to(4);
+                if total > 0.0 {
+                    for val in by_category.values_mut() {
+                        *val = (*val / total).round_to(4);
+                    }
                 }
             }
             DistributionMode::AvgScore => {
-                for row in rows.iter() {
-                    let map = row.scores.to_map();
-                    for (k, v) in map {
-                        *by_category.get_mut(&k).unwrap() += v;
+                // NULL for every key when there are no rows; those stay 0.
+                let averages: Vec<Option<f64>> =
+                    self.conn.query_row(&avg_score_sql(), [job_id], |row| {
+                        (0..CATEGORY_KEYS.len()).map(|i| row.get(i)).collect()
+                    })?;
+                for (key, avg) in CATEGORY_KEYS.iter().zip(averages) {
+                    if let Some(avg) = avg {
+                        *by_category.get_mut(key.as_str()).unwrap() = (avg as f32).round_to(4);
                     }
                 }
-                let total = rows.len() as f32;
-                for val in by_category.values_mut() {
-                    *val = (*val / total).round_to(4);
-                }
             }
         }
 
@@ -242,6 +667,334 @@ impl Db {
     }
 }
 
+fn migrate_base_tables(db: &Db) -> Result<()> {
+    db.conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS photos (
+            id TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            file_name TEXT NOT NULL,
+            category TEXT NOT NULL,
+            scores TEXT NOT NULL,
+            tags TEXT,
+            caption TEXT,
+            text_in_image TEXT,
+            export_status TEXT NOT NULL,
+            error_message TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS jobs (
+            id TEXT PRIMARY KEY,
+            source_root TEXT NOT NULL,
+            export_root TEXT NOT NULL,
+            settings TEXT NOT NULL,
+            input TEXT NOT NULL,
+            status TEXT NOT NULL,
+            started_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_photos_export_status ON photos(export_status);
+        CREATE INDEX IF NOT EXISTS idx_photos_category ON photos(category);
+    ",
+    )?;
+    db.ensure_column("photos", "analysis_log", "TEXT")?;
+    db.ensure_column("photos", "analysis_duration_ms", "INTEGER")?;
+    db.ensure_column("photos", "model", "TEXT")?;
+    db.ensure_column("photos", "is_valuable", "INTEGER")?;
+    db.ensure_column("photos", "valuable_score", "REAL")?;
+    Ok(())
+}
+
+fn migrate_export_columns(db: &Db) -> Result<()> {
+    db.ensure_column("photos", "source_path", "TEXT")?;
+    db.ensure_column("photos", "export_root", "TEXT")?;
+    db.ensure_column("photos", "subcategory", "TEXT")?;
+    db.ensure_column("photos", "value_bucket", "TEXT")?;
+    db.ensure_column("photos", "thumbnail_path", "TEXT")?;
+    Ok(())
+}
+
+fn migrate_token_counts(db: &Db) -> Result<()> {
+    db.ensure_column("photos", "prompt_tokens", "INTEGER")?;
+    db.ensure_column("photos", "eval_tokens", "INTEGER")?;
+    Ok(())
+}
+
+fn migrate_job_tracking(db: &Db) -> Result<()> {
+    db.ensure_column("photos", "job_id", "TEXT")?;
+    db.ensure_column("photos", "exif", "TEXT")?;
+    db.ensure_column("photos", "failure_kind", "TEXT")?;
+    db.ensure_column("jobs", "summary", "TEXT")?;
+    db.conn
+        .execute_batch("CREATE INDEX IF NOT EXISTS idx_photos_job_id ON photos(job_id);")?;
+    Ok(())
+}
+
+fn migrate_manual_override(db: &Db) -> Result<()> {
+    db.ensure_column("photos", "manual_override", "INTEGER NOT NULL DEFAULT 0")
+}
+
+// Rows from before timestamps were written explicitly hold CURRENT_TIMESTAMP's `YYYY-MM-DD
+// HH:MM:SS`, which sorts before the RFC 3339 form on the same day.
+fn migrate_rfc3339_timestamps(db: &Db) -> Result<()> {
+    db.conn.execute_batch(
+        "
+        UPDATE photos SET created_at = strftime('%Y-%m-%dT%H:%M:%SZ', created_at)
+        WHERE created_at NOT LIKE '%T%';
+        UPDATE jobs SET started_at = strftime('%Y-%m-%dT%H:%M:%SZ', started_at)
+        WHERE started_at NOT LIKE '%T%';
+    ",
+    )?;
+    Ok(())
+}
+
+// One row per distinct tag key of a photo, so tag counts don't parse every row's JSON. Existing
+// rows are backfilled from their `tags` column.
+fn migrate_photo_tags(db: &Db) -> Result<()> {
+    db.conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS photo_tags (
+            photo_id TEXT NOT NULL,
+            tag_key TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (photo_id, tag_key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_photo_tags_tag_key ON photo_tags(tag_key);
+    ",
+    )?;
+    let mut stmt = db.conn.prepare("SELECT id, tags FROM photos")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, tags) in rows {
+        let tags: Vec<String> = tags
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        insert_photo_tags(&db.conn, &id, &tags)?;
+    }
+    Ok(())
+}
+
+fn insert_photo_tags(conn: &Connection, photo_id: &str, tags: &[String]) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR IGNORE INTO photo_tags (photo_id, tag_key, tag) VALUES (?1, ?2, ?3)",
+    )?;
+    for tag in tags {
+        let key = tag_key(tag);
+        if !key.is_empty() {
+            stmt.execute(params![
+                photo_id,
+                key,
+                tag.split_whitespace().collect::<Vec<_>>().join(" ")
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+// One row of per-category average scores over the rows of job `?1` (all rows when NULL). Each
+// row's scores are divided by their sum first, as `Scores::from_map` does; a missing key or
+// unreadable JSON counts as 0.
+fn avg_score_sql() -> String {
+    let keys: Vec<&str> = CATEGORY_KEYS.iter().map(|k| k.as_str()).collect();
+    let extract = keys
+        .iter()
+        .map(|k| {
+            format!(
+                "CASE WHEN json_valid(scores) THEN COALESCE(json_extract(scores, '$.{k}'), 0) ELSE 0 END AS {k}"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sum = keys.join(" + ");
+    let averages = keys
+        .iter()
+        .map(|k| format!("AVG({k} / denom)"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "SELECT {averages} FROM (
+            SELECT *, CASE WHEN {sum} <= 0 THEN 1.0 ELSE ({sum}) * 1.0 END AS denom
+            FROM (SELECT {extract} FROM photos WHERE ?1 IS NULL OR job_id = ?1)
+        )"
+    )
+}
+
+fn insert_photo_row(conn: &Connection, row: &PhotoDetail) -> Result<()> {
+    let scores_json = serde_json::to_string(&row.scores.to_map())?;
+    let tags_json = serde_json::to_string(&row.tags)?;
+    let exif_json = row.exif.as_ref().map(serde_json::to_string).transpose()?;
+    let failure_kind = row.failure_kind.as_ref().map(failure_kind_to_str);
+    // Cached on the connection, so batched inserts parse the statement once. A row that already
+    // has a `created_at` (a reanalysis replacing its original) keeps it.
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR REPLACE INTO photos
+        (id, path, file_name, category, scores, tags, caption, text_in_image, model, is_valuable, valuable_score, export_status, error_message, analysis_log, analysis_duration_ms, source_path, export_root, subcategory, value_bucket, thumbnail_path, prompt_tokens, eval_tokens, job_id, exif, failure_kind, manual_override, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, COALESCE(?27, strftime('%Y-%m-%dT%H:%M:%SZ', 'now')))",
+    )?;
+    stmt.execute(params![
+        row.id,
+        row.path,
+        row.file_name,
+        row.category.as_str(),
+        scores_json,
+        tags_json,
+        row.caption,
+        row.text_in_image,
+        row.model,
+        row.is_valuable.map(|b| if b { 1 } else { 0 }),
+        row.valuable_score,
+        export_status_to_str(&row.export_status),
+        row.error_message,
+        row.analysis_log,
+        row.analysis_duration_ms,
+        row.source_path,
+        row.export_root,
+        row.subcategory,
+        row.value_bucket,
+        row.thumbnail_path,
+        row.prompt_tokens,
+        row.eval_tokens,
+        row.job_id,
+        exif_json,
+        failure_kind,
+        row.manual_override,
+        row.created_at,
+    ])?;
+    conn.prepare_cached("DELETE FROM photo_tags WHERE photo_id=?1")?
+        .execute([&row.id])?;
+    insert_photo_tags(conn, &row.id, &row.tags)?;
+    Ok(())
+}
+
+// Only damage that a fresh file fixes; a database locked by another process is left alone.
+fn is_corruption(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<rusqlite::Error>()
+            .and_then(|e| e.sqlite_error_code()),
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+    )
+}
+
+// Renames `images.db` (and its WAL files, which belong to it) to `images.db.corrupt-<unix secs>`
+// so the data can still be inspected or salvaged.
+fn move_aside_corrupt(db_path: &Path) -> Result<PathBuf> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut aside = db_path.as_os_str().to_owned();
+    aside.push(format!(".corrupt-{}", stamp));
+    let aside = PathBuf::from(aside);
+    std::fs::rename(db_path, &aside)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut side = db_path.as_os_str().to_owned();
+        side.push(suffix);
+        let side = PathBuf::from(side);
+        if side.exists() {
+            let mut target = aside.as_os_str().to_owned();
+            target.push(suffix);
+            std::fs::rename(&side, PathBuf::from(target))?;
+        }
+    }
+    Ok(aside)
+}
+
+// WHERE clause and its parameters for `filter`. Only the filters that are set become clauses,
+// so the indexed columns stay usable.
+fn filter_clause(conn: &Connection, filter: &PhotoFilter) -> Result<(String, Vec<Value>)> {
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut values: Vec<Value> = Vec::new();
+    if let Some(status) = &filter.status {
+        clauses.push("export_status = ?");
+        values.push(Value::Text(export_status_to_str(status).to_string()));
+    }
+    if let Some(category) = &filter.category {
+        clauses.push("category = ?");
+        values.push(Value::Text(category.as_str().to_string()));
+    }
+    if let Some(valuable) = filter.valuable {
+        clauses.push("is_valuable = ?");
+        values.push(Value::Integer(valuable as i64));
+    }
+    if let Some(job_id) = &filter.job_id {
+        clauses.push("job_id = ?");
+        values.push(Value::Text(job_id.clone()));
+    }
+    if let Some(from) = &filter.from {
+        clauses.push("datetime(created_at) >= ?");
+        values.push(Value::Text(normalize_timestamp(conn, from)?));
+    }
+    if let Some(to) = &filter.to {
+        clauses.push("datetime(created_at) <= ?");
+        values.push(Value::Text(normalize_timestamp(conn, to)?));
+    }
+    if let Some(search) = filter.search.as_deref().map(str::trim) {
+        if !search.is_empty() {
+            clauses.push(
+                "(file_name LIKE ? ESCAPE '\\' OR tags LIKE ? ESCAPE '\\' OR caption LIKE ? ESCAPE '\\')",
+            );
+            let pattern = format!("%{}%", escape_like(search));
+            values.extend(vec![Value::Text(pattern); 3]);
+        }
+    }
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    Ok((where_sql, values))
+}
+
+// UTC `YYYY-MM-DD HH:MM:SS` as SQLite's datetime() reads it; an unreadable timestamp is an error
+// rather than a filter that matches nothing.
+fn normalize_timestamp(conn: &Connection, raw: &str) -> Result<String> {
+    let normalized: Option<String> =
+        conn.query_row("SELECT datetime(?1)", [raw.trim()], |row| row.get(0))?;
+    normalized.ok_or_else(|| anyhow!("invalid timestamp: {}", raw))
+}
+
+// `%` and `_` in user input match literally.
+fn escape_like(raw: &str) -> String {
+    raw.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// created_at is stored as UTC RFC 3339 (`YYYY-MM-DDTHH:MM:SSZ`); strftime keeps the output in
+// that form for any row it can parse.
+const PHOTO_ROW_COLUMNS: &str = "id, path, file_name, category, scores, tags, export_status, error_message, analysis_duration_ms, model, is_valuable, valuable_score, thumbnail_path, strftime('%Y-%m-%dT%H:%M:%SZ', created_at), failure_kind";
+
+fn photo_row(row: &rusqlite::Row) -> rusqlite::Result<PhotoRow> {
+    let scores_map: HashMap<String, f32> =
+        serde_json::from_str(row.get::<_, String>(4)?.as_str()).unwrap_or_default();
+    let scores = Scores::from_map(&scores_map);
+    let top = scores.top();
+    Ok(PhotoRow {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        file_name: row.get(2)?,
+        category: CategoryKey::from(row.get::<_, String>(3)?.as_str()),
+        scores: scores.clone(),
+        top_score: top.1,
+        top_k: scores.top_k(DEFAULT_TOP_K),
+        tags: serde_json::from_str(row.get::<_, String>(5)?.as_str()).unwrap_or_default(),
+        export_status: str_to_export_status(row.get::<_, String>(6)?.as_str()),
+        error_message: row.get(7)?,
+        analysis_duration_ms: row.get(8)?,
+        model: row.get(9)?,
+        is_valuable: row.get::<_, Option<i64>>(10)?.map(|v| v != 0),
+        valuable_score: row.get(11)?,
+        thumbnail_path: row.get(12)?,
+        created_at: row.get(13)?,
+        failure_kind: row
+            .get::<_, Option<String>>(14)?
+            .as_deref()
+            .map(str_to_failure_kind),
+    })
+}
+
 trait Roundable {
     fn round_to(self, digits: u32) -> Self;
 }
@@ -261,6 +1014,50 @@ fn export_status_to_str(status: &ExportStatus) -> &'static str {
     }
 }
 
+fn job_status_to_str(status: &JobStatus) -> &'static str {
+    match status {
+        JobStatus::Idle => "idle",
+        JobStatus::Running => "running",
+        JobStatus::Completed => "completed",
+        JobStatus::Canceled => "canceled",
+        JobStatus::Error => "error",
+        JobStatus::Aborted => "aborted",
+    }
+}
+
+fn str_to_job_status(raw: &str) -> JobStatus {
+    match raw {
+        "idle" => JobStatus::Idle,
+        "running" => JobStatus::Running,
+        "completed" => JobStatus::Completed,
+        "canceled" => JobStatus::Canceled,
+        "aborted" => JobStatus::Aborted,
+        _ => JobStatus::Error,
+    }
+}
+
+fn failure_kind_to_str(kind: &FailureKind) -> &'static str {
+    match kind {
+        FailureKind::Network => "network",
+        FailureKind::Timeout => "timeout",
+        FailureKind::ModelRefused => "model_refused",
+        FailureKind::ParseFailed => "parse_failed",
+        FailureKind::Canceled => "canceled",
+        FailureKind::Other => "other",
+    }
+}
+
+fn str_to_failure_kind(raw: &str) -> FailureKind {
+    match raw {
+        "network" => FailureKind::Network,
+        "timeout" => FailureKind::Timeout,
+        "model_refused" => FailureKind::ModelRefused,
+        "parse_failed" => FailureKind::ParseFailed,
+        "canceled" => FailureKind::Canceled,
+        _ => FailureKind::Other,
+    }
+}
+
 fn str_to_export_status(raw: &str) -> ExportStatus {
     match raw {
         "success" => ExportStatus::Success,
@@ -269,3 +1066,444 @@ fn str_to_export_status(raw: &str) -> ExportStatus {
         _ => ExportStatus::Error,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo(id: &str, category: CategoryKey, scores: &[(&str, f32)], job_id: &str) -> PhotoDetail {
+        let scores = Scores::from_map(&scores.iter().map(|(k, v)| (k.to_string(), *v)).collect());
+        PhotoDetail {
+            id: id.to_string(),
+            file_name: format!("{}.jpg", id),
+            path: format!("/export/{}.jpg", id),
+            category,
+            top_score: 0.0,
+            top_k: Vec::new(),
+            scores,
+            tags: vec!["beach".to_string()],
+            export_status: ExportStatus::Success,
+            error_message: None,
+            analysis_log: None,
+            analysis_duration_ms: Some(10),
+            caption: None,
+            text_in_image: None,
+            model: None,
+            is_valuable: Some(true),
+            valuable_score: None,
+            source_path: Some(format!("/src/{}.jpg", id)),
+            export_root: Some("/export".to_string()),
+            subcategory: None,
+            value_bucket: None,
+            thumbnail_path: None,
+            prompt_tokens: None,
+            eval_tokens: None,
+            created_at: None,
+            job_id: Some(job_id.to_string()),
+            exif: None,
+            failure_kind: None,
+            manual_override: false,
+        }
+    }
+
+    #[test]
+    fn in_memory_db_lists_inserted_rows() {
+        let db = Db::init_in_memory().unwrap();
+        db.insert_photos(&[
+            photo("a", CategoryKey::People, &[("people", 1.0)], "job1"),
+            photo("b", CategoryKey::FoodCafe, &[("food_cafe", 1.0)], "job1"),
+        ])
+        .unwrap();
+
+        let rows = db.list_photos(&PhotoFilter::default(), None, 0).unwrap();
+        let mut ids: Vec<_> = rows.iter().map(|r| r.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "b"]);
+        let detail = db.get_photo_detail("a").unwrap();
+        assert_eq!(detail.category, CategoryKey::People);
+        assert_eq!(detail.tags, ["beach"]);
+    }
+
+    #[test]
+    fn in_memory_db_count_distribution() {
+        let db = Db::init_in_memory().unwrap();
+        let empty = db
+            .get_distribution(DistributionMode::CountRatio, None)
+            .unwrap();
+        assert!(empty.by_category.values().all(|v| *v == 0.0));
+
+        db.insert_photos(&[
+            photo("a", CategoryKey::People, &[("people", 1.0)], "job1"),
+            photo("b", CategoryKey::People, &[("people", 1.0)], "job1"),
+            photo("c", CategoryKey::Other, &[("other", 1.0)], "job2"),
+        ])
+        .unwrap();
+
+        let all = db
+            .get_distribution(DistributionMode::CountRatio, None)
+            .unwrap();
+        assert_eq!(all.by_category["people"], 0.6667);
+        assert_eq!(all.by_category["other"], 0.3333);
+        assert_eq!(all.by_category.len(), CATEGORY_KEYS.len());
+        let job2 = db
+            .get_distribution(DistributionMode::CountRatio, Some("job2"))
+            .unwrap();
+        assert_eq!(job2.by_category["other"], 1.0);
+        assert_eq!(job2.by_category["people"], 0.0);
+    }
+
+    #[test]
+    fn in_memory_db_value_and_tag_stats() {
+        let db = Db::init_in_memory().unwrap();
+        let stats = db.get_value_stats(None).unwrap();
+        assert_eq!(
+            (stats.valuable, stats.not_valuable, stats.unknown),
+            (0, 0, 0)
+        );
+        assert_eq!(stats.by_category.len(), CATEGORY_KEYS.len());
+
+        let mut rows = vec![
+            photo("a", CategoryKey::People, &[("people", 1.0)], "job1"),
+            photo("b", CategoryKey::People, &[("people", 1.0)], "job1"),
+            photo("c", CategoryKey::People, &[("people", 1.0)], "job1"),
+            photo("d", CategoryKey::Other, &[("other", 1.0)], "job2"),
+        ];
+        rows[1].is_valuable = Some(false);
+        rows[2].is_valuable = None;
+        rows[0].tags = vec!["Beach".to_string(), "sunset".to_string()];
+        rows[3].tags = vec!["sunset".to_string()];
+        db.insert_photos(&rows).unwrap();
+
+        let all = db.get_value_stats(None).unwrap();
+        assert_eq!((all.valuable, all.not_valuable, all.unknown), (2, 1, 1));
+        let people = &all.by_category["people"];
+        assert_eq!(
+            (people.valuable, people.not_valuable, people.unknown),
+            (1, 1, 1)
+        );
+        assert_eq!(all.by_category["food_cafe"].valuable, 0);
+        let job2 = db.get_value_stats(Some("job2")).unwrap();
+        assert_eq!((job2.valuable, job2.not_valuable, job2.unknown), (1, 0, 0));
+
+        let tags = |category, job_id| {
+            db.get_tag_stats(10, category, job_id)
+                .unwrap()
+                .into_iter()
+                .map(|t| (t.tag, t.count))
+                .collect::<Vec<_>>()
+        };
+        // "Beach" and "beach" count as one tag.
+        assert_eq!(
+            tags(None, None),
+            [("Beach".to_string(), 3), ("sunset".to_string(), 2)]
+        );
+        assert_eq!(
+            tags(Some(CategoryKey::Other), None),
+            [("sunset".to_string(), 1)]
+        );
+        assert_eq!(tags(None, Some("job2")), [("sunset".to_string(), 1)]);
+
+        // Clearing one job leaves the other's rows and tags in place.
+        db.clear_photos(Some("job1")).unwrap();
+        let left = db.get_value_stats(None).unwrap();
+        assert_eq!((left.valuable, left.not_valuable, left.unknown), (1, 0, 0));
+        assert_eq!(tags(None, None), [("sunset".to_string(), 1)]);
+    }
+
+    // The per-row fold `get_distribution` used before averaging moved into SQL.
+    fn folded_avg_scores(db: &Db, job_id: Option<&str>) -> HashMap<String, f32> {
+        let mut stmt = db
+            .conn
+            .prepare("SELECT scores FROM photos WHERE ?1 IS NULL OR job_id = ?1")
+            .unwrap();
+        let raw: Vec<String> = stmt
+            .query_map([job_id], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut sums: HashMap<String, f32> = HashMap::new();
+        for scores in &raw {
+            let map: HashMap<String, f32> = serde_json::from_str(scores).unwrap_or_default();
+            for (k, v) in Scores::from_map(&map).to_map() {
+                *sums.entry(k).or_default() += v;
+            }
+        }
+        sums.into_iter()
+            .map(|(k, v)| (k, v / raw.len() as f32))
+            .collect()
+    }
+
+    #[test]
+    fn sql_avg_score_matches_the_row_fold() {
+        let db = Db::init_in_memory().unwrap();
+        db.insert_photos(&[
+            photo(
+                "a",
+                CategoryKey::People,
+                &[("people", 0.7), ("other", 0.3)],
+                "job1",
+            ),
+            photo("b", CategoryKey::People, &[], "job1"),
+            photo("c", CategoryKey::FoodCafe, &[], "job1"),
+            photo("d", CategoryKey::Other, &[], "job2"),
+            photo("e", CategoryKey::Other, &[], "job2"),
+        ])
+        .unwrap();
+        // Scores as older builds or hand edits could have left them: unnormalized, partial,
+        // all zero and unreadable.
+        for (id, scores) in [
+            (
+                "b",
+                r#"{"people": 0.9, "food_cafe": 0.9, "pets_animals": 0.2}"#,
+            ),
+            ("c", r#"{"food_cafe": 3}"#),
+            ("d", r#"{"people": 0, "other": 0}"#),
+            ("e", "not json"),
+        ] {
+            db.conn
+                .execute(
+                    "UPDATE photos SET scores=?1 WHERE id=?2",
+                    params![scores, id],
+                )
+                .unwrap();
+        }
+
+        for job_id in [None, Some("job1"), Some("job2")] {
+            let sql = db
+                .get_distribution(DistributionMode::AvgScore, job_id)
+                .unwrap();
+            let folded = folded_avg_scores(&db, job_id);
+            for key in CATEGORY_KEYS {
+                let key = key.as_str();
+                let diff = (sql.by_category[key] - folded[key]).abs();
+                assert!(
+                    diff < 1e-4,
+                    "{:?} {}: {} vs {}",
+                    job_id,
+                    key,
+                    sql.by_category[key],
+                    folded[key]
+                );
+            }
+        }
+        let job1 = db
+            .get_distribution(DistributionMode::AvgScore, Some("job1"))
+            .unwrap();
+        assert_eq!(job1.by_category["food_cafe"], 0.4833);
+    }
+
+    #[test]
+    fn migrates_a_database_from_before_versioning() {
+        // The shape of a pre-versioning database: `user_version` 0, base tables plus some of the
+        // later columns, and CURRENT_TIMESTAMP-style timestamps.
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE photos (
+                id TEXT PRIMARY KEY,
+                path TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                category TEXT NOT NULL,
+                scores TEXT NOT NULL,
+                tags TEXT,
+                caption TEXT,
+                text_in_image TEXT,
+                export_status TEXT NOT NULL,
+                error_message TEXT,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                source_path TEXT
+            );
+            CREATE TABLE jobs (
+                id TEXT PRIMARY KEY,
+                source_root TEXT NOT NULL,
+                export_root TEXT NOT NULL,
+                settings TEXT NOT NULL,
+                input TEXT NOT NULL,
+                status TEXT NOT NULL,
+                started_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO photos (id, path, file_name, category, scores, tags, export_status,
+                created_at, source_path)
+            VALUES ('a', '/export/a.jpg', 'a.jpg', 'people', '{\"people\": 1.0}',
+                '[\"Beach\", \"beach \", \"sunset\"]', 'success', '2024-05-01 10:00:00',
+                '/src/a.jpg');
+            INSERT INTO jobs (id, source_root, export_root, settings, input, status, started_at)
+            VALUES ('job1', '/src', '/export', '{}', '{}', 'completed', '2024-05-01 09:59:00');
+        ",
+        )
+        .unwrap();
+        let db = Db { conn };
+        db.migrate().unwrap();
+
+        let version: usize = db
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        let detail = db.get_photo_detail("a").unwrap();
+        assert_eq!(detail.source_path.as_deref(), Some("/src/a.jpg"));
+        assert_eq!(detail.job_id, None);
+        assert!(!detail.manual_override);
+        let (created, started): (String, String) = db
+            .conn
+            .query_row(
+                "SELECT p.created_at, j.started_at FROM photos p, jobs j",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(created, "2024-05-01T10:00:00Z");
+        assert_eq!(started, "2024-05-01T09:59:00Z");
+        let tags = db.get_tag_stats(10, None, None).unwrap();
+        let tags: Vec<_> = tags.iter().map(|t| (t.tag.as_str(), t.count)).collect();
+        assert_eq!(tags, [("Beach", 1), ("sunset", 1)]);
+
+        // Already at the latest version: nothing left to run.
+        db.migrate().unwrap();
+        assert_eq!(db.get_tag_stats(10, None, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn garbage_database_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("images.db");
+        std::fs::write(&db_path, vec![0xAB; 4096]).unwrap();
+
+        let err = Db::open_file(&db_path)
+            .err()
+            .expect("garbage should not open");
+        assert!(is_corruption(&err), "{}", err);
+        let aside = move_aside_corrupt(&db_path).unwrap();
+        assert!(!db_path.exists());
+        assert_eq!(std::fs::read(&aside).unwrap(), vec![0xAB; 4096]);
+
+        let db = Db::open_file(&db_path).unwrap();
+        assert_eq!(db.count_photos(&PhotoFilter::default()).unwrap(), 0);
+    }
+
+    #[test]
+    fn locked_database_is_not_treated_as_corrupt() {
+        let err = anyhow::Error::from(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        ));
+        assert!(!is_corruption(&err));
+    }
+
+    #[test]
+    fn concurrent_writer_and_reader_do_not_hit_locks() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("images.db");
+        Db::open_file(&db_path).unwrap();
+
+        // Each thread has its own connection, like a job and the UI; a "database is locked"
+        // error fails one of the unwraps.
+        let writer_path = db_path.clone();
+        let writer = std::thread::spawn(move || {
+            let db = Db::open_file(&writer_path).unwrap();
+            for batch in 0..50 {
+                let rows: Vec<_> = (0..10)
+                    .map(|i| {
+                        let id = format!("p{}_{}", batch, i);
+                        photo(&id, CategoryKey::People, &[("people", 1.0)], "job1")
+                    })
+                    .collect();
+                db.insert_photos(&rows).unwrap();
+            }
+        });
+        let reader = std::thread::spawn(move || {
+            let db = Db::open_file(&db_path).unwrap();
+            let mut last = 0;
+            for _ in 0..200 {
+                let count = db.count_photos(&PhotoFilter::default()).unwrap();
+                assert!(count >= last);
+                last = count;
+                db.list_photos(&PhotoFilter::default(), Some(20), 0)
+                    .unwrap();
+                db.get_distribution(DistributionMode::CountRatio, None)
+                    .unwrap();
+            }
+        });
+        writer.join().unwrap();
+        reader.join().unwrap();
+
+        let db = Db::open_file(&dir.path().join("images.db")).unwrap();
+        assert_eq!(db.count_photos(&PhotoFilter::default()).unwrap(), 500);
+    }
+
+    #[test]
+    fn batch_of_fifty_inserts_is_queryable() {
+        let db = Db::init_in_memory().unwrap();
+        let rows: Vec<_> = (0..50)
+            .map(|i| {
+                let (category, key) = if i % 2 == 0 {
+                    (CategoryKey::People, "people")
+                } else {
+                    (CategoryKey::FoodCafe, "food_cafe")
+                };
+                photo(&format!("p{:02}", i), category, &[(key, 1.0)], "job1")
+            })
+            .collect();
+        db.insert_photos(&rows).unwrap();
+
+        assert_eq!(db.count_photos(&PhotoFilter::default()).unwrap(), 50);
+        assert_eq!(
+            db.list_photos(&PhotoFilter::default(), None, 0)
+                .unwrap()
+                .len(),
+            50
+        );
+        let people = PhotoFilter {
+            category: Some(CategoryKey::People),
+            ..PhotoFilter::default()
+        };
+        assert_eq!(db.count_photos(&people).unwrap(), 25);
+        for row in &rows {
+            let detail = db.get_photo_detail(&row.id).unwrap();
+            assert_eq!(detail.category, row.category);
+            assert_eq!(detail.source_path, row.source_path);
+        }
+    }
+
+    #[test]
+    fn list_photos_since_filters_by_created_at() {
+        let db = Db::init_in_memory().unwrap();
+        let rows: Vec<_> = [
+            ("old", "2024-01-01T08:00:00Z"),
+            ("edge", "2024-03-01T00:00:00Z"),
+            ("new", "2024-03-05T12:30:00Z"),
+            // Stored with an offset; 2024-03-01T01:00:00Z in UTC.
+            ("offset", "2024-03-01T10:00:00+09:00"),
+        ]
+        .into_iter()
+        .map(|(id, created_at)| {
+            let mut row = photo(id, CategoryKey::People, &[("people", 1.0)], "job1");
+            row.created_at = Some(created_at.to_string());
+            row
+        })
+        .collect();
+        db.insert_photos(&rows).unwrap();
+
+        let ids = |since: &str| {
+            let mut ids: Vec<_> = db
+                .list_photos_since(since)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids("2024-03-01"), ["edge", "new", "offset"]);
+        assert_eq!(ids("2024-03-01T00:30:00Z"), ["new", "offset"]);
+        assert_eq!(ids("2024-03-01 10:30:00+09:00"), ["new"]);
+        assert_eq!(ids("2024-03-01T12:00:00.250Z"), ["new"]);
+        assert!(ids("2025-01-01").is_empty());
+        assert_eq!(ids("2023-12-31").len(), 4);
+
+        let new = db.get_photo_detail("new").unwrap();
+        assert_eq!(new.created_at.as_deref(), Some("2024-03-05T12:30:00Z"));
+        let err = db.list_photos_since("last tuesday").unwrap_err();
+        assert_eq!(err.to_string(), "invalid timestamp: last tuesday");
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const DEBUG_DIR: &str = "debug";
+
+fn debug_dir(app: &AppHandle) -> Result<PathBuf> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("app data dir: {}", e))?;
+    Ok(dir.join(DEBUG_DIR))
+}
+
+// `<app_data>/debug/<job_id>/<photo_id>.json`. Only builds the path; nothing touches the disk
+// until a response is actually written.
+pub fn capture_path(app: &AppHandle, job_id: &str, photo_id: &str) -> Result<PathBuf> {
+    Ok(debug_dir(app)?
+        .join(job_id)
+        .join(format!("{}.json", photo_id)))
+}
+
+pub fn write_capture(path: &Path, body: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, body)?;
+    Ok(())
+}
+
+pub fn clear_debug_captures(app: &AppHandle) -> Result<()> {
+    let dir = debug_dir(app)?;
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
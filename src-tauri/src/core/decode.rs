@@ -1,116 +1,378 @@
+use crate::core::model::PhotoExif;
 use anyhow::{anyhow, Result};
 use base64::Engine;
+use exif::{In, Tag, Value};
 use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
 use image::imageops::FilterType;
-use image::DynamicImage;
+use image::{DynamicImage, ImageEncoder, ImageFormat, ImageReader, RgbImage};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Cursor, Write};
 use std::path::Path;
 use std::process::Command;
-use tempfile::Builder;
+use tempfile::{Builder, NamedTempFile};
+use tokio_util::sync::CancellationToken;
 
 const DEFAULT_MAX_EDGE: u32 = 1280;
 const DEFAULT_JPEG_QUALITY: u8 = 75;
+pub const DEFAULT_ALPHA_BACKGROUND: [u8; 3] = [255, 255, 255];
+// An embedded RAW preview at least this large is used instead of a full decode; analysis never
+// needs more.
+const MIN_RAW_PREVIEW_EDGE: u32 = 1024;
 
 pub struct EncodedImage {
-    pub base64_jpeg: String,
+    pub base64: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeFormat {
+    Jpeg,
+    // Lossless; `jpeg_quality` is ignored.
+    Png,
+}
+
+// Target size for the resize step. Zero or non-finite parameters leave the image as is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeSpec {
+    // Caps the long edge.
+    MaxEdge(u32),
+    // Scales both edges to this percentage of the original, clamped to the range below.
+    Percent(f32),
+    // Largest size that fits inside the (width, height) box, keeping the aspect ratio.
+    ExactFit(u32, u32),
+}
+
+pub const MIN_RESIZE_PERCENT: f32 = 1.0;
+// Above 100 only applies with `allow_upscale`.
+pub const MAX_RESIZE_PERCENT: f32 = 400.0;
+
 #[derive(Debug, Clone, Copy)]
 pub struct DecodeOptions {
     pub resize_enabled: bool,
-    pub max_edge: u32,
+    pub resize: ResizeSpec,
+    // Off by default: enlarging only costs time and bytes without adding detail.
+    pub allow_upscale: bool,
     pub jpeg_quality: u8,
     pub resize_filter: FilterType,
+    pub encode_format: EncodeFormat,
+    // Transparent pixels are composited over this color; the plain RGB conversion would turn
+    // them black.
+    pub alpha_background: [u8; 3],
 }
 
 impl Default for DecodeOptions {
     fn default() -> Self {
         Self {
             resize_enabled: true,
-            max_edge: DEFAULT_MAX_EDGE,
+            resize: ResizeSpec::MaxEdge(DEFAULT_MAX_EDGE),
+            allow_upscale: false,
             jpeg_quality: DEFAULT_JPEG_QUALITY,
             resize_filter: FilterType::Lanczos3,
+            encode_format: EncodeFormat::Jpeg,
+            alpha_background: DEFAULT_ALPHA_BACKGROUND,
         }
     }
 }
 
-pub fn decode_resize_base64_with_options(path: &Path, opts: DecodeOptions) -> Result<EncodedImage> {
-    let ext = path
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-
-    let img = decode_dynamic_image_inner(path, &ext)?;
-    let rgb = img.to_rgb8();
-    let (w, h) = rgb.dimensions();
-    let long_edge = w.max(h);
+pub fn decode_resize_base64_with_options(
+    path: &Path,
+    opts: DecodeOptions,
+    cancel: Option<&CancellationToken>,
+) -> Result<EncodedImage> {
+    let img = decode_dynamic_image(path, cancel)?;
+    // Neither resize nor encode can stop midway, so this is the last chance to skip them.
+    check_canceled(cancel)?;
+    let resized = resize_rgb(img, &opts);
+    let (new_w, new_h) = resized.dimensions();
     let jpeg_quality = opts.jpeg_quality.clamp(1, 100);
 
-    let (new_w, new_h, resized) =
-        if opts.resize_enabled && opts.max_edge > 0 && long_edge > opts.max_edge {
-            let scale = opts.max_edge as f32 / long_edge as f32;
-            let new_w = ((w as f32) * scale).round().max(1.0) as u32;
-            let new_h = ((h as f32) * scale).round().max(1.0) as u32;
-            let resized = image::imageops::resize(&rgb, new_w, new_h, opts.resize_filter);
-            (new_w, new_h, resized)
-        } else {
-            (w, h, rgb)
-        };
+    let mut buf: Vec<u8> = Vec::new();
+    match opts.encode_format {
+        EncodeFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(&mut buf, jpeg_quality);
+            encoder.encode(
+                resized.as_raw(),
+                new_w,
+                new_h,
+                image::ColorType::Rgb8.into(),
+            )?;
+        }
+        EncodeFormat::Png => {
+            PngEncoder::new(&mut buf).write_image(
+                resized.as_raw(),
+                new_w,
+                new_h,
+                image::ColorType::Rgb8.into(),
+            )?;
+        }
+    }
+    let base64 = base64::engine::general_purpose::STANDARD.encode(buf);
+    Ok(EncodedImage { base64 })
+}
 
+// A small flat grey JPEG, enough for a server to show whether the model accepts images at all.
+pub fn probe_image_base64() -> Result<String> {
+    let img = RgbImage::from_pixel(16, 16, image::Rgb([128, 128, 128]));
     let mut buf: Vec<u8> = Vec::new();
-    {
-        let mut encoder = JpegEncoder::new_with_quality(&mut buf, jpeg_quality);
-        encoder.encode(
-            resized.as_raw(),
-            new_w,
-            new_h,
-            image::ColorType::Rgb8.into(),
-        )?;
+    JpegEncoder::new_with_quality(&mut buf, DEFAULT_JPEG_QUALITY).encode(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        image::ColorType::Rgb8.into(),
+    )?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(buf))
+}
+
+// Same resize as the analysis path, but the JPEG goes to `dest` instead of base64.
+// `encode_format` is ignored; thumbnails are always JPEG.
+pub fn write_resized_jpeg(source: &Path, dest: &Path, opts: DecodeOptions) -> Result<(u32, u32)> {
+    let img = decode_dynamic_image(source, None)?;
+    let resized = resize_rgb(img, &opts);
+    let (w, h) = resized.dimensions();
+    let mut file = BufWriter::new(File::create(dest)?);
+    JpegEncoder::new_with_quality(&mut file, opts.jpeg_quality.clamp(1, 100)).encode(
+        resized.as_raw(),
+        w,
+        h,
+        image::ColorType::Rgb8.into(),
+    )?;
+    file.flush()?;
+    Ok((w, h))
+}
+
+pub fn target_size(w: u32, h: u32, spec: ResizeSpec, allow_upscale: bool) -> (u32, u32) {
+    let scale = match spec {
+        ResizeSpec::MaxEdge(max_edge) if max_edge > 0 => max_edge as f32 / w.max(h).max(1) as f32,
+        ResizeSpec::Percent(percent) if percent.is_finite() => {
+            percent.clamp(MIN_RESIZE_PERCENT, MAX_RESIZE_PERCENT) / 100.0
+        }
+        ResizeSpec::ExactFit(box_w, box_h) if box_w > 0 && box_h > 0 => {
+            (box_w as f32 / w.max(1) as f32).min(box_h as f32 / h.max(1) as f32)
+        }
+        _ => return (w, h),
+    };
+    if scale >= 1.0 && !allow_upscale {
+        return (w, h);
     }
-    let base64_jpeg = base64::engine::general_purpose::STANDARD.encode(buf);
-    Ok(EncodedImage { base64_jpeg })
+    let new_w = ((w as f32) * scale).round().max(1.0) as u32;
+    let new_h = ((h as f32) * scale).round().max(1.0) as u32;
+    (new_w, new_h)
 }
 
-pub fn decode_dynamic_image(path: &Path) -> Result<DynamicImage> {
+// `to_rgb8` just drops alpha, which leaves the stored color under transparent pixels (usually
+// black). Blend each pixel over `background` instead.
+pub fn flatten_alpha(img: &DynamicImage, background: [u8; 3]) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let a = a as u32;
+        let blend = |c: u8, bg: u8| ((c as u32 * a + bg as u32 * (255 - a) + 127) / 255) as u8;
+        image::Rgb([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    })
+}
+
+fn resize_rgb(img: DynamicImage, opts: &DecodeOptions) -> RgbImage {
+    let rgb = flatten_alpha(&img, opts.alpha_background);
+    let (w, h) = rgb.dimensions();
+    if !opts.resize_enabled {
+        return rgb;
+    }
+    let (new_w, new_h) = target_size(w, h, opts.resize, opts.allow_upscale);
+    if (new_w, new_h) == (w, h) {
+        rgb
+    } else {
+        image::imageops::resize(&rgb, new_w, new_h, opts.resize_filter)
+    }
+}
+
+// Engines and the HEIC/DNG converters work on paths, so in-memory input is written to a temp file
+// with the right extension. The file is removed when the returned handle is dropped.
+pub fn write_temp_image(bytes: &[u8], ext: &str) -> Result<NamedTempFile> {
+    if bytes.is_empty() {
+        return Err(anyhow!("empty image data"));
+    }
+    let mut tmp = Builder::new().suffix(&format!(".{}", ext)).tempfile()?;
+    tmp.write_all(bytes)?;
+    tmp.flush()?;
+    Ok(tmp)
+}
+
+// `cancel` is checked before decoding and kills a running `sips` conversion; an in-process decode
+// can't be interrupted and runs to completion.
+pub fn decode_dynamic_image(
+    path: &Path,
+    cancel: Option<&CancellationToken>,
+) -> Result<DynamicImage> {
     let ext = path
         .extension()
         .and_then(|s| s.to_str())
         .unwrap_or("")
         .to_lowercase();
-    decode_dynamic_image_inner(path, &ext)
+    check_canceled(cancel)?;
+    match ext.as_str() {
+        "heic" => decode_heic(path, cancel),
+        "dng" => decode_dng(path, cancel),
+        "cr2" => decode_raw(path, "CR2", cancel),
+        "nef" => decode_raw(path, "NEF", cancel),
+        "arw" => decode_raw(path, "ARW", cancel),
+        _ => Ok(image::open(path)?),
+    }
 }
 
-fn decode_dynamic_image_inner(path: &Path, ext: &str) -> Result<DynamicImage> {
-    match ext {
-        "heic" => decode_heic(path),
-        "dng" => decode_dng(path),
-        _ => Ok(image::open(path)?),
+// Reads capture metadata from the file's EXIF block. Files without one (PNG screenshots, stripped
+// exports) return None; a tag that is missing or malformed just leaves its field empty.
+pub fn read_exif(path: &Path) -> Option<PhotoExif> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    let ascii = |tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(parts) => parts
+            .first()
+            .map(|s| String::from_utf8_lossy(s).trim().to_string())
+            .filter(|s| !s.is_empty()),
+        _ => None,
+    };
+    let rationals = |tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(v) => Some(v.iter().map(|r| r.to_f64()).collect::<Vec<_>>()),
+        _ => None,
+    };
+    // Degrees, minutes, seconds plus an N/S or E/W reference.
+    let coordinate = |tag, ref_tag, negative: &str| {
+        let dms = rationals(tag)?;
+        let [d, m, s] = dms.as_slice() else {
+            return None;
+        };
+        let value = d + m / 60.0 + s / 3600.0;
+        let sign = if ascii(ref_tag).as_deref() == Some(negative) {
+            -1.0
+        } else {
+            1.0
+        };
+        value.is_finite().then_some(sign * value)
+    };
+    let date_time_original = exif
+        .get_field(Tag::DateTimeOriginal, In::PRIMARY)
+        .and_then(|f| match &f.value {
+            Value::Ascii(parts) => exif::DateTime::from_ascii(parts.first()?).ok(),
+            _ => None,
+        })
+        .map(|dt| {
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second
+            )
+        });
+    Some(PhotoExif {
+        make: ascii(Tag::Make),
+        model: ascii(Tag::Model),
+        date_time_original,
+        focal_length_mm: rationals(Tag::FocalLength)
+            .and_then(|v| v.first().copied())
+            .filter(|v| v.is_finite()),
+        gps_latitude: coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+        gps_longitude: coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+    })
+}
+
+pub fn check_canceled(cancel: Option<&CancellationToken>) -> Result<()> {
+    if cancel.is_some_and(|c| c.is_cancelled()) {
+        return Err(anyhow!("canceled"));
     }
+    Ok(())
 }
 
-fn decode_heic(path: &Path) -> Result<DynamicImage> {
-    // macOS: leverage `sips` for HEIC -> JPEG conversion to temp file
+// Converts to a temp JPEG with `sips`. A large RAW can take seconds, so the child is polled and
+// killed as soon as `cancel` fires instead of being waited out.
+#[cfg(target_os = "macos")]
+fn convert_with_sips(
+    path: &Path,
+    kind: &str,
+    cancel: Option<&CancellationToken>,
+) -> Result<DynamicImage> {
+    let tmp = Builder::new().suffix(".jpg").tempfile()?;
+    let out_path = tmp.path().to_owned();
+    let mut child = Command::new("sips")
+        .args(["-s", "format", "jpeg", path.to_str().unwrap(), "--out"])
+        .arg(&out_path)
+        .spawn()?;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Err(e) = check_canceled(cancel) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    };
+    if !status.success() {
+        return Err(anyhow!("sips failed to convert {}", kind));
+    }
+    Ok(image::open(&out_path)?)
+}
+
+fn decode_heic(path: &Path, cancel: Option<&CancellationToken>) -> Result<DynamicImage> {
+    // macOS: leverage `sips` for HEIC -> JPEG conversion to temp file.
+    // `sips` only converts the primary image, so Live Photo motion and burst/secondary frames are ignored.
     #[cfg(target_os = "macos")]
     {
-        let tmp = Builder::new().suffix(".jpg").tempfile()?;
-        let out_path = tmp.path().to_owned();
-        let status = Command::new("sips")
-            .args(["-s", "format", "jpeg", path.to_str().unwrap(), "--out"])
-            .arg(&out_path)
-            .status()?;
-        if !status.success() {
-            return Err(anyhow!("sips failed to convert HEIC"));
-        }
-        let img = image::open(&out_path)?;
-        return Ok(img);
+        return convert_with_sips(path, "HEIC", cancel);
+    }
+    #[cfg(all(not(target_os = "macos"), feature = "heif"))]
+    {
+        check_canceled(cancel)?;
+        decode_heif_primary(path)
     }
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(all(not(target_os = "macos"), not(feature = "heif")))]
     {
-        Err(anyhow!("HEIC decoding not supported on this platform"))
+        let _ = cancel;
+        Err(anyhow!(
+            "HEIC decoding not supported on this platform; it needs the `heif` feature"
+        ))
     }
 }
 
-fn decode_dng(path: &Path) -> Result<DynamicImage> {
+// Bursts and Live Photos hold several images. Like `sips`, only the primary one is decoded, not
+// the first in the file; motion and secondary frames are ignored.
+#[cfg(feature = "heif")]
+fn decode_heif_primary(path: &Path) -> Result<DynamicImage> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid path: {}", path.display()))?;
+    let ctx =
+        HeifContext::read_from_file(path_str).map_err(|e| anyhow!("HEIC read failed: {}", e))?;
+    let handle = ctx
+        .primary_image_handle()
+        .map_err(|e| anyhow!("HEIC has no primary image: {}", e))?;
+    let image = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(|e| anyhow!("HEIC decode failed: {}", e))?;
+    let planes = image.planes();
+    let plane = planes
+        .interleaved
+        .ok_or_else(|| anyhow!("HEIC decode returned no RGB plane"))?;
+    let row = plane.width as usize * 3;
+    let mut data = Vec::with_capacity(row * plane.height as usize);
+    for line in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        data.extend_from_slice(&line[..row]);
+    }
+    let rgb = RgbImage::from_raw(plane.width, plane.height, data)
+        .ok_or_else(|| anyhow!("HEIC decode returned a short buffer"))?;
+    Ok(DynamicImage::ImageRgb8(rgb))
+}
+
+fn decode_dng(path: &Path, cancel: Option<&CancellationToken>) -> Result<DynamicImage> {
     // Attempt with image crate (tiff/dng) first
     match image::open(path) {
         Ok(img) => Ok(img),
@@ -118,22 +380,339 @@ fn decode_dng(path: &Path) -> Result<DynamicImage> {
             #[cfg(target_os = "macos")]
             {
                 // fallback to sips
-                let tmp = Builder::new().suffix(".jpg").tempfile()?;
-                let out_path = tmp.path().to_owned();
-                let status = Command::new("sips")
-                    .args(["-s", "format", "jpeg", path.to_str().unwrap(), "--out"])
-                    .arg(&out_path)
-                    .status()?;
-                if !status.success() {
-                    return Err(anyhow!("sips failed to convert DNG"));
-                }
-                let img = image::open(&out_path)?;
-                Ok(img)
+                convert_with_sips(path, "DNG", cancel)
             }
             #[cfg(not(target_os = "macos"))]
             {
-                Err(anyhow!("DNG decoding not supported on this platform"))
+                decode_raw(path, "DNG", cancel)
+            }
+        }
+    }
+}
+
+// The embedded JPEG preview comes first: it decodes in milliseconds and is larger than any
+// analysis size. Without one, a full demosaic needs the `raw` feature (or `sips` on macOS).
+fn decode_raw(path: &Path, kind: &str, cancel: Option<&CancellationToken>) -> Result<DynamicImage> {
+    if let Some(preview) = embedded_jpeg_preview(path, cancel)? {
+        return Ok(preview);
+    }
+    check_canceled(cancel)?;
+    #[cfg(feature = "raw")]
+    {
+        demosaic_raw(path, kind)
+    }
+    #[cfg(all(not(feature = "raw"), target_os = "macos"))]
+    {
+        convert_with_sips(path, kind, cancel)
+    }
+    #[cfg(all(not(feature = "raw"), not(target_os = "macos")))]
+    {
+        Err(anyhow!(
+            "{} has no usable embedded preview; full RAW decoding needs the `raw` feature",
+            kind
+        ))
+    }
+}
+
+// CR2, NEF and ARW all embed at least one JPEG. Candidates are found by their start marker and
+// sized from their headers; the largest one that decodes wins. The sensor data of some formats is
+// itself a lossless JPEG the decoder rejects, which is why failures move on to the next candidate.
+pub fn embedded_jpeg_preview(
+    path: &Path,
+    cancel: Option<&CancellationToken>,
+) -> Result<Option<DynamicImage>> {
+    let data = fs::read(path)?;
+    let mut candidates = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = data[pos..].windows(3).position(|w| w == [0xFF, 0xD8, 0xFF]) {
+        let start = pos + offset;
+        let reader = ImageReader::with_format(Cursor::new(&data[start..]), ImageFormat::Jpeg);
+        if let Ok((w, h)) = reader.into_dimensions() {
+            if w.max(h) >= MIN_RAW_PREVIEW_EDGE {
+                candidates.push((w.max(h), start));
+            }
+        }
+        pos = start + 3;
+    }
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.0));
+    for (_, start) in candidates {
+        check_canceled(cancel)?;
+        if let Ok(img) = image::load_from_memory_with_format(&data[start..], ImageFormat::Jpeg) {
+            return Ok(Some(img));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "raw")]
+fn demosaic_raw(path: &Path, kind: &str) -> Result<DynamicImage> {
+    let decoded = imagepipe::simple_decode_8bit(path, 0, 0)
+        .map_err(|e| anyhow!("{} decode failed: {}", kind, e))?;
+    let rgb = RgbImage::from_raw(decoded.width as u32, decoded.height as u32, decoded.data)
+        .ok_or_else(|| anyhow!("{} decode returned a short buffer", kind))?;
+    Ok(DynamicImage::ImageRgb8(rgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(all(feature = "heif", not(target_os = "macos")))]
+    use libheif_rs::{
+        Channel, ColorSpace, CompressionFormat, EncoderQuality, HeifContext, Image, LibHeif,
+        RgbChroma,
+    };
+
+    #[test]
+    fn each_resize_spec_gives_the_expected_size() {
+        assert_eq!(
+            target_size(4000, 3000, ResizeSpec::MaxEdge(1024), false),
+            (1024, 768)
+        );
+        assert_eq!(
+            target_size(3000, 4000, ResizeSpec::MaxEdge(1024), false),
+            (768, 1024)
+        );
+        assert_eq!(
+            target_size(4000, 3000, ResizeSpec::Percent(50.0), false),
+            (2000, 1500)
+        );
+        assert_eq!(
+            target_size(4000, 3000, ResizeSpec::ExactFit(800, 800), false),
+            (800, 600)
+        );
+        assert_eq!(
+            target_size(4000, 1000, ResizeSpec::ExactFit(800, 800), false),
+            (800, 200)
+        );
+
+        // Never upscaled unless allowed.
+        assert_eq!(
+            target_size(400, 300, ResizeSpec::MaxEdge(1024), false),
+            (400, 300)
+        );
+        assert_eq!(
+            target_size(400, 300, ResizeSpec::Percent(200.0), false),
+            (400, 300)
+        );
+        assert_eq!(
+            target_size(400, 300, ResizeSpec::Percent(200.0), true),
+            (800, 600)
+        );
+        assert_eq!(
+            target_size(400, 300, ResizeSpec::ExactFit(800, 800), true),
+            (800, 600)
+        );
+
+        // Percent is clamped; degenerate specs leave the size alone.
+        assert_eq!(
+            target_size(1000, 500, ResizeSpec::Percent(0.01), false),
+            (10, 5)
+        );
+        assert_eq!(
+            target_size(100, 50, ResizeSpec::Percent(1000.0), true),
+            (400, 200)
+        );
+        assert_eq!(
+            target_size(100, 50, ResizeSpec::Percent(f32::NAN), false),
+            (100, 50)
+        );
+        assert_eq!(
+            target_size(100, 50, ResizeSpec::MaxEdge(0), false),
+            (100, 50)
+        );
+        assert_eq!(
+            target_size(100, 50, ResizeSpec::ExactFit(0, 10), false),
+            (100, 50)
+        );
+    }
+
+    #[test]
+    fn semi_transparent_png_is_composited_over_the_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logo.png");
+        // Half-transparent red, fully transparent, and opaque blue.
+        let mut rgba = image::RgbaImage::new(3, 1);
+        rgba.put_pixel(0, 0, image::Rgba([255, 0, 0, 128]));
+        rgba.put_pixel(1, 0, image::Rgba([0, 0, 0, 0]));
+        rgba.put_pixel(2, 0, image::Rgba([0, 0, 255, 255]));
+        rgba.save(&path).unwrap();
+
+        let flattened = |alpha_background: [u8; 3]| {
+            let opts = DecodeOptions {
+                resize_enabled: false,
+                encode_format: EncodeFormat::Png,
+                alpha_background,
+                ..DecodeOptions::default()
+            };
+            let encoded = decode_resize_base64_with_options(&path, opts, None).unwrap();
+            let png = base64::engine::general_purpose::STANDARD
+                .decode(encoded.base64)
+                .unwrap();
+            let rgb = image::load_from_memory(&png).unwrap().to_rgb8();
+            (0..3).map(|x| rgb.get_pixel(x, 0).0).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            flattened(DEFAULT_ALPHA_BACKGROUND),
+            [[255, 127, 127], [255, 255, 255], [0, 0, 255]]
+        );
+        assert_eq!(flattened([0, 0, 0]), [[128, 0, 0], [0, 0, 0], [0, 0, 255]]);
+        assert_eq!(
+            flattened([0, 128, 0]),
+            [[128, 64, 0], [0, 128, 0], [0, 0, 255]]
+        );
+    }
+
+    #[test]
+    fn exif_fields_are_read_from_a_jpeg() {
+        use exif::experimental::Writer;
+        use exif::{Field, Rational};
+
+        let field = |tag, value| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        };
+        let ascii = |s: &str| Value::Ascii(vec![s.as_bytes().to_vec()]);
+        let dms = |d, m, s| {
+            Value::Rational(vec![
+                Rational { num: d, denom: 1 },
+                Rational { num: m, denom: 1 },
+                Rational { num: s, denom: 1 },
+            ])
+        };
+        let fields = [
+            field(Tag::Make, ascii("Canon")),
+            field(Tag::Model, ascii("Canon EOS R5")),
+            field(Tag::DateTimeOriginal, ascii("2024:05:17 14:03:09")),
+            field(
+                Tag::FocalLength,
+                Value::Rational(vec![Rational { num: 70, denom: 2 }]),
+            ),
+            field(Tag::GPSLatitude, dms(37, 33, 36)),
+            field(Tag::GPSLatitudeRef, ascii("N")),
+            field(Tag::GPSLongitude, dms(122, 25, 12)),
+            field(Tag::GPSLongitudeRef, ascii("W")),
+        ];
+        let mut writer = Writer::new();
+        for f in &fields {
+            writer.push_field(f);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let tiff = tiff.into_inner();
+
+        // APP1 segment carrying the EXIF block, spliced in right after the JPEG's SOI marker.
+        let mut jpeg = Vec::new();
+        JpegEncoder::new(&mut jpeg)
+            .write_image(&[128u8; 8 * 8 * 3], 8, 8, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        app1.extend_from_slice(b"Exif\0\0");
+        app1.extend_from_slice(&tiff);
+        jpeg.splice(2..2, app1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shot.jpg");
+        fs::write(&path, &jpeg).unwrap();
+
+        let exif = read_exif(&path).unwrap();
+        assert_eq!(exif.make.as_deref(), Some("Canon"));
+        assert_eq!(exif.model.as_deref(), Some("Canon EOS R5"));
+        assert_eq!(
+            exif.date_time_original.as_deref(),
+            Some("2024-05-17T14:03:09")
+        );
+        assert_eq!(exif.focal_length_mm, Some(35.0));
+        assert!((exif.gps_latitude.unwrap() - 37.56).abs() < 1e-9);
+        assert!((exif.gps_longitude.unwrap() + 122.42).abs() < 1e-9);
+        // The image itself still decodes with the extra segment in place.
+        assert_eq!(
+            decode_dynamic_image(&path, None)
+                .unwrap()
+                .to_rgb8()
+                .dimensions(),
+            (8, 8)
+        );
+
+        let png = dir.path().join("screenshot.png");
+        RgbImage::new(4, 4).save(&png).unwrap();
+        assert!(read_exif(&png).is_none());
+    }
+
+    #[test]
+    fn cr2_decodes_from_its_largest_embedded_preview() {
+        let jpeg = |width, height, colour: [u8; 3]| {
+            let img = RgbImage::from_pixel(width, height, image::Rgb(colour));
+            let mut out = Vec::new();
+            JpegEncoder::new(&mut out)
+                .write_image(img.as_raw(), width, height, image::ExtendedColorType::Rgb8)
+                .unwrap();
+            out
+        };
+        // Little-endian TIFF header with the CR2 signature, a small thumbnail, the full-size
+        // preview, then JPEG headers with no scan data standing in for sensor data the decoder
+        // can't read.
+        let mut cr2 = b"II*\0\x10\0\0\0CR\x02\0\0\0\0\0".to_vec();
+        cr2.extend(jpeg(160, 120, [255, 0, 0]));
+        cr2.extend([0u8; 64]);
+        cr2.extend(jpeg(1200, 800, [0, 0, 255]));
+        cr2.extend([0u8; 64]);
+        let sensor = jpeg(1600, 1200, [0, 255, 0]);
+        let scan = sensor.windows(2).position(|w| w == [0xFF, 0xDA]).unwrap();
+        cr2.extend(&sensor[..scan]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("IMG_0001.CR2");
+        fs::write(&path, &cr2).unwrap();
+
+        let img = decode_dynamic_image(&path, None).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (1200, 800));
+        let [r, g, b] = img.get_pixel(600, 400).0;
+        assert!(b > 200 && r < 60 && g < 60, "decoded {:?}", [r, g, b]);
+
+        // Only a thumbnail: nothing is big enough to stand in for the photo.
+        let thumb_only = dir.path().join("thumb.cr2");
+        fs::write(&thumb_only, jpeg(160, 120, [255, 0, 0])).unwrap();
+        assert!(embedded_jpeg_preview(&thumb_only, None).unwrap().is_none());
+    }
+
+    #[cfg(all(feature = "heif", not(target_os = "macos")))]
+    fn solid(width: u32, height: u32, colour: [u8; 3]) -> Image {
+        let mut img = Image::new(width, height, ColorSpace::Rgb(RgbChroma::Rgb)).unwrap();
+        img.create_plane(Channel::Interleaved, width, height, 24)
+            .unwrap();
+        let plane = img.planes_mut().interleaved.unwrap();
+        for line in plane.data.chunks_mut(plane.stride) {
+            for px in line[..width as usize * 3].chunks_mut(3) {
+                px.copy_from_slice(&colour);
             }
         }
+        img
+    }
+
+    #[cfg(all(feature = "heif", not(target_os = "macos")))]
+    #[test]
+    fn multi_image_heic_decodes_the_primary_image() {
+        let lib = LibHeif::new();
+        let mut encoder = lib.encoder_for_format(CompressionFormat::Hevc).unwrap();
+        encoder.set_quality(EncoderQuality::Lossy(90)).unwrap();
+        // A red frame comes first in the file; the blue one, with other dimensions, is primary.
+        let mut ctx = HeifContext::new().unwrap();
+        ctx.encode_image(&solid(64, 48, [255, 0, 0]), &mut encoder, None)
+            .unwrap();
+        let mut primary = ctx
+            .encode_image(&solid(32, 40, [0, 0, 255]), &mut encoder, None)
+            .unwrap();
+        ctx.set_primary_image(&mut primary).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("burst.heic");
+        fs::write(&path, ctx.write_to_bytes().unwrap()).unwrap();
+
+        let img = decode_dynamic_image(&path, None).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (32, 40));
+        let [r, _, b] = img.get_pixel(16, 20).0;
+        assert!(b > 200 && r < 60, "decoded {:?}", [r, b]);
     }
 }
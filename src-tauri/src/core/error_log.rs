@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+const ERROR_LOG_FILE: &str = "errors.log";
+const ROTATED_LOG_FILE: &str = "errors.log.1";
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+// Serializes appends so failures from concurrent tasks never interleave partial lines.
+static WRITE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+pub fn error_log_path(app: &AppHandle) -> Result<PathBuf> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("app data dir: {}", e))?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(ERROR_LOG_FILE))
+}
+
+// One line per failure: `<utc timestamp>\t<path>\t<error>`. Multi-line errors are flattened.
+pub fn append_error(app: &AppHandle, path: &Path, error: &str) -> Result<()> {
+    append_to(&error_log_path(app)?, path, error)
+}
+
+fn append_to(log_path: &Path, path: &Path, error: &str) -> Result<()> {
+    let line = format!(
+        "{}\t{}\t{}\n",
+        utc_timestamp(SystemTime::now()),
+        path.display(),
+        flatten(error)
+    );
+
+    let _guard = WRITE_LOCK.lock();
+    if fs::metadata(log_path).is_ok_and(|m| m.len() >= MAX_LOG_BYTES) {
+        fs::rename(log_path, log_path.with_file_name(ROTATED_LOG_FILE))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+pub fn clear_error_log(app: &AppHandle) -> Result<()> {
+    let log_path = error_log_path(app)?;
+    let _guard = WRITE_LOCK.lock();
+    for p in [log_path.clone(), log_path.with_file_name(ROTATED_LOG_FILE)] {
+        if p.exists() {
+            fs::remove_file(p)?;
+        }
+    }
+    Ok(())
+}
+
+fn flatten(error: &str) -> String {
+    error
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(" | ")
+        .replace('\t', " ")
+}
+
+// `YYYY-MM-DDTHH:MM:SSZ` without pulling in a date crate (civil-from-days conversion).
+fn utc_timestamp(now: SystemTime) -> String {
+    let secs = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_failures_make_two_well_formed_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join(ERROR_LOG_FILE);
+        std::thread::scope(|scope| {
+            scope.spawn(|| append_to(&log_path, Path::new("/p/a.jpg"), "decode failed").unwrap());
+            scope.spawn(|| {
+                append_to(
+                    &log_path,
+                    Path::new("/p/b.heic"),
+                    "ollama error 500:\n\tmodel crashed\n",
+                )
+                .unwrap()
+            });
+        });
+
+        let log = fs::read_to_string(&log_path).unwrap();
+        assert!(log.ends_with('\n'));
+        let mut lines: Vec<Vec<&str>> = log.lines().map(|l| l.split('\t').collect()).collect();
+        lines.sort_by_key(|fields| fields[1]);
+        assert_eq!(lines.len(), 2, "{log}");
+        for fields in &lines {
+            assert_eq!(fields.len(), 3, "{fields:?}");
+            let ts = fields[0];
+            assert!(
+                ts.len() == 20 && ts.ends_with('Z') && &ts[10..11] == "T",
+                "{ts}"
+            );
+        }
+        assert_eq!(lines[0][1..], ["/p/a.jpg", "decode failed"]);
+        assert_eq!(
+            lines[1][1..],
+            ["/p/b.heic", "ollama error 500: | model crashed"]
+        );
+    }
+
+    #[test]
+    fn timestamp_is_utc_calendar_time() {
+        let t = UNIX_EPOCH + std::time::Duration::from_secs(1_709_210_096);
+        assert_eq!(utc_timestamp(t), "2024-02-29T12:34:56Z");
+    }
+}
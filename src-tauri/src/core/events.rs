@@ -1,2 +1,9 @@
 pub const PROGRESS_EVENT: &str = "analysis://progress";
 pub const STREAM_EVENT: &str = "analysis://stream";
+pub const PHOTO_UPDATED_EVENT: &str = "analysis://photo-updated";
+pub const PHOTO_EVENT: &str = "analysis://photo";
+pub const JOB_SUMMARY_EVENT: &str = "analysis://job-summary";
+pub const JOB_QUEUE_EVENT: &str = "analysis://job-queue";
+pub const SCAN_PROGRESS_EVENT: &str = "analysis://scan-progress";
+pub const WARMUP_EVENT: &str = "analysis://clip-warmup";
+pub const MODEL_PULL_EVENT: &str = "ollama://model-pull";
@@ -1,6 +1,23 @@
+use crate::core::model::{CategoryKey, PhotoDetail};
 use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+// Field names stay snake_case: the file is read by other tools, not by the frontend.
+#[derive(Serialize)]
+struct Sidecar<'a> {
+    category: CategoryKey,
+    scores: HashMap<String, f32>,
+    tags: &'a [String],
+    caption: Option<&'a str>,
+    text_in_image: Option<&'a str>,
+    model: Option<&'a str>,
+    is_valuable: Option<bool>,
+}
 
 pub fn copy_to_category(
     export_root: &Path,
@@ -25,8 +42,104 @@ pub fn copy_to_category_nested(
     copy_to_dir(&target_dir, file_name, source)
 }
 
+// Sums the sizes of `files` and compares them with the free space on the export volume.
+pub fn ensure_free_space(export_root: &Path, files: &[PathBuf]) -> Result<()> {
+    let needed: u64 = files
+        .iter()
+        .filter_map(|p| fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum();
+    let available = fs2::available_space(export_root).map_err(|e| {
+        anyhow!(
+            "failed to read free space for {}: {}",
+            export_root.display(),
+            e
+        )
+    })?;
+    check_free_space(needed, available)
+}
+
+fn check_free_space(needed: u64, available: u64) -> Result<()> {
+    if needed > available {
+        const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+        return Err(anyhow!(
+            "not enough disk space on export volume: need {:.2} GB, have {:.2} GB",
+            needed as f64 / GB,
+            available as f64 / GB
+        ));
+    }
+    Ok(())
+}
+
+// User-configured labels end up as folder names; keep them to a single path component.
+pub fn folder_component(label: &str) -> String {
+    label
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_matches('.')
+        .to_string()
+}
+
+// `photo.jpg` gets `photo.jpg.json`, so sidecars of same-stem files never collide.
+pub fn sidecar_path(export_path: &Path) -> PathBuf {
+    let mut name = export_path.as_os_str().to_owned();
+    name.push(".json");
+    PathBuf::from(name)
+}
+
+// `detail.path` is the exported file. The JSON goes through a temp file in the same directory
+// and is renamed into place, so readers never see a partial sidecar.
+pub fn write_sidecar(detail: &PhotoDetail) -> Result<PathBuf> {
+    let dest = sidecar_path(Path::new(&detail.path));
+    let dir = dest
+        .parent()
+        .ok_or_else(|| anyhow!("export path has no parent: {}", detail.path))?;
+    let sidecar = Sidecar {
+        category: detail.category,
+        scores: detail.scores.to_map(),
+        tags: &detail.tags,
+        caption: detail.caption.as_deref(),
+        text_in_image: detail.text_in_image.as_deref(),
+        model: detail.model.as_deref(),
+        is_valuable: detail.is_valuable,
+    };
+    let mut tmp = NamedTempFile::new_in(dir)?;
+    tmp.write_all(&serde_json::to_vec_pretty(&sidecar)?)?;
+    tmp.flush()?;
+    tmp.persist(&dest)?;
+    Ok(dest)
+}
+
 fn copy_to_dir(target_dir: &Path, file_name: &str, source: &Path) -> Result<PathBuf> {
-    fs::create_dir_all(&target_dir)?;
+    let target = free_target(target_dir, file_name)?;
+    fs::copy(source, &target)?;
+    Ok(target)
+}
+
+// Moves an already exported file into `target_dir`, with the same `_1`, `_2` suffixes as a fresh
+// export when the name is taken. Falls back to copy and remove across volumes.
+pub fn move_to_dir(target_dir: &Path, source: &Path) -> Result<PathBuf> {
+    let file_name = source
+        .file_name()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow!("invalid file name: {}", source.display()))?;
+    let target = free_target(target_dir, file_name)?;
+    if fs::rename(source, &target).is_err() {
+        fs::copy(source, &target)?;
+        fs::remove_file(source)?;
+    }
+    Ok(target)
+}
+
+// Creates `target_dir` and picks a name in it that doesn't exist yet.
+fn free_target(target_dir: &Path, file_name: &str) -> Result<PathBuf> {
+    fs::create_dir_all(target_dir)?;
     let mut target = target_dir.join(file_name);
     if target.exists() {
         let stem = target
@@ -47,6 +160,27 @@ fn copy_to_dir(target_dir: &Path, file_name: &str, source: &Path) -> Result<Path
             }
         }
     }
-    fs::copy(source, &target)?;
     Ok(target)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn free_space_check_passes_when_files_fit() {
+        assert!(check_free_space(2 * GB, 3 * GB).is_ok());
+        assert!(check_free_space(3 * GB, 3 * GB).is_ok());
+    }
+
+    #[test]
+    fn free_space_check_reports_needed_and_available() {
+        let err = check_free_space(5 * GB, GB).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "not enough disk space on export volume: need 5.00 GB, have 1.00 GB"
+        );
+    }
+}
@@ -3,10 +3,15 @@ pub mod clip;
 pub mod commands;
 pub mod config;
 pub mod db;
+pub mod debug_capture;
 pub mod decode;
+pub mod error_log;
 pub mod events;
 pub mod export;
 pub mod model;
 pub mod ollama;
+pub mod openai;
 pub mod pipeline;
 pub mod scan;
+pub mod self_test;
+pub mod thumbnail;
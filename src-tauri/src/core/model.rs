@@ -1,3 +1,4 @@
+use crate::core::clip::prompts::{value_drop_prompts, value_keep_prompts};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -12,7 +13,7 @@ pub const CATEGORY_KEYS: &[CategoryKey] = &[
     CategoryKey::Other,
 ];
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum CategoryKey {
     ScreenshotDocument,
@@ -22,6 +23,7 @@ pub enum CategoryKey {
     CityStreetTravel,
     PetsAnimals,
     ProductsObjects,
+    #[default]
     Other,
 }
 
@@ -131,6 +133,33 @@ impl Scores {
         self
     }
 
+    pub fn get(&self, key: CategoryKey) -> f32 {
+        match key {
+            CategoryKey::ScreenshotDocument => self.screenshot_document,
+            CategoryKey::People => self.people,
+            CategoryKey::FoodCafe => self.food_cafe,
+            CategoryKey::NatureLandscape => self.nature_landscape,
+            CategoryKey::CityStreetTravel => self.city_street_travel,
+            CategoryKey::PetsAnimals => self.pets_animals,
+            CategoryKey::ProductsObjects => self.products_objects,
+            CategoryKey::Other => self.other,
+        }
+    }
+
+    // Highest scores first; equal scores keep CATEGORY_KEYS order (stable sort).
+    pub fn top_k(&self, n: usize) -> Vec<CategoryScore> {
+        let mut all: Vec<CategoryScore> = CATEGORY_KEYS
+            .iter()
+            .map(|k| CategoryScore {
+                category: *k,
+                score: self.get(*k),
+            })
+            .collect();
+        all.sort_by(|a, b| b.score.total_cmp(&a.score));
+        all.truncate(n);
+        all
+    }
+
     pub fn top(&self) -> (CategoryKey, f32) {
         let map = self.to_map();
         map.into_iter()
@@ -144,6 +173,15 @@ impl Scores {
     }
 }
 
+pub const DEFAULT_TOP_K: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryScore {
+    pub category: CategoryKey,
+    pub score: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
@@ -151,22 +189,96 @@ pub struct Settings {
     pub ollama_base_url: String,
     #[serde(default = "default_model")]
     pub ollama_model: String,
+    // Wire protocol spoken by the server at `ollama_base_url`.
+    #[serde(default)]
+    pub ollama_backend: OllamaBackend,
     #[serde(default)]
     pub ollama_think: bool,
     #[serde(default)]
     pub ollama_stream: bool,
+    // Language Ollama writes tags/caption/text-in-image in.
+    #[serde(default)]
+    pub output_language: OutputLanguage,
+    // Off keeps tags/caption/text-in-image verbatim instead of stripping other scripts; for
+    // debugging what the model actually produced.
+    #[serde(default = "default_sanitize_output")]
+    pub sanitize_output: bool,
+    // Where a model answer goes when neither its category nor its scores pick one out (unknown
+    // or missing category with tied or all-zero scores).
+    #[serde(default = "default_fallback_category")]
+    pub fallback_category: CategoryKey,
+    // Optional overrides for the built-in Ollama messages; see `ollama::ChatOptions`.
+    #[serde(default)]
+    pub ollama_system_prompt: Option<String>,
+    #[serde(default)]
+    pub ollama_user_prompt: Option<String>,
+    // Extra entries for the request's `options` (e.g. num_ctx, num_predict); passed through as-is.
+    #[serde(default)]
+    pub ollama_options: HashMap<String, serde_json::Value>,
+    // Request `keep_alive` ("10m", "-1", "0"); empty leaves the server default.
+    #[serde(default)]
+    pub ollama_keep_alive: String,
+    // Sent as `Authorization: Bearer <key>` for Ollama behind an authenticating proxy.
+    #[serde(default)]
+    pub ollama_api_key: Option<String>,
+    #[serde(default)]
+    pub ollama_extra_headers: HashMap<String, String>,
+    #[serde(default = "default_ollama_max_response_bytes")]
+    pub ollama_max_response_bytes: usize,
+    // Per-request timeout in seconds (idle timeout between chunks when streaming); 0 = unlimited.
+    #[serde(default = "default_ollama_timeout_secs")]
+    pub ollama_timeout_secs: u64,
+    // Ollama requests in flight at once across the app, whatever `analysis_concurrency` is.
+    #[serde(default = "default_ollama_max_parallel")]
+    pub ollama_max_parallel: u32,
+    // Tags kept per photo after duplicates are merged, earliest first.
+    #[serde(default = "default_ollama_max_tags")]
+    pub ollama_max_tags: u32,
+    // Longer tags are usually sentences, not tags, and are dropped; 0 = no limit.
+    #[serde(default = "default_ollama_max_tag_chars")]
+    pub ollama_max_tag_chars: u32,
+    // Check the Ollama model for vision support before a job starts instead of failing per photo.
+    #[serde(default = "default_preflight_checks")]
+    pub preflight_checks: bool,
+    // Writes every Ollama response body to `<app_data>/debug/<job_id>/<photo_id>.json`.
+    #[serde(default)]
+    pub debug_capture_raw_responses: bool,
     #[serde(default = "default_analysis_resize_enabled")]
     pub analysis_resize_enabled: bool,
     #[serde(default = "default_analysis_max_edge")]
     pub analysis_max_edge: u32,
+    // When set, the analysis image is scaled to this percentage instead of capping the long edge.
+    #[serde(default)]
+    pub analysis_resize_percent: Option<f32>,
     #[serde(default = "default_analysis_jpeg_quality")]
     pub analysis_jpeg_quality: u8,
     #[serde(default)]
+    pub analysis_image_format: AnalysisImageFormat,
+    #[serde(default)]
     pub analysis_value_enabled: bool,
+    // Off: value results are only recorded in the DB and exports stay flat by category.
+    #[serde(default = "default_value_as_nested_folders")]
+    pub value_as_nested_folders: bool,
+    // Writes `<exported file>.json` next to each export so the result travels with the photo.
+    #[serde(default)]
+    pub write_sidecar: bool,
+    // Results go to an in-memory database instead of `images.db`, for throwaway runs. They are
+    // lost when the app quits or the setting is turned off again.
+    #[serde(default)]
+    pub ephemeral_db: bool,
+    // RGB color transparent pixels are composited over before analysis.
+    #[serde(default = "default_alpha_background")]
+    pub alpha_background: [u8; 3],
     #[serde(default = "default_analysis_concurrency")]
     pub analysis_concurrency: u32,
+    // Workers decoding and preprocessing ahead of the `analysis_concurrency` inference workers.
+    #[serde(default = "default_decode_concurrency")]
+    pub decode_concurrency: u32,
     #[serde(default = "default_analysis_engine")]
     pub analysis_engine: AnalysisEngine,
+    // Which CLIP-classified photos the hybrid engine sends to Ollama for a caption.
+    #[serde(default)]
+    pub hybrid_caption_scope: HybridCaptionScope,
     #[serde(default)]
     pub clip_model_dir: Option<String>,
     #[serde(default = "default_clip_model_file")]
@@ -178,6 +290,8 @@ pub struct Settings {
     #[serde(default = "default_clip_ep_coreml")]
     pub clip_ep_coreml: bool,
     #[serde(default)]
+    pub clip_ep_tensorrt: bool,
+    #[serde(default)]
     pub clip_ep_cuda: bool,
     #[serde(default)]
     pub clip_ep_rocm: bool,
@@ -185,6 +299,149 @@ pub struct Settings {
     pub clip_ep_directml: bool,
     #[serde(default)]
     pub clip_ep_openvino: bool,
+    #[serde(default)]
+    pub clip_gpu_device_id: u32,
+    #[serde(default)]
+    pub clip_session_pool_size: Option<u32>,
+    #[serde(default)]
+    pub clip_intra_threads: Option<u32>,
+    // ONNX Runtime inter-op pool; only used with `clip_parallel_execution`. None = 1.
+    #[serde(default)]
+    pub clip_inter_threads: Option<u32>,
+    #[serde(default)]
+    pub clip_parallel_execution: bool,
+    // Trades some speed for lower resident memory on the CPU provider.
+    #[serde(default)]
+    pub clip_disable_cpu_arena: bool,
+    #[serde(default)]
+    pub clip_auto_unload: bool,
+    // How many CLIP models (distinct dir + file) stay loaded at once; the least recently used one
+    // is dropped beyond that. Each keeps its own session pool in memory.
+    #[serde(default = "default_clip_max_loaded_models")]
+    pub clip_max_loaded_models: u32,
+    #[serde(default = "default_clip_tags_enabled")]
+    pub clip_tags_enabled: bool,
+    #[serde(default)]
+    pub clip_tag_prompts: Vec<ClipTagPrompt>,
+    #[serde(default = "default_clip_tag_top_n")]
+    pub clip_tag_top_n: u32,
+    #[serde(default = "default_clip_tag_threshold")]
+    pub clip_tag_threshold: f32,
+    #[serde(default)]
+    pub clip_subcategories_enabled: bool,
+    #[serde(default = "default_clip_subcategories")]
+    pub clip_subcategories: HashMap<CategoryKey, Vec<ClipSubcategory>>,
+    // Value buckets compared by softmax when `analysis_value_enabled` is on.
+    #[serde(default = "default_clip_value_buckets")]
+    pub clip_value_buckets: Vec<ValueBucket>,
+    // Additive bias on the CLIP category logits; positive values favour a category.
+    #[serde(default)]
+    pub category_bias: HashMap<CategoryKey, f32>,
+    // Nudges CLIP toward screenshot_document when the image has screenshot-like pixels.
+    #[serde(default)]
+    pub screenshot_heuristic: bool,
+    // Matched against each entry's path relative to the source root and against its file name.
+    #[serde(default)]
+    pub scan_exclude_globs: Vec<String>,
+    #[serde(default)]
+    pub scan_max_depth: Option<usize>,
+    #[serde(default)]
+    pub scan_sort: ScanSort,
+}
+
+// Second-stage split under a top-level category. `label` doubles as the export folder name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipSubcategory {
+    pub label: String,
+    pub prompts: Vec<String>,
+}
+
+// Quality bucket for the value feature. `name` doubles as the top-level export folder; photos in a
+// `keep` bucket count as valuable.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueBucket {
+    pub name: String,
+    pub prompts: Vec<String>,
+    #[serde(default)]
+    pub keep: bool,
+}
+
+// Extra tag concept on top of the built-in bank: `prompt` is embedded, `label` is shown.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipTagPrompt {
+    pub prompt: String,
+    pub label: String,
+}
+
+// Encoding of the image sent to Ollama. `Auto` sends PNG for likely screenshots so text stays crisp.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisImageFormat {
+    #[default]
+    Auto,
+    Jpeg,
+    Png,
+}
+
+// Processing order of scanned files. A fixed order keeps reruns (and the `_1`, `_2` export
+// suffixes) reproducible; `None` keeps the platform-dependent walk order.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanSort {
+    None,
+    #[default]
+    NameAsc,
+    NameDesc,
+    MtimeAsc,
+    MtimeDesc,
+}
+
+// `OpenaiCompatible` covers LM Studio, llama.cpp server and similar `/v1/chat/completions` servers.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OllamaBackend {
+    #[default]
+    Ollama,
+    OpenaiCompatible,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputLanguage {
+    #[default]
+    Ko,
+    En,
+    Ja,
+}
+
+impl OutputLanguage {
+    // English name used inside the prompts.
+    pub fn prompt_name(&self) -> &'static str {
+        match self {
+            OutputLanguage::Ko => "Korean (Hangul)",
+            OutputLanguage::En => "English",
+            OutputLanguage::Ja => "Japanese",
+        }
+    }
+
+    pub fn fallback_tag(&self) -> &'static str {
+        match self {
+            OutputLanguage::Ko => "기타",
+            OutputLanguage::En => "other",
+            OutputLanguage::Ja => "その他",
+        }
+    }
+
+    pub fn empty_caption(&self) -> &'static str {
+        match self {
+            OutputLanguage::Ko => "설명 없음",
+            OutputLanguage::En => "No description",
+            OutputLanguage::Ja => "説明なし",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -192,6 +449,28 @@ pub struct Settings {
 pub enum AnalysisEngine {
     Clip,
     Ollama,
+    // CLIP classifies and drives the export; Ollama then fills caption/tags/text in a second pass.
+    Hybrid,
+}
+
+impl AnalysisEngine {
+    pub fn uses_clip(self) -> bool {
+        matches!(self, AnalysisEngine::Clip | AnalysisEngine::Hybrid)
+    }
+
+    pub fn uses_ollama(self) -> bool {
+        matches!(self, AnalysisEngine::Ollama | AnalysisEngine::Hybrid)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HybridCaptionScope {
+    #[default]
+    All,
+    // Photos CLIP judged not valuable are skipped; without value scoring every photo counts.
+    ValuableOnly,
+    None,
 }
 
 pub fn default_base_url() -> String {
@@ -202,6 +481,26 @@ pub fn default_model() -> String {
     "qwen2.5vl:7b".to_string()
 }
 
+pub fn default_ollama_max_response_bytes() -> usize {
+    4 * 1024 * 1024
+}
+
+pub fn default_ollama_timeout_secs() -> u64 {
+    120
+}
+
+pub fn default_ollama_max_parallel() -> u32 {
+    2
+}
+
+pub fn default_ollama_max_tags() -> u32 {
+    8
+}
+
+pub fn default_ollama_max_tag_chars() -> u32 {
+    20
+}
+
 pub fn default_analysis_resize_enabled() -> bool {
     true
 }
@@ -222,10 +521,38 @@ pub fn default_analysis_concurrency() -> u32 {
     cores.min(4)
 }
 
+pub fn default_decode_concurrency() -> u32 {
+    let cores = std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(4)
+        .max(1);
+    (cores / 2).clamp(1, 4)
+}
+
 pub fn default_analysis_engine() -> AnalysisEngine {
     AnalysisEngine::Clip
 }
 
+pub fn default_sanitize_output() -> bool {
+    true
+}
+
+pub fn default_fallback_category() -> CategoryKey {
+    CategoryKey::Other
+}
+
+pub fn default_preflight_checks() -> bool {
+    true
+}
+
+pub fn default_value_as_nested_folders() -> bool {
+    true
+}
+
+pub fn default_alpha_background() -> [u8; 3] {
+    [255, 255, 255]
+}
+
 pub fn default_clip_fallback_to_ollama() -> bool {
     false
 }
@@ -238,36 +565,219 @@ pub fn default_clip_ep_auto() -> bool {
     true
 }
 
+pub fn default_clip_max_loaded_models() -> u32 {
+    1
+}
+
 pub fn default_clip_ep_coreml() -> bool {
     cfg!(target_vendor = "apple")
 }
 
+pub fn default_clip_tags_enabled() -> bool {
+    true
+}
+
+pub fn default_clip_tag_top_n() -> u32 {
+    5
+}
+
+pub fn default_clip_tag_threshold() -> f32 {
+    0.24
+}
+
+// Two buckets, same prompts and folder names as the original keep/drop split.
+pub fn default_clip_value_buckets() -> Vec<ValueBucket> {
+    fn bucket(name: &str, prompts: &[&str], keep: bool) -> ValueBucket {
+        ValueBucket {
+            name: name.to_string(),
+            prompts: prompts.iter().map(|p| p.to_string()).collect(),
+            keep,
+        }
+    }
+    vec![
+        bucket("가치있음", value_keep_prompts(), true),
+        bucket("가치없음", value_drop_prompts(), false),
+    ]
+}
+
+pub fn default_clip_subcategories() -> HashMap<CategoryKey, Vec<ClipSubcategory>> {
+    fn sub(label: &str, prompts: &[&str]) -> ClipSubcategory {
+        ClipSubcategory {
+            label: label.to_string(),
+            prompts: prompts.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+    HashMap::from([
+        (
+            CategoryKey::ScreenshotDocument,
+            vec![
+                sub(
+                    "채팅",
+                    &[
+                        "a screenshot of a chat conversation",
+                        "a screenshot of a messaging app",
+                    ],
+                ),
+                sub(
+                    "웹페이지",
+                    &[
+                        "a screenshot of a web page",
+                        "a screenshot of a website in a browser",
+                    ],
+                ),
+                sub(
+                    "코드",
+                    &[
+                        "a screenshot of source code",
+                        "a screenshot of a code editor or terminal",
+                    ],
+                ),
+                sub(
+                    "지도",
+                    &["a screenshot of a map", "a screenshot of a navigation app"],
+                ),
+            ],
+        ),
+        (
+            CategoryKey::People,
+            vec![
+                sub(
+                    "단체",
+                    &["a group photo of several people", "a crowd of people"],
+                ),
+                sub(
+                    "인물",
+                    &[
+                        "a portrait of a single person",
+                        "a close-up photo of one person",
+                    ],
+                ),
+            ],
+        ),
+    ])
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             ollama_base_url: default_base_url(),
             ollama_model: default_model(),
+            ollama_backend: OllamaBackend::default(),
             ollama_think: false,
             ollama_stream: false,
+            output_language: OutputLanguage::default(),
+            sanitize_output: default_sanitize_output(),
+            fallback_category: default_fallback_category(),
+            ollama_system_prompt: None,
+            ollama_user_prompt: None,
+            ollama_options: HashMap::new(),
+            ollama_keep_alive: String::new(),
+            ollama_api_key: None,
+            ollama_extra_headers: HashMap::new(),
+            ollama_max_response_bytes: default_ollama_max_response_bytes(),
+            ollama_timeout_secs: default_ollama_timeout_secs(),
+            ollama_max_parallel: default_ollama_max_parallel(),
+            ollama_max_tags: default_ollama_max_tags(),
+            ollama_max_tag_chars: default_ollama_max_tag_chars(),
+            preflight_checks: default_preflight_checks(),
+            debug_capture_raw_responses: false,
             analysis_resize_enabled: default_analysis_resize_enabled(),
             analysis_max_edge: default_analysis_max_edge(),
+            analysis_resize_percent: None,
             analysis_jpeg_quality: default_analysis_jpeg_quality(),
+            analysis_image_format: AnalysisImageFormat::default(),
             analysis_value_enabled: false,
+            value_as_nested_folders: default_value_as_nested_folders(),
+            write_sidecar: false,
+            ephemeral_db: false,
+            alpha_background: default_alpha_background(),
             analysis_concurrency: default_analysis_concurrency(),
+            decode_concurrency: default_decode_concurrency(),
             analysis_engine: default_analysis_engine(),
+            hybrid_caption_scope: HybridCaptionScope::default(),
             clip_model_dir: None,
             clip_model_file: default_clip_model_file(),
             clip_fallback_to_ollama: default_clip_fallback_to_ollama(),
             clip_ep_auto: default_clip_ep_auto(),
             clip_ep_coreml: default_clip_ep_coreml(),
+            clip_ep_tensorrt: false,
             clip_ep_cuda: false,
             clip_ep_rocm: false,
             clip_ep_directml: false,
             clip_ep_openvino: false,
+            clip_gpu_device_id: 0,
+            clip_session_pool_size: None,
+            clip_intra_threads: None,
+            clip_inter_threads: None,
+            clip_parallel_execution: false,
+            clip_disable_cpu_arena: false,
+            clip_auto_unload: false,
+            clip_max_loaded_models: default_clip_max_loaded_models(),
+            clip_tags_enabled: default_clip_tags_enabled(),
+            clip_tag_prompts: Vec::new(),
+            clip_tag_top_n: default_clip_tag_top_n(),
+            clip_tag_threshold: default_clip_tag_threshold(),
+            clip_subcategories_enabled: false,
+            clip_subcategories: default_clip_subcategories(),
+            clip_value_buckets: default_clip_value_buckets(),
+            category_bias: HashMap::new(),
+            screenshot_heuristic: false,
+            scan_exclude_globs: Vec::new(),
+            scan_max_depth: None,
+            scan_sort: ScanSort::default(),
         }
     }
 }
 
+impl Settings {
+    // Worker and thread counts beyond the core count only add contention.
+    pub fn clamp_concurrency(&mut self) {
+        let max = std::thread::available_parallelism()
+            .map(|n| n.get() as u32)
+            .unwrap_or(4)
+            .max(1);
+        self.analysis_concurrency = self.analysis_concurrency.clamp(1, max);
+        self.decode_concurrency = self.decode_concurrency.clamp(1, max);
+        self.ollama_max_parallel = self.ollama_max_parallel.max(1);
+        self.clip_session_pool_size = self.clip_session_pool_size.map(|n| n.clamp(1, max));
+        self.clip_intra_threads = self.clip_intra_threads.map(|n| n.clamp(1, max));
+        self.clip_inter_threads = self.clip_inter_threads.map(|n| n.clamp(1, max));
+    }
+
+    // The settings one job runs with: the stored ones with the job's overrides on top.
+    pub fn with_override(mut self, overrides: Option<&SettingsOverride>) -> Self {
+        let Some(o) = overrides else {
+            return self;
+        };
+        if let Some(engine) = o.analysis_engine {
+            self.analysis_engine = engine;
+        }
+        if let Some(enabled) = o.analysis_value_enabled {
+            self.analysis_value_enabled = enabled;
+        }
+        if let Some(n) = o.analysis_concurrency {
+            self.analysis_concurrency = n;
+        }
+        if let Some(enabled) = o.analysis_resize_enabled {
+            self.analysis_resize_enabled = enabled;
+        }
+        if let Some(edge) = o.analysis_max_edge {
+            self.analysis_max_edge = edge;
+        }
+        if o.analysis_resize_percent.is_some() {
+            self.analysis_resize_percent = o.analysis_resize_percent;
+        }
+        if let Some(q) = o.analysis_jpeg_quality {
+            self.analysis_jpeg_quality = q;
+        }
+        if let Some(model) = o.ollama_model.as_ref().filter(|m| !m.trim().is_empty()) {
+            self.ollama_model = model.clone();
+        }
+        self.clamp_concurrency();
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClipProviderCapability {
@@ -281,10 +791,154 @@ pub struct ClipProviderCapability {
 pub struct ClipAccelCapabilities {
     pub cpu: ClipProviderCapability,
     pub coreml: ClipProviderCapability,
+    pub tensorrt: ClipProviderCapability,
     pub cuda: ClipProviderCapability,
     pub rocm: ClipProviderCapability,
     pub directml: ClipProviderCapability,
     pub openvino: ClipProviderCapability,
+    #[serde(default)]
+    pub gpu_devices: Vec<ClipGpuDevice>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipGpuDevice {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipEngineStatus {
+    pub loaded: bool,
+    // Model paths of every cached engine, most recently used first. The other fields describe
+    // that first one.
+    pub loaded_models: Vec<String>,
+    pub cache_key: Option<String>,
+    pub model_path: Option<String>,
+    pub eps_log: Option<String>,
+    // Providers that actually ran nodes during warmup, e.g. "coreml(3 nodes)+cpu(12 nodes)".
+    pub effective_eps: Option<String>,
+    pub session_pool_size: Option<usize>,
+    pub intra_threads: Option<usize>,
+    pub model_load_ms: Option<u64>,
+    pub text_cache_ms: Option<u64>,
+    pub input_ids_name: Option<String>,
+    pub attention_mask_name: Option<String>,
+    pub pixel_values_name: Option<String>,
+    pub output_image_embeds: Option<String>,
+    pub output_text_embeds: Option<String>,
+    // Latest warmup event, so a status poll can tell a model still loading from none configured.
+    #[serde(default)]
+    pub warmup: Option<ClipWarmupEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipReloadResult {
+    pub elapsed_ms: u64,
+    pub status: ClipEngineStatus,
+}
+
+// One image scored against a trial prompt set. `embed_ms` is the on-demand text embedding,
+// `infer_ms` the vision run and scoring.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipPromptTestResult {
+    pub scores: Scores,
+    pub category: CategoryKey,
+    pub embed_ms: u64,
+    pub infer_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestStage {
+    pub ok: bool,
+    pub elapsed_ms: u64,
+    pub detail: Option<String>,
+    pub error: Option<String>,
+}
+
+// `ollama` is only checked when Ollama is the configured engine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    pub decode: SelfTestStage,
+    pub clip: SelfTestStage,
+    pub ollama: Option<SelfTestStage>,
+    pub clip_model_dir: Option<String>,
+    pub clip_eps: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipWarmupPhase {
+    LoadingModel,
+    CachingText,
+    SmokeTest,
+    BuildingPool,
+    Ready,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipWarmupEvent {
+    pub phase: ClipWarmupPhase,
+    pub elapsed_ms: u64,
+    pub error: Option<String>,
+    // Set during `BuildingPool`: 1-based session being opened and the pool size.
+    #[serde(default)]
+    pub pool_index: Option<usize>,
+    #[serde(default)]
+    pub pool_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OllamaModelInfo {
+    pub name: String,
+    pub size: u64,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    pub quantization: Option<String>,
+    pub likely_vision: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OllamaModelCheck {
+    pub name: String,
+    // False on servers too old to report capabilities; `supports_vision` is then a guess.
+    pub capabilities_reported: bool,
+    pub capabilities: Vec<String>,
+    pub supports_vision: bool,
+    pub context_length: Option<u64>,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    pub quantization: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanProgressEvent {
+    pub job_id: String,
+    pub scanned: usize,
+    pub found: usize,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPullEvent {
+    pub model: String,
+    // Ollama's status line ("pulling manifest", "pulling <digest>", "success", ...).
+    pub status: String,
+    pub completed: Option<u64>,
+    pub total: Option<u64>,
+    pub done: bool,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -292,6 +946,9 @@ pub struct ClipAccelCapabilities {
 pub struct StreamChunk {
     pub job_id: String,
     pub file_name: String,
+    // Which of the job's parallel workers produced the chunk; 0 when running one at a time.
+    #[serde(default)]
+    pub slot: usize,
     pub delta: String,
     pub done: bool,
     #[serde(default)]
@@ -303,9 +960,12 @@ pub struct StreamChunk {
 pub struct ModelOut {
     pub category: CategoryKey,
     pub scores: Scores,
-    pub tags_ko: Vec<String>,
-    pub caption_ko: String,
-    pub text_in_image_ko: String,
+    pub tags: Vec<String>,
+    pub caption: String,
+    pub text_in_image: String,
+    // Which `parse_model_out` repair rules fired on the raw scores.
+    #[serde(default)]
+    pub score_repairs: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -316,6 +976,19 @@ pub enum ExportStatus {
     Error,
 }
 
+// Why an analysis failed, so a retry can target what is worth retrying (a flaky connection)
+// and skip what needs a settings change (a model that can't see images).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Network,
+    Timeout,
+    ModelRefused,
+    ParseFailed,
+    Canceled,
+    Other,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PhotoRow {
@@ -324,14 +997,34 @@ pub struct PhotoRow {
     pub path: String,
     pub category: CategoryKey,
     pub top_score: f32,
+    #[serde(default)]
+    pub top_k: Vec<CategoryScore>,
     pub scores: Scores,
     pub tags: Vec<String>,
     pub export_status: ExportStatus,
     pub error_message: Option<String>,
+    // Set on failed rows only.
+    #[serde(default)]
+    pub failure_kind: Option<FailureKind>,
     pub analysis_duration_ms: Option<i64>,
     pub model: Option<String>,
     pub is_valuable: Option<bool>,
     pub valuable_score: Option<f32>,
+    #[serde(default)]
+    pub thumbnail_path: Option<String>,
+    // When the row was last written by an analysis, UTC ISO 8601.
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+// Payload of `PHOTO_EVENT`: rows a batch job just wrote, successes and failures alike, in
+// completion order. Fast jobs get several rows per event so the frontend sees at most ~20 events
+// per second. `created_at` is None here; the DB fills it on insert.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhotoEvent {
+    pub job_id: String,
+    pub rows: Vec<PhotoRow>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -342,6 +1035,8 @@ pub struct PhotoDetail {
     pub path: String,
     pub category: CategoryKey,
     pub top_score: f32,
+    #[serde(default)]
+    pub top_k: Vec<CategoryScore>,
     pub scores: Scores,
     pub tags: Vec<String>,
     pub export_status: ExportStatus,
@@ -353,6 +1048,103 @@ pub struct PhotoDetail {
     pub model: Option<String>,
     pub is_valuable: Option<bool>,
     pub valuable_score: Option<f32>,
+    #[serde(default)]
+    pub source_path: Option<String>,
+    #[serde(default)]
+    pub export_root: Option<String>,
+    #[serde(default)]
+    pub subcategory: Option<String>,
+    #[serde(default)]
+    pub value_bucket: Option<String>,
+    #[serde(default)]
+    pub thumbnail_path: Option<String>,
+    // Ollama only: `prompt_eval_count` / `eval_count` from the final response.
+    #[serde(default)]
+    pub prompt_tokens: Option<i64>,
+    #[serde(default)]
+    pub eval_tokens: Option<i64>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    // The batch job that wrote the row; None for reanalysis.
+    #[serde(default)]
+    pub job_id: Option<String>,
+    // None when the source had no readable EXIF.
+    #[serde(default)]
+    pub exif: Option<PhotoExif>,
+    // Set on failed rows only.
+    #[serde(default)]
+    pub failure_kind: Option<FailureKind>,
+    // The category was picked by hand; `scores` still hold what the model produced.
+    #[serde(default)]
+    pub manual_override: bool,
+}
+
+// `warning` explains why only the database changed, e.g. the exported file was gone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecategorizeResult {
+    pub photo: PhotoDetail,
+    pub warning: Option<String>,
+}
+
+// `files` counts exported files moved to the trash (or that would be, on a dry run); `rows` counts
+// result rows removed, one per trashed file. `failed` lists files that could not be trashed, as
+// "path: error"; their rows are kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoExportReport {
+    pub job_id: String,
+    pub files: usize,
+    pub rows: usize,
+    pub failed: Vec<String>,
+}
+
+// Capture metadata from the source's EXIF. `date_time_original` is the camera's local time as
+// `YYYY-MM-DDTHH:MM:SS`; GPS coordinates are signed decimal degrees.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhotoExif {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub date_time_original: Option<String>,
+    pub focal_length_mm: Option<f64>,
+    pub gps_latitude: Option<f64>,
+    pub gps_longitude: Option<f64>,
+}
+
+impl From<&PhotoDetail> for PhotoRow {
+    fn from(d: &PhotoDetail) -> Self {
+        PhotoRow {
+            id: d.id.clone(),
+            file_name: d.file_name.clone(),
+            path: d.path.clone(),
+            category: d.category,
+            top_score: d.top_score,
+            top_k: d.top_k.clone(),
+            scores: d.scores.clone(),
+            tags: d.tags.clone(),
+            export_status: d.export_status.clone(),
+            error_message: d.error_message.clone(),
+            failure_kind: d.failure_kind,
+            analysis_duration_ms: d.analysis_duration_ms,
+            model: d.model.clone(),
+            is_valuable: d.is_valuable,
+            valuable_score: d.valuable_score,
+            thumbnail_path: d.thumbnail_path.clone(),
+            created_at: d.created_at.clone(),
+        }
+    }
+}
+
+// Outcome of `delete_photos` for one id. `error` is set when the row was missing or the exported
+// file could not be removed; the row itself may still have been deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletePhotoResult {
+    pub id: String,
+    pub deleted: bool,
+    pub file_removed: bool,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -361,6 +1153,25 @@ pub struct ValueStats {
     pub valuable: usize,
     pub not_valuable: usize,
     pub unknown: usize,
+    // The same counts per category key; every category is present, zeros included.
+    #[serde(default)]
+    pub by_category: HashMap<String, ValueCounts>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueCounts {
+    pub valuable: usize,
+    pub not_valuable: usize,
+    pub unknown: usize,
+}
+
+// `tag` is one spelling of the tag; `count` covers every spelling with the same key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -371,6 +1182,8 @@ pub enum JobStatus {
     Completed,
     Canceled,
     Error,
+    // Still marked running when the app started again, i.e. cut off by a crash or quit.
+    Aborted,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -384,6 +1197,68 @@ pub struct Progress {
     pub errors: usize,
 }
 
+// Emitted once when a job ends and kept with the last job's metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSummary {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub engine: AnalysisEngine,
+    pub total: usize,
+    pub processed: usize,
+    pub errors: usize,
+    // Files never reached because the job was canceled.
+    pub skipped: usize,
+    pub elapsed_ms: u64,
+    pub images_per_sec: f64,
+    // CLIP only: mean of the per-image `vision_infer_ms`.
+    pub avg_vision_infer_ms: Option<f64>,
+    // Ollama only: generated tokens over generation time, summed across the job.
+    #[serde(default)]
+    pub avg_eval_tokens_per_sec: Option<f64>,
+    // Set when the job was canceled or stopped on an error; the counts below cover only what
+    // finished. A resumed job counts the photos of its own run.
+    #[serde(default)]
+    pub partial: bool,
+    #[serde(default)]
+    pub category_counts: HashMap<CategoryKey, usize>,
+    #[serde(default)]
+    pub valuable: usize,
+    #[serde(default)]
+    pub not_valuable: usize,
+    // Sum and mean of the per-photo analysis time of successful photos.
+    #[serde(default)]
+    pub total_duration_ms: u64,
+    #[serde(default)]
+    pub avg_duration_ms: Option<f64>,
+    // Slowest first.
+    #[serde(default)]
+    pub slowest: Vec<JobSummaryPhoto>,
+    // Capped at `MAX_SUMMARY_FAILURES`; `errors` has the full count.
+    #[serde(default)]
+    pub failures: Vec<JobSummaryFailure>,
+    // Retry jobs only: failed photos whose source file no longer exists. They are not counted
+    // in `total`.
+    #[serde(default)]
+    pub missing: Vec<String>,
+}
+
+pub const MAX_SUMMARY_FAILURES: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSummaryPhoto {
+    pub path: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSummaryFailure {
+    pub path: String,
+    pub error: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum DistributionMode {
@@ -403,10 +1278,81 @@ pub struct Distribution {
 pub struct StartAnalysisInput {
     pub source_root: String,
     pub export_root: String,
+    #[serde(default)]
+    pub max_images: Option<usize>,
+    #[serde(default)]
+    pub sample_random: bool,
+    #[serde(default)]
+    pub sample_seed: Option<u64>,
+    // Applies to this job only; the stored settings stay as they are.
+    #[serde(default)]
+    pub settings_override: Option<SettingsOverride>,
+}
+
+// A subset of `Settings` a single job can change; None keeps the stored value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsOverride {
+    #[serde(default)]
+    pub analysis_engine: Option<AnalysisEngine>,
+    #[serde(default)]
+    pub analysis_value_enabled: Option<bool>,
+    #[serde(default)]
+    pub analysis_concurrency: Option<u32>,
+    #[serde(default)]
+    pub analysis_resize_enabled: Option<bool>,
+    #[serde(default)]
+    pub analysis_max_edge: Option<u32>,
+    #[serde(default)]
+    pub analysis_resize_percent: Option<f32>,
+    #[serde(default)]
+    pub analysis_jpeg_quality: Option<u8>,
+    #[serde(default)]
+    pub ollama_model: Option<String>,
+}
+
+// A persisted job. `done` counts the photo rows written for it so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRecord {
+    pub id: String,
+    pub source_root: String,
+    pub export_root: String,
+    pub status: JobStatus,
+    pub started_at: Option<String>,
+    pub done: usize,
+    // None while the job runs, and for jobs from before summaries were stored.
+    #[serde(default)]
+    pub summary: Option<JobSummary>,
+    // When the first and last photo rows were written; None until the job has any.
+    #[serde(default)]
+    pub first_photo_at: Option<String>,
+    #[serde(default)]
+    pub last_photo_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StartAnalysisResult {
     pub job_id: String,
+    // 1-based place in the queue; None when the job started right away.
+    #[serde(default)]
+    pub queue_position: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedJobInfo {
+    pub job_id: String,
+    pub source_root: String,
+    pub export_root: String,
+    pub position: usize,
+}
+
+// Sent whenever the queue changes. `promoted` is the job that just left the queue to run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobQueueEvent {
+    pub promoted: Option<String>,
+    pub queue: Vec<QueuedJobInfo>,
 }
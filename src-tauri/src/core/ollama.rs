@@ -1,9 +1,20 @@
-use crate::core::model::{CategoryKey, ModelOut, Scores};
+use crate::core::debug_capture::write_capture;
+use crate::core::decode::probe_image_base64;
+use crate::core::model::{
+    CategoryKey, ModelOut, OllamaModelCheck, OllamaModelInfo, OutputLanguage, Scores, Settings,
+    CATEGORY_KEYS,
+};
 use anyhow::{anyhow, Result};
 use once_cell::sync::Lazy;
-use reqwest::Client;
+use parking_lot::Mutex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Client, StatusCode};
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 
@@ -49,20 +60,367 @@ static JSON_SCHEMA: Lazy<Value> = Lazy::new(|| {
             "other"
           ]
         },
-        "tags_ko": {
+        "tags": {
           "type": "array",
           "minItems": 0,
           "maxItems": 12,
           "items": {"type": "string"}
         },
-        "caption_ko": {"type": "string"},
-        "text_in_image_ko": {"type": "string"}
+        "caption": {"type": "string"},
+        "text_in_image": {"type": "string"}
       },
-      "required": ["category", "scores", "tags_ko", "caption_ko", "text_in_image_ko"]
+      "required": ["category", "scores", "tags", "caption", "text_in_image"]
     })
 });
 
-fn strip_code_fences(s: &str) -> &str {
+// One pooled client shared by every Ollama call so keep-alive connections are reused across
+// images. It is keyed by a hash of the credentials and rebuilt when they change; timeouts vary
+// per call, so they are set on each request instead of on the client.
+static CLIENT: Lazy<Mutex<Option<(u64, Client)>>> = Lazy::new(|| Mutex::new(None));
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+// Credentials for Ollama behind an authenticating proxy, sent on every request. The key and
+// header values are secrets: `redact` scrubs them from logs and errors.
+#[derive(Debug, Clone, Default)]
+pub struct OllamaAuth {
+    pub api_key: Option<String>,
+    pub headers: HashMap<String, String>,
+}
+
+impl OllamaAuth {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            api_key: settings
+                .ollama_api_key
+                .as_deref()
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(str::to_string),
+            headers: settings.ollama_extra_headers.clone(),
+        }
+    }
+
+    fn header_map(&self) -> Result<HeaderMap> {
+        let mut map = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|_| anyhow!("invalid Ollama header name: {}", name))?;
+            let mut value = HeaderValue::from_str(value.trim())
+                .map_err(|_| anyhow!("invalid value for Ollama header {}", name))?;
+            value.set_sensitive(true);
+            map.insert(name, value);
+        }
+        if let Some(key) = &self.api_key {
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", key))
+                .map_err(|_| anyhow!("invalid Ollama API key"))?;
+            value.set_sensitive(true);
+            map.insert(AUTHORIZATION, value);
+        }
+        Ok(map)
+    }
+
+    fn config_hash(&self) -> u64 {
+        let mut headers = self.headers.iter().collect::<Vec<_>>();
+        headers.sort();
+        let mut hasher = DefaultHasher::new();
+        self.api_key.hash(&mut hasher);
+        headers.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub(crate) fn client(&self) -> Result<Client> {
+        let key = self.config_hash();
+        let mut cached = CLIENT.lock();
+        if let Some((cached_key, client)) = cached.as_ref() {
+            if *cached_key == key {
+                return Ok(client.clone());
+            }
+        }
+        let client = Client::builder()
+            .default_headers(self.header_map()?)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .build()?;
+        *cached = Some((key, client.clone()));
+        Ok(client)
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut out = text.to_string();
+        let secrets = self.api_key.iter().chain(self.headers.values());
+        for secret in secrets.map(|s| s.trim()).filter(|s| s.len() >= 4) {
+            out = out.replace(secret, "***");
+        }
+        out
+    }
+
+    pub fn redact_error(&self, e: anyhow::Error) -> anyhow::Error {
+        anyhow!(self.redact(&format!("{:#}", e)))
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        let mut names = self.headers.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        format!(
+            "api_key: {}\nextra_headers: [{}]",
+            if self.api_key.is_some() {
+                "set"
+            } else {
+                "none"
+            },
+            names.join(", ")
+        )
+    }
+}
+
+// Per-request chat settings. Template placeholders: `{schema}` (the JSON shape parsing expects),
+// `{categories}` (`a|b|...`) and `{language}`. Empty templates fall back to the built-ins.
+#[derive(Debug, Clone, Default)]
+pub struct ChatOptions {
+    pub language: OutputLanguage,
+    pub system_template: Option<String>,
+    pub user_template: Option<String>,
+    // Merged over `temperature: 0`; unknown keys are left for Ollama to ignore.
+    pub options: HashMap<String, Value>,
+    pub keep_alive: Option<String>,
+    pub auth: OllamaAuth,
+    // Keeps tags/caption/text-in-image exactly as the model wrote them (`sanitize_output` off).
+    pub raw_output: bool,
+    pub fallback_category: CategoryKey,
+    // Idle limit for each wait on the server; 0 waits indefinitely.
+    pub timeout_secs: u64,
+    // Streaming responses are cut off past this many bytes.
+    pub max_response_bytes: usize,
+    // Set per photo when `debug_capture_raw_responses` is on: the complete response body is
+    // written here and the analysis log points at the file instead of inlining the content.
+    pub raw_capture: Option<PathBuf>,
+}
+
+impl ChatOptions {
+    pub fn from_settings(settings: &Settings) -> Self {
+        // set_settings rejects these, but a hand-edited settings file can still get here.
+        if let Err(e) = validate_prompt_templates(
+            settings.ollama_system_prompt.as_deref(),
+            settings.ollama_user_prompt.as_deref(),
+        ) {
+            eprintln!("warning: {}; responses will likely fail to parse", e);
+        }
+        Self {
+            language: settings.output_language,
+            system_template: custom_template(settings.ollama_system_prompt.as_deref())
+                .map(str::to_string),
+            user_template: custom_template(settings.ollama_user_prompt.as_deref())
+                .map(str::to_string),
+            options: settings.ollama_options.clone(),
+            keep_alive: Some(settings.ollama_keep_alive.trim())
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+            auth: OllamaAuth::from_settings(settings),
+            raw_output: !settings.sanitize_output,
+            fallback_category: settings.fallback_category,
+            timeout_secs: settings.ollama_timeout_secs,
+            max_response_bytes: settings.ollama_max_response_bytes,
+            raw_capture: None,
+        }
+    }
+
+    // Called before parsing so a response that fails to parse is kept as well.
+    pub(crate) fn capture_raw(&self, body: &str) -> Option<Result<&Path>> {
+        let path = self.raw_capture.as_deref()?;
+        Some(write_capture(path, body).map(|_| path))
+    }
+
+    // Streamed responses are captured as a JSON array of their chunks.
+    pub(crate) fn raw_chunks(&self) -> Option<Vec<Value>> {
+        self.raw_capture.is_some().then(Vec::new)
+    }
+
+    pub(crate) fn capture_chunks(&self, chunks: &Option<Vec<Value>>) -> Option<Result<&Path>> {
+        let body = serde_json::to_string(chunks.as_ref()?).unwrap_or_default();
+        self.capture_raw(&body)
+    }
+
+    pub(crate) fn request_options(&self) -> Value {
+        let mut options = serde_json::Map::new();
+        options.insert("temperature".to_string(), json!(0));
+        for (k, v) in &self.options {
+            options.insert(k.clone(), v.clone());
+        }
+        Value::Object(options)
+    }
+
+    // Plain integers are seconds for Ollama and must go out as numbers; durations stay strings.
+    fn keep_alive_value(&self) -> Option<Value> {
+        let raw = self.keep_alive.as_deref()?;
+        Some(match raw.parse::<i64>() {
+            Ok(secs) => json!(secs),
+            Err(_) => json!(raw),
+        })
+    }
+
+    fn apply_to_body(&self, body: &mut Value) {
+        if let Some(obj) = body.as_object_mut() {
+            obj.insert("options".to_string(), self.request_options());
+            if let Some(keep_alive) = self.keep_alive_value() {
+                obj.insert("keep_alive".to_string(), keep_alive);
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "options: {}\nkeep_alive: {}\n{}",
+            self.request_options(),
+            self.keep_alive.as_deref().unwrap_or("<default>"),
+            self.auth.describe()
+        )
+    }
+}
+
+const DEFAULT_USER_TEMPLATE: &str = "Analyze the image and output JSON with EXACT keys: {schema}. tags and caption MUST be {language} only. scores must be between 0 and 1 and sum to 1.";
+
+fn custom_template(template: Option<&str>) -> Option<&str> {
+    template.filter(|t| !t.trim().is_empty())
+}
+
+const REQUIRED_KEYS: [&str; 5] = ["category", "scores", "tags", "caption", "text_in_image"];
+
+// Parsing depends on the response shape, so it has to reach the model through one of the two
+// messages (the built-in user prompt carries it): either via `{schema}` or by spelling out every
+// key, e.g. in a few-shot example.
+pub fn validate_prompt_templates(system: Option<&str>, user: Option<&str>) -> Result<()> {
+    let has_schema =
+        |t: &str| t.contains("{schema}") || REQUIRED_KEYS.iter().all(|k| t.contains(k));
+    let user_ok = custom_template(user).is_none_or(has_schema);
+    if user_ok || custom_template(system).is_some_and(has_schema) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "ollama prompt templates must include the {{schema}} placeholder or the keys {}",
+            REQUIRED_KEYS.join(", ")
+        ))
+    }
+}
+
+fn categories_hint() -> String {
+    CATEGORY_KEYS
+        .iter()
+        .map(|k| k.as_str())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn schema_hint() -> String {
+    let scores = CATEGORY_KEYS
+        .iter()
+        .map(|k| format!("\"{}\": number", k.as_str()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{{\"category\": \"{}\", \"scores\": {{{}}}, \"tags\": string[], \"caption\": string, \"text_in_image\": string}}",
+        categories_hint(),
+        scores
+    )
+}
+
+fn expand_template(template: &str, language: OutputLanguage) -> String {
+    template
+        .replace("{schema}", &schema_hint())
+        .replace("{categories}", &categories_hint())
+        .replace("{language}", language.prompt_name())
+}
+
+// System + user prompts asking for the JSON shape above, with free-text fields in the output
+// language.
+pub(crate) fn chat_prompts(chat: &ChatOptions) -> (String, String) {
+    let language = chat.language;
+    let system = match &chat.system_template {
+        Some(t) => expand_template(t, language),
+        None => {
+            let lang = language.prompt_name();
+            let script_rule = match language {
+                OutputLanguage::Ko => {
+                    " Do NOT use Chinese characters(Hanja), Japanese, or English."
+                }
+                _ => "",
+            };
+            format!(
+                "You are a strict JSON generator. Return ONLY a JSON object, no markdown, no prose, no code fences. IMPORTANT: For tags, caption, text_in_image you MUST output {lang} only.{script_rule} If any text in the image is in another language, translate it to {lang}; if you cannot translate reliably, output an empty string for text_in_image."
+            )
+        }
+    };
+    let user_template = chat
+        .user_template
+        .as_deref()
+        .unwrap_or(DEFAULT_USER_TEMPLATE);
+    (system, expand_template(user_template, language))
+}
+
+fn chat_messages(chat: &ChatOptions, base64_jpeg: &str) -> Value {
+    let (system, user) = chat_prompts(chat);
+    json!([
+        {"role": "system", "content": system},
+        {"role": "user", "content": user, "images": [base64_jpeg]}
+    ])
+}
+
+// Some older or proxied deployments only accept images on /api/generate, so classification
+// falls back to it when /api/chat is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    Chat,
+    Generate,
+}
+
+impl Endpoint {
+    fn path(self) -> &'static str {
+        match self {
+            Endpoint::Chat => "/api/chat",
+            Endpoint::Generate => "/api/generate",
+        }
+    }
+
+    // Where the generated text lives, both in full responses and in stream chunks.
+    fn content_pointer(self) -> &'static str {
+        match self {
+            Endpoint::Chat => "/message/content",
+            Endpoint::Generate => "/response",
+        }
+    }
+
+    fn body(self, chat: &ChatOptions, model: &str, base64_jpeg: &str, stream: bool) -> Value {
+        match self {
+            Endpoint::Chat => json!({
+              "model": model,
+              "stream": stream,
+              "messages": chat_messages(chat, base64_jpeg)
+            }),
+            Endpoint::Generate => {
+                let (system, user) = chat_prompts(chat);
+                json!({
+                  "model": model,
+                  "stream": stream,
+                  "prompt": format!("{}\n\n{}", system, user),
+                  "images": [base64_jpeg]
+                })
+            }
+        }
+    }
+}
+
+// A 404 naming the model is a missing model, not a missing endpoint.
+fn is_chat_endpoint_unsupported(status: StatusCode, text: &str) -> bool {
+    let lowered = text.to_lowercase();
+    if status == StatusCode::NOT_FOUND && lowered.contains("model") {
+        return false;
+    }
+    matches!(
+        status,
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED
+    ) || lowered.contains("/api/chat")
+        || lowered.contains("endpoint")
+}
+
+pub(crate) fn strip_code_fences(s: &str) -> &str {
     let trimmed = s.trim();
     let trimmed = trimmed
         .strip_prefix("```json")
@@ -97,7 +455,8 @@ fn extract_first_json_object(s: &str) -> Option<&str> {
     None
 }
 
-fn parse_model_out(content: &str) -> Result<ModelOut> {
+pub(crate) fn parse_model_out(content: &str, chat: &ChatOptions) -> Result<ModelOut> {
+    let language = chat.language;
     let content = strip_code_fences(content);
     let candidate = extract_first_json_object(content).unwrap_or(content);
     let parsed: Value = serde_json::from_str(candidate).map_err(|e| {
@@ -105,6 +464,24 @@ fn parse_model_out(content: &str) -> Result<ModelOut> {
         anyhow!("parse model json: {} | head: {}", e, head)
     })?;
 
+    // Korean output drops other scripts (models like to slip into Hanja); other languages only
+    // lose control characters. Raw output skips both so debugging shows what the model wrote.
+    let raw_output = chat.raw_output;
+    let sanitize = |s: &str, language: OutputLanguage| -> String {
+        if raw_output {
+            return s.to_string();
+        }
+        match language {
+            OutputLanguage::Ko => sanitize_korean_only(s),
+            _ => s
+                .chars()
+                .filter(|ch| !ch.is_control() || matches!(ch, '\n' | '\t'))
+                .collect::<String>()
+                .trim()
+                .to_string(),
+        }
+    };
+
     fn sanitize_korean_only(s: &str) -> String {
         // Keep Hangul + whitespace + digits + basic punctuation; strip other scripts (e.g. CJK Han characters).
         let mut out = String::with_capacity(s.len());
@@ -131,43 +508,45 @@ fn parse_model_out(content: &str) -> Result<ModelOut> {
 
     let category_raw = parsed.get("category").and_then(|v| v.as_str());
     let scores_obj = parsed.get("scores").and_then(|v| v.as_object());
+    let known_category =
+        category_raw.and_then(|cat| CATEGORY_KEYS.iter().copied().find(|k| k.as_str() == cat));
 
+    let mut score_repairs = Vec::new();
+    if let (Some(raw), None) = (category_raw, known_category) {
+        score_repairs.push(format!("unknown category {:?}", raw));
+    }
     let scores = if let Some(obj) = scores_obj {
-        let scores_map: HashMap<String, f32> = obj
-            .iter()
-            .filter_map(|(k, v)| v.as_f64().map(|f| (k.clone(), f as f32)))
-            .collect::<HashMap<_, _>>();
-        let mut s = Scores::from_map(&scores_map);
-        s = s.normalize();
-        s
-    } else if let Some(cat) = category_raw {
+        repair_scores(obj, known_category, &mut score_repairs)
+    } else if category_raw.is_some() {
         // Fallback: if only category is present, create a one-hot style distribution.
-        let mut map = HashMap::<String, f32>::new();
-        for k in [
-            "screenshot_document",
-            "people",
-            "food_cafe",
-            "nature_landscape",
-            "city_street_travel",
-            "pets_animals",
-            "products_objects",
-            "other",
-        ] {
-            map.insert(k.to_string(), if k == cat { 1.0 } else { 0.0 });
-        }
-        Scores::from_map(&map)
+        score_repairs.push("scores missing: one-hot from category".to_string());
+        one_hot(known_category.unwrap_or(chat.fallback_category))
     } else {
         return Err(anyhow!("scores missing"));
     };
 
-    let category = if let Some(cat) = category_raw {
-        CategoryKey::from(cat)
-    } else {
-        scores.top().0
+    // Without a known category the argmax decides, unless it is tied (e.g. uniform scores).
+    let category = match known_category {
+        Some(cat) => cat,
+        None => match scores.top_k(2).as_slice() {
+            [first, second] if first.score > second.score => first.category,
+            _ => {
+                score_repairs.push(format!(
+                    "no clear category: fallback {}",
+                    chat.fallback_category.as_str()
+                ));
+                chat.fallback_category
+            }
+        },
     };
 
-    let tags = parsed
-        .get("tags_ko")
+    // Older prompts used `_ko` suffixed keys; accept both.
+    let field = |key: &str| {
+        parsed
+            .get(key)
+            .or_else(|| parsed.get(format!("{}_ko", key).as_str()))
+    };
+    let tags = field("tags")
         .and_then(|v| v.as_array())
         .map(|arr| {
             arr.iter()
@@ -177,84 +556,246 @@ fn parse_model_out(content: &str) -> Result<ModelOut> {
         .unwrap_or_default();
     let mut tags = tags
         .into_iter()
-        .map(|t| sanitize_korean_only(&t))
+        .map(|t| sanitize(&t, language))
         .filter(|t| !t.is_empty())
         .collect::<Vec<_>>();
     if tags.is_empty() {
-        tags.push("기타".to_string());
+        tags.push(language.fallback_tag().to_string());
     }
 
-    let caption = parsed
-        .get("caption_ko")
+    let caption = field("caption")
         .and_then(|v| v.as_str())
         .unwrap_or_default()
         .to_string();
     let caption = {
-        let s = sanitize_korean_only(&caption);
+        let s = sanitize(&caption, language);
         if s.is_empty() {
-            "설명 없음".to_string()
+            language.empty_caption().to_string()
         } else {
             s
         }
     };
-    let text_in_image = parsed
-        .get("text_in_image_ko")
+    let text_in_image = field("text_in_image")
         .and_then(|v| v.as_str())
         .unwrap_or_default()
         .to_string();
-    let text_in_image = sanitize_korean_only(&text_in_image);
+    let text_in_image = sanitize(&text_in_image, language);
 
     Ok(ModelOut {
         category,
         scores,
-        tags_ko: tags,
-        caption_ko: caption,
-        text_in_image_ko: text_in_image,
+        tags,
+        caption,
+        text_in_image,
+        score_repairs,
     })
 }
 
+// How far the raw sum may stray from 1 before the renormalization is worth reporting.
+const SCORE_SUM_TOLERANCE: f32 = 0.1;
+// Probability gap between the argmax and the stated category beyond which the category wins.
+const CATEGORY_DISAGREEMENT_MARGIN: f32 = 0.3;
+// Weight of the one-hot stated category when it overrides the scores.
+const CATEGORY_OVERRIDE_WEIGHT: f32 = 0.5;
+
+fn one_hot(category: CategoryKey) -> Scores {
+    let map = CATEGORY_KEYS
+        .iter()
+        .map(|k| {
+            (
+                k.as_str().to_string(),
+                if *k == category { 1.0 } else { 0.0 },
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    Scores::from_map(&map)
+}
+
+// Models often ignore the "scores sum to 1" instruction. Values are clamped to [0, 1] and
+// renormalized; when the stated category loses to the argmax by a wide margin, the category is
+// trusted and blended in. Every rule that fires is noted in `repairs` for the analysis log.
+fn repair_scores(
+    obj: &serde_json::Map<String, Value>,
+    category: Option<CategoryKey>,
+    repairs: &mut Vec<String>,
+) -> Scores {
+    let mut missing = Vec::new();
+    let mut invalid = Vec::new();
+    let mut clamped = Vec::new();
+    let mut map = HashMap::<String, f32>::new();
+    for k in CATEGORY_KEYS {
+        let key = k.as_str();
+        let value = match obj.get(key) {
+            None => {
+                missing.push(key);
+                0.0
+            }
+            Some(v) => match v.as_f64().map(|f| f as f32).filter(|f| f.is_finite()) {
+                Some(f) if !(0.0..=1.0).contains(&f) => {
+                    clamped.push(key);
+                    f.clamp(0.0, 1.0)
+                }
+                Some(f) => f,
+                None => {
+                    invalid.push(key);
+                    0.0
+                }
+            },
+        };
+        map.insert(key.to_string(), value);
+    }
+    for (label, keys) in [
+        ("missing", &missing),
+        ("not a finite number", &invalid),
+        ("clamped to [0, 1]", &clamped),
+    ] {
+        if !keys.is_empty() {
+            repairs.push(format!("{}: {}", label, keys.join(", ")));
+        }
+    }
+
+    let sum: f32 = map.values().sum();
+    if sum <= 0.0 {
+        repairs.push(match category {
+            Some(cat) => format!("all scores zero: one-hot from category {}", cat.as_str()),
+            None => "all scores zero: uniform".to_string(),
+        });
+        return match category {
+            Some(cat) => one_hot(cat),
+            None => Scores::from_map(&map.keys().map(|k| (k.clone(), 1.0)).collect()),
+        };
+    }
+    if (sum - 1.0).abs() > SCORE_SUM_TOLERANCE {
+        repairs.push(format!("renormalized: sum was {:.3}", sum));
+    }
+    let scores = Scores::from_map(&map);
+
+    let Some(cat) = category else {
+        return scores;
+    };
+    let (top, top_p) = scores.top();
+    let cat_p = scores.get(cat);
+    if top == cat || top_p - cat_p <= CATEGORY_DISAGREEMENT_MARGIN {
+        return scores;
+    }
+    repairs.push(format!(
+        "category {} disagrees with argmax {} ({:.3} vs {:.3}): blended toward category",
+        cat.as_str(),
+        top.as_str(),
+        cat_p,
+        top_p
+    ));
+    let hot = one_hot(cat);
+    let blended = CATEGORY_KEYS
+        .iter()
+        .map(|k| {
+            let v = (1.0 - CATEGORY_OVERRIDE_WEIGHT) * scores.get(*k)
+                + CATEGORY_OVERRIDE_WEIGHT * hot.get(*k);
+            (k.as_str().to_string(), v)
+        })
+        .collect::<HashMap<_, _>>();
+    Scores::from_map(&blended)
+}
+
+// Cleans up model tags: trims surrounding whitespace and punctuation, collapses inner
+// whitespace, drops tags over `max_chars` (0 = no limit), merges duplicates that differ only in
+// case or spacing, and keeps the first `max_tags`. Falls back to `fallback` if nothing is left.
+pub(crate) fn normalize_tags(
+    tags: Vec<String>,
+    max_tags: usize,
+    max_chars: usize,
+    fallback: &str,
+) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut out = tags
+        .iter()
+        .map(|t| {
+            t.trim_matches(|c: char| {
+                c.is_whitespace()
+                    || c.is_ascii_punctuation()
+                    || matches!(c, '·' | '…' | '—' | '“' | '”' | '‘' | '’')
+            })
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+        })
+        .filter(|t| !t.is_empty() && (max_chars == 0 || t.chars().count() <= max_chars))
+        .filter(|t| seen.insert(tag_key(t)))
+        .take(max_tags.max(1))
+        .collect::<Vec<_>>();
+    if out.is_empty() {
+        out.push(fallback.to_string());
+    }
+    out
+}
+
+// Tags that differ only in case or spacing share a key.
+pub(crate) fn tag_key(tag: &str) -> String {
+    tag.split_whitespace().collect::<String>().to_lowercase()
+}
+
+// Appended to the analysis log when `parse_model_out` had to repair the scores.
+pub(crate) fn score_repairs_log(out: &ModelOut) -> String {
+    if out.score_repairs.is_empty() {
+        return String::new();
+    }
+    format!("\nscore_repairs:\n- {}\n", out.score_repairs.join("\n- "))
+}
+
 pub async fn classify_image_with_options(
     base_url: &str,
     model: &str,
     think: bool,
+    chat: &ChatOptions,
+    base64_jpeg: &str,
+    cancel: &CancellationToken,
+) -> Result<(ModelOut, String)> {
+    let auth = &chat.auth;
+    classify_image_inner(base_url, model, think, chat, base64_jpeg, cancel)
+        .await
+        .map(|(out, log)| (out, auth.redact(&log)))
+        .map_err(|e| auth.redact_error(e))
+}
+
+async fn classify_image_inner(
+    base_url: &str,
+    model: &str,
+    think: bool,
+    chat: &ChatOptions,
     base64_jpeg: &str,
     cancel: &CancellationToken,
 ) -> Result<(ModelOut, String)> {
     if model.trim().is_empty() {
         return Err(anyhow!("ollama model is empty"));
     }
-    let url = format!("{}/api/chat", base_url.trim_end_matches('/'));
-    let client = Client::new();
+    let timeout = request_timeout(chat.timeout_secs);
+    let client = chat.auth.client()?;
     async fn send_and_read(
         client: &Client,
         url: &str,
         body: &Value,
+        timeout: Option<Duration>,
         cancel: &CancellationToken,
     ) -> Result<(reqwest::StatusCode, String)> {
+        let mut req = client.post(url).json(body);
+        if let Some(t) = timeout {
+            req = req.timeout(t);
+        }
         let resp = tokio::select! {
             _ = cancel.cancelled() => return Err(anyhow!("canceled")),
-            r = client.post(url).json(body).send() => r?
+            r = req.send() => r.map_err(|e| request_error(e, timeout))?
         };
         let status = resp.status();
         let text = tokio::select! {
             _ = cancel.cancelled() => return Err(anyhow!("canceled")),
-            t = resp.text() => t?
+            t = resp.text() => t.map_err(|e| request_error(e, timeout))?
         };
         Ok((status, text))
     }
 
-    let make_base_body = |with_think: bool| {
-        let mut body = json!({
-          "model": model,
-          "stream": false,
-          "options": {
-            "temperature": 0
-          },
-          "messages": [
-              {"role": "system", "content": "You are a strict JSON generator. Return ONLY a JSON object, no markdown, no prose, no code fences. IMPORTANT: For tags_ko, caption_ko, text_in_image_ko you MUST output Korean only (Hangul). Do NOT use Chinese characters(Hanja), Japanese, or English. If any non-Korean text appears in the image, translate it to Korean; if you cannot translate reliably, output an empty string for text_in_image_ko."},
-              {"role": "user", "content": "Analyze the image and output JSON with EXACT keys: {\"category\": \"screenshot_document|people|food_cafe|nature_landscape|city_street_travel|pets_animals|products_objects|other\", \"scores\": {\"screenshot_document\": number, \"people\": number, \"food_cafe\": number, \"nature_landscape\": number, \"city_street_travel\": number, \"pets_animals\": number, \"products_objects\": number, \"other\": number}, \"tags_ko\": string[], \"caption_ko\": string, \"text_in_image_ko\": string}. tags_ko and caption_ko MUST be Korean(Hangul) only. scores must be between 0 and 1 and sum to 1.", "images": [base64_jpeg]}
-          ]
-        });
+    let make_base_body = |endpoint: Endpoint, with_think: bool| {
+        let mut body = endpoint.body(chat, model, base64_jpeg, false);
+        chat.apply_to_body(&mut body);
         if !with_think {
             if let Some(obj) = body.as_object_mut() {
                 obj.insert("think".to_string(), Value::Bool(false));
@@ -262,92 +803,118 @@ pub async fn classify_image_with_options(
         }
         body
     };
-    let base_body = make_base_body(think);
 
-    let try_with_schema = || {
-        let mut body = base_body.clone();
-        if let Some(obj) = body.as_object_mut() {
-            obj.insert("format".to_string(), JSON_SCHEMA.clone());
-        }
-        body
-    };
-
-    let mut body = try_with_schema();
-    let (mut status, mut text) = send_and_read(&client, &url, &body, cancel).await?;
-    if !status.is_success() {
-        // Some Ollama versions/models don't support JSON schema `format` on /api/chat.
-        // If we detect that, retry with `"format": "json"` and then without format.
-        let lowered = text.to_lowercase();
-        let format_problem = lowered.contains("format")
-            || lowered.contains("json schema")
-            || lowered.contains("schema")
-            || lowered.contains("expected")
-            || lowered.contains("unknown field");
-
-        if format_problem {
-            // Try JSON mode (string format) first.
-            body = base_body.clone();
+    // Walks the format ladder (schema -> "json" -> none), then the same without `think`.
+    async fn send_with_fallbacks(
+        client: &Client,
+        url: &str,
+        base_body: &Value,
+        base_body_no_think: &Value,
+        timeout: Option<Duration>,
+        cancel: &CancellationToken,
+    ) -> Result<(reqwest::StatusCode, String)> {
+        let try_with_schema = || {
+            let mut body = base_body.clone();
             if let Some(obj) = body.as_object_mut() {
-                obj.insert("format".to_string(), Value::String("json".to_string()));
+                obj.insert("format".to_string(), JSON_SCHEMA.clone());
             }
-            (status, text) = send_and_read(&client, &url, &body, cancel).await?;
-            if !status.is_success() {
-                // Finally, retry without any format.
-                body = base_body.clone();
-                (status, text) = send_and_read(&client, &url, &body, cancel).await?;
-            }
-        }
+            body
+        };
+
+        let mut body = try_with_schema();
+        let (mut status, mut text) = send_and_read(client, url, &body, timeout, cancel).await?;
         if !status.is_success() {
+            // Some Ollama versions/models don't support JSON schema `format` on /api/chat.
+            // If we detect that, retry with `"format": "json"` and then without format.
             let lowered = text.to_lowercase();
-            let think_unsupported = lowered.contains("unknown field")
-                && (lowered.contains("think") || lowered.contains("\"think\""));
-            if think_unsupported {
-                // Retry without `think` for older servers.
-                let base_body_no_think = make_base_body(true);
-                body = base_body_no_think.clone();
+            let format_problem = lowered.contains("format")
+                || lowered.contains("json schema")
+                || lowered.contains("schema")
+                || lowered.contains("expected")
+                || lowered.contains("unknown field");
+
+            if format_problem {
+                // Try JSON mode (string format) first.
+                body = base_body.clone();
                 if let Some(obj) = body.as_object_mut() {
-                    obj.insert("format".to_string(), JSON_SCHEMA.clone());
+                    obj.insert("format".to_string(), Value::String("json".to_string()));
                 }
-                (status, text) = send_and_read(&client, &url, &body, cancel).await?;
+                (status, text) = send_and_read(client, url, &body, timeout, cancel).await?;
                 if !status.is_success() {
+                    // Finally, retry without any format.
+                    body = base_body.clone();
+                    (status, text) = send_and_read(client, url, &body, timeout, cancel).await?;
+                }
+            }
+            if !status.is_success() {
+                let lowered = text.to_lowercase();
+                let think_unsupported = lowered.contains("unknown field")
+                    && (lowered.contains("think") || lowered.contains("\"think\""));
+                if think_unsupported {
+                    // Retry without `think` for older servers.
                     body = base_body_no_think.clone();
                     if let Some(obj) = body.as_object_mut() {
-                        obj.insert("format".to_string(), Value::String("json".to_string()));
+                        obj.insert("format".to_string(), JSON_SCHEMA.clone());
                     }
-                    (status, text) = send_and_read(&client, &url, &body, cancel).await?;
+                    (status, text) = send_and_read(client, url, &body, timeout, cancel).await?;
                     if !status.is_success() {
-                        body = base_body_no_think;
-                        (status, text) = send_and_read(&client, &url, &body, cancel).await?;
+                        body = base_body_no_think.clone();
+                        if let Some(obj) = body.as_object_mut() {
+                            obj.insert("format".to_string(), Value::String("json".to_string()));
+                        }
+                        (status, text) = send_and_read(client, url, &body, timeout, cancel).await?;
+                        if !status.is_success() {
+                            body = base_body_no_think.clone();
+                            (status, text) =
+                                send_and_read(client, url, &body, timeout, cancel).await?;
+                        }
                     }
                 }
             }
         }
+        Ok((status, text))
     }
+
+    let mut endpoint = Endpoint::Chat;
+    let (url, status, text) = loop {
+        let url = format!("{}{}", base_url.trim_end_matches('/'), endpoint.path());
+        let (status, text) = send_with_fallbacks(
+            &client,
+            &url,
+            &make_base_body(endpoint, think),
+            &make_base_body(endpoint, true),
+            timeout,
+            cancel,
+        )
+        .await?;
+        if endpoint == Endpoint::Chat
+            && !status.is_success()
+            && is_chat_endpoint_unsupported(status, &text)
+        {
+            endpoint = Endpoint::Generate;
+            continue;
+        }
+        break (url, status, text);
+    };
     if !status.is_success() {
+        if is_auth_status(status) {
+            return Err(unauthorized(status));
+        }
         let lowered = text.to_lowercase();
         if status.as_u16() == 404 && lowered.contains("model") {
-            return Err(anyhow!(
-                "ollama model not found ({}). Run `ollama pull {}` then retry. raw: {}",
-                model,
-                model,
-                text
-            ));
-        }
-        if lowered.contains("does not support image")
-            || lowered.contains("images are not supported")
-        {
-            return Err(anyhow!(
-                "ollama model does not support images ({}). Choose a vision model (e.g. llava / qwen2.5vl). raw: {}",
-                model,
-                text
-            ));
+            return Err(model_not_found(model, &text));
+        }
+        if is_images_unsupported(&text) {
+            return Err(images_unsupported(model, &text));
         }
         return Err(anyhow!("ollama error {}: {}", status, text));
     }
 
-    let outer: Value = serde_json::from_str(&text)?;
+    let captured = chat.capture_raw(&text);
+    let outer: Value =
+        serde_json::from_str(&text).map_err(|e| note_capture(e.into(), &captured))?;
     let content_str = outer
-        .pointer("/message/content")
+        .pointer(endpoint.content_pointer())
         .and_then(|v| v.as_str())
         .ok_or_else(|| anyhow!("missing message content"))?;
 
@@ -360,14 +927,21 @@ pub async fn classify_image_with_options(
         out
     }
 
-    let out = parse_model_out(content_str).or_else(|_| parse_model_out(text.trim()))?;
+    let out = parse_model_out(content_str, chat)
+        .or_else(|_| parse_model_out(text.trim(), chat))
+        .map_err(|e| note_capture(e, &captured))?;
     let log = format!(
-        "url: {url}\nmodel: {model}\nthink: {think}\n\nmessage.content:\n{content}\n",
+        "url: {url}\nendpoint: {endpoint}\nmodel: {model}\nthink: {think}\n{chat}\n{content}",
         url = url,
+        endpoint = endpoint.path(),
         model = model,
         think = think,
-        content = truncate(content_str, 20000)
-    );
+        chat = chat.describe(),
+        content = content_log(&captured, "message.content", || truncate(
+            content_str,
+            20000
+        ))
+    ) + &metrics_log(&outer);
     Ok((out, log))
 }
 
@@ -375,6 +949,26 @@ pub async fn classify_image_streaming_with_options<F>(
     base_url: &str,
     model: &str,
     think: bool,
+    chat: &ChatOptions,
+    base64_jpeg: &str,
+    cancel: &CancellationToken,
+    on_delta: F,
+) -> Result<(ModelOut, String)>
+where
+    F: FnMut(&str) + Send,
+{
+    let auth = &chat.auth;
+    classify_image_streaming_inner(base_url, model, think, chat, base64_jpeg, cancel, on_delta)
+        .await
+        .map(|(out, log)| (out, auth.redact(&log)))
+        .map_err(|e| auth.redact_error(e))
+}
+
+async fn classify_image_streaming_inner<F>(
+    base_url: &str,
+    model: &str,
+    think: bool,
+    chat: &ChatOptions,
     base64_jpeg: &str,
     cancel: &CancellationToken,
     mut on_delta: F,
@@ -385,34 +979,28 @@ where
     if model.trim().is_empty() {
         return Err(anyhow!("ollama model is empty"));
     }
-    let url = format!("{}/api/chat", base_url.trim_end_matches('/'));
-    let client = Client::new();
+    // No client-wide timeout here: it would also cap the total stream duration. Instead each
+    // wait on the server (response headers, every chunk) gets its own idle limit.
+    let timeout = request_timeout(chat.timeout_secs);
+    let client = chat.auth.client()?;
 
     async fn send_streaming(
         client: &Client,
         url: &str,
         body: &Value,
+        timeout: Option<Duration>,
         cancel: &CancellationToken,
     ) -> Result<reqwest::Response> {
         let resp = tokio::select! {
             _ = cancel.cancelled() => return Err(anyhow!("canceled")),
-            r = client.post(url).json(body).send() => r?
+            r = with_idle_timeout(timeout, client.post(url).json(body).send()) => r?
         };
         Ok(resp)
     }
 
-    let make_base_body = |with_think_field: bool| {
-        let mut body = json!({
-          "model": model,
-          "stream": true,
-          "options": {
-            "temperature": 0
-          },
-          "messages": [
-              {"role": "system", "content": "You are a strict JSON generator. Return ONLY a JSON object, no markdown, no prose, no code fences. IMPORTANT: For tags_ko, caption_ko, text_in_image_ko you MUST output Korean only (Hangul). Do NOT use Chinese characters(Hanja), Japanese, or English. If any non-Korean text appears in the image, translate it to Korean; if you cannot translate reliably, output an empty string for text_in_image_ko."},
-              {"role": "user", "content": "Analyze the image and output JSON with EXACT keys: {\"category\": \"screenshot_document|people|food_cafe|nature_landscape|city_street_travel|pets_animals|products_objects|other\", \"scores\": {\"screenshot_document\": number, \"people\": number, \"food_cafe\": number, \"nature_landscape\": number, \"city_street_travel\": number, \"pets_animals\": number, \"products_objects\": number, \"other\": number}, \"tags_ko\": string[], \"caption_ko\": string, \"text_in_image_ko\": string}. tags_ko and caption_ko MUST be Korean(Hangul) only. scores must be between 0 and 1 and sum to 1.", "images": [base64_jpeg]}
-          ]
-        });
+    let make_base_body = |endpoint: Endpoint, with_think_field: bool| {
+        let mut body = endpoint.body(chat, model, base64_jpeg, true);
+        chat.apply_to_body(&mut body);
         if !with_think_field {
             if let Some(obj) = body.as_object_mut() {
                 obj.insert("think".to_string(), Value::Bool(false));
@@ -420,27 +1008,25 @@ where
         }
         body
     };
-    let base_body = make_base_body(think);
-    let base_body_no_think = make_base_body(true);
-
     async fn try_streaming_sequence(
         client: &Client,
         url: &str,
         base_body: &Value,
+        timeout: Option<Duration>,
         cancel: &CancellationToken,
-    ) -> Result<reqwest::Response> {
+    ) -> Result<std::result::Result<reqwest::Response, (StatusCode, String)>> {
         // 1) JSON schema format
         let mut body = base_body.clone();
         if let Some(obj) = body.as_object_mut() {
             obj.insert("format".to_string(), JSON_SCHEMA.clone());
         }
-        let resp = send_streaming(client, url, &body, cancel).await?;
+        let resp = send_streaming(client, url, &body, timeout, cancel).await?;
         if resp.status().is_success() {
-            return Ok(resp);
+            return Ok(Ok(resp));
         }
         let text = tokio::select! {
             _ = cancel.cancelled() => return Err(anyhow!("canceled")),
-            t = resp.text() => t?
+            t = with_idle_timeout(timeout, resp.text()) => t?
         };
         let lowered = text.to_lowercase();
         let format_problem = lowered.contains("format")
@@ -455,73 +1041,78 @@ where
             if let Some(obj) = body.as_object_mut() {
                 obj.insert("format".to_string(), Value::String("json".to_string()));
             }
-            let resp2 = send_streaming(client, url, &body, cancel).await?;
+            let resp2 = send_streaming(client, url, &body, timeout, cancel).await?;
             if resp2.status().is_success() {
-                return Ok(resp2);
+                return Ok(Ok(resp2));
             }
         }
 
         // 3) no format
-        let resp3 = send_streaming(client, url, base_body, cancel).await?;
+        let resp3 = send_streaming(client, url, base_body, timeout, cancel).await?;
         if resp3.status().is_success() {
-            return Ok(resp3);
+            return Ok(Ok(resp3));
         }
         let status3 = resp3.status();
         let text3 = tokio::select! {
             _ = cancel.cancelled() => return Err(anyhow!("canceled")),
-            t = resp3.text() => t?
+            t = with_idle_timeout(timeout, resp3.text()) => t?
         };
-        Err(anyhow!("ollama error {}: {}", status3, text3))
+        Ok(Err((status3, text3)))
     }
 
-    // Try with think setting first, then fall back if server doesn't support `think`.
-    let mut resp = match try_streaming_sequence(&client, &url, &base_body, cancel).await {
-        Ok(r) => r,
-        Err(e) => {
-            let msg = e.to_string().to_lowercase();
-            let think_unsupported = msg.contains("unknown field")
-                && (msg.contains("think") || msg.contains("\"think\""));
+    let mut endpoint = Endpoint::Chat;
+    let (url, attempt) = loop {
+        let url = format!("{}{}", base_url.trim_end_matches('/'), endpoint.path());
+        // Try with think setting first, then fall back if server doesn't support `think`.
+        let base_body = make_base_body(endpoint, think);
+        let mut attempt =
+            try_streaming_sequence(&client, &url, &base_body, timeout, cancel).await?;
+        if let Err((_, text)) = &attempt {
+            let lowered = text.to_lowercase();
+            let think_unsupported = lowered.contains("unknown field")
+                && (lowered.contains("think") || lowered.contains("\"think\""));
             if think_unsupported {
-                try_streaming_sequence(&client, &url, &base_body_no_think, cancel).await?
-            } else {
-                return Err(e);
+                let base_body_no_think = make_base_body(endpoint, true);
+                attempt =
+                    try_streaming_sequence(&client, &url, &base_body_no_think, timeout, cancel)
+                        .await?;
+            }
+        }
+        if let Err((status, text)) = &attempt {
+            if endpoint == Endpoint::Chat && is_chat_endpoint_unsupported(*status, text) {
+                endpoint = Endpoint::Generate;
+                continue;
             }
         }
+        break (url, attempt);
     };
-    let status = resp.status();
-    if !status.is_success() {
-        let text = tokio::select! {
-            _ = cancel.cancelled() => return Err(anyhow!("canceled")),
-            t = resp.text() => t?
-        };
-        let lowered = text.to_lowercase();
-        if status.as_u16() == 404 && lowered.contains("model") {
-            return Err(anyhow!(
-                "ollama model not found ({}). Run `ollama pull {}` then retry. raw: {}",
-                model,
-                model,
-                text
-            ));
-        }
-        if lowered.contains("does not support image")
-            || lowered.contains("images are not supported")
-        {
-            return Err(anyhow!(
-                "ollama model does not support images ({}). Choose a vision model (e.g. llava / qwen2.5vl). raw: {}",
-                model,
-                text
-            ));
+    let mut resp = match attempt {
+        Ok(resp) => resp,
+        Err((status, text)) => {
+            if is_auth_status(status) {
+                return Err(unauthorized(status));
+            }
+            let lowered = text.to_lowercase();
+            if status.as_u16() == 404 && lowered.contains("model") {
+                return Err(model_not_found(model, &text));
+            }
+            if is_images_unsupported(&text) {
+                return Err(images_unsupported(model, &text));
+            }
+            return Err(anyhow!("ollama error {}: {}", status, text));
         }
-        return Err(anyhow!("ollama error {}: {}", status, text));
-    }
+    };
 
-    // Parse NDJSON stream, accumulate ONLY `message.content`.
+    // Parse NDJSON stream, accumulate ONLY the generated content.
     let mut buf = String::new();
     let mut accumulated = String::new();
-    loop {
+    let mut raw_chunks = chat.raw_chunks();
+    let max_response_bytes = chat.max_response_bytes.max(1);
+    let mut truncated = false;
+    'read: loop {
         let next = tokio::select! {
             _ = cancel.cancelled() => return Err(anyhow!("canceled")),
-            c = resp.chunk() => c?
+            c = with_idle_timeout(timeout, resp.chunk()) => c?
         };
         let Some(chunk) = next else { break };
         let part = String::from_utf8_lossy(&chunk);
@@ -534,12 +1125,24 @@ where
             }
             let v: Value = match serde_json::from_str(&line) {
                 Ok(v) => v,
-                Err(_) => continue,
+                Err(_) => {
+                    if let Some(raw) = raw_chunks.as_mut() {
+                        raw.push(Value::String(line));
+                    }
+                    continue;
+                }
             };
+            if let Some(raw) = raw_chunks.as_mut() {
+                raw.push(v.clone());
+            }
             let delta = v
-                .pointer("/message/content")
+                .pointer(endpoint.content_pointer())
                 .and_then(|v| v.as_str())
                 .unwrap_or("");
+            if accumulated.len() + delta.len() > max_response_bytes {
+                truncated = true;
+                break 'read;
+            }
             if !delta.is_empty() {
                 accumulated.push_str(delta);
                 on_delta(delta);
@@ -547,14 +1150,18 @@ where
             let done = v.get("done").and_then(|v| v.as_bool()).unwrap_or(false);
             if done {
                 // Some servers may send a final line without '\n'; still fine.
-                let out = parse_model_out(accumulated.trim())
-                    .or_else(|_| parse_model_out(strip_code_fences(accumulated.trim())))?;
+                let captured = chat.capture_chunks(&raw_chunks);
+                let out = parse_model_out(accumulated.trim(), chat)
+                    .or_else(|_| parse_model_out(strip_code_fences(accumulated.trim()), chat))
+                    .map_err(|e| note_capture(e, &captured))?;
                 let log = format!(
-                    "url: {url}\nmodel: {model}\nthink: {think}\nstream: true\n\nmessage.content(accumulated):\n{content}\n",
+                    "url: {url}\nendpoint: {endpoint}\nmodel: {model}\nthink: {think}\n{chat}\nstream: true\n{content}",
                     url = url,
+                    endpoint = endpoint.path(),
                     model = model,
                     think = think,
-                    content = {
+                    chat = chat.describe(),
+                    content = content_log(&captured, "message.content(accumulated)", || {
                         if accumulated.len() <= 20000 {
                             accumulated.clone()
                         } else {
@@ -562,37 +1169,449 @@ where
                             s.push_str("\n…(truncated)…");
                             s
                         }
-                    }
-                );
+                    })
+                ) + &metrics_log(&v);
                 return Ok((out, log));
             }
         }
+        // A line that never ends counts against the cap too.
+        if accumulated.len() + buf.len() > max_response_bytes {
+            truncated = true;
+            break;
+        }
+    }
+    if truncated {
+        // Runaway generation: stop reading (dropping `resp` closes the stream) and salvage the
+        // deltas that fit under the cap.
+        drop(resp);
+        let captured = chat.capture_chunks(&raw_chunks);
+        let out = parse_model_out(accumulated.trim(), chat).map_err(|e| {
+            note_capture(
+                anyhow!(
+                    "ollama response too large (> {} bytes) and the partial output could not be parsed: {}",
+                    max_response_bytes,
+                    e
+                ),
+                &captured,
+            )
+        })?;
+        let log = format!(
+            "url: {url}\nendpoint: {endpoint}\nmodel: {model}\nthink: {think}\n{chat}\nstream: true\ntruncated_at_bytes: {max}\n{content}",
+            url = url,
+            endpoint = endpoint.path(),
+            model = model,
+            think = think,
+            chat = chat.describe(),
+            max = max_response_bytes,
+            content = content_log(&captured, "message.content(accumulated)", || {
+                accumulated.chars().take(20000).collect::<String>()
+            })
+        );
+        return Ok((out, log));
+    }
+
+    let captured = chat.capture_chunks(&raw_chunks);
+    Err(note_capture(
+        anyhow!("ollama stream ended unexpectedly"),
+        &captured,
+    ))
+}
+
+// The model output section of the analysis log: a pointer to the capture file when one was
+// written, otherwise the content inline.
+pub(crate) fn content_log(
+    captured: &Option<Result<&Path>>,
+    label: &str,
+    content: impl FnOnce() -> String,
+) -> String {
+    match captured {
+        Some(Ok(path)) => format!("raw_response: {}\n", path.display()),
+        Some(Err(e)) => format!(
+            "raw_response: capture failed: {}\n\n{}:\n{}\n",
+            e,
+            label,
+            content()
+        ),
+        None => format!("\n{}:\n{}\n", label, content()),
+    }
+}
+
+pub(crate) fn note_capture(e: anyhow::Error, captured: &Option<Result<&Path>>) -> anyhow::Error {
+    match captured {
+        Some(Ok(path)) => anyhow!("{} (raw response: {})", e, path.display()),
+        _ => e,
+    }
+}
+
+// Counters from the final response, appended to the analysis log. Durations arrive in
+// nanoseconds and are logged in ms. The pipeline reads `prompt_tokens`, `eval_tokens` and
+// `eval_ms` back out of the log, so no key may be a suffix of another.
+fn metrics_log(v: &Value) -> String {
+    let count = |key: &str| v.get(key).and_then(Value::as_u64);
+    let ms = |key: &str| count(key).map(|ns| ns / 1_000_000);
+    let fields = [
+        ("prompt_tokens", count("prompt_eval_count")),
+        ("eval_tokens", count("eval_count")),
+        ("total_ms", ms("total_duration")),
+        ("load_ms", ms("load_duration")),
+        ("prompt_ms", ms("prompt_eval_duration")),
+        ("eval_ms", ms("eval_duration")),
+    ];
+    let lines = fields
+        .iter()
+        .filter_map(|(key, value)| value.map(|v| format!("{}: {}\n", key, v)))
+        .collect::<String>();
+    if lines.is_empty() {
+        String::new()
+    } else {
+        format!("\nollama_metrics:\n{}", lines)
+    }
+}
+
+pub(crate) fn request_timeout(timeout_secs: u64) -> Option<Duration> {
+    (timeout_secs > 0).then(|| Duration::from_secs(timeout_secs))
+}
+
+fn timed_out(timeout: Duration) -> anyhow::Error {
+    anyhow!(
+        "ollama request timed out after {}s; raise the Ollama timeout in settings for slow models",
+        timeout.as_secs()
+    )
+}
+
+pub(crate) fn request_error(e: reqwest::Error, timeout: Option<Duration>) -> anyhow::Error {
+    match timeout {
+        Some(t) if e.is_timeout() => timed_out(t),
+        _ => e.into(),
+    }
+}
+
+pub(crate) async fn with_idle_timeout<T>(
+    timeout: Option<Duration>,
+    fut: impl Future<Output = reqwest::Result<T>>,
+) -> Result<T> {
+    match timeout {
+        Some(t) => tokio::time::timeout(t, fut)
+            .await
+            .map_err(|_| timed_out(t))?
+            .map_err(Into::into),
+        None => fut.await.map_err(Into::into),
+    }
+}
+
+pub(crate) fn is_auth_status(status: StatusCode) -> bool {
+    matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+}
+
+fn unauthorized(status: StatusCode) -> anyhow::Error {
+    anyhow!(
+        "ollama rejected the request ({}). If Ollama is behind a proxy, check the API key / extra headers in the Ollama settings",
+        status
+    )
+}
+
+fn status_error(status: StatusCode, text: &str) -> anyhow::Error {
+    if is_auth_status(status) {
+        return unauthorized(status);
+    }
+    anyhow!("ollama error {}: {}", status, text)
+}
+
+fn model_not_found(model: &str, raw: &str) -> anyhow::Error {
+    anyhow!(
+        "ollama model not found ({}). Run `ollama pull {}` then retry. raw: {}",
+        model,
+        model,
+        raw
+    )
+}
+
+fn images_unsupported(model: &str, raw: &str) -> anyhow::Error {
+    anyhow!(
+        "ollama model does not support images ({}). Choose a vision model (e.g. llava / qwen2.5vl). raw: {}",
+        model,
+        raw
+    )
+}
+
+// `/api/show` for one model. Newer servers report `capabilities`; older ones only expose the
+// families, so vision support falls back to the same guess the model list uses.
+pub async fn check_model(
+    base_url: &str,
+    auth: &OllamaAuth,
+    model: &str,
+) -> Result<OllamaModelCheck> {
+    let url = format!("{}/api/show", base_url.trim_end_matches('/'));
+    let client = auth.client()?;
+    let resp = client
+        .post(url)
+        .json(&json!({ "model": model }))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?;
+    let status = resp.status();
+    let text = resp.text().await?;
+    if status.as_u16() == 404 {
+        return Err(model_not_found(model, &text));
+    }
+    if !status.is_success() {
+        return Err(status_error(status, &text));
+    }
+    let json: Value = serde_json::from_str(&text)?;
+    Ok(parse_model_check(model, &json))
+}
+
+fn parse_model_check(model: &str, json: &Value) -> OllamaModelCheck {
+    let details = json.get("details");
+    let detail_str = |key: &str| {
+        details
+            .and_then(|d| d.get(key))
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+    let family = detail_str("family");
+    let families = details
+        .and_then(|d| d.get("families"))
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let capabilities = json
+        .get("capabilities")
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect::<Vec<_>>()
+        });
+    let supports_vision = match &capabilities {
+        Some(caps) => caps.iter().any(|c| c == "vision"),
+        None => {
+            json.get("projector_info").is_some()
+                || is_likely_vision(model, family.as_deref(), &families)
+        }
+    };
+    // `model_info` keys are prefixed with the architecture, e.g. `qwen2.context_length`.
+    let context_length = json
+        .get("model_info")
+        .and_then(|v| v.as_object())
+        .and_then(|info| {
+            info.iter()
+                .find(|(k, _)| k.ends_with(".context_length"))
+                .and_then(|(_, v)| v.as_u64())
+        });
+    OllamaModelCheck {
+        name: model.to_string(),
+        capabilities_reported: capabilities.is_some(),
+        capabilities: capabilities.unwrap_or_default(),
+        supports_vision,
+        context_length,
+        family,
+        parameter_size: detail_str("parameter_size"),
+        quantization: detail_str("quantization_level"),
+    }
+}
+
+// Fails with the same message a per-photo request would produce, before any work starts.
+pub async fn ensure_vision_model(
+    base_url: &str,
+    auth: &OllamaAuth,
+    model: &str,
+) -> Result<OllamaModelCheck> {
+    let check = check_model(base_url, auth, model).await?;
+    if !check.supports_vision {
+        let raw = if check.capabilities_reported {
+            format!("capabilities: [{}]", check.capabilities.join(", "))
+        } else {
+            format!("family: {}", check.family.as_deref().unwrap_or("unknown"))
+        };
+        return Err(images_unsupported(model, &raw));
+    }
+    Ok(check)
+}
+
+// Results of `probe_vision`, keyed by (base_url, model). Only definite answers are cached, so
+// a probe that failed for another reason runs again on the next job.
+static VISION_PROBES: Lazy<Mutex<HashMap<(String, String), bool>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Sends one tiny image so a text-only model fails the job up front instead of on every photo.
+// `/api/show` can only guess on servers that predate `capabilities`; this asks the model itself.
+// Errors other than "images unsupported" are left for the job to report.
+pub async fn probe_vision(
+    base_url: &str,
+    auth: &OllamaAuth,
+    model: &str,
+    timeout_secs: u64,
+) -> Result<()> {
+    let base_url = base_url.trim_end_matches('/');
+    let key = (base_url.to_string(), model.to_string());
+    if let Some(supported) = VISION_PROBES.lock().get(&key).copied() {
+        return if supported {
+            Ok(())
+        } else {
+            Err(images_unsupported(model, "cached probe result"))
+        };
+    }
+    let body = json!({
+      "model": model,
+      "stream": false,
+      "messages": [{
+        "role": "user",
+        "content": "Reply with OK.",
+        "images": [probe_image_base64()?]
+      }],
+      "options": { "num_predict": 1 }
+    });
+    let mut req = auth
+        .client()?
+        .post(format!("{}/api/chat", base_url))
+        .json(&body);
+    if let Some(t) = request_timeout(timeout_secs) {
+        req = req.timeout(t);
+    }
+    let Ok(resp) = req.send().await else {
+        return Ok(());
+    };
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    let supported = if status.is_success() {
+        true
+    } else if is_images_unsupported(&text) {
+        false
+    } else {
+        return Ok(());
+    };
+    VISION_PROBES.lock().insert(key, supported);
+    if supported {
+        Ok(())
+    } else {
+        Err(images_unsupported(model, &text))
     }
+}
+
+fn is_images_unsupported(text: &str) -> bool {
+    let lowered = text.to_lowercase();
+    lowered.contains("does not support image") || lowered.contains("images are not supported")
+}
 
-    Err(anyhow!("ollama stream ended unexpectedly"))
+fn pull_error(model: &str, raw: &str) -> anyhow::Error {
+    let lowered = raw.to_lowercase();
+    if lowered.contains("manifest")
+        && (lowered.contains("not found") || lowered.contains("not exist"))
+    {
+        return anyhow!(
+            "model \"{}\" does not exist in the Ollama library; check the name and tag (e.g. qwen2.5vl:7b). raw: {}",
+            model,
+            raw
+        );
+    }
+    anyhow!("ollama pull failed ({}): {}", model, raw)
 }
 
-pub async fn test_connection(base_url: &str) -> Result<String> {
+// Streams `/api/pull`. No request timeout: layers can take minutes; `cancel` is the way out.
+// Every status line is forwarded, with byte counts on the download lines.
+pub async fn pull_model<F>(
+    base_url: &str,
+    auth: &OllamaAuth,
+    model: &str,
+    cancel: &CancellationToken,
+    mut on_progress: F,
+) -> Result<()>
+where
+    F: FnMut(&str, Option<u64>, Option<u64>),
+{
+    let model = model.trim();
+    if model.is_empty() {
+        return Err(anyhow!("ollama model is empty"));
+    }
+    let url = format!("{}/api/pull", base_url.trim_end_matches('/'));
+    let client = auth.client()?;
+    let body = json!({ "model": model, "stream": true });
+    let mut resp = tokio::select! {
+        _ = cancel.cancelled() => return Err(anyhow!("canceled")),
+        r = client.post(url).json(&body).send() => r?
+    };
+    let status = resp.status();
+    if is_auth_status(status) {
+        return Err(unauthorized(status));
+    }
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(pull_error(model, &text));
+    }
+
+    let mut buf = String::new();
+    loop {
+        let chunk = tokio::select! {
+            _ = cancel.cancelled() => return Err(anyhow!("canceled")),
+            c = resp.chunk() => c?
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        buf.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(pos) = buf.find('\n') {
+            let line = buf[..pos].trim().to_string();
+            buf.drain(..=pos);
+            if line.is_empty() {
+                continue;
+            }
+            let v: Value = match serde_json::from_str(&line) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            if let Some(err) = v.get("error").and_then(|v| v.as_str()) {
+                return Err(pull_error(model, err));
+            }
+            let status = v.get("status").and_then(|v| v.as_str()).unwrap_or("");
+            on_progress(
+                status,
+                v.get("completed").and_then(|v| v.as_u64()),
+                v.get("total").and_then(|v| v.as_u64()),
+            );
+            if status == "success" {
+                return Ok(());
+            }
+        }
+    }
+
+    Err(anyhow!("ollama pull stream ended unexpectedly"))
+}
+
+pub async fn test_connection(base_url: &str, auth: &OllamaAuth) -> Result<String> {
     let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
-    let client = Client::builder().timeout(Duration::from_secs(5)).build()?;
-    let resp = client.get(url).send().await?;
+    let client = auth.client()?;
+    let resp = client
+        .get(url)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await?;
     if resp.status().is_success() {
         Ok("연결 성공".to_string())
     } else {
         let status = resp.status();
         let text = resp.text().await.unwrap_or_default();
-        Err(anyhow!("ollama error {}: {}", status, text))
+        Err(status_error(status, &text))
     }
 }
 
-pub async fn list_models(base_url: &str) -> Result<Vec<String>> {
+pub async fn list_models(base_url: &str, auth: &OllamaAuth) -> Result<Vec<String>> {
     let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
-    let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
-    let resp = client.get(url).send().await?;
+    let client = auth.client()?;
+    let resp = client
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?;
     let status = resp.status();
     let text = resp.text().await?;
     if !status.is_success() {
-        return Err(anyhow!("ollama error {}: {}", status, text));
+        return Err(status_error(status, &text));
     }
     let json: Value = serde_json::from_str(&text)?;
     let models = json
@@ -614,3 +1633,606 @@ pub async fn list_models(base_url: &str) -> Result<Vec<String>> {
     names.dedup();
     Ok(names)
 }
+
+pub async fn list_models_detailed(
+    base_url: &str,
+    auth: &OllamaAuth,
+) -> Result<Vec<OllamaModelInfo>> {
+    let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
+    let client = auth.client()?;
+    let resp = client
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?;
+    let status = resp.status();
+    let text = resp.text().await?;
+    if !status.is_success() {
+        return Err(status_error(status, &text));
+    }
+    let json: Value = serde_json::from_str(&text)?;
+    parse_model_infos(&json)
+}
+
+fn parse_model_infos(json: &Value) -> Result<Vec<OllamaModelInfo>> {
+    let models = json
+        .get("models")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow!("missing models field"))?;
+
+    let mut infos = models
+        .iter()
+        .filter_map(|m| {
+            let name = m
+                .get("name")
+                .and_then(|v| v.as_str())
+                .or_else(|| m.get("model").and_then(|v| v.as_str()))?
+                .to_string();
+            let details = m.get("details");
+            let detail_str = |key: &str| {
+                details
+                    .and_then(|d| d.get(key))
+                    .and_then(|v| v.as_str())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+            };
+            let family = detail_str("family");
+            let families = details
+                .and_then(|d| d.get("families"))
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            let likely_vision = is_likely_vision(&name, family.as_deref(), &families);
+            Some(OllamaModelInfo {
+                size: m.get("size").and_then(|v| v.as_u64()).unwrap_or(0),
+                family,
+                parameter_size: detail_str("parameter_size"),
+                quantization: detail_str("quantization_level"),
+                likely_vision,
+                name,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    infos.sort_by(|a, b| a.name.cmp(&b.name));
+    infos.dedup_by(|a, b| a.name == b.name);
+    Ok(infos)
+}
+
+pub(crate) fn is_likely_vision(name: &str, family: Option<&str>, families: &[String]) -> bool {
+    // `/api/tags` has no capability flag, so go by the projector family (clip/mllama) and
+    // well-known vision model names.
+    const VISION_HINTS: &[&str] = &[
+        "llava",
+        "bakllava",
+        "qwen2.5vl",
+        "qwen2.5-vl",
+        "qwen2-vl",
+        "qwen2vl",
+        "llama3.2-vision",
+        "mllama",
+        "minicpm-v",
+        "moondream",
+        "gemma3",
+        "granite3.2-vision",
+        "vision",
+    ];
+    let name = name.to_lowercase();
+    let family = family.unwrap_or_default().to_lowercase();
+    families
+        .iter()
+        .any(|f| matches!(f.to_lowercase().as_str(), "clip" | "mllama"))
+        || VISION_HINTS
+            .iter()
+            .any(|h| name.contains(h) || family.contains(h))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Reads one HTTP request, head and body, off the connection.
+    fn read_request(stream: &mut TcpStream) -> String {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = stream.read(&mut buf).unwrap_or(0);
+            if n == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&data);
+            if let Some(head_end) = text.find("\r\n\r\n") {
+                let body_len = text[..head_end]
+                    .lines()
+                    .find_map(|l| {
+                        l.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                    })
+                    .unwrap_or(0);
+                if data.len() >= head_end + 4 + body_len {
+                    break;
+                }
+            }
+        }
+        String::from_utf8_lossy(&data).into_owned()
+    }
+
+    // A local stand-in for the Ollama server. `respond` gets each request and writes the whole
+    // response; the connection closes after it. Returns the base URL.
+    pub(crate) fn mock_server(
+        mut respond: impl FnMut(&str, &mut TcpStream) + Send + 'static,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let request = read_request(&mut stream);
+                respond(&request, &mut stream);
+            }
+        });
+        url
+    }
+
+    pub(crate) fn json_response(stream: &mut TcpStream, body: &Value) {
+        let body = body.to_string();
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+    }
+
+    // The JSON body of a captured request.
+    pub(crate) fn request_body(request: &str) -> Value {
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    // Streams `first` and then `filler` as chat deltas until the client hangs up.
+    fn runaway_stream(first: &'static str, filler: &'static str) -> String {
+        mock_server(move |_, stream| {
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n",
+            );
+            let delta = |content: &str| {
+                format!(
+                    "{}\n",
+                    json!({"message": {"content": content}, "done": false})
+                )
+            };
+            let _ = stream.write_all(delta(first).as_bytes());
+            let filler = delta(&filler.repeat(512));
+            for _ in 0..10_000 {
+                if stream.write_all(filler.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    fn capped_chat(max_response_bytes: usize) -> ChatOptions {
+        ChatOptions {
+            timeout_secs: 10,
+            max_response_bytes,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn oversized_stream_stops_at_the_cap() {
+        let url = runaway_stream("", "a");
+        let mut streamed = 0;
+        let err = classify_image_streaming_with_options(
+            &url,
+            "llava",
+            false,
+            &capped_chat(4096),
+            "",
+            &CancellationToken::new(),
+            |delta| streamed += delta.len(),
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("response too large (> 4096 bytes)"), "{err}");
+        assert!(streamed <= 4096, "read {streamed} bytes past the cap");
+    }
+
+    #[tokio::test]
+    async fn oversized_stream_keeps_output_parsed_before_the_cap() {
+        let url = runaway_stream(r#"{"category": "people", "scores": {"people": 1}}"#, " ");
+        let (out, log) = classify_image_streaming_with_options(
+            &url,
+            "llava",
+            false,
+            &capped_chat(4096),
+            "",
+            &CancellationToken::new(),
+            |_| {},
+        )
+        .await
+        .unwrap();
+        assert_eq!(out.category, CategoryKey::People);
+        assert!(log.contains("truncated_at_bytes: 4096"), "{log}");
+    }
+
+    fn scores_obj(value: Value) -> serde_json::Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    // All eight keys, in `CATEGORY_KEYS` order.
+    fn full(values: [f64; 8]) -> serde_json::Map<String, Value> {
+        CATEGORY_KEYS
+            .iter()
+            .zip(values)
+            .map(|(k, v)| (k.as_str().to_string(), json!(v)))
+            .collect()
+    }
+
+    fn assert_sums_to_one(scores: &Scores) {
+        let sum: f32 = scores.to_map().values().sum();
+        assert!((sum - 1.0).abs() < 1e-5, "sum was {}", sum);
+    }
+
+    #[test]
+    fn well_formed_scores_need_no_repair() {
+        let mut repairs = Vec::new();
+        let scores = repair_scores(
+            &full([0.0, 0.8, 0.1, 0.1, 0.0, 0.0, 0.0, 0.0]),
+            Some(CategoryKey::People),
+            &mut repairs,
+        );
+        assert!(repairs.is_empty(), "{:?}", repairs);
+        assert_eq!(scores.top().0, CategoryKey::People);
+    }
+
+    #[test]
+    fn non_numeric_scores_are_zeroed() {
+        let mut obj = full([0.0, 0.0, 0.9, 0.1, 0.0, 0.0, 0.0, 0.0]);
+        obj.insert("people".into(), json!("NaN"));
+        obj.insert("other".into(), Value::Null);
+        let mut repairs = Vec::new();
+        let scores = repair_scores(&obj, Some(CategoryKey::FoodCafe), &mut repairs);
+        assert_eq!(repairs, ["not a finite number: people, other"]);
+        assert_eq!(scores.people, 0.0);
+        assert_eq!(scores.other, 0.0);
+        assert_sums_to_one(&scores);
+    }
+
+    #[test]
+    fn missing_keys_are_zeroed() {
+        let obj = scores_obj(json!({"people": 0.7, "pets_animals": 0.3}));
+        let mut repairs = Vec::new();
+        let scores = repair_scores(&obj, Some(CategoryKey::People), &mut repairs);
+        assert_eq!(
+            repairs,
+            [
+                "missing: screenshot_document, food_cafe, nature_landscape, city_street_travel, \
+              products_objects, other"
+            ]
+        );
+        assert!((scores.people - 0.7).abs() < 1e-6);
+        assert!((scores.pets_animals - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn out_of_range_scores_are_clamped() {
+        let mut repairs = Vec::new();
+        let scores = repair_scores(
+            &full([-0.5, 1.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            Some(CategoryKey::People),
+            &mut repairs,
+        );
+        assert_eq!(repairs, ["clamped to [0, 1]: screenshot_document, people"]);
+        assert_eq!(scores.screenshot_document, 0.0);
+        assert_eq!(scores.people, 1.0);
+    }
+
+    #[test]
+    fn scores_far_from_one_are_renormalized() {
+        let mut repairs = Vec::new();
+        let scores = repair_scores(
+            &full([0.9, 0.9, 0.9, 0.9, 0.9, 0.9, 0.9, 0.9]),
+            Some(CategoryKey::Other),
+            &mut repairs,
+        );
+        assert_eq!(repairs, ["renormalized: sum was 7.200"]);
+        assert_sums_to_one(&scores);
+        assert!((scores.other - 0.125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn all_zero_scores_fall_back_to_category_or_uniform() {
+        let zeros = full([0.0; 8]);
+        let mut repairs = Vec::new();
+        let scores = repair_scores(&zeros, Some(CategoryKey::NatureLandscape), &mut repairs);
+        assert_eq!(
+            repairs,
+            ["all scores zero: one-hot from category nature_landscape"]
+        );
+        assert_eq!(scores.nature_landscape, 1.0);
+
+        let mut repairs = Vec::new();
+        let scores = repair_scores(&zeros, None, &mut repairs);
+        assert_eq!(repairs, ["all scores zero: uniform"]);
+        assert!(CATEGORY_KEYS
+            .iter()
+            .all(|k| (scores.get(*k) - 0.125).abs() < 1e-6));
+    }
+
+    #[test]
+    fn category_disagreeing_with_argmax_is_blended_in() {
+        let mut repairs = Vec::new();
+        let scores = repair_scores(
+            &full([0.0, 0.9, 0.0, 0.0, 0.0, 0.1, 0.0, 0.0]),
+            Some(CategoryKey::PetsAnimals),
+            &mut repairs,
+        );
+        assert_eq!(repairs.len(), 1);
+        assert!(
+            repairs[0].starts_with("category pets_animals disagrees with argmax people"),
+            "{}",
+            repairs[0]
+        );
+        assert_eq!(scores.top().0, CategoryKey::PetsAnimals);
+        assert!((scores.pets_animals - 0.55).abs() < 1e-6);
+        assert!((scores.people - 0.45).abs() < 1e-6);
+        assert_sums_to_one(&scores);
+    }
+
+    #[test]
+    fn small_disagreement_keeps_the_scores() {
+        let mut repairs = Vec::new();
+        let scores = repair_scores(
+            &full([0.0, 0.5, 0.0, 0.0, 0.0, 0.4, 0.0, 0.1]),
+            Some(CategoryKey::PetsAnimals),
+            &mut repairs,
+        );
+        assert!(repairs.is_empty(), "{:?}", repairs);
+        assert!((scores.people - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn degenerate_output_lands_in_fallback_category() {
+        let chat = ChatOptions {
+            fallback_category: CategoryKey::PetsAnimals,
+            ..Default::default()
+        };
+        let uniform = r#"{"category": "unsure", "scores": {"screenshot_document": 0.5,
+            "people": 0.5, "food_cafe": 0.5, "nature_landscape": 0.5, "city_street_travel": 0.5,
+            "pets_animals": 0.5, "products_objects": 0.5, "other": 0.5}}"#;
+        let out = parse_model_out(uniform, &chat).unwrap();
+        assert_eq!(out.category, CategoryKey::PetsAnimals);
+        assert!(out
+            .score_repairs
+            .contains(&"no clear category: fallback pets_animals".to_string()));
+
+        let zeros = r#"{"scores": {"people": 0, "other": 0}}"#;
+        let out = parse_model_out(zeros, &chat).unwrap();
+        assert_eq!(out.category, CategoryKey::PetsAnimals);
+
+        let unknown_only = r#"{"category": "vehicles"}"#;
+        let out = parse_model_out(unknown_only, &chat).unwrap();
+        assert_eq!(out.category, CategoryKey::PetsAnimals);
+        assert_eq!(out.scores.pets_animals, 1.0);
+    }
+
+    #[tokio::test]
+    async fn prompt_overrides_are_sent_with_the_schema() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let url = mock_server(move |request, stream| {
+            tx.send(request_body(request)).unwrap();
+            let content = r#"{"category": "people", "scores": {"people": 1}}"#;
+            json_response(
+                stream,
+                &json!({"message": {"content": content}, "done": true}),
+            );
+        });
+        let chat = ChatOptions {
+            system_template: Some("Answer with one JSON object shaped like {schema}.".into()),
+            user_template: Some("Few-shot: a selfie is people. Pick one of {categories}.".into()),
+            timeout_secs: 10,
+            ..Default::default()
+        };
+        let (out, _) = classify_image_with_options(
+            &url,
+            "llava",
+            false,
+            &chat,
+            "aW1n",
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(out.category, CategoryKey::People);
+
+        let body = rx.recv().unwrap();
+        let system = body["messages"][0]["content"].as_str().unwrap();
+        let user = body["messages"][1]["content"].as_str().unwrap();
+        assert!(
+            system.starts_with("Answer with one JSON object shaped like {"),
+            "{system}"
+        );
+        assert!(!system.contains("{schema}"));
+        assert!(
+            user.starts_with("Few-shot: a selfie is people. Pick one of "),
+            "{user}"
+        );
+        assert!(user.contains("people|"), "{user}");
+        assert_eq!(body["messages"][1]["images"][0], "aW1n");
+        // The override only changes the wording; the response format is still enforced.
+        assert_eq!(body["format"], *JSON_SCHEMA);
+    }
+
+    #[test]
+    fn english_caption_survives_with_sanitizing_off() {
+        let content = r#"{"category": "pets_animals", "scores": {"pets_animals": 1.0},
+            "tags": ["dog", "beach"], "caption": "A dog running on the beach.",
+            "text_in_image": "SALE 50%"}"#;
+        let raw = ChatOptions::from_settings(&Settings {
+            output_language: OutputLanguage::Ko,
+            sanitize_output: false,
+            ..Settings::default()
+        });
+        let out = parse_model_out(content, &raw).unwrap();
+        assert_eq!(out.caption, "A dog running on the beach.");
+        assert_eq!(out.tags, ["dog", "beach"]);
+        assert_eq!(out.text_in_image, "SALE 50%");
+
+        // The Korean sanitizer would have dropped all of it.
+        let sanitized = ChatOptions::from_settings(&Settings {
+            output_language: OutputLanguage::Ko,
+            ..Settings::default()
+        });
+        let out = parse_model_out(content, &sanitized).unwrap();
+        assert_ne!(out.caption, "A dog running on the beach.");
+        assert_eq!(out.text_in_image, "50");
+    }
+
+    #[tokio::test]
+    async fn detailed_models_are_parsed_from_api_tags() {
+        // Shape of a real `/api/tags` response, trimmed to the fields we read.
+        let url = mock_server(|request, stream| {
+            assert!(request.starts_with("GET /api/tags "));
+            json_response(
+                stream,
+                &serde_json::json!({"models": [
+                    {
+                        "name": "llava:7b",
+                        "model": "llava:7b",
+                        "size": 4733363377u64,
+                        "details": {
+                            "format": "gguf",
+                            "family": "llama",
+                            "families": ["llama", "clip"],
+                            "parameter_size": "7B",
+                            "quantization_level": "Q4_0"
+                        }
+                    },
+                    {
+                        "name": "llama3:8b",
+                        "model": "llama3:8b",
+                        "size": 4661224676u64,
+                        "details": {
+                            "family": "llama",
+                            "families": ["llama"],
+                            "parameter_size": "8.0B",
+                            "quantization_level": "Q4_0"
+                        }
+                    },
+                    {"model": "moondream:latest", "details": {"family": "", "families": null}}
+                ]}),
+            );
+        });
+        let models = list_models_detailed(&url, &OllamaAuth::default())
+            .await
+            .unwrap();
+
+        let names: Vec<_> = models.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["llama3:8b", "llava:7b", "moondream:latest"]);
+        let llava = &models[1];
+        assert_eq!(llava.size, 4733363377);
+        assert_eq!(llava.family.as_deref(), Some("llama"));
+        assert_eq!(llava.parameter_size.as_deref(), Some("7B"));
+        assert_eq!(llava.quantization.as_deref(), Some("Q4_0"));
+        assert!(llava.likely_vision);
+        assert!(!models[0].likely_vision);
+        let moondream = &models[2];
+        assert_eq!((moondream.size, moondream.family.as_deref()), (0, None));
+        assert!(moondream.likely_vision);
+    }
+
+    #[tokio::test]
+    async fn probe_rejects_a_text_only_model_and_caches_the_answer() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let seen = requests.clone();
+        let url = mock_server(move |request, stream| {
+            seen.fetch_add(1, Ordering::SeqCst);
+            let body = request_body(request);
+            assert_eq!(body["messages"][0]["images"].as_array().unwrap().len(), 1);
+            if body["model"] == "probe-text-only" {
+                let error = r#"{"error":"model probe-text-only does not support images"}"#;
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 500 Internal Server Error\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    error.len(),
+                    error
+                );
+            } else {
+                json_response(stream, &serde_json::json!({"message": {"content": "OK"}}));
+            }
+        });
+        let auth = OllamaAuth::default();
+
+        let err = probe_vision(&url, &auth, "probe-text-only", 10)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("does not support images"), "{}", err);
+        assert!(err.contains("probe-text-only"), "{}", err);
+        // Answered from the cache; the server isn't asked again.
+        let cached = probe_vision(&url, &auth, "probe-text-only", 10).await;
+        assert!(cached.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        probe_vision(&url, &auth, "probe-llava", 10).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn korean_tags_are_trimmed_deduped_and_capped() {
+        let tags = [
+            " 강아지 ",
+            "강아지!",
+            "강아지  사진",
+            "강아지 사진",
+            "“산책”",
+            "바닷가 풍경",
+            "아주 아주 긴 설명이 붙은 태그입니다",
+            "...",
+            "고양이",
+            "공원",
+            "해변",
+        ]
+        .map(String::from)
+        .to_vec();
+        let fallback = OutputLanguage::Ko.fallback_tag();
+
+        let out = normalize_tags(tags, 5, 10, fallback);
+        assert_eq!(
+            out,
+            ["강아지", "강아지 사진", "산책", "바닷가 풍경", "고양이"]
+        );
+    }
+
+    #[test]
+    fn tags_emptied_by_the_korean_sanitizer_fall_back() {
+        // Hanja and Latin are stripped, leaving nothing but punctuation or spaces.
+        let content = r#"{"category": "pets_animals", "scores": {"pets_animals": 1.0},
+            "tags": ["犬", "dog!", " - ", "강아지", "강아지 "]}"#;
+        let out = parse_model_out(content, &ChatOptions::default()).unwrap();
+        let fallback = OutputLanguage::Ko.fallback_tag();
+        assert_eq!(normalize_tags(out.tags, 8, 20, fallback), ["강아지"]);
+
+        let nothing_left = r#"{"category": "other", "scores": {"other": 1.0},
+            "tags": ["犬", "cat", "!!"]}"#;
+        let out = parse_model_out(nothing_left, &ChatOptions::default()).unwrap();
+        assert_eq!(normalize_tags(out.tags, 8, 20, fallback), [fallback]);
+        assert_eq!(
+            normalize_tags(vec!["  ".to_string()], 8, 20, fallback),
+            [fallback]
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use crate::core::model::{ModelOut, OllamaModelInfo};
+use crate::core::ollama::{
+    chat_prompts, content_log, is_auth_status, is_likely_vision, note_capture, parse_model_out,
+    request_timeout, strip_code_fences, with_idle_timeout, ChatOptions, OllamaAuth,
+};
+use anyhow::{anyhow, Result};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+// Same `(ModelOut, log)` contract as `ollama::classify_image_*`, spoken over the OpenAI chat
+// completions schema (LM Studio, llama.cpp server, vLLM, ...). `think` and `keep_alive` have no
+// equivalent here and are ignored.
+
+const LOG_MAX_CHARS: usize = 20000;
+
+// LM Studio shows its base URL with the `/v1` suffix and llama.cpp without; accept both.
+fn api_url(base_url: &str, path: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    let base = base.strip_suffix("/v1").unwrap_or(base);
+    format!("{}/v1/{}", base, path)
+}
+
+fn chat_body(
+    model: &str,
+    chat: &ChatOptions,
+    base64_jpeg: &str,
+    stream: bool,
+    json_mode: bool,
+) -> Value {
+    let (system, user) = chat_prompts(chat);
+    let mut body = json!({
+      "model": model,
+      "stream": stream,
+      "messages": [
+        {"role": "system", "content": system},
+        {"role": "user", "content": [
+          {"type": "text", "text": user},
+          {"type": "image_url", "image_url": {"url": format!("data:image/jpeg;base64,{}", base64_jpeg)}}
+        ]}
+      ]
+    });
+    if let Some(obj) = body.as_object_mut() {
+        // Sampling options are top-level fields in this schema rather than a nested `options`.
+        if let Value::Object(options) = chat.request_options() {
+            for (k, v) in options {
+                obj.entry(k).or_insert(v);
+            }
+        }
+        if json_mode {
+            obj.insert(
+                "response_format".to_string(),
+                json!({"type": "json_object"}),
+            );
+        }
+    }
+    body
+}
+
+// Servers without JSON mode reject `response_format` with a 400/422; those get one retry without it.
+fn json_mode_rejected(status: StatusCode) -> bool {
+    status.is_client_error() && !is_auth_status(status) && status != StatusCode::NOT_FOUND
+}
+
+fn status_error(model: &str, status: StatusCode, text: &str) -> anyhow::Error {
+    if is_auth_status(status) {
+        return anyhow!(
+            "openai-compatible server rejected the request ({}). Check the API key / extra headers in the Ollama settings",
+            status
+        );
+    }
+    if status == StatusCode::NOT_FOUND && text.to_lowercase().contains("model") {
+        return anyhow!(
+            "model not found on the openai-compatible server ({}). Load it on the server then retry. raw: {}",
+            model,
+            text
+        );
+    }
+    anyhow!("openai-compatible server error {}: {}", status, text)
+}
+
+fn truncate(s: &str) -> String {
+    if s.len() <= LOG_MAX_CHARS {
+        return s.to_string();
+    }
+    let mut out = s.chars().take(LOG_MAX_CHARS).collect::<String>();
+    out.push_str("\n…(truncated)…");
+    out
+}
+
+fn request_log(url: &str, model: &str, chat: &ChatOptions, json_mode: bool) -> String {
+    format!(
+        "url: {}\nbackend: openai_compatible\nmodel: {}\noptions: {}\njson_mode: {}\n{}",
+        url,
+        model,
+        chat.request_options(),
+        json_mode,
+        chat.auth.describe()
+    )
+}
+
+async fn send(
+    client: &Client,
+    url: &str,
+    body: &Value,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<reqwest::Response> {
+    tokio::select! {
+        _ = cancel.cancelled() => Err(anyhow!("canceled")),
+        r = with_idle_timeout(timeout, client.post(url).json(body).send()) => r
+    }
+}
+
+async fn read_text(
+    resp: reqwest::Response,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<String> {
+    tokio::select! {
+        _ = cancel.cancelled() => Err(anyhow!("canceled")),
+        t = with_idle_timeout(timeout, resp.text()) => t
+    }
+}
+
+// Sends with JSON mode first and falls back to a plain request; returns the successful response and
+// whether JSON mode was used.
+async fn send_chat(
+    client: &Client,
+    url: &str,
+    model: &str,
+    chat: &ChatOptions,
+    base64_jpeg: &str,
+    stream: bool,
+    cancel: &CancellationToken,
+) -> Result<(reqwest::Response, bool)> {
+    let timeout = request_timeout(chat.timeout_secs);
+    let body = chat_body(model, chat, base64_jpeg, stream, true);
+    let resp = send(client, url, &body, timeout, cancel).await?;
+    if resp.status().is_success() {
+        return Ok((resp, true));
+    }
+    let status = resp.status();
+    if !json_mode_rejected(status) {
+        let text = read_text(resp, timeout, cancel).await?;
+        return Err(status_error(model, status, &text));
+    }
+
+    let body = chat_body(model, chat, base64_jpeg, stream, false);
+    let resp = send(client, url, &body, timeout, cancel).await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = read_text(resp, timeout, cancel).await?;
+        return Err(status_error(model, status, &text));
+    }
+    Ok((resp, false))
+}
+
+pub async fn classify_image(
+    base_url: &str,
+    model: &str,
+    chat: &ChatOptions,
+    base64_jpeg: &str,
+    cancel: &CancellationToken,
+) -> Result<(ModelOut, String)> {
+    let auth = &chat.auth;
+    classify_image_inner(base_url, model, chat, base64_jpeg, cancel)
+        .await
+        .map(|(out, log)| (out, auth.redact(&log)))
+        .map_err(|e| auth.redact_error(e))
+}
+
+async fn classify_image_inner(
+    base_url: &str,
+    model: &str,
+    chat: &ChatOptions,
+    base64_jpeg: &str,
+    cancel: &CancellationToken,
+) -> Result<(ModelOut, String)> {
+    if model.trim().is_empty() {
+        return Err(anyhow!("ollama model is empty"));
+    }
+    let url = api_url(base_url, "chat/completions");
+    // Every wait is bounded by `with_idle_timeout`, so the request itself has no timeout.
+    let timeout = request_timeout(chat.timeout_secs);
+    let client = chat.auth.client()?;
+
+    let (resp, json_mode) =
+        send_chat(&client, &url, model, chat, base64_jpeg, false, cancel).await?;
+    let text = read_text(resp, timeout, cancel).await?;
+
+    let captured = chat.capture_raw(&text);
+    let outer: Value =
+        serde_json::from_str(&text).map_err(|e| note_capture(e.into(), &captured))?;
+    let content = outer
+        .pointer("/choices/0/message/content")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("missing choices[0].message.content"))?;
+    let out = parse_model_out(content, chat)
+        .or_else(|_| parse_model_out(strip_code_fences(content.trim()), chat))
+        .map_err(|e| note_capture(e, &captured))?;
+    let log = format!(
+        "{}\n{}",
+        request_log(&url, model, chat, json_mode),
+        content_log(&captured, "message.content", || truncate(content))
+    );
+    Ok((out, log))
+}
+
+pub async fn classify_image_streaming<F>(
+    base_url: &str,
+    model: &str,
+    chat: &ChatOptions,
+    base64_jpeg: &str,
+    cancel: &CancellationToken,
+    on_delta: F,
+) -> Result<(ModelOut, String)>
+where
+    F: FnMut(&str) + Send,
+{
+    let auth = &chat.auth;
+    classify_image_streaming_inner(base_url, model, chat, base64_jpeg, cancel, on_delta)
+        .await
+        .map(|(out, log)| (out, auth.redact(&log)))
+        .map_err(|e| auth.redact_error(e))
+}
+
+async fn classify_image_streaming_inner<F>(
+    base_url: &str,
+    model: &str,
+    chat: &ChatOptions,
+    base64_jpeg: &str,
+    cancel: &CancellationToken,
+    mut on_delta: F,
+) -> Result<(ModelOut, String)>
+where
+    F: FnMut(&str) + Send,
+{
+    if model.trim().is_empty() {
+        return Err(anyhow!("ollama model is empty"));
+    }
+    let url = api_url(base_url, "chat/completions");
+    // As with Ollama streaming, the timeout is per wait rather than for the whole stream.
+    let timeout = request_timeout(chat.timeout_secs);
+    let client = chat.auth.client()?;
+
+    let (mut resp, json_mode) =
+        send_chat(&client, &url, model, chat, base64_jpeg, true, cancel).await?;
+
+    // SSE: `data: {chunk}` lines, terminated by `data: [DONE]` (some servers just close).
+    let mut buf = String::new();
+    let mut accumulated = String::new();
+    let mut raw_chunks = chat.raw_chunks();
+    let max_response_bytes = chat.max_response_bytes.max(1);
+    let mut truncated = false;
+    'read: loop {
+        let next = tokio::select! {
+            _ = cancel.cancelled() => return Err(anyhow!("canceled")),
+            c = with_idle_timeout(timeout, resp.chunk()) => c?
+        };
+        let Some(chunk) = next else { break };
+        buf.push_str(&String::from_utf8_lossy(&chunk));
+        if accumulated.len() + buf.len() > max_response_bytes {
+            truncated = true;
+            break;
+        }
+        while let Some(pos) = buf.find('\n') {
+            let line = buf[..pos].trim().to_string();
+            buf.drain(..=pos);
+            let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+            if data == "[DONE]" {
+                break 'read;
+            }
+            let v: Value = match serde_json::from_str(data) {
+                Ok(v) => v,
+                Err(_) => {
+                    if let Some(raw) = raw_chunks.as_mut() {
+                        raw.push(Value::String(data.to_string()));
+                    }
+                    continue;
+                }
+            };
+            if let Some(raw) = raw_chunks.as_mut() {
+                raw.push(v.clone());
+            }
+            if let Some(message) = v.pointer("/error/message").and_then(|v| v.as_str()) {
+                return Err(anyhow!("openai-compatible server error: {}", message));
+            }
+            let delta = v
+                .pointer("/choices/0/delta/content")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            if !delta.is_empty() {
+                accumulated.push_str(delta);
+                on_delta(delta);
+            }
+        }
+    }
+    // Dropping the response closes the stream when we stopped early.
+    drop(resp);
+
+    let captured = chat.capture_chunks(&raw_chunks);
+    if accumulated.trim().is_empty() {
+        return Err(note_capture(
+            anyhow!("openai-compatible stream ended without content"),
+            &captured,
+        ));
+    }
+    let out = parse_model_out(accumulated.trim(), chat)
+        .or_else(|_| parse_model_out(strip_code_fences(accumulated.trim()), chat))
+        .map_err(|e| {
+            let e = if truncated {
+                anyhow!(
+                    "response too large (> {} bytes) and the partial output could not be parsed: {}",
+                    max_response_bytes,
+                    e
+                )
+            } else {
+                e
+            };
+            note_capture(e, &captured)
+        })?;
+    let mut log = format!(
+        "{}\nstream: true\n",
+        request_log(&url, model, chat, json_mode)
+    );
+    if truncated {
+        log.push_str(&format!("truncated_at_bytes: {}\n", max_response_bytes));
+    }
+    log.push_str(&content_log(
+        &captured,
+        "message.content(accumulated)",
+        || truncate(&accumulated),
+    ));
+    Ok((out, log))
+}
+
+async fn get_models(base_url: &str, auth: &OllamaAuth, timeout: Duration) -> Result<Value> {
+    let url = api_url(base_url, "models");
+    let client = auth.client()?;
+    let resp = client.get(&url).timeout(timeout).send().await?;
+    let status = resp.status();
+    let text = resp.text().await?;
+    if status == StatusCode::NOT_FOUND {
+        return Err(anyhow!(
+            "{} not found; is this an OpenAI-compatible server?",
+            url
+        ));
+    }
+    if !status.is_success() {
+        return Err(status_error("", status, &text));
+    }
+    Ok(serde_json::from_str(&text)?)
+}
+
+fn model_ids(json: &Value) -> Result<Vec<String>> {
+    let data = json
+        .get("data")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow!("missing data field"))?;
+    let mut ids = data
+        .iter()
+        .filter_map(|m| m.get("id").and_then(|v| v.as_str()).map(str::to_string))
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+    Ok(ids)
+}
+
+pub async fn test_connection(base_url: &str, auth: &OllamaAuth) -> Result<String> {
+    get_models(base_url, auth, Duration::from_secs(5)).await?;
+    Ok("연결 성공".to_string())
+}
+
+pub async fn list_models(base_url: &str, auth: &OllamaAuth) -> Result<Vec<String>> {
+    model_ids(&get_models(base_url, auth, Duration::from_secs(10)).await?)
+}
+
+// `/v1/models` carries no size or capability details, so vision support is guessed from the name.
+pub async fn list_models_detailed(
+    base_url: &str,
+    auth: &OllamaAuth,
+) -> Result<Vec<OllamaModelInfo>> {
+    let ids = list_models(base_url, auth).await?;
+    Ok(ids
+        .into_iter()
+        .map(|name| OllamaModelInfo {
+            size: 0,
+            family: None,
+            parameter_size: None,
+            quantization: None,
+            likely_vision: is_likely_vision(&name, None, &[]),
+            name,
+        })
+        .collect())
+}
@@ -1,29 +1,69 @@
-use crate::core::classifier::{build_classifier, Classifier, ClassifyInput, OllamaClassifier};
-use crate::core::db::Db;
-use crate::core::decode::{decode_resize_base64_with_options, DecodeOptions};
-use crate::core::events::PROGRESS_EVENT;
-use crate::core::export::{copy_to_category, copy_to_category_nested};
+use crate::core::classifier::{
+    build_classifier, prepare_clip_input, ClassificationOutput, Classifier, ClassifyInput,
+    OllamaClassifier,
+};
+use crate::core::clip::preprocess::PreprocessOutput;
+use crate::core::db::{Db, FailedPhoto, PhotoFiles};
+use crate::core::decode::{
+    decode_resize_base64_with_options, read_exif, write_temp_image, DecodeOptions, EncodeFormat,
+    ResizeSpec,
+};
+use crate::core::error_log::append_error;
+use crate::core::events::{
+    JOB_QUEUE_EVENT, JOB_SUMMARY_EVENT, PHOTO_EVENT, PHOTO_UPDATED_EVENT, PROGRESS_EVENT,
+    SCAN_PROGRESS_EVENT,
+};
+use crate::core::export::{
+    copy_to_category_nested, ensure_free_space, folder_component, move_to_dir, sidecar_path,
+    write_sidecar,
+};
 use crate::core::model::{
-    AnalysisEngine, ExportStatus, JobStatus, PhotoDetail, Progress, Scores, Settings,
-    StartAnalysisInput,
+    AnalysisEngine, AnalysisImageFormat, CategoryKey, ExportStatus, FailureKind,
+    HybridCaptionScope, JobQueueEvent, JobStatus, JobSummary, JobSummaryFailure, JobSummaryPhoto,
+    OllamaBackend, PhotoDetail, PhotoEvent, PhotoRow, Progress, QueuedJobInfo, RecategorizeResult,
+    ScanProgressEvent, Scores, Settings, StartAnalysisInput, StartAnalysisResult, UndoExportReport,
+    DEFAULT_TOP_K, MAX_SUMMARY_FAILURES,
 };
-use crate::core::ollama::test_connection;
-use crate::core::scan::scan_sources;
+use crate::core::ollama::{ensure_vision_model, probe_vision, test_connection, OllamaAuth};
+use crate::core::openai;
+use crate::core::scan::{limit_files, scan_sources, ScanOptions, ScanProgress, ALLOWED_EXT};
+use crate::core::thumbnail::try_generate_thumbnail;
 use anyhow::{anyhow, Result};
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tauri::async_runtime;
-use tauri::{AppHandle, Emitter};
+use tauri::async_runtime::{self, JoinHandle};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc;
 use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
+// Cheap to clone: every field is shared, so a finished job's task can start the next queued one.
+#[derive(Clone)]
 pub struct Pipeline {
     pub current: Arc<Mutex<Option<ActiveJob>>>,
     pub latest: Arc<Mutex<Option<Progress>>>,
     pub last_job: Arc<Mutex<Option<JobMeta>>>,
+    queue: Arc<Mutex<VecDeque<QueuedJob>>>,
+    // The active job's task, awaited by `shutdown`.
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    shutting_down: Arc<AtomicBool>,
+    // Set while the CLIP engine reloads outside the pipeline lock; no job starts meanwhile.
+    reloading: Arc<AtomicBool>,
+}
+
+pub struct EngineReload(Arc<AtomicBool>);
+
+impl Drop for EngineReload {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
 }
 
 #[derive(Clone)]
@@ -32,18 +72,114 @@ pub struct ActiveJob {
     pub cancel: CancellationToken,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct JobMeta {
+    // Job that owns the rows the run wrote, which `undo_last_export` rolls back. A retry updates
+    // rows in place under their original job, so it records that job rather than its own id.
+    // None in `last_job.json` files written before ids were recorded.
+    #[serde(default)]
+    pub job_id: Option<String>,
     pub export_root: String,
     pub engine: AnalysisEngine,
+    // Value results went to the DB only, so the export root has no value folders to count.
+    #[serde(default)]
+    pub flat_value_export: bool,
+    // Written when the job ends (completed or canceled); None while it is still running.
+    #[serde(default)]
+    pub summary: Option<JobSummary>,
+}
+
+const LAST_JOB_FILE: &str = "last_job.json";
+
+// Photos listed as slowest in the job summary.
+const SUMMARY_SLOWEST: usize = 5;
+
+// Rows per DB transaction while a job runs.
+const DB_BATCH_ROWS: usize = 25;
+// Pending rows are flushed (and announced with `PHOTO_EVENT`) at most this often unless the batch
+// fills up first. A timer in `run_job` flushes leftovers, so slow engines don't hold a finished
+// row back until the next photo.
+const PHOTO_EVENT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+// Routine per-photo progress is emitted at most this often; status changes and errors go out
+// immediately.
+const PROGRESS_EVENT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+fn last_job_path(app: &AppHandle) -> Result<PathBuf> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("app data dir: {}", e))?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(LAST_JOB_FILE))
+}
+
+fn load_last_job(app: &AppHandle) -> Option<JobMeta> {
+    load_last_job_from(&last_job_path(app).ok()?)
+}
+
+pub(crate) fn load_last_job_from(path: &Path) -> Option<JobMeta> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_last_job(app: &AppHandle, meta: &JobMeta) -> Result<()> {
+    save_last_job_to(&last_job_path(app)?, meta)
+}
+
+pub(crate) fn save_last_job_to(path: &Path, meta: &JobMeta) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(meta)?)?;
+    Ok(())
 }
 
 impl Pipeline {
-    pub fn new() -> Self {
+    pub fn new(app: &AppHandle) -> Self {
         Self {
             current: Arc::new(Mutex::new(None)),
             latest: Arc::new(Mutex::new(None)),
-            last_job: Arc::new(Mutex::new(None)),
+            last_job: Arc::new(Mutex::new(load_last_job(app))),
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            task: Arc::new(Mutex::new(None)),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            reloading: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.current.lock().is_some()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    // Jobs refuse to start until the returned guard drops.
+    pub fn begin_engine_reload(&self) -> Result<EngineReload> {
+        if self.is_running() {
+            return Err(anyhow!("analysis job is running"));
+        }
+        if self.reloading.swap(true, Ordering::SeqCst) {
+            return Err(anyhow!("CLIP engine is already reloading"));
+        }
+        Ok(EngineReload(self.reloading.clone()))
+    }
+
+    // Called on app exit: drops the queue and cancels the active job. The returned future ends
+    // once the job has flushed its rows and been marked aborted, so it can be resumed on the next
+    // launch. No job starts after this.
+    pub fn shutdown(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        self.queue.lock().clear();
+        if let Some(active) = &*self.current.lock() {
+            active.cancel.cancel();
+        }
+        let task = self.task.lock().take();
+        async move {
+            if let Some(task) = task {
+                if let Err(e) = task.await {
+                    eprintln!("job task failed during shutdown: {}", e);
+                }
+            }
         }
     }
 
@@ -55,231 +191,970 @@ impl Pipeline {
         self.last_job.lock().clone()
     }
 
-    pub fn cancel(&mut self, job_id: &str) -> Result<()> {
+    pub fn last_job_summary(&self) -> Option<JobSummary> {
+        self.last_job.lock().as_ref()?.summary.clone()
+    }
+
+    // Canceling the active job lets the next queued one start unless `cancel_all` also empties
+    // the queue. A queued job id is simply removed.
+    pub fn cancel(&mut self, app: &AppHandle, job_id: &str, cancel_all: bool) -> Result<()> {
+        if cancel_all {
+            let mut queue = self.queue.lock();
+            queue.clear();
+            emit_queue(app, None, &queue);
+        }
         if let Some(active) = &*self.current.lock() {
             if active.id == job_id {
                 active.cancel.cancel();
                 return Ok(());
             }
         }
+        if cancel_all || self.remove_queued(app, job_id).is_ok() {
+            return Ok(());
+        }
         Err(anyhow!("no running job"))
     }
 
+    // Runs right away when idle; otherwise the job waits in the queue and starts when the ones
+    // ahead of it finish. `settings` already include the input's overrides and are stored with
+    // the job, so a resume runs with them too.
     pub fn start(
         &mut self,
         app: AppHandle,
         db: Arc<Mutex<Db>>,
         settings: Settings,
         input: StartAnalysisInput,
-    ) -> Result<String> {
-        if self.current.lock().is_some() {
-            return Err(anyhow!("job already running"));
+    ) -> Result<StartAnalysisResult> {
+        self.enqueue_or_launch(
+            app,
+            db,
+            QueuedJob {
+                id: Uuid::new_v4().to_string(),
+                input,
+                settings,
+                resuming: false,
+                retry: None,
+            },
+        )
+    }
+
+    // Runs a persisted job again with its original input and settings, skipping every source
+    // file that already has a row for it.
+    pub fn resume(
+        &mut self,
+        app: AppHandle,
+        db: Arc<Mutex<Db>>,
+        job_id: String,
+        input: StartAnalysisInput,
+        settings: Settings,
+    ) -> Result<StartAnalysisResult> {
+        self.enqueue_or_launch(
+            app,
+            db,
+            QueuedJob {
+                id: job_id,
+                input,
+                settings,
+                resuming: true,
+                retry: None,
+            },
+        )
+    }
+
+    // Failed rows are grouped by export root and original job, one queued job per group. Each
+    // photo keeps its row id and job id so a success replaces the error row in place.
+    pub fn retry_failed(
+        &mut self,
+        app: AppHandle,
+        db: Arc<Mutex<Db>>,
+        settings: Settings,
+        failed: Vec<FailedPhoto>,
+    ) -> Result<Vec<StartAnalysisResult>> {
+        if failed.is_empty() {
+            return Err(anyhow!("no failed photos to retry"));
         }
-        let job_id = Uuid::new_v4().to_string();
-        let job_id_for_state = job_id.clone();
-        let job_id_return = job_id.clone();
+        let mut groups: BTreeMap<(String, Option<String>), Vec<RetryPhoto>> = BTreeMap::new();
+        for photo in failed {
+            let retry = RetryPhoto {
+                id: photo.id,
+                source: PathBuf::from(photo.source_path),
+                job_id: photo.job_id.clone(),
+            };
+            groups
+                .entry((photo.export_root, photo.job_id))
+                .or_default()
+                .push(retry);
+        }
+        let mut started = Vec::new();
+        for ((export_root, _), photos) in groups {
+            let source_root = common_parent(photos.iter().map(|p| p.source.as_path()));
+            started.push(self.enqueue_or_launch(
+                app.clone(),
+                db.clone(),
+                QueuedJob {
+                    id: Uuid::new_v4().to_string(),
+                    input: StartAnalysisInput {
+                        source_root: source_root.to_string_lossy().to_string(),
+                        export_root,
+                        max_images: None,
+                        sample_random: false,
+                        sample_seed: None,
+                        settings_override: None,
+                    },
+                    settings: settings.clone(),
+                    resuming: false,
+                    retry: Some(photos),
+                },
+            )?);
+        }
+        Ok(started)
+    }
+
+    pub fn job_queue(&self) -> Vec<QueuedJobInfo> {
+        queue_info(&self.queue.lock())
+    }
+
+    pub fn remove_queued(&self, app: &AppHandle, job_id: &str) -> Result<()> {
+        let mut queue = self.queue.lock();
+        let before = queue.len();
+        queue.retain(|job| job.id != job_id);
+        if queue.len() == before {
+            return Err(anyhow!("job not in queue"));
+        }
+        emit_queue(app, None, &queue);
+        Ok(())
+    }
+
+    fn enqueue_or_launch(
+        &self,
+        app: AppHandle,
+        db: Arc<Mutex<Db>>,
+        job: QueuedJob,
+    ) -> Result<StartAnalysisResult> {
+        if self.is_shutting_down() {
+            return Err(anyhow!("app is shutting down"));
+        }
+        if self.reloading.load(Ordering::SeqCst) {
+            return Err(anyhow!("CLIP engine is reloading"));
+        }
+        if job.retry.is_none() && !Path::new(&job.input.source_root).exists() {
+            return Err(anyhow!("source path not found"));
+        }
+        let job_id = job.id.clone();
+        let cancel = {
+            let mut queue = self.queue.lock();
+            let mut current = self.current.lock();
+            let active = current.as_ref().map(|a| a.id.as_str());
+            if active == Some(job_id.as_str()) || queue.iter().any(|q| q.id == job_id) {
+                return Err(anyhow!("job already queued"));
+            }
+            if current.is_some() || !queue.is_empty() {
+                queue.push_back(job);
+                emit_queue(&app, None, &queue);
+                return Ok(StartAnalysisResult {
+                    job_id,
+                    queue_position: Some(queue.len()),
+                });
+            }
+            // Claimed under the queue lock so a job finishing meanwhile can't promote past this
+            // one; the launch itself runs after both locks are released.
+            claim(&mut current, &job_id)
+        };
+        if let Err(e) = self.launch(app.clone(), db.clone(), job, cancel) {
+            release(&self.current, &job_id);
+            // Jobs queued behind the claim would otherwise wait for a job that never ran.
+            self.start_next(app, db);
+            return Err(e);
+        }
+        Ok(StartAnalysisResult {
+            job_id,
+            queue_position: None,
+        })
+    }
+
+    // Called when a job ends. A queued job that fails to start (source gone, export root not
+    // writable) is reported as an errored job and the next one is tried. The queue lock is only
+    // held while the next job is picked.
+    fn start_next(&self, app: AppHandle, db: Arc<Mutex<Db>>) {
+        loop {
+            let (job, cancel) = {
+                let mut queue = self.queue.lock();
+                let mut current = self.current.lock();
+                if current.is_some() {
+                    return;
+                }
+                let Some(job) = queue.pop_front() else {
+                    return;
+                };
+                emit_queue(&app, Some(job.id.clone()), &queue);
+                let cancel = claim(&mut current, &job.id);
+                (job, cancel)
+            };
+            let job_id = job.id.clone();
+            match self.launch(app.clone(), db.clone(), job, cancel) {
+                Ok(()) => return,
+                Err(e) => {
+                    release(&self.current, &job_id);
+                    emit_job_error(&app, self.latest.clone(), &job_id);
+                    eprintln!("queued job {} failed to start: {}", job_id, e);
+                }
+            }
+        }
+    }
+
+    // Expects the job to be claimed as the active one already.
+    fn launch(
+        &self,
+        app: AppHandle,
+        db: Arc<Mutex<Db>>,
+        job: QueuedJob,
+        cancel: CancellationToken,
+    ) -> Result<()> {
+        if self.reloading.load(Ordering::SeqCst) {
+            return Err(anyhow!("CLIP engine is reloading"));
+        }
+        let QueuedJob {
+            id: job_id,
+            input,
+            settings,
+            resuming,
+            retry,
+        } = job;
+        let source_root = PathBuf::from(&input.source_root);
+        let export_root = PathBuf::from(&input.export_root);
+        let retrying = retry.is_some();
+        // Every photo of a retry comes from the same job (see `retry_failed`).
+        let rows_job_id = match &retry {
+            Some(photos) => photos.first().and_then(|p| p.job_id.clone()),
+            None => Some(job_id.clone()),
+        };
+        if !retrying && !source_root.exists() {
+            return Err(anyhow!("source path not found"));
+        }
+        fs::create_dir_all(&export_root)?;
+        // A retry isn't stored as a job: calling retry_failed again picks up whatever still
+        // fails.
+        if resuming {
+            db.lock().set_job_status(&job_id, &JobStatus::Running)?;
+        } else if !retrying {
+            db.lock().insert_job(&job_id, &input, &settings)?;
+        }
+
         {
             let mut guard = self.last_job.lock();
             *guard = Some(JobMeta {
+                job_id: rows_job_id,
                 export_root: input.export_root.clone(),
                 engine: settings.analysis_engine,
+                flat_value_export: !settings.value_as_nested_folders,
+                summary: None,
             });
         }
-        let cancel = CancellationToken::new();
-        let cancel_clone = cancel.clone();
         let latest = self.latest.clone();
-        let latest_clone = latest.clone();
         let handle_app = app.clone();
-        let handle_app_for_err = app.clone();
         let current_ref = self.current.clone();
-        async_runtime::spawn(async move {
-            if let Err(e) = run_job(
-                handle_app,
-                db,
-                settings,
-                input,
-                job_id.clone(),
-                cancel_clone,
-                latest_clone.clone(),
-                current_ref.clone(),
-            )
-            .await
-            {
-                let progress = Progress {
+        let last_job = self.last_job.clone();
+        let pipeline = self.clone();
+        let engine = settings.analysis_engine;
+        let task = async_runtime::spawn(async move {
+            let cleanup = JobCleanup {
+                current_ref,
+                job_id: job_id.clone(),
+            };
+            // The scan can take minutes on a network drive, so it runs here on the blocking pool
+            // rather than in `launch`, which is called under the pipeline lock. A failed scan or
+            // free-space check ends the job like any other error.
+            let scan = {
+                let (app, db, job_id) = (handle_app.clone(), db.clone(), job_id.clone());
+                let settings = settings.clone();
+                async_runtime::spawn_blocking(move || {
+                    collect_job_files(&app, &db, &job_id, &input, &settings, resuming, retry)
+                })
+            };
+            let mut stats = JobStats::new(0, 0);
+            let result = async {
+                let JobFiles {
+                    files,
+                    retried,
+                    missing,
+                    already_done,
+                } = scan.await??;
+                stats = JobStats::new(files.len() + already_done, already_done);
+                stats.missing = missing;
+                let ctx = JobContext {
+                    app: handle_app,
+                    db: db.clone(),
+                    settings,
+                    export_root,
                     job_id: job_id.clone(),
-                    status: JobStatus::Error,
-                    current_file: None,
-                    processed: 0,
-                    total: 0,
-                    errors: 1,
+                    cancel,
+                    latest: latest.clone(),
+                    last_job: last_job.clone(),
                 };
-                let _ = emit_progress(&handle_app_for_err, latest_clone.clone(), progress);
+                run_job(ctx, files, retried, already_done, &mut stats).await
+            }
+            .await;
+            drop(cleanup);
+            // Cut off by app exit: keep the job resumable instead of canceled or errored.
+            if pipeline.is_shutting_down() {
+                if let Err(e) = db.lock().set_job_status(&job_id, &JobStatus::Aborted) {
+                    eprintln!("failed to update job status: {}", e);
+                }
+                return;
+            }
+            if let Err(e) = result {
+                if let Err(db_err) = db.lock().set_job_status(&job_id, &JobStatus::Error) {
+                    eprintln!("failed to update job status: {}", db_err);
+                }
+                // Counts come from this job's stats; `latest` may already hold another job's.
+                let progress = stats.progress(&job_id, JobStatus::Error);
+                let _ = emit_progress(&app, latest, progress.clone());
                 eprintln!("pipeline error: {}", e);
+                let summary = stats.summary(&job_id, engine, &progress);
+                if let Err(e) = finish_job(&app, &db, &last_job, summary) {
+                    eprintln!("failed to record job summary: {}", e);
+                }
             }
+            pipeline.start_next(app, db);
         });
+        *self.task.lock() = Some(task);
 
-        *self.current.lock() = Some(ActiveJob {
-            id: job_id_for_state,
-            cancel,
-        });
-        Ok(job_id_return)
+        Ok(())
     }
 }
 
-async fn run_job(
-    app: AppHandle,
-    db: Arc<Mutex<Db>>,
-    settings: Settings,
+struct QueuedJob {
+    id: String,
     input: StartAnalysisInput,
-    job_id: String,
-    cancel: CancellationToken,
-    latest: Arc<Mutex<Option<Progress>>>,
-    current_ref: Arc<Mutex<Option<ActiveJob>>>,
-) -> Result<()> {
-    struct JobCleanup {
-        current_ref: Arc<Mutex<Option<ActiveJob>>>,
-        job_id: String,
+    settings: Settings,
+    resuming: bool,
+    // Set for retry_failed: these photos replace the folder scan.
+    retry: Option<Vec<RetryPhoto>>,
+}
+
+// A failed photo analyzed again under its existing row id. The row keeps its original job id;
+// the retry's own id is never stored as a job.
+struct RetryPhoto {
+    id: String,
+    source: PathBuf,
+    job_id: Option<String>,
+}
+
+// Marks `job_id` as the active job before it launches, so the job's cleanup can't run first and
+// leave a stale entry.
+fn claim(current: &mut Option<ActiveJob>, job_id: &str) -> CancellationToken {
+    let cancel = CancellationToken::new();
+    *current = Some(ActiveJob {
+        id: job_id.to_string(),
+        cancel: cancel.clone(),
+    });
+    cancel
+}
+
+fn release(current: &Mutex<Option<ActiveJob>>, job_id: &str) {
+    let mut guard = current.lock();
+    if guard.as_ref().is_some_and(|active| active.id == job_id) {
+        *guard = None;
     }
-    impl Drop for JobCleanup {
-        fn drop(&mut self) {
-            let mut guard = self.current_ref.lock();
-            if let Some(active) = guard.as_ref() {
-                if active.id == self.job_id {
-                    *guard = None;
-                }
-            }
-        }
+}
+
+// Clears the active job when its task ends, however it ends.
+struct JobCleanup {
+    current_ref: Arc<Mutex<Option<ActiveJob>>>,
+    job_id: String,
+}
+
+impl Drop for JobCleanup {
+    fn drop(&mut self) {
+        release(&self.current_ref, &self.job_id);
     }
-    let _cleanup = JobCleanup {
-        current_ref: current_ref.clone(),
-        job_id: job_id.clone(),
-    };
+}
 
+// What a job will analyze: the scanned (or retried) files minus those a resumed job already has
+// rows for.
+struct JobFiles {
+    files: Vec<PathBuf>,
+    retried: HashMap<PathBuf, RetryPhoto>,
+    missing: Vec<String>,
+    already_done: usize,
+}
+
+fn collect_job_files(
+    app: &AppHandle,
+    db: &Mutex<Db>,
+    job_id: &str,
+    input: &StartAnalysisInput,
+    settings: &Settings,
+    resuming: bool,
+    retry: Option<Vec<RetryPhoto>>,
+) -> Result<JobFiles> {
     let source_root = PathBuf::from(&input.source_root);
     let export_root = PathBuf::from(&input.export_root);
-    if !source_root.exists() {
-        return Err(anyhow!("source path not found"));
-    }
-    fs::create_dir_all(&export_root)?;
-    let files = scan_sources(&source_root)?;
-    let total = files.len();
-    let job_started = std::time::Instant::now();
-    let mut clip_vision_ms_total: u128 = 0;
-    let mut clip_vision_count: u64 = 0;
-    let requested_concurrency = settings.analysis_concurrency.max(1) as usize;
-    let effective_concurrency = if settings.ollama_stream {
-        1usize
+    let mut retried = HashMap::new();
+    let mut missing = Vec::new();
+    let mut files = if let Some(photos) = retry {
+        let mut files = Vec::new();
+        for photo in photos {
+            if photo.source.exists() {
+                files.push(photo.source.clone());
+                retried.insert(photo.source.clone(), photo);
+            } else {
+                missing.push(photo.source.to_string_lossy().to_string());
+            }
+        }
+        files
     } else {
-        requested_concurrency
+        // The total isn't known until the walk ends, which can take a while on network
+        // drives, so the running count is reported on its own event.
+        let mut report = |p: ScanProgress| {
+            let event = ScanProgressEvent {
+                job_id: job_id.to_string(),
+                scanned: p.scanned,
+                found: p.found,
+                done: p.done,
+            };
+            if let Err(e) = app.emit(SCAN_PROGRESS_EVENT, event) {
+                eprintln!("failed to emit scan progress: {}", e);
+            }
+        };
+        limit_files(
+            scan_sources(
+                &source_root,
+                &ScanOptions {
+                    exclude_globs: settings.scan_exclude_globs.clone(),
+                    max_depth: settings.scan_max_depth,
+                    exclude_dir: Some(export_root.clone()),
+                    sort: settings.scan_sort,
+                },
+                Some(&mut report),
+            )?,
+            input.max_images,
+            input.sample_random,
+            input.sample_seed,
+        )
     };
-    let mut progress = Progress {
-        job_id: job_id.clone(),
-        status: JobStatus::Running,
-        current_file: None,
-        processed: 0,
-        total,
-        errors: 0,
+    let done = if resuming {
+        db.lock().job_source_paths(job_id)?
+    } else {
+        HashSet::new()
     };
-    emit_progress(&app, latest.clone(), progress.clone())?;
+    let already_done = done.len();
+    if already_done > 0 {
+        files.retain(|p| !done.contains(p.to_string_lossy().as_ref()));
+        // An unseeded random sample draws a different set, so keep the original size.
+        if let Some(max) = input.max_images {
+            files.truncate(max.saturating_sub(already_done));
+        }
+    }
+    // Every export is a copy; fail up front instead of filling the disk halfway through.
+    ensure_free_space(&export_root, &files)?;
+    Ok(JobFiles {
+        files,
+        retried,
+        missing,
+        already_done,
+    })
+}
 
-    #[derive(Debug)]
-    enum TaskOutcome {
-        Finished {
-            path: PathBuf,
-            file_name: String,
-            duration_ms: i64,
-            result: Result<PhotoDetail>,
-        },
-        Canceled,
+// Deepest directory containing every path; only recorded as the retry job's source root.
+fn common_parent<'a>(paths: impl Iterator<Item = &'a Path>) -> PathBuf {
+    let mut common: Option<PathBuf> = None;
+    for path in paths {
+        let dir = path.parent().unwrap_or(path);
+        common = Some(match common {
+            None => dir.to_path_buf(),
+            Some(prev) => prev
+                .ancestors()
+                .find(|a| dir.starts_with(a))
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+        });
     }
+    common.unwrap_or_default()
+}
 
-    let mut join_set: JoinSet<TaskOutcome> = JoinSet::new();
-    let mut pending = files.into_iter();
-    let mut running: usize = 0;
+fn queue_info(queue: &VecDeque<QueuedJob>) -> Vec<QueuedJobInfo> {
+    queue
+        .iter()
+        .enumerate()
+        .map(|(i, job)| QueuedJobInfo {
+            job_id: job.id.clone(),
+            source_root: job.input.source_root.clone(),
+            export_root: job.input.export_root.clone(),
+            position: i + 1,
+        })
+        .collect()
+}
 
-    let spawn_next = |join_set: &mut JoinSet<TaskOutcome>,
-                          pending: &mut std::vec::IntoIter<PathBuf>,
-                          running: &mut usize,
-                          progress: &mut Progress|
-     -> Option<()> {
-        let path = pending.next()?;
-        let file_name = path
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("image")
-            .to_string();
-        let app = app.clone();
-        let job_id = job_id.clone();
-        let settings = settings.clone();
-        let export_root = export_root.clone();
-        let cancel = cancel.clone();
-        *running += 1;
-        progress.current_file = Some(format!("({}/{}) {}", *running, effective_concurrency, file_name));
-        join_set.spawn(async move {
-            let started = std::time::Instant::now();
-            let result = tokio::select! {
-                _ = cancel.cancelled() => {
-                    return TaskOutcome::Canceled;
-                }
-                res = process_one(&app, &job_id, &settings, &export_root, &path, &file_name, &cancel) => res,
-            };
-            let duration_ms = started.elapsed().as_millis() as i64;
-            TaskOutcome::Finished {
-                path,
-                file_name,
-                duration_ms,
-                result,
-            }
-        });
-        Some(())
+fn emit_queue(app: &AppHandle, promoted: Option<String>, queue: &VecDeque<QueuedJob>) {
+    let event = JobQueueEvent {
+        promoted,
+        queue: queue_info(queue),
     };
+    if let Err(e) = app.emit(JOB_QUEUE_EVENT, event) {
+        eprintln!("failed to emit job queue: {}", e);
+    }
+}
 
-    while running < effective_concurrency {
-        if spawn_next(&mut join_set, &mut pending, &mut running, &mut progress).is_none() {
-            break;
+// Counters kept as results come in, so a summary can be built however the job ends.
+struct JobStats {
+    started: std::time::Instant,
+    total: usize,
+    already_done: usize,
+    // Rows finished so far, `already_done` included, and how many of them failed.
+    processed: usize,
+    errors: usize,
+    clip_vision_ms_total: u128,
+    clip_vision_count: u64,
+    ollama_eval_tokens: u128,
+    ollama_eval_ms: u128,
+    category_counts: HashMap<CategoryKey, usize>,
+    valuable: usize,
+    not_valuable: usize,
+    duration_ms_total: u64,
+    duration_count: usize,
+    slowest: Vec<JobSummaryPhoto>,
+    failures: Vec<JobSummaryFailure>,
+    missing: Vec<String>,
+}
+
+impl JobStats {
+    fn new(total: usize, already_done: usize) -> Self {
+        Self {
+            started: std::time::Instant::now(),
+            total,
+            already_done,
+            processed: already_done,
+            errors: 0,
+            clip_vision_ms_total: 0,
+            clip_vision_count: 0,
+            ollama_eval_tokens: 0,
+            ollama_eval_ms: 0,
+            category_counts: HashMap::new(),
+            valuable: 0,
+            not_valuable: 0,
+            duration_ms_total: 0,
+            duration_count: 0,
+            slowest: Vec::new(),
+            failures: Vec::new(),
+            missing: Vec::new(),
         }
     }
-    emit_progress(&app, latest.clone(), progress.clone())?;
 
-    while progress.processed < total {
-        if cancel.is_cancelled() {
-            join_set.abort_all();
-            progress.status = JobStatus::Canceled;
-            progress.current_file = None;
-            emit_progress(&app, latest.clone(), progress.clone())?;
-            return Ok(());
+    fn record_success(&mut self, detail: &PhotoDetail) {
+        self.processed += 1;
+        if detail.model.as_deref() == Some("clip-vit-b32-onnx") {
+            if let Some(ms) = extract_u128_field(detail.analysis_log.as_deref(), "vision_infer_ms")
+            {
+                self.clip_vision_ms_total += ms;
+                self.clip_vision_count += 1;
+            }
         }
+        self.record_eval(detail.analysis_log.as_deref());
+        *self.category_counts.entry(detail.category).or_default() += 1;
+        match detail.is_valuable {
+            Some(true) => self.valuable += 1,
+            Some(false) => self.not_valuable += 1,
+            None => {}
+        }
+        let duration_ms = detail.analysis_duration_ms.unwrap_or(0).max(0) as u64;
+        self.duration_ms_total += duration_ms;
+        self.duration_count += 1;
+        self.slowest.push(JobSummaryPhoto {
+            path: detail
+                .source_path
+                .clone()
+                .unwrap_or_else(|| detail.path.clone()),
+            duration_ms,
+        });
+        self.slowest
+            .sort_by_key(|p| std::cmp::Reverse(p.duration_ms));
+        self.slowest.truncate(SUMMARY_SLOWEST);
+    }
 
-        let joined = tokio::select! {
-            _ = cancel.cancelled() => None,
-            res = join_set.join_next() => res,
-        };
-
-        let Some(joined) = joined else {
-            join_set.abort_all();
-            progress.status = JobStatus::Canceled;
-            progress.current_file = None;
-            emit_progress(&app, latest.clone(), progress.clone())?;
-            return Ok(());
+    // A failed photo, or a task that died without one (`path` is `None`).
+    fn record_failure(&mut self, path: Option<&Path>, error: &str) {
+        self.processed += 1;
+        self.errors += 1;
+        let Some(path) = path else {
+            return;
         };
+        if self.failures.len() < MAX_SUMMARY_FAILURES {
+            self.failures.push(JobSummaryFailure {
+                path: path.to_string_lossy().to_string(),
+                error: error.to_string(),
+            });
+        }
+    }
 
-        let outcome = match joined {
-            Ok(v) => v,
-            Err(e) => {
-                progress.errors += 1;
-                progress.processed += 1;
-                running = running.saturating_sub(1);
-                progress.current_file = Some(format!("병렬 처리 중: {}개", running));
-                emit_progress(&app, latest.clone(), progress.clone())?;
-                eprintln!("pipeline task join error: {}", e);
-                while running < effective_concurrency {
-                    if spawn_next(&mut join_set, &mut pending, &mut running, &mut progress).is_none() {
-                        break;
-                    }
-                }
-                continue;
-            }
-        };
+    fn progress(&self, job_id: &str, status: JobStatus) -> Progress {
+        Progress {
+            job_id: job_id.to_string(),
+            status,
+            current_file: None,
+            processed: self.processed,
+            total: self.total,
+            errors: self.errors,
+        }
+    }
+
+    fn record_eval(&mut self, log: Option<&str>) {
+        if let Some((tokens, ms)) = eval_stats(log) {
+            self.ollama_eval_tokens += tokens;
+            self.ollama_eval_ms += ms;
+        }
+    }
+
+    fn summary(&self, job_id: &str, engine: AnalysisEngine, progress: &Progress) -> JobSummary {
+        let elapsed = self.started.elapsed();
+        JobSummary {
+            job_id: job_id.to_string(),
+            status: progress.status.clone(),
+            engine,
+            total: self.total,
+            processed: progress.processed,
+            errors: progress.errors,
+            skipped: self.total.saturating_sub(progress.processed),
+            elapsed_ms: elapsed.as_millis() as u64,
+            images_per_sec: progress.processed.saturating_sub(self.already_done) as f64
+                / elapsed.as_secs_f64().max(0.001),
+            avg_vision_infer_ms: (self.clip_vision_count > 0)
+                .then(|| self.clip_vision_ms_total as f64 / self.clip_vision_count as f64),
+            avg_eval_tokens_per_sec: (self.ollama_eval_ms > 0)
+                .then(|| self.ollama_eval_tokens as f64 * 1000.0 / self.ollama_eval_ms as f64),
+            partial: !matches!(progress.status, JobStatus::Completed),
+            category_counts: self.category_counts.clone(),
+            valuable: self.valuable,
+            not_valuable: self.not_valuable,
+            total_duration_ms: self.duration_ms_total,
+            avg_duration_ms: (self.duration_count > 0)
+                .then(|| self.duration_ms_total as f64 / self.duration_count as f64),
+            slowest: self.slowest.clone(),
+            failures: self.failures.clone(),
+            missing: self.missing.clone(),
+        }
+    }
+}
+
+// Keeps the summary with the last job's metadata and on the job's row, then announces it.
+fn finish_job(
+    app: &AppHandle,
+    db: &Mutex<Db>,
+    last_job: &Mutex<Option<JobMeta>>,
+    summary: JobSummary,
+) -> Result<()> {
+    let meta = {
+        let mut guard = last_job.lock();
+        if let Some(meta) = guard.as_mut() {
+            meta.summary = Some(summary.clone());
+        }
+        guard.clone()
+    };
+    if let Some(meta) = meta {
+        if let Err(e) = save_last_job(app, &meta) {
+            eprintln!("failed to persist last job: {}", e);
+        }
+    }
+    db.lock().set_job_summary(&summary.job_id, &summary)?;
+    app.emit(JOB_SUMMARY_EVENT, summary)?;
+    Ok(())
+}
+
+fn emit_job_error(app: &AppHandle, latest: Arc<Mutex<Option<Progress>>>, job_id: &str) {
+    let progress = Progress {
+        job_id: job_id.to_string(),
+        status: JobStatus::Error,
+        current_file: None,
+        processed: 0,
+        total: 0,
+        errors: 1,
+    };
+    let _ = emit_progress(app, latest, progress);
+}
+
+// Finished rows wait here and reach the DB in one transaction per batch instead of one lock and
+// transaction per photo. Dropping flushes, so cancel and early returns keep what was analyzed.
+// Each flush is followed by one `PHOTO_EVENT` with the rows it wrote.
+struct PendingRows {
+    app: AppHandle,
+    job_id: String,
+    db: Arc<Mutex<Db>>,
+    rows: Vec<PhotoDetail>,
+    last_flush: std::time::Instant,
+}
+
+impl PendingRows {
+    fn push(&mut self, row: PhotoDetail) -> Result<()> {
+        self.rows.push(row);
+        if self.rows.len() >= DB_BATCH_ROWS || self.last_flush.elapsed() >= PHOTO_EVENT_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.last_flush = std::time::Instant::now();
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.rows);
+        self.db.lock().insert_photos(&rows)?;
+        let event = PhotoEvent {
+            job_id: self.job_id.clone(),
+            rows: rows.iter().map(PhotoRow::from).collect(),
+        };
+        if let Err(e) = self.app.emit(PHOTO_EVENT, event) {
+            eprintln!("failed to emit photo rows: {}", e);
+        }
+        Ok(())
+    }
+
+    // Time left before the waiting rows are due.
+    fn flush_delay(&self) -> std::time::Duration {
+        PHOTO_EVENT_INTERVAL.saturating_sub(self.last_flush.elapsed())
+    }
+}
+
+// Coalesces per-photo `PROGRESS_EVENT`s so fast CLIP runs don't flood IPC. `latest` takes every
+// update, so `get_progress` polling stays exact between events.
+struct ProgressThrottle {
+    app: AppHandle,
+    latest: Arc<Mutex<Option<Progress>>>,
+    last_emit: std::time::Instant,
+    dirty: bool,
+}
+
+impl ProgressThrottle {
+    fn update(&mut self, progress: &Progress) -> Result<()> {
+        if self.last_emit.elapsed() >= PROGRESS_EVENT_INTERVAL {
+            return self.emit(progress);
+        }
+        *self.latest.lock() = Some(progress.clone());
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn emit(&mut self, progress: &Progress) -> Result<()> {
+        self.last_emit = std::time::Instant::now();
+        self.dirty = false;
+        emit_progress(&self.app, self.latest.clone(), progress.clone())
+    }
+
+    // Time left before a coalesced update is due.
+    fn flush_delay(&self) -> std::time::Duration {
+        PROGRESS_EVENT_INTERVAL.saturating_sub(self.last_emit.elapsed())
+    }
+}
+
+impl Drop for PendingRows {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            eprintln!("failed to save buffered results: {}", e);
+        }
+    }
+}
+
+// What a running job works with besides its file list.
+struct JobContext {
+    app: AppHandle,
+    db: Arc<Mutex<Db>>,
+    settings: Settings,
+    export_root: PathBuf,
+    job_id: String,
+    cancel: CancellationToken,
+    latest: Arc<Mutex<Option<Progress>>>,
+    last_job: Arc<Mutex<Option<JobMeta>>>,
+}
+
+async fn run_job(
+    ctx: JobContext,
+    files: Vec<PathBuf>,
+    retried: HashMap<PathBuf, RetryPhoto>,
+    already_done: usize,
+    stats: &mut JobStats,
+) -> Result<()> {
+    let JobContext {
+        app,
+        db,
+        settings,
+        export_root,
+        job_id,
+        cancel,
+        latest,
+        last_job,
+    } = ctx;
+    // A resumed job counts its earlier rows as processed so the totals cover the whole job.
+    let total = stats.total;
+    let effective_concurrency = effective_concurrency(&settings);
+    let mut progress = Progress {
+        job_id: job_id.clone(),
+        status: JobStatus::Running,
+        current_file: None,
+        processed: already_done,
+        total,
+        errors: 0,
+    };
+    emit_progress(&app, latest.clone(), progress.clone())?;
+
+    #[derive(Debug)]
+    enum TaskOutcome {
+        Finished {
+            slot: usize,
+            photo_id: String,
+            row_job_id: Option<String>,
+            path: PathBuf,
+            file_name: String,
+            duration_ms: i64,
+            result: Result<PhotoDetail>,
+        },
+        Canceled,
+    }
+
+    let mut join_set: JoinSet<TaskOutcome> = JoinSet::new();
+    let mut running: usize = 0;
+    // Stream panes are keyed by slot, so a finished task hands its slot to the next one.
+    let mut free_slots: Vec<usize> = (0..effective_concurrency).rev().collect();
+    let mut describe_queue: Vec<PhotoDetail> = Vec::new();
+    let mut pending_rows = PendingRows {
+        app: app.clone(),
+        job_id: job_id.clone(),
+        db: db.clone(),
+        rows: Vec::with_capacity(DB_BATCH_ROWS),
+        last_flush: std::time::Instant::now(),
+    };
+    let mut throttle = ProgressThrottle {
+        app: app.clone(),
+        latest: latest.clone(),
+        last_emit: std::time::Instant::now(),
+        dirty: false,
+    };
+
+    // Decoding runs ahead in its own stage. The channel holds up to two prepared inputs per
+    // inference worker; the decode stage waits for room before decoding more.
+    let (prepared_tx, mut prepared_rx) = mpsc::channel(effective_concurrency * 2);
+    async_runtime::spawn(prepare_files(
+        app.clone(),
+        settings.clone(),
+        files,
+        prepared_tx,
+        cancel.clone(),
+    ));
+    let mut decoding = true;
+
+    let spawn_next = |join_set: &mut JoinSet<TaskOutcome>,
+                          prepared: PreparedFile,
+                          running: &mut usize,
+                          free_slots: &mut Vec<usize>,
+                          progress: &mut Progress| {
+        let PreparedFile { path, input } = prepared;
+        let slot = free_slots.pop().unwrap_or(0);
+        let retry = retried.get(&path);
+        let photo_id = retry.map_or_else(|| Uuid::new_v4().to_string(), |r| r.id.clone());
+        let row_job_id = retry.map_or_else(|| Some(job_id.clone()), |r| r.job_id.clone());
+        let file_name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("image")
+            .to_string();
+        let app = app.clone();
+        let job_id = job_id.clone();
+        let settings = settings.clone();
+        let export_root = export_root.clone();
+        let cancel = cancel.clone();
+        *running += 1;
+        progress.current_file = Some(format!("({}/{}) {}", *running, effective_concurrency, file_name));
+        join_set.spawn(async move {
+            let started = std::time::Instant::now();
+            let result = match input {
+                Err(_) if cancel.is_cancelled() => return TaskOutcome::Canceled,
+                // Decoding again inline would only fail the same way.
+                Err(e) => Err(e),
+                Ok(input) => tokio::select! {
+                    _ = cancel.cancelled() => {
+                        return TaskOutcome::Canceled;
+                    }
+                    res = process_one(
+                        PhotoContext {
+                            app: &app,
+                            job_id: &job_id,
+                            photo_id: &photo_id,
+                            path: &path,
+                            file_name: &file_name,
+                            slot,
+                            cancel: &cancel,
+                        },
+                        input,
+                        &settings,
+                        &export_root,
+                    ) => res,
+                },
+            };
+            let duration_ms = started.elapsed().as_millis() as i64;
+            TaskOutcome::Finished {
+                slot,
+                photo_id,
+                row_job_id,
+                path,
+                file_name,
+                duration_ms,
+                result,
+            }
+        });
+    };
+
+    while progress.processed < total {
+        if cancel.is_cancelled() {
+            join_set.abort_all();
+            progress.status = JobStatus::Canceled;
+            break;
+        }
+        // The decode stage only stops early when canceled, which the check above catches.
+        if !decoding && running == 0 {
+            break;
+        }
+
+        let joined = tokio::select! {
+            _ = cancel.cancelled() => None,
+            prepared = prepared_rx.recv(), if decoding && running < effective_concurrency => {
+                match prepared {
+                    Some(prepared) => {
+                        spawn_next(&mut join_set, prepared, &mut running, &mut free_slots, &mut progress);
+                        throttle.update(&progress)?;
+                    }
+                    None => decoding = false,
+                }
+                continue;
+            }
+            res = join_set.join_next(), if running > 0 => res,
+            _ = tokio::time::sleep(throttle.flush_delay()), if throttle.dirty => {
+                throttle.emit(&progress)?;
+                continue;
+            }
+            _ = tokio::time::sleep(pending_rows.flush_delay()), if !pending_rows.rows.is_empty() => {
+                pending_rows.flush()?;
+                continue;
+            }
+        };
+
+        let Some(joined) = joined else {
+            join_set.abort_all();
+            progress.status = JobStatus::Canceled;
+            break;
+        };
+
+        let outcome = match joined {
+            Ok(v) => v,
+            Err(e) => {
+                progress.errors += 1;
+                progress.processed += 1;
+                stats.record_failure(None, &e.to_string());
+                running = running.saturating_sub(1);
+                progress.current_file = Some(format!("병렬 처리 중: {}개", running));
+                throttle.emit(&progress)?;
+                eprintln!("pipeline task join error: {}", e);
+                continue;
+            }
+        };
 
         running = running.saturating_sub(1);
 
@@ -287,32 +1162,39 @@ async fn run_job(
             TaskOutcome::Canceled => {
                 join_set.abort_all();
                 progress.status = JobStatus::Canceled;
-                progress.current_file = None;
-                emit_progress(&app, latest.clone(), progress.clone())?;
-                return Ok(());
+                break;
             }
             TaskOutcome::Finished {
+                slot,
+                photo_id,
+                row_job_id,
                 path,
                 file_name,
                 duration_ms,
                 result,
             } => {
+                free_slots.push(slot);
+                let failed = result.is_err();
                 match result {
                     Ok(mut detail) => {
                         detail.analysis_duration_ms = Some(duration_ms);
-                        if detail.model.as_deref() == Some("clip-vit-b32-onnx") {
-                            if let Some(ms) =
-                                extract_u128_field(detail.analysis_log.as_deref(), "vision_infer_ms")
-                            {
-                                clip_vision_ms_total += ms;
-                                clip_vision_count += 1;
-                            }
+                        detail.job_id = row_job_id;
+                        stats.record_success(&detail);
+                        if wants_description(
+                            &settings,
+                            detail.is_valuable,
+                            detail.caption.as_deref(),
+                        ) {
+                            describe_queue.push(detail.clone());
                         }
-                        let guard = db.lock();
-                        guard.insert_photo(&detail)?;
+                        pending_rows.push(detail)?;
                     }
                     Err(e) => {
                         progress.errors += 1;
+                        stats.record_failure(Some(&path), &e.to_string());
+                        if let Err(log_err) = append_error(&app, &path, &e.to_string()) {
+                            eprintln!("failed to write error log: {}", log_err);
+                        }
                         let analysis_log = format!(
                             "engine: {engine:?}\nclip_model_dir: {clip_dir:?}\nclip_fallback_to_ollama: {fallback}\n\nbase_url: {base}\nollama_model: {model}\nthink: {think}\nstream: {stream}\nresize_enabled: {re}\nmax_edge: {me}\njpeg_quality: {q}\n\nerror:\n{err}\n",
                             engine = settings.analysis_engine,
@@ -328,11 +1210,12 @@ async fn run_job(
                             err = e
                         );
                         let failed_detail = PhotoDetail {
-                            id: Uuid::new_v4().to_string(),
+                            id: photo_id,
                             file_name: file_name.clone(),
                             path: path.to_string_lossy().to_string(),
                             category: crate::core::model::CategoryKey::Other,
                             top_score: 0.0,
+                            top_k: vec![],
                             scores: Scores::default(),
                             tags: vec![],
                             export_status: ExportStatus::Error,
@@ -342,51 +1225,119 @@ async fn run_job(
                             caption: None,
                             text_in_image: None,
                             model: Some(match settings.analysis_engine {
-                                crate::core::model::AnalysisEngine::Clip => {
+                                crate::core::model::AnalysisEngine::Clip
+                                | crate::core::model::AnalysisEngine::Hybrid => {
                                     "clip-vit-b32-onnx".to_string()
                                 }
                                 crate::core::model::AnalysisEngine::Ollama => settings.ollama_model.clone(),
                             }),
                             is_valuable: None,
                             valuable_score: None,
+                            source_path: Some(path.to_string_lossy().to_string()),
+                            export_root: Some(export_root.to_string_lossy().to_string()),
+                            subcategory: None,
+                            value_bucket: None,
+                            thumbnail_path: None,
+                            prompt_tokens: None,
+                            eval_tokens: None,
+                            created_at: None,
+                            job_id: row_job_id,
+                            exif: None,
+                            failure_kind: Some(failure_kind(&e)),
+                            manual_override: false,
                         };
-                        let guard = db.lock();
-                        let _ = guard.insert_photo(&failed_detail);
+                        pending_rows.push(failed_detail)?;
                     }
                 }
 
                 progress.processed += 1;
                 progress.current_file = Some(format!("병렬 처리 중: {}개", running));
-                emit_progress(&app, latest.clone(), progress.clone())?;
+                if failed {
+                    throttle.emit(&progress)?;
+                } else {
+                    throttle.update(&progress)?;
+                }
             }
         }
+    }
+
+    pending_rows.flush()?;
 
-        while running < effective_concurrency {
-            if spawn_next(&mut join_set, &mut pending, &mut running, &mut progress).is_none() {
+    // Hybrid second pass. Every row is already classified and exported, so canceling here only
+    // leaves the remaining captions empty.
+    let describe_total = describe_queue.len();
+    for (i, mut detail) in describe_queue.into_iter().enumerate() {
+        if matches!(progress.status, JobStatus::Canceled) {
+            break;
+        }
+        if cancel.is_cancelled() {
+            progress.status = JobStatus::Canceled;
+            break;
+        }
+        let source = PathBuf::from(detail.source_path.as_deref().unwrap_or(&detail.path));
+        progress.current_file = Some(format!(
+            "캡션 생성 ({}/{}) {}",
+            i + 1,
+            describe_total,
+            detail.file_name
+        ));
+        emit_progress(&app, latest.clone(), progress.clone())?;
+        let described = tokio::select! {
+            _ = cancel.cancelled() => {
+                progress.status = JobStatus::Canceled;
                 break;
             }
+            res = describe_with_ollama(
+                PhotoContext {
+                    app: &app,
+                    job_id: &job_id,
+                    photo_id: &detail.id,
+                    path: &source,
+                    file_name: &detail.file_name,
+                    slot: 0,
+                    cancel: &cancel,
+                },
+                &settings,
+            ) => res,
+        };
+        if let Err(e) = &described {
+            if let Err(log_err) = append_error(&app, &source, &e.to_string()) {
+                eprintln!("failed to write error log: {}", log_err);
+            }
+        }
+        detail.analysis_log = Some(described_log(
+            detail.analysis_log.as_deref().unwrap_or_default(),
+            &described,
+        ));
+        if let Ok(d) = described {
+            stats.record_eval(Some(&d.analysis_log));
+            (detail.prompt_tokens, detail.eval_tokens) = token_counts(Some(&d.analysis_log));
+            if !d.tags.is_empty() {
+                detail.tags = d.tags;
+            }
+            detail.caption = d.caption;
+            detail.text_in_image = d.text_in_image;
+            // The first sidecar was written before the caption existed.
+            try_write_sidecar(&settings, &detail);
         }
+        db.lock().insert_photo(&detail)?;
+        app.emit(PHOTO_UPDATED_EVENT, detail)?;
     }
 
-    progress.status = JobStatus::Completed;
-    progress.current_file = None;
-    emit_progress(&app, latest, progress)?;
-
-    let elapsed = job_started.elapsed().as_secs_f64().max(0.001);
-    let ips = (total as f64) / elapsed;
-    if clip_vision_count > 0 {
-        let avg = (clip_vision_ms_total as f64) / (clip_vision_count as f64);
-        eprintln!(
-            "clip perf: images={} elapsed={:.2}s throughput={:.2} img/s avg_vision_infer_ms={:.1}",
-            total, elapsed, ips, avg
-        );
-    } else {
-        eprintln!(
-            "perf: images={} elapsed={:.2}s throughput={:.2} img/s",
-            total, elapsed, ips
-        );
+    if !matches!(progress.status, JobStatus::Canceled) {
+        progress.status = JobStatus::Completed;
     }
-    Ok(())
+    progress.current_file = None;
+    emit_progress(&app, latest, progress.clone())?;
+    db.lock().set_job_status(&job_id, &progress.status)?;
+
+    let summary = stats.summary(&job_id, settings.analysis_engine, &progress);
+    finish_job(&app, &db, &last_job, summary)
+}
+
+// Photos analyzed at once. Streaming doesn't serialize the job, whatever the engine.
+fn effective_concurrency(settings: &Settings) -> usize {
+    settings.analysis_concurrency.max(1) as usize
 }
 
 fn extract_u128_field(log: Option<&str>, key: &str) -> Option<u128> {
@@ -401,79 +1352,162 @@ fn extract_u128_field(log: Option<&str>, key: &str) -> Option<u128> {
     num.parse::<u128>().ok()
 }
 
+// `prompt_tokens` / `eval_tokens` from the `ollama_metrics` block of an analysis log.
+fn token_counts(log: Option<&str>) -> (Option<i64>, Option<i64>) {
+    let count = |key| extract_u128_field(log, key).and_then(|n| i64::try_from(n).ok());
+    (count("prompt_tokens"), count("eval_tokens"))
+}
+
+// (generated tokens, generation ms) for the tokens/sec figure in the job summary.
+fn eval_stats(log: Option<&str>) -> Option<(u128, u128)> {
+    Some((
+        extract_u128_field(log, "eval_tokens")?,
+        extract_u128_field(log, "eval_ms")?,
+    ))
+}
+
+// One photo under analysis: the job and row it belongs to and the stream slot it reports on.
+#[derive(Clone, Copy)]
+struct PhotoContext<'a> {
+    app: &'a AppHandle,
+    job_id: &'a str,
+    // Names the raw response capture; the row id when there is one.
+    photo_id: &'a str,
+    path: &'a Path,
+    file_name: &'a str,
+    slot: usize,
+    cancel: &'a CancellationToken,
+}
+
+impl<'a> PhotoContext<'a> {
+    fn classify_input(
+        self,
+        base64_jpeg: Option<&'a str>,
+        preprocessed: Option<PreprocessOutput>,
+    ) -> ClassifyInput<'a> {
+        ClassifyInput {
+            app: self.app,
+            job_id: self.job_id,
+            photo_id: self.photo_id,
+            file_name: self.file_name,
+            path: self.path,
+            base64_jpeg,
+            preprocessed,
+            slot: self.slot,
+            cancel: self.cancel,
+        }
+    }
+}
+
 async fn process_one(
-    app: &AppHandle,
-    job_id: &str,
+    photo: PhotoContext<'_>,
+    prepared: Option<PreparedInput>,
     settings: &Settings,
-    export_root: &PathBuf,
-    path: &PathBuf,
-    file_name: &str,
-    cancel: &CancellationToken,
+    export_root: &Path,
 ) -> Result<PhotoDetail> {
-    let (engine, classifier) = build_classifier(settings);
-    let mut encoded: Option<String> = None;
-    let ensure_encoded = |encoded: &mut Option<String>| -> Result<()> {
-        if encoded.is_some() {
-            return Ok(());
+    let out = analyze_one(photo, prepared, settings, false).await?;
+    let export_path = export_one(settings, export_root, &out, photo.file_name, photo.path)?;
+    let mut detail = build_detail(
+        photo.photo_id.to_string(),
+        photo.file_name,
+        photo.path,
+        export_root,
+        &export_path,
+        out,
+    );
+    detail.thumbnail_path = try_generate_thumbnail(photo.app, &detail.id, photo.path);
+    try_write_sidecar(settings, &detail);
+    Ok(detail)
+}
+
+// Provider errors usually reach here flattened to text (redaction rebuilds them), so a reqwest
+// error still in the chain is checked first and the message after that.
+fn failure_kind(e: &anyhow::Error) -> FailureKind {
+    if let Some(req) = e.chain().find_map(|c| c.downcast_ref::<reqwest::Error>()) {
+        if req.is_timeout() {
+            return FailureKind::Timeout;
         }
-        let out = decode_resize_base64_with_options(
-            path,
-            DecodeOptions {
-                resize_enabled: settings.analysis_resize_enabled,
-                max_edge: settings.analysis_max_edge,
-                jpeg_quality: settings.analysis_jpeg_quality,
-                resize_filter: image::imageops::FilterType::Triangle,
-            },
-        )?;
-        *encoded = Some(out.base64_jpeg);
-        Ok(())
+        if req.is_connect() || req.is_request() || req.is_body() {
+            return FailureKind::Network;
+        }
+    }
+    let msg = format!("{:#}", e).to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| msg.contains(n));
+    if has(&["canceled", "cancelled"]) {
+        FailureKind::Canceled
+    } else if has(&["timed out", "deadline has elapsed"]) {
+        FailureKind::Timeout
+    } else if has(&[
+        "parse model json",
+        "scores missing",
+        "missing message content",
+        "missing choices",
+        "ended without content",
+    ]) {
+        FailureKind::ParseFailed
+    } else if has(&[
+        "model not found",
+        "does not support images",
+        "rejected the request",
+        "model is empty",
+        "ollama error",
+        "server error",
+    ]) {
+        FailureKind::ModelRefused
+    } else if has(&[
+        "error sending request",
+        "connection refused",
+        "connection reset",
+        "tcp connect",
+        "dns error",
+        "stream ended unexpectedly",
+        "error decoding response body",
+    ]) {
+        FailureKind::Network
+    } else {
+        FailureKind::Other
+    }
+}
+
+// `describe_inline` runs the hybrid Ollama stage right away; batch jobs queue it instead.
+async fn analyze_one(
+    photo: PhotoContext<'_>,
+    prepared: Option<PreparedInput>,
+    settings: &Settings,
+    describe_inline: bool,
+) -> Result<ClassificationOutput> {
+    let (engine, classifier) = build_classifier(settings);
+    let (preprocessed, encoded) = match prepared {
+        Some(PreparedInput::Clip(pre)) => (Some(pre), None),
+        Some(PreparedInput::Encoded(b64)) => (None, Some(b64)),
+        None => (None, None),
     };
 
     let mut output = match engine {
-        crate::core::model::AnalysisEngine::Clip => {
+        crate::core::model::AnalysisEngine::Clip | crate::core::model::AnalysisEngine::Hybrid => {
             classifier
-                .classify(ClassifyInput {
-                    app,
-                    job_id,
-                    file_name,
-                    path,
-                    base64_jpeg: None,
-                    cancel,
-                })
+                .classify(photo.classify_input(None, preprocessed))
                 .await
         }
         crate::core::model::AnalysisEngine::Ollama => {
-            ensure_encoded(&mut encoded)?;
-            let b64 = encoded.as_deref().unwrap_or_default();
+            let b64 = match encoded {
+                Some(b64) => b64,
+                None => encode_for_analysis(settings, photo.path, photo.cancel).await?,
+            };
             classifier
-                .classify(ClassifyInput {
-                    app,
-                    job_id,
-                    file_name,
-                    path,
-                    base64_jpeg: Some(b64),
-                    cancel,
-                })
+                .classify(photo.classify_input(Some(&b64), None))
                 .await
         }
     };
 
     if let Err(clip_err) = &output {
-        if engine == crate::core::model::AnalysisEngine::Clip && settings.clip_fallback_to_ollama {
-            ensure_encoded(&mut encoded)?;
-            let b64 = encoded.as_deref().unwrap_or_default();
+        if engine.uses_clip() && settings.clip_fallback_to_ollama {
+            let b64 = encode_for_analysis(settings, photo.path, photo.cancel).await?;
             let ollama = OllamaClassifier {
                 settings: settings.clone(),
             };
             output = ollama
-                .classify(ClassifyInput {
-                    app,
-                    job_id,
-                    file_name,
-                    path,
-                    base64_jpeg: Some(b64),
-                    cancel,
-                })
+                .classify(photo.classify_input(Some(&b64), None))
                 .await
                 .map_err(|ollama_err| {
                     anyhow!(
@@ -485,50 +1519,645 @@ async fn process_one(
         }
     }
 
-    let out = output?;
-    let analysis_log = format!(
-        "engine: {engine:?}\nresize_enabled: {re}\nmax_edge: {me}\njpeg_quality: {q}\n\n{rest}",
+    let mut out = output?;
+    if describe_inline && wants_description(settings, out.is_valuable, out.caption.as_deref()) {
+        let described = describe_with_ollama(PhotoContext { slot: 0, ..photo }, settings).await;
+        out.analysis_log = described_log(&out.analysis_log, &described);
+        if let Ok(d) = described {
+            if !d.tags.is_empty() {
+                out.tags = d.tags;
+            }
+            out.caption = d.caption;
+            out.text_in_image = d.text_in_image;
+        }
+    }
+    out.analysis_log = format!(
+        "engine: {engine:?}\nvalue_enabled: {ve}\nconcurrency: {cc}\nresize_enabled: {re}\nmax_edge: {me}\nresize_percent: {rp:?}\njpeg_quality: {q}\nimage_format: {fmt:?}\n\n{rest}",
         engine = settings.analysis_engine,
+        ve = settings.analysis_value_enabled,
+        cc = settings.analysis_concurrency,
         re = settings.analysis_resize_enabled,
         me = settings.analysis_max_edge,
+        rp = settings.analysis_resize_percent,
         q = settings.analysis_jpeg_quality,
+        fmt = settings.analysis_image_format,
         rest = out.analysis_log
     );
+    Ok(out)
+}
 
-    let category_dir = out.category.dir_name_ko();
-    let export_path = if settings.analysis_value_enabled {
-        match out.is_valuable {
-            Some(true) => copy_to_category_nested(export_root, &["가치있음", category_dir], file_name, path)?,
-            Some(false) => {
-                copy_to_category_nested(export_root, &["가치없음", category_dir], file_name, path)?
+fn wants_description(
+    settings: &Settings,
+    is_valuable: Option<bool>,
+    caption: Option<&str>,
+) -> bool {
+    settings.analysis_engine == AnalysisEngine::Hybrid
+        // A CLIP failure that fell back to Ollama already has a caption.
+        && caption.is_none_or(|c| c.trim().is_empty())
+        && match settings.hybrid_caption_scope {
+            HybridCaptionScope::All => true,
+            HybridCaptionScope::ValuableOnly => is_valuable != Some(false),
+            HybridCaptionScope::None => false,
+        }
+}
+
+// Hybrid's Ollama stage. Only caption, tags and text-in-image are taken from the result: CLIP's
+// category, scores and value already drove the export. A failure keeps the CLIP result.
+async fn describe_with_ollama(
+    photo: PhotoContext<'_>,
+    settings: &Settings,
+) -> Result<ClassificationOutput> {
+    let encoded = encode_for_analysis(settings, photo.path, photo.cancel).await?;
+    let ollama = OllamaClassifier {
+        settings: settings.clone(),
+    };
+    ollama
+        .classify(photo.classify_input(Some(&encoded), None))
+        .await
+}
+
+// What the decode stage hands to inference: the CLIP tensor, or the encoded image an Ollama
+// request carries.
+enum PreparedInput {
+    Clip(PreprocessOutput),
+    Encoded(String),
+}
+
+// A decode error is recorded as the photo's failure without taking an inference slot. None when
+// the CLIP engine failed to load; the inference stage then runs the photo's usual inline path,
+// which reports that error or falls back to Ollama.
+struct PreparedFile {
+    path: PathBuf,
+    input: Result<Option<PreparedInput>>,
+}
+
+// Decode stage of a batch job, at `decode_concurrency` workers. A channel slot is reserved before
+// each decode, so decoded inputs waiting for inference never exceed the channel capacity. Returning
+// drops the sender, which tells the inference stage nothing more is coming; on cancel that also
+// aborts the decodes in flight.
+async fn prepare_files(
+    app: AppHandle,
+    settings: Settings,
+    files: Vec<PathBuf>,
+    tx: mpsc::Sender<PreparedFile>,
+    cancel: CancellationToken,
+) {
+    let workers = settings.decode_concurrency.max(1) as usize;
+    let mut join_set = JoinSet::new();
+    for path in files {
+        if join_set.len() >= workers {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = join_set.join_next() => {}
             }
-            None => copy_to_category(export_root, category_dir, file_name, path)?,
         }
-    } else {
-        copy_to_category(export_root, category_dir, file_name, path)?
+        let permit = tokio::select! {
+            _ = cancel.cancelled() => return,
+            permit = tx.clone().reserve_owned() => match permit {
+                Ok(permit) => permit,
+                // The job ended and dropped the receiver.
+                Err(_) => return,
+            },
+        };
+        let app = app.clone();
+        let settings = settings.clone();
+        let cancel = cancel.clone();
+        join_set.spawn(async move {
+            let input = prepare_input(&app, &settings, &path, &cancel).await;
+            permit.send(PreparedFile { path, input });
+        });
+    }
+    while join_set.join_next().await.is_some() {}
+}
+
+async fn prepare_input(
+    app: &AppHandle,
+    settings: &Settings,
+    path: &Path,
+    cancel: &CancellationToken,
+) -> Result<Option<PreparedInput>> {
+    if !settings.analysis_engine.uses_clip() {
+        let encoded = encode_for_analysis(settings, path, cancel).await?;
+        return Ok(Some(PreparedInput::Encoded(encoded)));
+    }
+    let app = app.clone();
+    let settings = settings.clone();
+    let source = path.to_path_buf();
+    let pre = run_cancelable(cancel, move |token| {
+        prepare_clip_input(&app, &settings, &source, &token)
+    })
+    .await?;
+    Ok(pre.map(PreparedInput::Clip))
+}
+
+// Decoding runs on the blocking pool so a cancel returns right away instead of waiting for a large
+// file; a `sips` conversion is killed through the same token.
+async fn encode_for_analysis(
+    settings: &Settings,
+    path: &Path,
+    cancel: &CancellationToken,
+) -> Result<String> {
+    let source = path.to_path_buf();
+    let opts = analysis_decode_options(settings, path);
+    let encoded = run_cancelable(cancel, move |token| {
+        decode_resize_base64_with_options(&source, opts, Some(&token))
+    })
+    .await?;
+    Ok(encoded.base64)
+}
+
+// Runs `work` on the blocking pool with its own clone of `cancel` and returns as soon as the token
+// fires. Work that never checks the token keeps its blocking thread until it finishes.
+async fn run_cancelable<T: Send + 'static>(
+    cancel: &CancellationToken,
+    work: impl FnOnce(CancellationToken) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let token = cancel.clone();
+    let task = async_runtime::spawn_blocking(move || work(token));
+    tokio::select! {
+        _ = cancel.cancelled() => Err(anyhow!("canceled")),
+        res = task => res?,
+    }
+}
+
+fn described_log(clip_log: &str, described: &Result<ClassificationOutput>) -> String {
+    let section = match described {
+        Ok(d) => d.analysis_log.clone(),
+        Err(e) => format!("error: {}\n", e),
     };
-    let top = out.scores.top();
+    format!("{}\n\n[hybrid: ollama description]\n{}", clip_log, section)
+}
+
+fn analysis_decode_options(settings: &Settings, path: &Path) -> DecodeOptions {
+    DecodeOptions {
+        resize_enabled: settings.analysis_resize_enabled,
+        resize: match settings.analysis_resize_percent {
+            Some(percent) => ResizeSpec::Percent(percent),
+            None => ResizeSpec::MaxEdge(settings.analysis_max_edge),
+        },
+        allow_upscale: false,
+        jpeg_quality: settings.analysis_jpeg_quality,
+        resize_filter: image::imageops::FilterType::Triangle,
+        encode_format: resolve_encode_format(settings.analysis_image_format, path),
+        alpha_background: settings.alpha_background,
+    }
+}
+
+fn resolve_encode_format(format: AnalysisImageFormat, path: &Path) -> EncodeFormat {
+    match format {
+        AnalysisImageFormat::Jpeg => EncodeFormat::Jpeg,
+        AnalysisImageFormat::Png => EncodeFormat::Png,
+        AnalysisImageFormat::Auto if is_likely_screenshot(path) => EncodeFormat::Png,
+        AnalysisImageFormat::Auto => EncodeFormat::Jpeg,
+    }
+}
+
+// Cheap pre-classification guess from the file name. The extension alone says nothing: plenty of
+// PNGs are exports, edits or graphics rather than screenshots.
+fn is_likely_screenshot(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    ["screenshot", "screen shot", "screen_shot", "스크린샷"]
+        .iter()
+        .any(|hint| name.contains(hint))
+}
+
+// Like thumbnails, a sidecar is extra: failing to write one doesn't fail the photo.
+fn try_write_sidecar(settings: &Settings, detail: &PhotoDetail) {
+    if !settings.write_sidecar {
+        return;
+    }
+    if let Err(e) = write_sidecar(detail) {
+        eprintln!("sidecar failed for {}: {}", detail.path, e);
+    }
+}
+
+fn export_one(
+    settings: &Settings,
+    export_root: &Path,
+    out: &ClassificationOutput,
+    file_name: &str,
+    path: &Path,
+) -> Result<PathBuf> {
+    let category_dir = out.category.dir_name_ko();
+    let sub_dir = out
+        .subcategory
+        .as_deref()
+        .map(folder_component)
+        .unwrap_or_default();
+    if settings.analysis_value_enabled && settings.value_as_nested_folders {
+        // The value bucket names the top-level folder; engines without buckets fall back to the
+        // keep/drop split.
+        let value_dir = match (&out.value_bucket, out.is_valuable) {
+            (Some(bucket), _) => folder_component(bucket),
+            (None, Some(true)) => "가치있음".to_string(),
+            (None, Some(false)) => "가치없음".to_string(),
+            (None, None) => String::new(),
+        };
+        copy_to_category_nested(
+            export_root,
+            &[&value_dir, category_dir, &sub_dir],
+            file_name,
+            path,
+        )
+    } else {
+        copy_to_category_nested(export_root, &[category_dir, &sub_dir], file_name, path)
+    }
+}
 
-    Ok(PhotoDetail {
-        id: Uuid::new_v4().to_string(),
+fn build_detail(
+    id: String,
+    file_name: &str,
+    source: &Path,
+    export_root: &Path,
+    export_path: &Path,
+    out: ClassificationOutput,
+) -> PhotoDetail {
+    let top = out.scores.top();
+    let (prompt_tokens, eval_tokens) = token_counts(Some(&out.analysis_log));
+    PhotoDetail {
+        id,
         file_name: file_name.to_string(),
         path: export_path.to_string_lossy().to_string(),
         category: out.category,
         top_score: top.1,
+        top_k: out.scores.top_k(DEFAULT_TOP_K),
         scores: out.scores,
         tags: out.tags,
         export_status: ExportStatus::Success,
         error_message: None,
-        analysis_log: Some(analysis_log),
+        analysis_log: Some(out.analysis_log),
         analysis_duration_ms: None,
         caption: out.caption,
         text_in_image: out.text_in_image,
         model: Some(out.model),
         is_valuable: out.is_valuable,
         valuable_score: out.valuable_score,
+        source_path: Some(source.to_string_lossy().to_string()),
+        export_root: Some(export_root.to_string_lossy().to_string()),
+        subcategory: out.subcategory,
+        value_bucket: out.value_bucket,
+        thumbnail_path: None,
+        prompt_tokens,
+        eval_tokens,
+        created_at: None,
+        job_id: None,
+        exif: read_exif(source),
+        failure_kind: None,
+        manual_override: false,
+    }
+}
+
+pub async fn reanalyze_photo(
+    app: &AppHandle,
+    db: Arc<Mutex<Db>>,
+    settings: &Settings,
+    fallback_export_root: Option<&str>,
+    id: &str,
+) -> Result<PhotoDetail> {
+    let previous = db.lock().get_photo_detail(id)?;
+    // Failed rows never got exported, so their `path` is still the source file.
+    let source = previous
+        .source_path
+        .clone()
+        .or_else(|| match previous.export_status {
+            ExportStatus::Error => Some(previous.path.clone()),
+            _ => None,
+        })
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("source path was not recorded for this photo"))?;
+    if !source.exists() {
+        return Err(anyhow!(
+            "source file not found (moved or deleted): {}",
+            source.display()
+        ));
+    }
+    let export_root = previous
+        .export_root
+        .as_deref()
+        .or(fallback_export_root)
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("export root was not recorded for this photo"))?;
+    fs::create_dir_all(&export_root)?;
+
+    let file_name = source
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(previous.file_name.as_str())
+        .to_string();
+    let job_id = format!("reanalyze-{}", id);
+    let cancel = CancellationToken::new();
+    let started = std::time::Instant::now();
+    let photo = PhotoContext {
+        app,
+        job_id: &job_id,
+        photo_id: id,
+        path: &source,
+        file_name: &file_name,
+        slot: 0,
+        cancel: &cancel,
+    };
+    let out = analyze_one(photo, None, settings, true).await?;
+
+    let mut detail =
+        export_reanalyzed(settings, &previous, &source, &export_root, &file_name, out)?;
+    detail.analysis_duration_ms = Some(started.elapsed().as_millis() as i64);
+    detail.thumbnail_path = try_generate_thumbnail(app, id, &source);
+    try_write_sidecar(settings, &detail);
+    db.lock().insert_photo(&detail)?;
+    app.emit(PHOTO_UPDATED_EVENT, detail.clone())?;
+    Ok(detail)
+}
+
+// Exports the reanalyzed photo in place of `previous` and builds its replacement row. The row
+// keeps the id, job and analysis time of the original, so the photo stays in its job's filters,
+// stats and undo, and resuming the job still counts it as done.
+fn export_reanalyzed(
+    settings: &Settings,
+    previous: &PhotoDetail,
+    source: &Path,
+    export_root: &Path,
+    file_name: &str,
+    out: ClassificationOutput,
+) -> Result<PhotoDetail> {
+    // The previous copy is renamed aside while the new one is exported, so an unchanged category
+    // reuses its name instead of getting a `_1` suffix, and a failed export puts it back.
+    let previous_copy = match previous.export_status {
+        ExportStatus::Success => Some(PathBuf::from(&previous.path))
+            .filter(|old| old != source && old.starts_with(export_root) && old.exists()),
+        _ => None,
+    };
+    let aside = match previous_copy {
+        Some(old) => {
+            let mut name = old.as_os_str().to_owned();
+            name.push(".reanalyze");
+            let aside = PathBuf::from(name);
+            fs::rename(&old, &aside)?;
+            Some((old, aside))
+        }
+        None => None,
+    };
+    let export_path = match export_one(settings, export_root, &out, file_name, source) {
+        Ok(path) => path,
+        Err(e) => {
+            if let Some((old, aside)) = &aside {
+                if let Err(restore_err) = fs::rename(aside, old) {
+                    eprintln!("failed to restore {}: {}", old.display(), restore_err);
+                }
+            }
+            return Err(e);
+        }
+    };
+    if let Some((old, aside)) = aside {
+        fs::remove_file(&aside)?;
+        // Same path: the new sidecar written below replaces the old one.
+        let old_sidecar = sidecar_path(&old);
+        if old != export_path && old_sidecar.exists() {
+            fs::remove_file(&old_sidecar)?;
+        }
+    }
+    let mut detail = build_detail(
+        previous.id.clone(),
+        file_name,
+        source,
+        export_root,
+        &export_path,
+        out,
+    );
+    detail.job_id = previous.job_id.clone();
+    detail.created_at = previous.created_at.clone();
+    Ok(detail)
+}
+
+// Rolls back what a job exported: its exported files (and sidecars) go to the OS trash and their
+// rows are removed. Only files the job itself wrote count: exports never overwrite, rows must be
+// successful, inside the export root and not the source, and no other row may point at the same
+// file. With `dry_run` nothing changes and the report shows what would be undone.
+pub fn undo_export(
+    app: &AppHandle,
+    db: &Mutex<Db>,
+    job_id: &str,
+    dry_run: bool,
+) -> Result<UndoExportReport> {
+    let report = undo_job_files(db, job_id, dry_run, |path| Ok(trash::delete(path)?))?;
+    if !dry_run {
+        app.emit(PHOTO_UPDATED_EVENT, ())?;
+    }
+    Ok(report)
+}
+
+// `undo_export` with the trash swapped for `discard`. A row goes only once its file is gone, so
+// files that couldn't be discarded can be undone again later; the job's failed rows never
+// exported anything and stay as well.
+fn undo_job_files(
+    db: &Mutex<Db>,
+    job_id: &str,
+    dry_run: bool,
+    mut discard: impl FnMut(&Path) -> Result<()>,
+) -> Result<UndoExportReport> {
+    let files: Vec<PhotoFiles> = db
+        .lock()
+        .job_exported_files(job_id)?
+        .into_iter()
+        .filter(|f| {
+            let path = Path::new(&f.path);
+            let in_root = f
+                .export_root
+                .as_deref()
+                .is_some_and(|root| path.starts_with(root));
+            in_root && f.source_path.as_deref() != Some(f.path.as_str()) && path.is_file()
+        })
+        .collect();
+    let mut report = UndoExportReport {
+        job_id: job_id.to_string(),
+        files: files.len(),
+        rows: files.len(),
+        failed: Vec::new(),
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    let mut undone = Vec::new();
+    for file in &files {
+        let path = Path::new(&file.path);
+        if let Err(e) = discard(path) {
+            report.failed.push(format!("{}: {}", path.display(), e));
+            continue;
+        }
+        undone.push(file.id.clone());
+        let sidecar = sidecar_path(path);
+        if sidecar.exists() {
+            if let Err(e) = discard(&sidecar) {
+                report.failed.push(format!("{}: {}", sidecar.display(), e));
+            }
+        }
+        // Category folders the job created are left empty; `remove_dir` keeps any that aren't.
+        if let Some(root) = file.export_root.as_deref() {
+            let mut dir = path.parent();
+            while let Some(d) = dir.filter(|d| *d != Path::new(root) && d.starts_with(root)) {
+                if fs::remove_dir(d).is_err() {
+                    break;
+                }
+                dir = d.parent();
+            }
+        }
+    }
+    let removed = db.lock().delete_photos(&undone)?;
+    for thumbnail in removed.iter().filter_map(|f| f.thumbnail_path.as_deref()) {
+        let _ = fs::remove_file(thumbnail);
+    }
+    report.files = undone.len();
+    report.rows = removed.len();
+    Ok(report)
+}
+
+// Moves a photo to a hand-picked category. The exported copy follows: the old category folder in
+// its path is swapped for the new one, so value folders stay and a subcategory folder is dropped.
+// Scores are left as the model produced them. When there is no exported file to move, only the
+// row changes and the result carries a warning.
+pub fn recategorize_photo(
+    app: &AppHandle,
+    db: &Mutex<Db>,
+    id: &str,
+    category: CategoryKey,
+) -> Result<RecategorizeResult> {
+    let detail = db.lock().get_photo_detail(id)?;
+    let result = recategorize_row(detail, category, |detail| {
+        db.lock().set_photo_category(id, category, &detail.path)
+    })?;
+    app.emit(PHOTO_UPDATED_EVENT, result.photo.clone())?;
+    Ok(result)
+}
+
+// Moves the exported file into `category`'s folder, then stores the row with `update`. A failed
+// update moves the file back so the row still points at it; the sidecar is only rewritten once
+// the row is stored.
+fn recategorize_row(
+    mut detail: PhotoDetail,
+    category: CategoryKey,
+    update: impl FnOnce(&PhotoDetail) -> Result<()>,
+) -> Result<RecategorizeResult> {
+    let mut warning = None;
+    let mut moved_from = None;
+    if detail.category != category {
+        match exported_copy(&detail) {
+            Some((old, export_root)) if old.exists() => {
+                let target_dir = recategorized_dir(&old, &export_root, detail.category, category);
+                let moved = move_to_dir(&target_dir, &old)?;
+                detail.path = moved.to_string_lossy().to_string();
+                moved_from = Some(old);
+            }
+            Some((old, _)) => {
+                warning = Some(format!(
+                    "exported file not found, only the category was updated: {}",
+                    old.display()
+                ));
+            }
+            None => {
+                warning = Some("photo was never exported, only the category was updated".into());
+            }
+        }
+    }
+    detail.category = category;
+    detail.subcategory = None;
+    detail.manual_override = true;
+    if let Err(e) = update(&detail) {
+        if let Some(old) = &moved_from {
+            let moved = Path::new(&detail.path);
+            let restored = fs::rename(moved, old)
+                .or_else(|_| fs::copy(moved, old).and_then(|_| fs::remove_file(moved)));
+            if let Err(restore_err) = restored {
+                eprintln!("failed to move {} back: {}", detail.path, restore_err);
+            }
+        }
+        return Err(e);
+    }
+    if let Some(old) = moved_from {
+        let old_sidecar = sidecar_path(&old);
+        if old_sidecar.exists() {
+            if let Err(e) = fs::remove_file(&old_sidecar) {
+                eprintln!("failed to remove {}: {}", old_sidecar.display(), e);
+            }
+            if let Err(e) = write_sidecar(&detail) {
+                eprintln!("sidecar failed for {}: {}", detail.path, e);
+            }
+        }
+    }
+    Ok(RecategorizeResult {
+        photo: detail,
+        warning,
     })
 }
 
+// The exported file and its export root, unless the row never got a copy of its own.
+fn exported_copy(detail: &PhotoDetail) -> Option<(PathBuf, PathBuf)> {
+    if !matches!(detail.export_status, ExportStatus::Success) {
+        return None;
+    }
+    let path = PathBuf::from(&detail.path);
+    let export_root = PathBuf::from(detail.export_root.as_deref()?);
+    let is_source = detail.source_path.as_deref().map(Path::new) == Some(path.as_path());
+    (path.starts_with(&export_root) && !is_source).then_some((path, export_root))
+}
+
+fn recategorized_dir(
+    old: &Path,
+    export_root: &Path,
+    from: CategoryKey,
+    to: CategoryKey,
+) -> PathBuf {
+    let rel = old
+        .parent()
+        .and_then(|p| p.strip_prefix(export_root).ok())
+        .unwrap_or(Path::new(""));
+    let mut dir = export_root.to_path_buf();
+    for component in rel.components() {
+        if component.as_os_str() == from.dir_name_ko() {
+            return dir.join(to.dir_name_ko());
+        }
+        dir.push(component);
+    }
+    // Not under a category folder (moved by hand?); start one at the root.
+    export_root.join(to.dir_name_ko())
+}
+
+// Headless entry point: classifies an in-memory image with the configured engine. Nothing is
+// exported or stored.
+pub async fn classify_bytes(
+    app: &AppHandle,
+    settings: &Settings,
+    bytes: &[u8],
+    ext: &str,
+) -> Result<ClassificationOutput> {
+    let (tmp, file_name) = temp_input_image(bytes, ext)?;
+    let job_id = format!("bytes-{}", Uuid::new_v4());
+    let cancel = CancellationToken::new();
+    let photo = PhotoContext {
+        app,
+        job_id: &job_id,
+        photo_id: "input",
+        path: tmp.path(),
+        file_name: &file_name,
+        slot: 0,
+        cancel: &cancel,
+    };
+    analyze_one(photo, None, settings, true).await
+}
+
+// Decoders (and `sips`) work on paths, so the bytes go to a temp file named after `ext`.
+fn temp_input_image(bytes: &[u8], ext: &str) -> Result<(tempfile::NamedTempFile, String)> {
+    let ext = ext.trim().trim_start_matches('.').to_lowercase();
+    if !ALLOWED_EXT.contains(&ext.as_str()) {
+        return Err(anyhow!("unsupported image type: {}", ext));
+    }
+    let tmp = write_temp_image(bytes, &ext)?;
+    Ok((tmp, format!("input.{}", ext)))
+}
+
 fn emit_progress(
     app: &AppHandle,
     latest: Arc<Mutex<Option<Progress>>>,
@@ -542,6 +2171,589 @@ fn emit_progress(
     Ok(())
 }
 
-pub async fn test_ollama_connection(base_url: &str) -> Result<String> {
-    test_connection(base_url).await
+// Checks that need the network, so they run before `Pipeline::start` rather than inside it.
+// OpenAI-compatible servers have no capability endpoint, so only Ollama is checked.
+pub async fn preflight(settings: &Settings) -> Result<()> {
+    if !settings.preflight_checks
+        || !settings.analysis_engine.uses_ollama()
+        || settings.ollama_backend != OllamaBackend::Ollama
+    {
+        return Ok(());
+    }
+    let auth = OllamaAuth::from_settings(settings);
+    ensure_vision_model(&settings.ollama_base_url, &auth, &settings.ollama_model)
+        .await
+        .map_err(|e| auth.redact_error(e))?;
+    probe_vision(
+        &settings.ollama_base_url,
+        &auth,
+        &settings.ollama_model,
+        settings.ollama_timeout_secs,
+    )
+    .await
+    .map_err(|e| auth.redact_error(e))?;
+    Ok(())
+}
+
+pub async fn test_ollama_connection(
+    base_url: &str,
+    backend: OllamaBackend,
+    auth: &OllamaAuth,
+) -> Result<String> {
+    match backend {
+        OllamaBackend::Ollama => test_connection(base_url, auth).await,
+        OllamaBackend::OpenaiCompatible => openai::test_connection(base_url, auth).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screenshot_guess_needs_a_name_hint() {
+        assert!(is_likely_screenshot(Path::new(
+            "/p/Screenshot 2024-05-01 at 10.00.00.png"
+        )));
+        assert!(is_likely_screenshot(Path::new("/p/스크린샷_20240501.jpg")));
+        assert!(!is_likely_screenshot(Path::new("/p/IMG_0001.png")));
+        assert!(!is_likely_screenshot(Path::new(
+            "/p/screenshots/IMG_0001.png"
+        )));
+    }
+
+    fn output(category: CategoryKey) -> ClassificationOutput {
+        let scores = Scores::from_map(&HashMap::from([(category.as_str().to_string(), 1.0)]));
+        ClassificationOutput {
+            model: "test".to_string(),
+            scores,
+            category,
+            tags: vec!["tag".to_string()],
+            caption: None,
+            text_in_image: None,
+            analysis_log: String::new(),
+            is_valuable: None,
+            valuable_score: None,
+            subcategory: None,
+            value_bucket: None,
+        }
+    }
+
+    // A successful row of `job_id` whose export of `source` lives at `export_path`.
+    fn exported_row(
+        id: &str,
+        job_id: &str,
+        source: &Path,
+        export_root: &Path,
+        export_path: &Path,
+    ) -> PhotoDetail {
+        let file_name = source.file_name().unwrap().to_string_lossy().into_owned();
+        let out = output(CategoryKey::People);
+        let mut detail = build_detail(
+            id.to_string(),
+            &file_name,
+            source,
+            export_root,
+            export_path,
+            out,
+        );
+        detail.job_id = Some(job_id.to_string());
+        detail
+    }
+
+    #[test]
+    fn undo_removes_only_files_the_job_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        let export_root = dir.path().join("export");
+        let people = export_root.join(CategoryKey::People.dir_name_ko());
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&people).unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg", "d.jpg"] {
+            fs::write(source.join(name), b"src").unwrap();
+        }
+        // Already in the export folder before the job ran; no row points at it.
+        let existing = people.join("existing.jpg");
+        fs::write(&existing, b"old").unwrap();
+        for name in ["a.jpg", "b.jpg", "d.jpg", "a.jpg.json"] {
+            fs::write(people.join(name), b"new").unwrap();
+        }
+
+        let db = Mutex::new(Db::init_in_memory().unwrap());
+        let mut failed = exported_row(
+            "c",
+            "job1",
+            &source.join("c.jpg"),
+            &export_root,
+            &source.join("c.jpg"),
+        );
+        failed.export_status = ExportStatus::Error;
+        db.lock()
+            .insert_photos(&[
+                exported_row(
+                    "a",
+                    "job1",
+                    &source.join("a.jpg"),
+                    &export_root,
+                    &people.join("a.jpg"),
+                ),
+                exported_row(
+                    "b",
+                    "job1",
+                    &source.join("b.jpg"),
+                    &export_root,
+                    &people.join("b.jpg"),
+                ),
+                failed,
+                exported_row(
+                    "d",
+                    "job2",
+                    &source.join("d.jpg"),
+                    &export_root,
+                    &people.join("d.jpg"),
+                ),
+            ])
+            .unwrap();
+
+        let dry =
+            undo_job_files(&db, "job1", true, |_| panic!("dry run discarded a file")).unwrap();
+        assert_eq!((dry.files, dry.rows), (2, 2));
+
+        // `b.jpg` can't be discarded, so it and its row stay for another attempt.
+        let mut discarded = Vec::new();
+        let report = undo_job_files(&db, "job1", false, |path| {
+            if path.ends_with("b.jpg") {
+                return Err(anyhow!("in use"));
+            }
+            discarded.push(path.to_path_buf());
+            Ok(fs::remove_file(path)?)
+        })
+        .unwrap();
+        assert_eq!((report.files, report.rows), (1, 1));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(discarded, [people.join("a.jpg"), people.join("a.jpg.json")]);
+
+        assert!(existing.exists());
+        assert!(people.join("b.jpg").exists());
+        assert!(people.join("d.jpg").exists());
+        assert!(source.join("a.jpg").exists());
+        let db = db.lock();
+        assert!(db.get_photo_detail("a").is_err());
+        for id in ["b", "c", "d"] {
+            assert!(db.get_photo_detail(id).is_ok(), "row {} was removed", id);
+        }
+    }
+
+    #[test]
+    fn reanalysis_replaces_the_result_on_the_same_row() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.jpg");
+        let export_root = dir.path().join("export");
+        let people = export_root.join(CategoryKey::People.dir_name_ko());
+        fs::create_dir_all(&people).unwrap();
+        fs::write(&source, b"src").unwrap();
+        fs::write(people.join("a.jpg"), b"src").unwrap();
+        let db = Db::init_in_memory().unwrap();
+        let mut original = exported_row("a", "job1", &source, &export_root, &people.join("a.jpg"));
+        original.created_at = Some("2024-05-01T10:00:00Z".to_string());
+        db.insert_photo(&original).unwrap();
+        let previous = db.get_photo_detail("a").unwrap();
+
+        let detail = export_reanalyzed(
+            &Settings::default(),
+            &previous,
+            &source,
+            &export_root,
+            "a.jpg",
+            output(CategoryKey::PetsAnimals),
+        )
+        .unwrap();
+        db.insert_photo(&detail).unwrap();
+
+        let pets = export_root.join(CategoryKey::PetsAnimals.dir_name_ko());
+        assert!(pets.join("a.jpg").exists());
+        assert!(!people.join("a.jpg").exists());
+        assert_eq!(db.count_photos(&Default::default()).unwrap(), 1);
+        let row = db.get_photo_detail("a").unwrap();
+        assert_eq!(row.category, CategoryKey::PetsAnimals);
+        assert_eq!(row.scores.pets_animals, 1.0);
+        assert_eq!(row.scores.people, 0.0);
+        assert_eq!(PathBuf::from(&row.path), pets.join("a.jpg"));
+        assert_eq!(row.job_id.as_deref(), Some("job1"));
+        assert_eq!(row.created_at.as_deref(), Some("2024-05-01T10:00:00Z"));
+    }
+
+    #[test]
+    fn error_progress_counts_only_this_jobs_photos() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.jpg");
+        let row = exported_row("a", "job-2", &source, dir.path(), &dir.path().join("a.jpg"));
+        // Resumed with 3 rows done before; one success, one failure and one dead task since.
+        let mut stats = JobStats::new(10, 3);
+        stats.record_success(&row);
+        stats.record_failure(Some(Path::new("/p/b.jpg")), "decode failed");
+        stats.record_failure(None, "task panicked");
+
+        let progress = stats.progress("job-2", JobStatus::Error);
+        assert_eq!(progress.job_id, "job-2");
+        assert!(matches!(progress.status, JobStatus::Error));
+        assert_eq!(
+            (progress.processed, progress.total, progress.errors),
+            (6, 10, 2)
+        );
+        let summary = stats.summary("job-2", AnalysisEngine::Clip, &progress);
+        assert!(summary.partial);
+        assert_eq!(
+            (summary.processed, summary.errors, summary.skipped),
+            (6, 2, 4)
+        );
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(summary.failures[0].path, "/p/b.jpg");
+    }
+
+    // An exported People row with a sidecar, in a fresh in-memory DB.
+    fn recategorize_fixture(dir: &Path) -> (Db, PathBuf, PathBuf) {
+        let source = dir.join("src").join("a.jpg");
+        let export_root = dir.join("export");
+        let exported = export_root
+            .join(CategoryKey::People.dir_name_ko())
+            .join("a.jpg");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::create_dir_all(exported.parent().unwrap()).unwrap();
+        fs::write(&source, b"src").unwrap();
+        fs::write(&exported, b"img").unwrap();
+        let mut row = exported_row("a", "job-1", &source, &export_root, &exported);
+        row.subcategory = Some("selfie".to_string());
+        write_sidecar(&row).unwrap();
+        let db = Db::init_in_memory().unwrap();
+        db.insert_photo(&row).unwrap();
+        (db, export_root, exported)
+    }
+
+    #[test]
+    fn recategorize_moves_the_file_and_updates_row_and_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, export_root, old) = recategorize_fixture(dir.path());
+        let moved = export_root
+            .join(CategoryKey::FoodCafe.dir_name_ko())
+            .join("a.jpg");
+
+        let detail = db.get_photo_detail("a").unwrap();
+        let result = recategorize_row(detail, CategoryKey::FoodCafe, |d| {
+            db.set_photo_category("a", CategoryKey::FoodCafe, &d.path)
+        })
+        .unwrap();
+        assert!(result.warning.is_none());
+        assert!(!old.exists() && !sidecar_path(&old).exists());
+        assert_eq!(fs::read(&moved).unwrap(), b"img");
+
+        let row = db.get_photo_detail("a").unwrap();
+        assert_eq!(row.category, CategoryKey::FoodCafe);
+        assert_eq!(Path::new(&row.path), moved);
+        assert_eq!(row.subcategory, None);
+        assert!(row.manual_override);
+        assert_eq!(result.photo.subcategory, None);
+
+        let sidecar: serde_json::Value =
+            serde_json::from_slice(&fs::read(sidecar_path(&moved)).unwrap()).unwrap();
+        assert_eq!(sidecar["category"], "food_cafe");
+    }
+
+    #[test]
+    fn failed_row_update_moves_the_file_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, export_root, old) = recategorize_fixture(dir.path());
+        let target = export_root
+            .join(CategoryKey::FoodCafe.dir_name_ko())
+            .join("a.jpg");
+
+        let detail = db.get_photo_detail("a").unwrap();
+        let failed = recategorize_row(detail, CategoryKey::FoodCafe, |_| {
+            Err(anyhow!("database is locked"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read(&old).unwrap(), b"img");
+        assert!(!target.exists() && !sidecar_path(&target).exists());
+        let sidecar: serde_json::Value =
+            serde_json::from_slice(&fs::read(sidecar_path(&old)).unwrap()).unwrap();
+        assert_eq!(sidecar["category"], "people");
+        let row = db.get_photo_detail("a").unwrap();
+        assert_eq!(row.category, CategoryKey::People);
+        assert_eq!(Path::new(&row.path), old);
+    }
+
+    #[test]
+    fn clip_with_stream_on_keeps_the_requested_concurrency() {
+        let settings = Settings {
+            analysis_engine: AnalysisEngine::Clip,
+            ollama_stream: true,
+            analysis_concurrency: 4,
+            ..Settings::default()
+        };
+        assert_eq!(effective_concurrency(&settings), 4);
+        let zero = Settings {
+            analysis_concurrency: 0,
+            ..settings
+        };
+        assert_eq!(effective_concurrency(&zero), 1);
+    }
+
+    #[tokio::test]
+    async fn cancel_during_a_slow_decode_returns_promptly() {
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            trigger.cancel();
+        });
+        let started = std::time::Instant::now();
+        // Stands in for a decode that never looks at the token.
+        let res = run_cancelable(&cancel, |_| {
+            std::thread::sleep(std::time::Duration::from_secs(3));
+            Ok(())
+        })
+        .await;
+        assert_eq!(res.unwrap_err().to_string(), "canceled");
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+        let done = run_cancelable(&CancellationToken::new(), |_| Ok(7)).await;
+        assert_eq!(done.unwrap(), 7);
+    }
+
+    #[test]
+    fn sidecar_is_written_next_to_the_export_with_the_analysis() {
+        let dir = tempfile::tempdir().unwrap();
+        let export_path = dir.path().join("a.jpg");
+        fs::write(&export_path, b"img").unwrap();
+        let mut row = exported_row(
+            "a",
+            "job1",
+            Path::new("/src/a.jpg"),
+            dir.path(),
+            &export_path,
+        );
+        row.caption = Some("바닷가 사진".to_string());
+        row.text_in_image = Some("SALE".to_string());
+        row.is_valuable = Some(true);
+
+        try_write_sidecar(&Settings::default(), &row);
+        assert!(!sidecar_path(&export_path).exists());
+
+        let settings = Settings {
+            write_sidecar: true,
+            ..Settings::default()
+        };
+        try_write_sidecar(&settings, &row);
+        let raw = fs::read_to_string(sidecar_path(&export_path)).unwrap();
+        let sidecar: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!(sidecar["category"], serde_json::json!(CategoryKey::People));
+        assert_eq!(sidecar["scores"]["people"], 1.0);
+        assert_eq!(sidecar["tags"], serde_json::json!(["tag"]));
+        assert_eq!(sidecar["caption"], "바닷가 사진");
+        assert_eq!(sidecar["text_in_image"], "SALE");
+        assert_eq!(sidecar["model"], "test");
+        assert_eq!(sidecar["is_valuable"], true);
+    }
+
+    #[test]
+    fn representative_errors_map_to_each_failure_kind() {
+        let kind = |e: anyhow::Error| failure_kind(&e.context("analyze a.jpg"));
+        assert_eq!(kind(anyhow!("canceled")), FailureKind::Canceled);
+        assert_eq!(kind(anyhow!("deadline has elapsed")), FailureKind::Timeout);
+        assert_eq!(
+            kind(anyhow!(
+                "parse model json: expected value at line 1 column 1 | head: I can't"
+            )),
+            FailureKind::ParseFailed
+        );
+        assert_eq!(kind(anyhow!("scores missing")), FailureKind::ParseFailed);
+        assert_eq!(
+            kind(anyhow!(
+                "ollama error 500 Internal Server Error: model llama3 does not support images"
+            )),
+            FailureKind::ModelRefused
+        );
+        assert_eq!(
+            kind(anyhow!(
+                "error sending request for url (http://127.0.0.1:11434/api/chat)"
+            )),
+            FailureKind::Network
+        );
+        assert_eq!(kind(anyhow!("disk full")), FailureKind::Other);
+    }
+
+    #[tokio::test]
+    async fn reqwest_errors_map_to_network_and_timeout() {
+        // A port that was just free refuses the connection.
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let refused = reqwest::get(&closed_url).await.unwrap_err();
+        assert_eq!(failure_kind(&refused.into()), FailureKind::Network);
+
+        // Accepts but never answers.
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let silent_url = format!("http://{}", silent.local_addr().unwrap());
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let timed_out = client.get(&silent_url).send().await.unwrap_err();
+        assert_eq!(failure_kind(&timed_out.into()), FailureKind::Timeout);
+        drop(silent);
+    }
+
+    #[tokio::test]
+    async fn in_memory_png_is_decoded_and_classified() {
+        use crate::core::ollama::tests::{json_response, mock_server, request_body};
+        use crate::core::ollama::{classify_image_with_options, ChatOptions};
+        use base64::Engine;
+
+        let mut png = Vec::new();
+        image::RgbImage::from_pixel(64, 48, image::Rgb([30, 160, 60]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let (tmp, file_name) = temp_input_image(&png, ".PNG").unwrap();
+        assert_eq!(file_name, "input.png");
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let url = mock_server(move |request, stream| {
+            tx.send(request_body(request)).unwrap();
+            let content = r#"{"category": "nature_landscape", "scores": {"nature_landscape": 0.9, "other": 0.1}, "tags": ["숲"], "caption": "초록 풍경"}"#;
+            json_response(
+                stream,
+                &serde_json::json!({"message": {"content": content}}),
+            );
+        });
+        let settings = Settings::default();
+        let cancel = CancellationToken::new();
+        let b64 = encode_for_analysis(&settings, tmp.path(), &cancel)
+            .await
+            .unwrap();
+        let chat = ChatOptions::from_settings(&settings);
+        let (out, _) = classify_image_with_options(&url, "llava", false, &chat, &b64, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(out.category, CategoryKey::NatureLandscape);
+        assert_eq!(out.tags, ["숲"]);
+
+        // The server got the PNG's pixels, re-encoded for analysis.
+        let body = rx.recv().unwrap();
+        let user = body["messages"].as_array().unwrap().last().unwrap();
+        let sent = base64::engine::general_purpose::STANDARD
+            .decode(user["images"][0].as_str().unwrap())
+            .unwrap();
+        let decoded = image::load_from_memory(&sent).unwrap().to_rgb8();
+        assert_eq!(decoded.dimensions(), (64, 48));
+        let px = decoded.get_pixel(32, 24);
+        assert!(px[1] > 140 && px[0] < 50, "{:?}", px);
+        assert!(temp_input_image(&png, "txt").is_err());
+    }
+
+    #[test]
+    fn summary_matches_a_controlled_run() {
+        let dir = tempfile::tempdir().unwrap();
+        // (id, category, valuable, duration ms, vision ms)
+        let photos = [
+            ("a", CategoryKey::People, Some(true), 120, 40),
+            ("b", CategoryKey::People, Some(false), 300, 60),
+            ("c", CategoryKey::FoodCafe, Some(true), 80, 20),
+            ("d", CategoryKey::NatureLandscape, None, 200, 80),
+        ];
+        let mut stats = JobStats::new(6, 0);
+        for (id, category, valuable, duration, vision) in photos {
+            let source = dir.path().join(format!("{}.jpg", id));
+            let mut row = exported_row(id, "job-1", &source, dir.path(), &source);
+            row.category = category;
+            row.is_valuable = valuable;
+            row.analysis_duration_ms = Some(duration);
+            row.model = Some("clip-vit-b32-onnx".to_string());
+            row.analysis_log = Some(format!("engine: clip\nvision_infer_ms: {}\n", vision));
+            stats.record_success(&row);
+        }
+        // An Ollama row only adds to the token rate: 300 tokens over 1.5 s.
+        let source = dir.path().join("e.jpg");
+        let mut ollama_row = exported_row("e", "job-1", &source, dir.path(), &source);
+        ollama_row.analysis_duration_ms = Some(100);
+        ollama_row.analysis_log = Some("eval_tokens: 300\neval_ms: 1500\n".to_string());
+        stats.record_success(&ollama_row);
+        stats.record_failure(Some(Path::new("/p/f.jpg")), "decode failed");
+
+        let progress = stats.progress("job-1", JobStatus::Completed);
+        let summary = stats.summary("job-1", AnalysisEngine::Clip, &progress);
+        assert_eq!(summary.job_id, "job-1");
+        assert!(!summary.partial);
+        assert_eq!(
+            (
+                summary.total,
+                summary.processed,
+                summary.errors,
+                summary.skipped
+            ),
+            (6, 6, 1, 0)
+        );
+        assert_eq!(summary.avg_vision_infer_ms, Some(50.0));
+        assert_eq!(summary.avg_eval_tokens_per_sec, Some(200.0));
+        assert_eq!(summary.category_counts[&CategoryKey::People], 3);
+        assert_eq!(summary.category_counts[&CategoryKey::FoodCafe], 1);
+        assert_eq!((summary.valuable, summary.not_valuable), (2, 1));
+        assert_eq!(summary.total_duration_ms, 800);
+        assert_eq!(summary.avg_duration_ms, Some(160.0));
+        let slowest: Vec<_> = summary.slowest.iter().map(|p| p.duration_ms).collect();
+        assert_eq!(slowest, [300, 200, 120, 100, 80]);
+        assert_eq!(summary.failures.len(), 1);
+        assert!(summary.images_per_sec > 0.0);
+    }
+
+    #[test]
+    fn flat_value_export_lands_in_the_category_folder_and_keeps_is_valuable() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.jpg");
+        fs::write(&source, b"img").unwrap();
+        let export_root = dir.path().join("export");
+        let mut out = output(CategoryKey::FoodCafe);
+        out.is_valuable = Some(true);
+        out.valuable_score = Some(0.8);
+
+        let flat = Settings {
+            analysis_value_enabled: true,
+            value_as_nested_folders: false,
+            ..Settings::default()
+        };
+        let exported = export_one(&flat, &export_root, &out, "a.jpg", &source).unwrap();
+        assert_eq!(
+            exported,
+            export_root
+                .join(CategoryKey::FoodCafe.dir_name_ko())
+                .join("a.jpg")
+        );
+        assert!(!export_root.join("가치있음").exists());
+
+        let db = Db::init_in_memory().unwrap();
+        let detail = build_detail(
+            "a".to_string(),
+            "a.jpg",
+            &source,
+            &export_root,
+            &exported,
+            out.clone(),
+        );
+        db.insert_photo(&detail).unwrap();
+        let stored = db.get_photo_detail("a").unwrap();
+        assert_eq!(stored.is_valuable, Some(true));
+        assert_eq!(stored.path, exported.to_string_lossy());
+
+        let nested = Settings {
+            value_as_nested_folders: true,
+            ..flat
+        };
+        let exported = export_one(&nested, &export_root, &out, "a.jpg", &source).unwrap();
+        assert_eq!(
+            exported,
+            export_root
+                .join("가치있음")
+                .join(CategoryKey::FoodCafe.dir_name_ko())
+                .join("a.jpg")
+        );
+    }
 }
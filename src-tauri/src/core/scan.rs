@@ -1,12 +1,84 @@
-use anyhow::Result;
+use crate::core::model::ScanSort;
+use anyhow::{anyhow, Result};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use walkdir::WalkDir;
 
-const ALLOWED_EXT: &[&str] = &["png", "jpg", "jpeg", "heic", "dng"];
+pub const ALLOWED_EXT: &[&str] = &["png", "jpg", "jpeg", "heic", "dng", "cr2", "nef", "arw"];
 
-pub fn scan_sources(root: &Path) -> Result<Vec<PathBuf>> {
+// Read from the source root; its patterns apply on top of `ScanOptions::exclude_globs`.
+pub const IGNORE_FILE_NAME: &str = ".imgsortignore";
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub exclude_globs: Vec<String>,
+    pub max_depth: Option<usize>,
+    // Skipped when nested under the source root (e.g. the export folder).
+    pub exclude_dir: Option<PathBuf>,
+    pub sort: ScanSort,
+}
+
+// Reported every `SCAN_PROGRESS_ENTRIES` walked entries or `SCAN_PROGRESS_INTERVAL`, whichever
+// comes first, and once more with `done` when the walk ends.
+const SCAN_PROGRESS_ENTRIES: usize = 1000;
+const SCAN_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanProgress {
+    // Entries walked so far (directories and non-image files included) and images among them.
+    pub scanned: usize,
+    pub found: usize,
+    pub done: bool,
+}
+
+pub fn scan_sources(
+    root: &Path,
+    opts: &ScanOptions,
+    mut on_progress: Option<&mut dyn FnMut(ScanProgress)>,
+) -> Result<Vec<PathBuf>> {
+    let excludes = build_exclude_set(&opts.exclude_globs)?;
+    let ignore_file = IgnoreFile::load(&root.join(IGNORE_FILE_NAME))?;
+    let excluded_dir = opts
+        .exclude_dir
+        .as_deref()
+        .and_then(|dir| nested_relative(root, dir));
+
+    let mut walker = WalkDir::new(root);
+    if let Some(depth) = opts.max_depth {
+        walker = walker.max_depth(depth);
+    }
     let mut files = Vec::new();
-    for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+    let entries = walker.into_iter().filter_entry(|entry| {
+        let Ok(rel) = entry.path().strip_prefix(root) else {
+            return true;
+        };
+        if rel.as_os_str().is_empty() {
+            return true;
+        }
+        if excluded_dir.as_deref() == Some(rel) {
+            return false;
+        }
+        if ignore_file.is_match(rel, entry.file_type().is_dir()) {
+            return false;
+        }
+        !(excludes.is_match(rel) || excludes.is_match(Path::new(entry.file_name())))
+    });
+    let mut progress = ScanProgress::default();
+    let mut last_report = (0, Instant::now());
+    for entry in entries.filter_map(|e| e.ok()) {
+        progress.scanned += 1;
+        if let Some(cb) = on_progress.as_mut() {
+            if progress.scanned - last_report.0 >= SCAN_PROGRESS_ENTRIES
+                || last_report.1.elapsed() >= SCAN_PROGRESS_INTERVAL
+            {
+                progress.found = files.len();
+                cb(progress);
+                last_report = (progress.scanned, Instant::now());
+            }
+        }
         if entry.file_type().is_file() {
             if let Some(ext) = entry.path().extension().and_then(|s| s.to_str()) {
                 if ALLOWED_EXT.contains(&ext.to_lowercase().as_str()) {
@@ -15,5 +87,370 @@ pub fn scan_sources(root: &Path) -> Result<Vec<PathBuf>> {
             }
         }
     }
+    if let Some(cb) = on_progress {
+        progress.found = files.len();
+        progress.done = true;
+        cb(progress);
+    }
+    sort_files(&mut files, opts.sort);
     Ok(files)
 }
+
+// Ties (same name or mtime) fall back to the full path so the order never depends on the walk.
+pub fn sort_files(files: &mut [PathBuf], sort: ScanSort) {
+    match sort {
+        ScanSort::None => {}
+        ScanSort::NameAsc => files.sort_by(|a, b| (a.file_name(), a).cmp(&(b.file_name(), b))),
+        ScanSort::NameDesc => files.sort_by(|a, b| (b.file_name(), b).cmp(&(a.file_name(), a))),
+        ScanSort::MtimeAsc | ScanSort::MtimeDesc => {
+            files.sort_by_cached_key(|p| (modified(p), p.clone()));
+            if sort == ScanSort::MtimeDesc {
+                files.reverse();
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> SystemTime {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+fn build_exclude_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        builder.add(Glob::new(pattern).map_err(|e| anyhow!("invalid exclude pattern: {}", e))?);
+    }
+    Ok(builder.build()?)
+}
+
+// A subset of `.gitignore` syntax: one glob per line, `#` comments, a leading `/` anchors the
+// pattern to the root (otherwise it matches at any depth) and a trailing `/` limits it to
+// folders. `*` stays within one path component; `**` crosses them. Negated `!` lines are not
+// supported and are skipped.
+#[derive(Default)]
+struct IgnoreFile {
+    any: GlobSet,
+    dirs: GlobSet,
+}
+
+impl IgnoreFile {
+    // A missing file ignores nothing.
+    fn load(path: &Path) -> Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(anyhow!("failed to read {}: {}", path.display(), e)),
+        };
+        let mut any = GlobSetBuilder::new();
+        let mut dirs = GlobSetBuilder::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+            let (line, dir_only) = match line.strip_suffix('/') {
+                Some(rest) => (rest, true),
+                None => (line, false),
+            };
+            let pattern = match line.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if line.contains('/') => line.to_string(),
+                None => format!("**/{}", line),
+            };
+            let glob = GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| {
+                    anyhow!(
+                        "invalid pattern in {} line {}: {}",
+                        IGNORE_FILE_NAME,
+                        idx + 1,
+                        e
+                    )
+                })?;
+            if dir_only {
+                dirs.add(glob);
+            } else {
+                any.add(glob);
+            }
+        }
+        Ok(Self {
+            any: any.build()?,
+            dirs: dirs.build()?,
+        })
+    }
+
+    fn is_match(&self, rel: &Path, is_dir: bool) -> bool {
+        self.any.is_match(rel) || (is_dir && self.dirs.is_match(rel))
+    }
+}
+
+// `dir` relative to `root` when it lives inside it; both are canonicalized so `..`/symlinked
+// spellings of the same folder still match.
+fn nested_relative(root: &Path, dir: &Path) -> Option<PathBuf> {
+    let root = root.canonicalize().ok()?;
+    let dir = dir.canonicalize().ok()?;
+    let rel = dir.strip_prefix(&root).ok()?;
+    (!rel.as_os_str().is_empty()).then(|| rel.to_path_buf())
+}
+
+const DEFAULT_SAMPLE_SEED: u64 = 0x5EED_1A6E_5047_2024;
+
+// Limits `files` to `max` entries for quick preview runs. Without `random` the first `max` files
+// in scan order are kept; with it a seeded shuffle (over the path-sorted list) picks them so the
+// same folder yields the same sample. Either way the kept files stay in scan order.
+pub fn limit_files(
+    mut files: Vec<PathBuf>,
+    max: Option<usize>,
+    random: bool,
+    seed: Option<u64>,
+) -> Vec<PathBuf> {
+    let Some(max) = max else {
+        return files;
+    };
+    if files.len() <= max {
+        return files;
+    }
+    if random {
+        let mut pool = files.clone();
+        pool.sort();
+        let mut state = seed.unwrap_or(DEFAULT_SAMPLE_SEED);
+        // Partial Fisher-Yates: only the first `max` slots need to be drawn.
+        for i in 0..max {
+            let j = i + (splitmix64(&mut state) % (pool.len() - i) as u64) as usize;
+            pool.swap(i, j);
+        }
+        pool.truncate(max);
+        let picked: HashSet<PathBuf> = pool.into_iter().collect();
+        files.retain(|p| picked.contains(p));
+    } else {
+        files.truncate(max);
+    }
+    files
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn name_sort_breaks_ties_on_the_full_path() {
+        let mut files = paths(&["/b/2.jpg", "/a/1.jpg", "/c/1.jpg", "/a/3.jpg"]);
+        sort_files(&mut files, ScanSort::NameAsc);
+        assert_eq!(
+            files,
+            paths(&["/a/1.jpg", "/c/1.jpg", "/b/2.jpg", "/a/3.jpg"])
+        );
+        sort_files(&mut files, ScanSort::NameDesc);
+        assert_eq!(
+            files,
+            paths(&["/a/3.jpg", "/b/2.jpg", "/c/1.jpg", "/a/1.jpg"])
+        );
+    }
+
+    #[test]
+    fn mtime_sort_orders_by_modification_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut files = Vec::new();
+        for (name, secs) in [("a.jpg", 300), ("b.jpg", 100), ("c.jpg", 200)] {
+            let path = dir.path().join(name);
+            let file = fs::File::create(&path).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+            files.push(path);
+        }
+        let names = |files: &[PathBuf]| -> Vec<String> {
+            files
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        sort_files(&mut files, ScanSort::MtimeAsc);
+        assert_eq!(names(&files), ["b.jpg", "c.jpg", "a.jpg"]);
+        sort_files(&mut files, ScanSort::MtimeDesc);
+        assert_eq!(names(&files), ["a.jpg", "c.jpg", "b.jpg"]);
+        sort_files(&mut files, ScanSort::None);
+        assert_eq!(names(&files), ["a.jpg", "c.jpg", "b.jpg"]);
+    }
+
+    fn touch(root: &Path, rel: &str) {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+
+    fn scanned(root: &Path, opts: &ScanOptions) -> Vec<String> {
+        scan_sources(root, opts, None)
+            .unwrap()
+            .iter()
+            .map(|p| {
+                p.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exclude_globs_skip_matching_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        for rel in [
+            "a.jpg",
+            "notes.txt",
+            "@eaDir/a.jpg",
+            "trip/.thumbnails/b.jpg",
+            "trip/b.jpg",
+            "trip/b_thumb.jpg",
+        ] {
+            touch(dir.path(), rel);
+        }
+        let opts = ScanOptions {
+            exclude_globs: vec!["@eaDir".into(), ".thumbnails".into(), "*_thumb.jpg".into()],
+            sort: ScanSort::NameAsc,
+            ..Default::default()
+        };
+        assert_eq!(scanned(dir.path(), &opts), ["a.jpg", "trip/b.jpg"]);
+    }
+
+    #[test]
+    fn invalid_exclude_glob_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ScanOptions {
+            exclude_globs: vec!["[".into()],
+            ..Default::default()
+        };
+        assert!(scan_sources(dir.path(), &opts, None).is_err());
+    }
+
+    #[test]
+    fn max_depth_limits_the_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        for rel in ["a.jpg", "one/b.jpg", "one/two/c.jpg"] {
+            touch(dir.path(), rel);
+        }
+        let opts = |max_depth| ScanOptions {
+            max_depth,
+            sort: ScanSort::NameAsc,
+            ..Default::default()
+        };
+        assert_eq!(scanned(dir.path(), &opts(Some(1))), ["a.jpg"]);
+        assert_eq!(scanned(dir.path(), &opts(Some(2))), ["a.jpg", "one/b.jpg"]);
+        assert_eq!(
+            scanned(dir.path(), &opts(None)),
+            ["a.jpg", "one/b.jpg", "one/two/c.jpg"]
+        );
+    }
+
+    #[test]
+    fn limit_keeps_the_first_files_in_scan_order() {
+        let files = paths(&["/c.jpg", "/a.jpg", "/b.jpg", "/d.jpg"]);
+        assert_eq!(limit_files(files.clone(), None, false, None), files);
+        assert_eq!(limit_files(files.clone(), Some(10), true, None), files);
+        assert_eq!(
+            limit_files(files, Some(2), false, None),
+            paths(&["/c.jpg", "/a.jpg"])
+        );
+    }
+
+    #[test]
+    fn random_limit_is_reproducible_and_keeps_scan_order() {
+        let files: Vec<PathBuf> = (0..50)
+            .map(|i| PathBuf::from(format!("/{:02}.jpg", 49 - i)))
+            .collect();
+        let sample = limit_files(files.clone(), Some(10), true, Some(7));
+        assert_eq!(sample.len(), 10);
+        assert_eq!(sample, limit_files(files.clone(), Some(10), true, Some(7)));
+        // The seed shuffles over the path-sorted list, so the walk order doesn't change the pick.
+        let mut shuffled = files.clone();
+        shuffled.reverse();
+        let mut resorted = limit_files(shuffled, Some(10), true, Some(7));
+        resorted.reverse();
+        assert_eq!(sample, resorted);
+        let positions: Vec<usize> = sample
+            .iter()
+            .map(|p| files.iter().position(|f| f == p).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert_ne!(sample, limit_files(files, Some(10), true, Some(8)));
+    }
+
+    #[test]
+    fn progress_is_reported_while_a_large_tree_is_walked() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..SCAN_PROGRESS_ENTRIES * 2 + 100 {
+            let ext = if i % 4 == 0 { "txt" } else { "jpg" };
+            touch(dir.path(), &format!("batch{}/{:05}.{}", i % 3, i, ext));
+        }
+        let mut reports = Vec::new();
+        let files = scan_sources(
+            dir.path(),
+            &ScanOptions::default(),
+            Some(&mut |p: ScanProgress| reports.push(p)),
+        )
+        .unwrap();
+
+        let (last, running) = reports.split_last().unwrap();
+        assert!(running.len() >= 2, "only {} running reports", running.len());
+        assert!(running.iter().all(|p| !p.done && p.found <= p.scanned));
+        assert!(running.windows(2).all(|w| w[0].scanned < w[1].scanned));
+        assert!(running.windows(2).all(|w| w[0].found <= w[1].found));
+        assert!(last.done);
+        assert_eq!(last.found, files.len());
+        assert_eq!(files.len(), (SCAN_PROGRESS_ENTRIES * 2 + 100) * 3 / 4);
+        // Every file and folder, plus the root itself.
+        assert_eq!(last.scanned, SCAN_PROGRESS_ENTRIES * 2 + 100 + 3 + 1);
+    }
+
+    #[test]
+    fn ignore_file_excludes_a_subfolder() {
+        let dir = tempfile::tempdir().unwrap();
+        for rel in [
+            "a.jpg",
+            "raw/b.jpg",
+            "trip/raw/c.jpg",
+            "trip/d.jpg",
+            "trip/d_edit.jpg",
+            "exports/e.jpg",
+            "exports.jpg",
+        ] {
+            touch(dir.path(), rel);
+        }
+        fs::write(
+            dir.path().join(IGNORE_FILE_NAME),
+            "# generated folders\n/raw/\nexports/\n\n*_edit.jpg\n!trip/raw/\n",
+        )
+        .unwrap();
+        let opts = ScanOptions {
+            sort: ScanSort::NameAsc,
+            ..Default::default()
+        };
+        // `/raw/` only matches at the root, `exports/` skips the folder but not a file of that
+        // name, and the `!` line is ignored.
+        assert_eq!(
+            scanned(dir.path(), &opts),
+            ["a.jpg", "trip/raw/c.jpg", "trip/d.jpg", "exports.jpg"]
+        );
+
+        // Settings globs still apply on top of the ignore file.
+        let opts = ScanOptions {
+            exclude_globs: vec!["trip".into()],
+            ..opts
+        };
+        assert_eq!(scanned(dir.path(), &opts), ["a.jpg", "exports.jpg"]);
+    }
+}
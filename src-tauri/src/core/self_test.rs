@@ -0,0 +1,124 @@
+use crate::core::classifier::clip_self_test;
+use crate::core::clip::ClipEngine;
+use crate::core::decode::{decode_dynamic_image, write_temp_image};
+use crate::core::model::{SelfTestReport, SelfTestStage, Settings};
+use crate::core::ollama::OllamaAuth;
+use crate::core::pipeline::test_ollama_connection;
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::time::Instant;
+use tauri::AppHandle;
+
+// 64x64 PNG shipped with the binary so the check doesn't depend on the user's files.
+const FIXTURE_PNG: &[u8] = include_bytes!("../../assets/self_test.png");
+
+// Runs decode -> CLIP -> (Ollama) once against the bundled fixture. Stage failures are reported in
+// the result instead of aborting, so one broken stage doesn't hide the others.
+pub async fn run_self_test(app: &AppHandle, settings: &Settings) -> Result<SelfTestReport> {
+    let (app_bg, settings_bg) = (app.clone(), settings.clone());
+    let mut report =
+        tauri::async_runtime::spawn_blocking(move || local_stages(&app_bg, &settings_bg)).await?;
+
+    if settings.analysis_engine.uses_ollama() {
+        let started = Instant::now();
+        let auth = OllamaAuth::from_settings(settings);
+        let connected =
+            test_ollama_connection(&settings.ollama_base_url, settings.ollama_backend, &auth)
+                .await
+                .map_err(|e| auth.redact_error(e));
+        report.ollama = Some(stage(started, connected));
+    }
+    Ok(report)
+}
+
+// Decode and CLIP are CPU-bound and share the fixture's temp file.
+fn local_stages(app: &AppHandle, settings: &Settings) -> SelfTestReport {
+    let clip_model_dir = ClipEngine::resolve_model_dir(app, settings.clip_model_dir.as_deref())
+        .ok()
+        .map(|p| p.display().to_string());
+    fixture_stages(clip_model_dir, |path| clip_self_test(app, settings, path))
+}
+
+// `clip` gets the decoded fixture's path and returns its summary and execution providers.
+fn fixture_stages(
+    clip_model_dir: Option<String>,
+    clip: impl FnOnce(&Path) -> Result<(String, String)>,
+) -> SelfTestReport {
+    let started = Instant::now();
+    let decoded = write_temp_image(FIXTURE_PNG, "png").and_then(|tmp| {
+        let img = decode_dynamic_image(tmp.path(), None)?;
+        Ok((tmp, img))
+    });
+    let (decode, fixture) = match decoded {
+        Ok((tmp, img)) => (
+            stage(started, Ok(format!("{}x{}", img.width(), img.height()))),
+            Some(tmp),
+        ),
+        Err(e) => (stage(started, Err(e)), None),
+    };
+
+    let started = Instant::now();
+    let (clip, clip_eps) = match fixture {
+        Some(tmp) => match clip(tmp.path()) {
+            Ok((summary, eps)) => (stage(started, Ok(summary)), Some(eps)),
+            Err(e) => (stage(started, Err(e)), None),
+        },
+        None => (stage(started, Err(anyhow!("skipped: decode failed"))), None),
+    };
+
+    SelfTestReport {
+        decode,
+        clip,
+        ollama: None,
+        clip_model_dir,
+        clip_eps,
+    }
+}
+
+fn stage(started: Instant, result: Result<String>) -> SelfTestStage {
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    match result {
+        Ok(detail) => SelfTestStage {
+            ok: true,
+            elapsed_ms,
+            detail: Some(detail),
+            error: None,
+        },
+        Err(e) => SelfTestStage {
+            ok: false,
+            elapsed_ms,
+            detail: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_and_clip_stages_pass_with_the_fixture() {
+        // Stands in for the CLIP engine, which needs model files; it still has to read the fixture.
+        let report = fixture_stages(Some("/models/clip".to_string()), |path| {
+            let img = decode_dynamic_image(path, None)?;
+            Ok((
+                format!("classified {}x{}", img.width(), img.height()),
+                "cpu".to_string(),
+            ))
+        });
+
+        assert!(report.decode.ok, "{:?}", report.decode.error);
+        assert_eq!(report.decode.detail.as_deref(), Some("64x64"));
+        assert!(report.clip.ok, "{:?}", report.clip.error);
+        assert_eq!(report.clip.detail.as_deref(), Some("classified 64x64"));
+        assert_eq!(report.clip_eps.as_deref(), Some("cpu"));
+        assert_eq!(report.clip_model_dir.as_deref(), Some("/models/clip"));
+        assert!(report.ollama.is_none());
+
+        let failed = fixture_stages(None, |_| Err(anyhow!("model.onnx not found")));
+        assert!(failed.decode.ok);
+        assert!(!failed.clip.ok);
+        assert_eq!(failed.clip.error.as_deref(), Some("model.onnx not found"));
+    }
+}
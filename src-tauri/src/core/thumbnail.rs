@@ -0,0 +1,93 @@
+use crate::core::decode::{
+    write_resized_jpeg, DecodeOptions, EncodeFormat, ResizeSpec, DEFAULT_ALPHA_BACKGROUND,
+};
+use anyhow::{anyhow, Result};
+use image::imageops::FilterType;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const THUMBNAIL_DIR: &str = "thumbnails";
+const THUMBNAIL_MAX_EDGE: u32 = 256;
+const THUMBNAIL_JPEG_QUALITY: u8 = 80;
+
+pub fn thumbnail_dir(app: &AppHandle) -> Result<PathBuf> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("app data dir: {}", e))?
+        .join(THUMBNAIL_DIR);
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// Photo ids are UUIDs, so they are safe to use as file names directly.
+pub fn thumbnail_path(app: &AppHandle, id: &str) -> Result<PathBuf> {
+    Ok(thumbnail_dir(app)?.join(format!("{}.jpg", id)))
+}
+
+pub fn generate_thumbnail(app: &AppHandle, id: &str, source: &Path) -> Result<PathBuf> {
+    let dest = thumbnail_path(app, id)?;
+    write_thumbnail(source, &dest)?;
+    Ok(dest)
+}
+
+fn write_thumbnail(source: &Path, dest: &Path) -> Result<(u32, u32)> {
+    write_resized_jpeg(
+        source,
+        dest,
+        DecodeOptions {
+            resize_enabled: true,
+            resize: ResizeSpec::MaxEdge(THUMBNAIL_MAX_EDGE),
+            allow_upscale: false,
+            jpeg_quality: THUMBNAIL_JPEG_QUALITY,
+            resize_filter: FilterType::Triangle,
+            encode_format: EncodeFormat::Jpeg,
+            alpha_background: DEFAULT_ALPHA_BACKGROUND,
+        },
+    )
+}
+
+// A missing thumbnail must never fail the photo itself, so errors are only reported.
+pub fn try_generate_thumbnail(app: &AppHandle, id: &str, source: &Path) -> Option<String> {
+    match generate_thumbnail(app, id, source) {
+        Ok(path) => Some(path.to_string_lossy().to_string()),
+        Err(e) => {
+            eprintln!("thumbnail failed for {}: {}", source.display(), e);
+            None
+        }
+    }
+}
+
+pub fn clear_thumbnails(app: &AppHandle) -> Result<()> {
+    let dir = thumbnail_dir(app)?;
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbnail_is_capped_at_the_max_edge() {
+        let dir = tempfile::tempdir().unwrap();
+        let wide = dir.path().join("wide.png");
+        image::RgbImage::from_pixel(800, 400, image::Rgb([200, 10, 10]))
+            .save(&wide)
+            .unwrap();
+        let dest = dir.path().join("wide.jpg");
+        assert_eq!(write_thumbnail(&wide, &dest).unwrap(), (256, 128));
+        let thumb = image::open(&dest).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (THUMBNAIL_MAX_EDGE, 128));
+
+        // Smaller images keep their size.
+        let small = dir.path().join("small.png");
+        image::RgbImage::from_pixel(100, 50, image::Rgb([0, 0, 0]))
+            .save(&small)
+            .unwrap();
+        let dest = dir.path().join("small.jpg");
+        write_thumbnail(&small, &dest).unwrap();
+        assert_eq!(image::image_dimensions(&dest).unwrap(), (100, 50));
+    }
+}
@@ -1,7 +1,11 @@
 mod core;
 
 use crate::core::commands::*;
-use tauri::Manager;
+use std::time::Duration;
+use tauri::{Manager, RunEvent};
+
+// How long exit waits for the active job to stop and save its rows.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -19,17 +23,67 @@ pub fn run() {
             set_settings,
             test_ollama,
             list_ollama_models,
+            list_ollama_models_detailed,
+            check_ollama_model,
+            pull_ollama_model,
+            cancel_model_pull,
             get_clip_model_files,
             get_clip_accel_capabilities,
+            get_clip_engine_status,
+            get_clip_warmup_status,
+            unload_clip_engine,
+            reload_clip_engine,
             start_analysis,
             cancel_analysis,
             list_photos,
+            count_photos,
+            list_photos_since,
+            list_jobs,
+            resume_job,
+            retry_failed,
+            get_job_queue,
+            remove_queued_job,
+            test_clip_prompts,
             get_photo_detail,
+            reveal_in_file_manager,
+            delete_photos,
+            set_photo_category,
+            undo_last_export,
+            get_thumbnail,
+            reanalyze_photo,
+            classify_bytes,
             get_distribution,
             get_progress,
+            get_last_job_summary,
             get_value_stats,
-            clear_results
+            get_tag_stats,
+            clear_results,
+            get_error_log_path,
+            clear_error_log,
+            clear_debug_captures,
+            self_test
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::ExitRequested { api, .. } = event {
+                let state = app.state::<AppState>();
+                let pipeline = state.pipeline.lock();
+                // The second request comes from `app.exit` below, after the job stopped or
+                // the timeout passed.
+                if !pipeline.is_running() || pipeline.is_shutting_down() {
+                    return;
+                }
+                api.prevent_exit();
+                let shutdown = pipeline.shutdown();
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    let stopped = tokio::time::timeout(SHUTDOWN_TIMEOUT, shutdown).await;
+                    if stopped.is_err() {
+                        eprintln!("job did not stop in time; exiting anyway");
+                    }
+                    app.exit(0);
+                });
+            }
+        });
 }
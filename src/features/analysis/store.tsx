@@ -14,24 +14,43 @@ import {
   getClipAccelCapabilities,
   getClipModelFiles,
   getDistribution,
+  getJobQueue,
   getPhotoDetail,
   getSettings as apiGetSettings,
   getValueStats,
   listOllamaModels,
   listPhotos,
+  onClipWarmup,
+  onJobQueue,
+  onJobSummary,
+  getLastJobSummary,
+  onPhotos,
   onProgress,
+  onScanProgress,
   onStream,
+  removeQueuedJob,
+  resumeJob,
+  retryFailed,
   setSettings as apiSetSettings,
   startAnalysis,
   testOllama,
 } from "../../lib/api";
 import {
+  AnalysisEngine,
+  AnalysisImageFormat,
   CategoryKey,
   ClipAccelCapabilities,
+  ClipWarmupEvent,
   Distribution,
+  FailureKind,
+  JobSummary,
+  OutputLanguage,
   PhotoDetail,
   PhotoRow,
   Progress,
+  QueuedJobInfo,
+  ScanProgressEvent,
+  SettingsOverride,
   StreamChunk,
   ValueStats,
 } from "../../lib/api/types";
@@ -44,25 +63,35 @@ interface AnalysisState {
   settingsModel: string;
   settingsThink: boolean;
   settingsStream: boolean;
+  settingsOllamaTimeoutSecs: number;
+  settingsOutputLanguage: OutputLanguage;
   settingsResizeEnabled: boolean;
   settingsMaxEdge: number;
+  settingsResizePercent: number;
   settingsJpegQuality: number;
+  settingsImageFormat: AnalysisImageFormat;
   settingsValueEnabled: boolean;
   settingsConcurrency: number;
-  settingsEngine: "clip" | "ollama";
+  settingsEngine: AnalysisEngine;
   settingsClipModelDir: string;
   settingsClipModelFile: string;
   settingsClipFallback: boolean;
   settingsClipEpAuto: boolean;
   settingsClipEpCoreml: boolean;
+  settingsClipEpTensorrt: boolean;
   settingsClipEpCuda: boolean;
   settingsClipEpRocm: boolean;
   settingsClipEpDirectml: boolean;
   settingsClipEpOpenvino: boolean;
+  settingsClipGpuDeviceId: number;
   clipAccelCaps: ClipAccelCapabilities | null;
+  clipWarmup: ClipWarmupEvent | null;
+  scanProgress: ScanProgressEvent | null;
   clipModelFiles: string[];
   availableModels: string[];
   jobElapsedMs: number | null;
+  jobSummary: JobSummary | null;
+  jobQueue: QueuedJobInfo[];
   progress: Progress;
   photos: PhotoRow[];
   distributionAvg: Distribution | null;
@@ -72,7 +101,18 @@ interface AnalysisState {
   selectedPhoto: PhotoDetail | null;
   loadingDetail: boolean;
   starting: boolean;
-  streamPanel: { fileName: string | null; text: string; isOpen: boolean };
+  streamPanel: StreamPanel;
+}
+
+// One pane per worker slot, so parallel streams don't interleave.
+export interface StreamPane {
+  fileName: string | null;
+  text: string;
+}
+
+export interface StreamPanel {
+  panes: Record<number, StreamPane>;
+  isOpen: boolean;
 }
 
 interface AnalysisActions {
@@ -83,25 +123,37 @@ interface AnalysisActions {
     model: string;
     think: boolean;
     stream: boolean;
+    ollamaTimeoutSecs: number;
+    outputLanguage: OutputLanguage;
     resizeEnabled: boolean;
     maxEdge: number;
+    resizePercent: number;
     jpegQuality: number;
+    imageFormat: AnalysisImageFormat;
     valueEnabled: boolean;
     concurrency: number;
-    engine: "clip" | "ollama";
+    engine: AnalysisEngine;
     clipModelDir: string;
     clipModelFile: string;
     clipFallbackToOllama: boolean;
     clipEpAuto: boolean;
     clipEpCoreml: boolean;
+    clipEpTensorrt: boolean;
     clipEpCuda: boolean;
     clipEpRocm: boolean;
     clipEpDirectml: boolean;
     clipEpOpenvino: boolean;
+    clipGpuDeviceId: number;
   }) => void;
   testConnection: (baseUrl: string) => Promise<void>;
-  startAnalysisNow: () => Promise<void>;
-  cancelCurrent: () => Promise<void>;
+  startAnalysisNow: (
+    sample?: { maxImages: number; sampleRandom: boolean },
+    settingsOverride?: SettingsOverride
+  ) => Promise<void>;
+  resumeJobNow: (jobId: string) => Promise<void>;
+  retryFailedNow: (jobId?: string, kinds?: FailureKind[]) => Promise<void>;
+  cancelCurrent: (cancelAll?: boolean) => Promise<void>;
+  removeFromQueue: (jobId: string) => Promise<void>;
   setCategoryFilter: (c: CategoryKey | "all") => void;
   loadPhotoDetail: (id: string) => Promise<void>;
   closeDetail: () => void;
@@ -120,24 +172,32 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
   const [settingsModel, setSettingsModel] = useState<string>("qwen2.5vl:7b");
   const [settingsThink, setSettingsThink] = useState<boolean>(false);
   const [settingsStream, setSettingsStream] = useState<boolean>(false);
+  const [settingsOllamaTimeoutSecs, setSettingsOllamaTimeoutSecs] = useState<number>(120);
+  const [settingsOutputLanguage, setSettingsOutputLanguage] = useState<OutputLanguage>("ko");
   const [settingsResizeEnabled, setSettingsResizeEnabled] =
     useState<boolean>(true);
   const [settingsMaxEdge, setSettingsMaxEdge] = useState<number>(768);
+  const [settingsResizePercent, setSettingsResizePercent] = useState<number>(0);
   const [settingsJpegQuality, setSettingsJpegQuality] = useState<number>(60);
+  const [settingsImageFormat, setSettingsImageFormat] = useState<AnalysisImageFormat>("auto");
   const [settingsValueEnabled, setSettingsValueEnabled] = useState<boolean>(false);
   const [settingsConcurrency, setSettingsConcurrency] = useState<number>(4);
-  const [settingsEngine, setSettingsEngine] = useState<"clip" | "ollama">("clip");
+  const [settingsEngine, setSettingsEngine] = useState<AnalysisEngine>("clip");
   const [settingsClipModelDir, setSettingsClipModelDir] = useState<string>("");
   const [settingsClipModelFile, setSettingsClipModelFile] =
     useState<string>("onnx/model_q4f16.onnx");
   const [settingsClipFallback, setSettingsClipFallback] = useState<boolean>(true);
   const [settingsClipEpAuto, setSettingsClipEpAuto] = useState<boolean>(true);
   const [settingsClipEpCoreml, setSettingsClipEpCoreml] = useState<boolean>(true);
+  const [settingsClipEpTensorrt, setSettingsClipEpTensorrt] = useState<boolean>(false);
   const [settingsClipEpCuda, setSettingsClipEpCuda] = useState<boolean>(false);
   const [settingsClipEpRocm, setSettingsClipEpRocm] = useState<boolean>(false);
   const [settingsClipEpDirectml, setSettingsClipEpDirectml] = useState<boolean>(false);
   const [settingsClipEpOpenvino, setSettingsClipEpOpenvino] = useState<boolean>(false);
+  const [settingsClipGpuDeviceId, setSettingsClipGpuDeviceId] = useState<number>(0);
   const [clipAccelCaps, setClipAccelCaps] = useState<ClipAccelCapabilities | null>(null);
+  const [clipWarmup, setClipWarmup] = useState<ClipWarmupEvent | null>(null);
+  const [scanProgress, setScanProgress] = useState<ScanProgressEvent | null>(null);
   const [clipModelFiles, setClipModelFiles] = useState<string[]>([]);
   const [availableModels, setAvailableModels] = useState<string[]>([]);
   const [progress, setProgress] = useState<Progress>({
@@ -157,11 +217,9 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
   const [starting, setStarting] = useState(false);
   const [jobElapsedMs, setJobElapsedMs] = useState<number | null>(null);
   const jobStartedAtRef = useRef<number | null>(null);
-  const [streamPanel, setStreamPanel] = useState<{
-    fileName: string | null;
-    text: string;
-    isOpen: boolean;
-  }>({ fileName: null, text: "", isOpen: false });
+  const [jobSummary, setJobSummary] = useState<JobSummary | null>(null);
+  const [jobQueue, setJobQueue] = useState<QueuedJobInfo[]>([]);
+  const [streamPanel, setStreamPanel] = useState<StreamPanel>({ panes: {}, isOpen: false });
 
   useEffect(() => {
     apiGetSettings().then((s) => {
@@ -169,9 +227,15 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       setSettingsModel(s.ollamaModel);
       setSettingsThink(s.ollamaThink);
       setSettingsStream(s.ollamaStream);
+      setSettingsOllamaTimeoutSecs(
+        Number.isFinite(s.ollamaTimeoutSecs) ? Number(s.ollamaTimeoutSecs) : 120
+      );
+      setSettingsOutputLanguage(s.outputLanguage ?? "ko");
       setSettingsResizeEnabled(s.analysisResizeEnabled);
       setSettingsMaxEdge(s.analysisMaxEdge);
+      setSettingsResizePercent(s.analysisResizePercent ?? 0);
       setSettingsJpegQuality(s.analysisJpegQuality);
+      setSettingsImageFormat(s.analysisImageFormat ?? "auto");
       setSettingsValueEnabled(Boolean(s.analysisValueEnabled));
       setSettingsConcurrency(
         Number.isFinite(s.analysisConcurrency) ? Number(s.analysisConcurrency) : 4
@@ -182,10 +246,12 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       setSettingsClipFallback(Boolean(s.clipFallbackToOllama));
       setSettingsClipEpAuto(Boolean(s.clipEpAuto));
       setSettingsClipEpCoreml(Boolean(s.clipEpCoreml));
+      setSettingsClipEpTensorrt(Boolean(s.clipEpTensorrt));
       setSettingsClipEpCuda(Boolean(s.clipEpCuda));
       setSettingsClipEpRocm(Boolean(s.clipEpRocm));
       setSettingsClipEpDirectml(Boolean(s.clipEpDirectml));
       setSettingsClipEpOpenvino(Boolean(s.clipEpOpenvino));
+      setSettingsClipGpuDeviceId(s.clipGpuDeviceId ?? 0);
     });
     getClipAccelCapabilities()
       .then((caps) => setClipAccelCaps(caps))
@@ -212,16 +278,64 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
     return unsub;
   }, []);
 
+  useEffect(() => {
+    const unsub = onClipWarmup((e) => setClipWarmup(e));
+    return unsub;
+  }, []);
+
+  useEffect(() => {
+    const unsub = onScanProgress((e) => setScanProgress(e.done ? null : e));
+    return unsub;
+  }, []);
+
+  useEffect(() => {
+    getLastJobSummary()
+      .then((s) => s && setJobSummary(s))
+      .catch(() => {});
+    const unsub = onJobSummary((s) => setJobSummary(s));
+    return unsub;
+  }, []);
+
+  useEffect(() => {
+    getJobQueue()
+      .then((q) => setJobQueue(q))
+      .catch(() => {});
+    const unsub = onJobQueue((e) => {
+      setJobQueue(e.queue);
+      if (e.promoted) {
+        jobStartedAtRef.current = Date.now();
+        setJobElapsedMs(0);
+        setStreamPanel({ panes: {}, isOpen: false });
+        toast.info("대기열의 다음 작업을 시작합니다");
+      }
+    });
+    return unsub;
+  }, []);
+
+  // Rows arrive as the job writes them, so the grid grows without re-listing every photo.
+  useEffect(() => {
+    const unsub = onPhotos((e) => {
+      setPhotos((prev) => {
+        const incoming = new Map(e.rows.map((r) => [r.id, r]));
+        const kept = prev.map((r) => incoming.get(r.id) ?? r);
+        const known = new Set(prev.map((r) => r.id));
+        const added = e.rows.filter((r) => !known.has(r.id)).reverse();
+        return [...added, ...kept];
+      });
+    });
+    return unsub;
+  }, []);
+
   useEffect(() => {
     const unsub = onStream((chunk: StreamChunk) => {
       setStreamPanel((prev) => {
+        const slot = chunk.slot ?? 0;
+        const pane = prev.panes[slot];
         const nextFile = chunk.fileName || null;
-        const isNewFile = chunk.reset || (nextFile && nextFile !== prev.fileName);
-        const base = isNewFile ? "" : prev.text;
-        const nextText = base + (chunk.delta || "");
+        const isNewFile = chunk.reset || (nextFile && nextFile !== pane?.fileName);
+        const base = isNewFile ? "" : pane?.text ?? "";
         return {
-          fileName: nextFile,
-          text: nextText,
+          panes: { ...prev.panes, [slot]: { fileName: nextFile, text: base + (chunk.delta || "") } },
           isOpen: true,
         };
       });
@@ -262,34 +376,47 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
     model: string;
     think: boolean;
     stream: boolean;
+    ollamaTimeoutSecs: number;
+    outputLanguage: OutputLanguage;
     resizeEnabled: boolean;
     maxEdge: number;
+    resizePercent: number;
     jpegQuality: number;
+    imageFormat: AnalysisImageFormat;
     valueEnabled: boolean;
     concurrency: number;
-    engine: "clip" | "ollama";
+    engine: AnalysisEngine;
     clipModelDir: string;
     clipModelFile: string;
     clipFallbackToOllama: boolean;
     clipEpAuto: boolean;
     clipEpCoreml: boolean;
+    clipEpTensorrt: boolean;
     clipEpCuda: boolean;
     clipEpRocm: boolean;
     clipEpDirectml: boolean;
     clipEpOpenvino: boolean;
+    clipGpuDeviceId: number;
   }) => {
     const maxEdge = Math.min(4096, Math.max(128, Math.floor(next.maxEdge || 0)));
+    // 0 keeps the max-edge cap; anything else downscales to that percentage.
+    const resizePercent =
+      next.resizePercent > 0 ? Math.min(100, Math.max(1, Math.round(next.resizePercent))) : 0;
     const jpegQuality = Math.min(95, Math.max(20, Math.floor(next.jpegQuality || 0)));
     const concurrency = Math.min(32, Math.max(1, Math.floor(next.concurrency || 1)));
-    const stream = concurrency > 1 ? false : next.stream;
+    const ollamaTimeoutSecs = Math.max(0, Math.floor(next.ollamaTimeoutSecs || 0));
     apiSetSettings({
       ollamaBaseUrl: next.baseUrl,
       ollamaModel: next.model,
       ollamaThink: next.think,
-      ollamaStream: stream,
+      ollamaStream: next.stream,
+      ollamaTimeoutSecs,
+      outputLanguage: next.outputLanguage,
       analysisResizeEnabled: next.resizeEnabled,
       analysisMaxEdge: maxEdge,
+      analysisResizePercent: resizePercent > 0 ? resizePercent : null,
       analysisJpegQuality: jpegQuality,
+      analysisImageFormat: next.imageFormat,
       analysisValueEnabled: Boolean(next.valueEnabled),
       analysisConcurrency: concurrency,
       analysisEngine: next.engine,
@@ -298,18 +425,24 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       clipFallbackToOllama: next.clipFallbackToOllama,
       clipEpAuto: next.clipEpAuto,
       clipEpCoreml: next.clipEpCoreml,
+      clipEpTensorrt: next.clipEpTensorrt,
       clipEpCuda: next.clipEpCuda,
       clipEpRocm: next.clipEpRocm,
       clipEpDirectml: next.clipEpDirectml,
       clipEpOpenvino: next.clipEpOpenvino,
+      clipGpuDeviceId: next.clipGpuDeviceId,
     }).then(() => {
       setSettingsBaseUrl(next.baseUrl);
       setSettingsModel(next.model);
       setSettingsThink(next.think);
-      setSettingsStream(stream);
+      setSettingsStream(next.stream);
+      setSettingsOllamaTimeoutSecs(ollamaTimeoutSecs);
+      setSettingsOutputLanguage(next.outputLanguage);
       setSettingsResizeEnabled(next.resizeEnabled);
       setSettingsMaxEdge(maxEdge);
+      setSettingsResizePercent(resizePercent);
       setSettingsJpegQuality(jpegQuality);
+      setSettingsImageFormat(next.imageFormat);
       setSettingsValueEnabled(Boolean(next.valueEnabled));
       setSettingsConcurrency(concurrency);
       setSettingsEngine(next.engine);
@@ -318,10 +451,12 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       setSettingsClipFallback(next.clipFallbackToOllama);
       setSettingsClipEpAuto(next.clipEpAuto);
       setSettingsClipEpCoreml(next.clipEpCoreml);
+      setSettingsClipEpTensorrt(next.clipEpTensorrt);
       setSettingsClipEpCuda(next.clipEpCuda);
       setSettingsClipEpRocm(next.clipEpRocm);
       setSettingsClipEpDirectml(next.clipEpDirectml);
       setSettingsClipEpOpenvino(next.clipEpOpenvino);
+      setSettingsClipGpuDeviceId(next.clipGpuDeviceId);
       getClipModelFiles()
         .then((files) => setClipModelFiles(files))
         .catch(() => setClipModelFiles([]));
@@ -341,13 +476,16 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
     if (!models.length) toast.warning("모델 목록을 가져오지 못했습니다. Ollama에 모델이 있는지 확인하세요.");
   };
 
-  const startAnalysisNow = async () => {
+  const startAnalysisNow = async (
+    sample?: { maxImages: number; sampleRandom: boolean },
+    settingsOverride?: SettingsOverride
+  ) => {
     if (!sourceRoot || !exportRoot) {
       toast.error("소스/Export 경로를 입력하세요");
       return;
     }
-    const shouldCheckOllama =
-      settingsEngine === "ollama" || (settingsEngine === "clip" && settingsClipFallback);
+    const engine = settingsOverride?.analysisEngine ?? settingsEngine;
+    const shouldCheckOllama = engine !== "clip" || settingsClipFallback;
     if (shouldCheckOllama) {
       const conn = await testOllama(settingsBaseUrl);
       if (!conn.ok) {
@@ -361,10 +499,19 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
     }
     setStarting(true);
     try {
+      const { jobId, queuePosition } = await startAnalysis({
+        sourceRoot,
+        exportRoot,
+        ...sample,
+        settingsOverride,
+      });
+      if (queuePosition != null) {
+        toast.info(`대기열에 추가했습니다 (${queuePosition}번째)`);
+        return;
+      }
       jobStartedAtRef.current = Date.now();
       setJobElapsedMs(0);
-      setStreamPanel({ fileName: null, text: "", isOpen: false });
-      const { jobId } = await startAnalysis({ sourceRoot, exportRoot });
+      setStreamPanel({ panes: {}, isOpen: false });
       setProgress({
         jobId,
         status: "running",
@@ -383,9 +530,68 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
     }
   };
 
-  const cancelCurrent = async () => {
+  const resumeJobNow = async (jobId: string) => {
+    setStarting(true);
+    try {
+      const { queuePosition } = await resumeJob(jobId);
+      if (queuePosition != null) {
+        toast.info(`대기열에 추가했습니다 (${queuePosition}번째)`);
+        return;
+      }
+      jobStartedAtRef.current = Date.now();
+      setJobElapsedMs(0);
+      setStreamPanel({ panes: {}, isOpen: false });
+      setProgress({
+        jobId,
+        status: "running",
+        processed: 0,
+        total: 0,
+        errors: 0,
+      });
+      toast.info("중단된 작업을 이어서 진행합니다");
+    } catch (e) {
+      toast.error(`이어하기에 실패했습니다: ${String(e)}`);
+    } finally {
+      setStarting(false);
+    }
+  };
+
+  const retryFailedNow = async (jobId?: string, kinds?: FailureKind[]) => {
+    setStarting(true);
+    try {
+      const started = await retryFailed(jobId, kinds);
+      const running = started.find((r) => r.queuePosition == null);
+      if (running) {
+        jobStartedAtRef.current = Date.now();
+        setJobElapsedMs(0);
+        setStreamPanel({ panes: {}, isOpen: false });
+        setProgress({
+          jobId: running.jobId,
+          status: "running",
+          processed: 0,
+          total: 0,
+          errors: 0,
+        });
+      }
+      toast.info(`실패한 사진을 다시 분석합니다 (작업 ${started.length}개)`);
+    } catch (e) {
+      toast.error(`재시도에 실패했습니다: ${String(e)}`);
+    } finally {
+      setStarting(false);
+    }
+  };
+
+  const cancelCurrent = async (cancelAll = false) => {
     if (!progress.jobId) return;
-    await cancelAnalysis(progress.jobId);
+    await cancelAnalysis(progress.jobId, cancelAll);
+  };
+
+  const removeFromQueue = async (jobId: string) => {
+    try {
+      await removeQueuedJob(jobId);
+    } catch (e) {
+      toast.error("대기열에서 제거하지 못했습니다");
+    }
   };
 
   const setCategoryFilter = (c: CategoryKey | "all") => setCategoryFilterState(c);
@@ -424,6 +630,7 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
     });
     jobStartedAtRef.current = null;
     setJobElapsedMs(null);
+    setJobSummary(null);
     toast.success("결과 목록이 초기화되었습니다");
   };
 
@@ -435,9 +642,13 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       settingsModel,
       settingsThink,
       settingsStream,
+      settingsOllamaTimeoutSecs,
+      settingsOutputLanguage,
       settingsResizeEnabled,
       settingsMaxEdge,
+      settingsResizePercent,
       settingsJpegQuality,
+      settingsImageFormat,
       settingsValueEnabled,
       settingsConcurrency,
       settingsEngine,
@@ -446,14 +657,20 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       settingsClipFallback,
       settingsClipEpAuto,
       settingsClipEpCoreml,
+      settingsClipEpTensorrt,
       settingsClipEpCuda,
       settingsClipEpRocm,
       settingsClipEpDirectml,
       settingsClipEpOpenvino,
+      settingsClipGpuDeviceId,
       clipAccelCaps,
+      clipWarmup,
+      scanProgress,
       clipModelFiles,
       availableModels,
       jobElapsedMs,
+      jobSummary,
+      jobQueue,
       progress,
       photos,
       distributionAvg,
@@ -469,7 +686,10 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       saveSettings,
       testConnection,
       startAnalysisNow,
+      resumeJobNow,
+      retryFailedNow,
       cancelCurrent,
+      removeFromQueue,
       setCategoryFilter,
       loadPhotoDetail,
       closeDetail,
@@ -482,6 +702,8 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       valueStats,
       exportRoot,
       jobElapsedMs,
+      jobSummary,
+      jobQueue,
       loadingDetail,
       photos,
       progress,
@@ -491,9 +713,13 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       settingsModel,
       settingsThink,
       settingsStream,
+      settingsOllamaTimeoutSecs,
+      settingsOutputLanguage,
       settingsResizeEnabled,
       settingsMaxEdge,
+      settingsResizePercent,
       settingsJpegQuality,
+      settingsImageFormat,
       settingsValueEnabled,
       settingsConcurrency,
       settingsEngine,
@@ -502,15 +728,21 @@ export function AnalysisProvider({ children }: { children: ReactNode }) {
       settingsClipFallback,
       settingsClipEpAuto,
       settingsClipEpCoreml,
+      settingsClipEpTensorrt,
       settingsClipEpCuda,
       settingsClipEpRocm,
       settingsClipEpDirectml,
       settingsClipEpOpenvino,
+      settingsClipGpuDeviceId,
       clipAccelCaps,
+      clipWarmup,
+      scanProgress,
       clipModelFiles,
       sourceRoot,
       starting,
       startAnalysisNow,
+      resumeJobNow,
+      retryFailedNow,
       availableModels,
       streamPanel,
     ]
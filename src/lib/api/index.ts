@@ -2,21 +2,48 @@ import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import type {
   CategoryKey,
+  ClassificationOutput,
   ClipAccelCapabilities,
+  ClipEngineStatus,
+  ClipPromptTestResult,
+  ClipReloadResult,
+  ClipWarmupEvent,
+  DeletePhotoResult,
   Distribution,
+  FailureKind,
+  JobQueueEvent,
+  JobRecord,
+  JobSummary,
+  ModelPullEvent,
+  OllamaModelCheck,
+  OllamaModelInfo,
   PhotoDetail,
+  PhotoEvent,
+  PhotoFilter,
   PhotoRow,
   Progress,
+  QueuedJobInfo,
+  RecategorizeResult,
+  ScanProgressEvent,
+  SelfTestReport,
   Settings,
   StartAnalysisInput,
   StartAnalysisResult,
   ScoreVector,
   StreamChunk,
+  TagCount,
+  UndoExportReport,
   ValueStats,
 } from "./types";
 
 const PROGRESS_EVENT = "analysis://progress";
+const JOB_SUMMARY_EVENT = "analysis://job-summary";
+const JOB_QUEUE_EVENT = "analysis://job-queue";
+const SCAN_PROGRESS_EVENT = "analysis://scan-progress";
+const PHOTO_EVENT = "analysis://photo";
 const STREAM_EVENT = "analysis://stream";
+const WARMUP_EVENT = "analysis://clip-warmup";
+const MODEL_PULL_EVENT = "ollama://model-pull";
 
 const CATEGORY_KEYS: CategoryKey[] = [
   "screenshot_document",
@@ -32,23 +59,67 @@ const CATEGORY_KEYS: CategoryKey[] = [
 const DEFAULT_SETTINGS: Settings = {
   ollamaBaseUrl: "http://127.0.0.1:11434",
   ollamaModel: "qwen2.5vl:7b",
+  ollamaBackend: "ollama",
   ollamaThink: false,
   ollamaStream: false,
+  ollamaMaxResponseBytes: 4 * 1024 * 1024,
+  ollamaTimeoutSecs: 120,
+  ollamaMaxParallel: 2,
+  ollamaMaxTags: 8,
+  ollamaMaxTagChars: 20,
+  outputLanguage: "ko",
+  sanitizeOutput: true,
+  fallbackCategory: "other",
+  ollamaSystemPrompt: null,
+  ollamaUserPrompt: null,
+  ollamaOptions: {},
+  ollamaKeepAlive: "",
+  ollamaApiKey: null,
+  ollamaExtraHeaders: {},
+  preflightChecks: true,
+  debugCaptureRawResponses: false,
   analysisResizeEnabled: true,
   analysisMaxEdge: 768,
+  analysisResizePercent: null,
   analysisJpegQuality: 60,
+  analysisImageFormat: "auto",
   analysisValueEnabled: false,
+  valueAsNestedFolders: true,
+  writeSidecar: false,
+  ephemeralDb: false,
+  alphaBackground: [255, 255, 255],
   analysisConcurrency: 4,
+  decodeConcurrency: 2,
   analysisEngine: "clip",
+  hybridCaptionScope: "all",
   clipModelDir: null,
   clipModelFile: "onnx/model_q4f16.onnx",
   clipFallbackToOllama: false,
   clipEpAuto: true,
   clipEpCoreml: true,
+  clipEpTensorrt: false,
   clipEpCuda: false,
   clipEpRocm: false,
   clipEpDirectml: false,
   clipEpOpenvino: false,
+  clipGpuDeviceId: 0,
+  clipSessionPoolSize: null,
+  clipIntraThreads: null,
+  clipInterThreads: null,
+  clipParallelExecution: false,
+  clipDisableCpuArena: false,
+  clipAutoUnload: false,
+  clipMaxLoadedModels: 1,
+  clipTagsEnabled: true,
+  clipTagPrompts: [],
+  clipTagTopN: 5,
+  clipTagThreshold: 0.24,
+  clipSubcategoriesEnabled: false,
+  categoryBias: {},
+  screenshotHeuristic: false,
+  scanExcludeGlobs: [],
+  scanMaxDepth: null,
+  scanSort: "name_asc",
 };
 
 const isTauri =
@@ -100,12 +171,16 @@ function generateMockRows(count: number): PhotoRow[] {
     const scores = normalizeScores(raw);
     const category = deriveCategory(scores);
     const topScore = Math.max(...Object.values(scores));
+    const topK = CATEGORY_KEYS.map((c) => ({ category: c, score: scores[c] }))
+      .sort((a, b) => b.score - a.score)
+      .slice(0, 3);
     rows.push({
       id,
       fileName: `photo_${String(i + 1).padStart(4, "0")}.jpg`,
       path: `/mock/source/photo_${i + 1}.jpg`,
       category,
       topScore: Number(topScore.toFixed(4)),
+      topK,
       scores,
       tags: [],
       exportStatus: Math.random() > 0.9 ? "error" : "success",
@@ -140,15 +215,45 @@ export async function getSettings(): Promise<Settings> {
 export async function setSettings(next: Settings): Promise<void> {
   settings = { ...settings, ...next };
   if (useMock) return;
-  await invoke("set_settings", { settings: next });
+  // Send the merged snapshot so fields the settings pages don't edit are preserved.
+  await invoke("set_settings", { settings });
 }
 
-export async function clearResults(): Promise<void> {
+// Without `jobId` every result is removed; mock rows belong to no job.
+export async function clearResults(jobId?: string): Promise<void> {
   if (useMock) {
-    mockRows = [];
+    if (!jobId) mockRows = [];
     return;
   }
-  await invoke("clear_results");
+  await invoke("clear_results", { jobId: jobId ?? null });
+}
+
+export async function getErrorLogPath(): Promise<string> {
+  if (useMock) return "/mock/app-data/errors.log";
+  return invoke("get_error_log_path");
+}
+
+export async function clearErrorLog(): Promise<void> {
+  if (useMock) return;
+  await invoke("clear_error_log");
+}
+
+export async function clearDebugCaptures(): Promise<void> {
+  if (useMock) return;
+  await invoke("clear_debug_captures");
+}
+
+export async function selfTest(): Promise<SelfTestReport> {
+  if (useMock) {
+    return {
+      decode: { ok: true, elapsedMs: 1, detail: "64x64" },
+      clip: { ok: true, elapsedMs: 12, detail: "category: other (model: mock)" },
+      ollama: null,
+      clipModelDir: "/mock/models/clip",
+      clipEps: "CPU",
+    };
+  }
+  return invoke("self_test");
 }
 
 export async function getClipAccelCapabilities(): Promise<ClipAccelCapabilities> {
@@ -160,18 +265,92 @@ export async function getClipAccelCapabilities(): Promise<ClipAccelCapabilities>
         available: false,
         name: "CoreML (Apple)",
       },
+      tensorrt: { supported: false, available: false, name: "TensorRT (NVIDIA)" },
       cuda: { supported: false, available: false, name: "CUDA (NVIDIA)" },
       rocm: { supported: false, available: false, name: "ROCm (AMD)" },
       directml: { supported: false, available: false, name: "DirectML (Windows)" },
       openvino: { supported: false, available: false, name: "OpenVINO (Intel)" },
+      gpuDevices: [],
     };
   }
   return invoke("get_clip_accel_capabilities");
 }
 
-export async function getValueStats(): Promise<ValueStats> {
+export async function getClipEngineStatus(): Promise<ClipEngineStatus> {
+  if (useMock) return { loaded: false };
+  return invoke("get_clip_engine_status");
+}
+
+export async function unloadClipEngine(): Promise<boolean> {
+  if (useMock) return false;
+  return invoke("unload_clip_engine");
+}
+
+export async function reloadClipEngine(): Promise<ClipReloadResult> {
+  if (useMock) return { elapsedMs: 0, status: { loaded: false } };
+  return invoke("reload_clip_engine");
+}
+
+// Scores one image against trial category prompts without changing the loaded engine. Each prompt
+// is [text, weight] in its category's average. Categories left out keep their built-in prompts.
+export async function testClipPrompts(
+  imagePath: string,
+  prompts: Partial<Record<CategoryKey, [string, number][]>>
+): Promise<ClipPromptTestResult> {
+  if (useMock) {
+    const scores = normalizeScores(CATEGORY_KEYS.map(() => Math.random() + 0.2));
+    return { scores, category: deriveCategory(scores), embedMs: 0, inferMs: 0 };
+  }
+  return invoke("test_clip_prompts", { imagePath, prompts });
+}
+
+export async function getClipWarmupStatus(): Promise<ClipWarmupEvent | null> {
+  if (useMock) return null;
+  return invoke("get_clip_warmup_status");
+}
+
+export function onClipWarmup(cb: (event: ClipWarmupEvent) => void): () => void {
+  if (useMock) return () => {};
+  let unlistenPromise = listen<ClipWarmupEvent>(WARMUP_EVENT, (event) => cb(event.payload));
+  // warmup starts with the app, so catch up on anything emitted before we subscribed
+  getClipWarmupStatus()
+    .then((e) => e && cb(e))
+    .catch(() => {});
+  return () => {
+    unlistenPromise.then((fn) => fn());
+  };
+}
+
+// Most used tags first; case and spacing variants of a tag are counted together.
+export async function getTagStats(
+  limit = 50,
+  category?: CategoryKey,
+  jobId?: string
+): Promise<TagCount[]> {
+  if (useMock) {
+    const counts = new Map<string, TagCount>();
+    for (const r of mockRows) {
+      if (category && r.category !== category) continue;
+      if (jobId) continue;
+      const seen = new Set<string>();
+      for (const raw of r.tags ?? []) {
+        const tag = raw.trim();
+        const key = tag.replace(/\s+/g, "").toLowerCase();
+        if (!key || seen.has(key)) continue;
+        seen.add(key);
+        const entry = counts.get(key) ?? { tag, count: 0 };
+        entry.count += 1;
+        counts.set(key, entry);
+      }
+    }
+    return [...counts.values()].sort((a, b) => b.count - a.count).slice(0, limit);
+  }
+  return invoke("get_tag_stats", { limit, category: category ?? null, jobId: jobId ?? null });
+}
+
+export async function getValueStats(jobId?: string): Promise<ValueStats> {
   if (useMock) return { valuable: 0, notValuable: 0, unknown: 0 };
-  return invoke("get_value_stats");
+  return invoke("get_value_stats", { jobId: jobId ?? null });
 }
 
 export async function getClipModelFiles(): Promise<string[]> {
@@ -200,6 +379,58 @@ export async function listOllamaModels(baseUrl: string): Promise<string[]> {
   }
 }
 
+export async function listOllamaModelsDetailed(
+  baseUrl: string
+): Promise<OllamaModelInfo[]> {
+  if (useMock) {
+    return [
+      { name: "gemma2:latest", size: 5443152417, family: "gemma2", likelyVision: false },
+      { name: "qwen2.5vl:7b", size: 5969245856, family: "qwen25vl", likelyVision: true },
+    ];
+  }
+  try {
+    return await invoke<OllamaModelInfo[]>("list_ollama_models_detailed", { baseUrl });
+  } catch {
+    return [];
+  }
+}
+
+// `/api/show` details for one model: vision support, context size and parameter size.
+export async function checkOllamaModel(baseUrl: string, model: string): Promise<OllamaModelCheck> {
+  if (useMock) {
+    return {
+      name: model,
+      capabilitiesReported: true,
+      capabilities: ["completion", "vision"],
+      supportsVision: true,
+      contextLength: 128000,
+      family: "qwen25vl",
+      parameterSize: "8.3B",
+      quantization: "Q4_K_M",
+    };
+  }
+  return invoke("check_ollama_model", { baseUrl, model });
+}
+
+// Resolves once the pull finishes; progress arrives through `onModelPull`.
+export async function pullOllamaModel(baseUrl: string, model: string): Promise<void> {
+  if (useMock) return;
+  await invoke("pull_ollama_model", { baseUrl, model });
+}
+
+export async function cancelModelPull(): Promise<void> {
+  if (useMock) return;
+  await invoke("cancel_model_pull");
+}
+
+export function onModelPull(cb: (event: ModelPullEvent) => void): () => void {
+  if (useMock) return () => {};
+  let unlistenPromise = listen<ModelPullEvent>(MODEL_PULL_EVENT, (event) => cb(event.payload));
+  return () => {
+    unlistenPromise.then((fn) => fn());
+  };
+}
+
 export async function testOllama(
   baseUrl: string
 ): Promise<{ ok: boolean; message: string }> {
@@ -273,12 +504,64 @@ export async function startAnalysis(
   return invoke("start_analysis", { input });
 }
 
-export async function cancelAnalysis(jobId: string): Promise<void> {
+// Continues a job cut off by a quit or crash; files it already wrote rows for are skipped.
+export async function resumeJob(jobId: string): Promise<StartAnalysisResult> {
+  if (useMock) {
+    mockJobId = jobId;
+    emitProgress({ jobId, status: "completed", currentFile: undefined });
+    return { jobId };
+  }
+  return invoke("resume_job", { jobId });
+}
+
+// Re-analyzes errored photos (optionally only one job's, only some failure kinds) with the
+// current settings. One job is started or queued per export root.
+export async function retryFailed(
+  jobId?: string,
+  kinds?: FailureKind[]
+): Promise<StartAnalysisResult[]> {
+  if (useMock) return [];
+  return invoke("retry_failed", { jobId: jobId ?? null, kinds: kinds ?? null });
+}
+
+export async function listJobs(): Promise<JobRecord[]> {
+  if (useMock) return [];
+  return invoke("list_jobs");
+}
+
+// Canceling the running job starts the next queued one unless `cancelAll` also clears the queue.
+export async function cancelAnalysis(jobId: string, cancelAll = false): Promise<void> {
   if (useMock) {
     if (mockJobId === jobId) emitProgress({ status: "canceled" });
     return;
   }
-  await invoke("cancel_analysis", { jobId });
+  await invoke("cancel_analysis", { jobId, cancelAll });
+}
+
+export async function getJobQueue(): Promise<QueuedJobInfo[]> {
+  if (useMock) return [];
+  return invoke("get_job_queue");
+}
+
+export async function removeQueuedJob(jobId: string): Promise<void> {
+  if (useMock) return;
+  await invoke("remove_queued_job", { jobId });
+}
+
+export function onJobQueue(cb: (event: JobQueueEvent) => void): () => void {
+  if (useMock) return () => {};
+  let unlistenPromise = listen<JobQueueEvent>(JOB_QUEUE_EVENT, (event) => cb(event.payload));
+  return () => {
+    unlistenPromise.then((fn) => fn());
+  };
+}
+
+export function onScanProgress(cb: (event: ScanProgressEvent) => void): () => void {
+  if (useMock) return () => {};
+  let unlistenPromise = listen<ScanProgressEvent>(SCAN_PROGRESS_EVENT, (event) => cb(event.payload));
+  return () => {
+    unlistenPromise.then((fn) => fn());
+  };
 }
 
 export function onProgress(cb: ProgressListener): () => void {
@@ -295,6 +578,27 @@ export function onProgress(cb: ProgressListener): () => void {
   };
 }
 
+export async function getLastJobSummary(): Promise<JobSummary | null> {
+  if (useMock) return null;
+  return invoke("get_last_job_summary");
+}
+
+export function onJobSummary(cb: (summary: JobSummary) => void): () => void {
+  if (useMock) return () => {};
+  let unlistenPromise = listen<JobSummary>(JOB_SUMMARY_EVENT, (event) => cb(event.payload));
+  return () => {
+    unlistenPromise.then((fn) => fn());
+  };
+}
+
+export function onPhotos(cb: (event: PhotoEvent) => void): () => void {
+  if (useMock) return () => {};
+  let unlistenPromise = listen<PhotoEvent>(PHOTO_EVENT, (event) => cb(event.payload));
+  return () => {
+    unlistenPromise.then((fn) => fn());
+  };
+}
+
 export function onStream(cb: StreamListener): () => void {
   if (useMock) {
     streamListeners.add(cb);
@@ -316,9 +620,55 @@ async function getProgressSnapshot(): Promise<Progress | null> {
   }
 }
 
-export async function listPhotos(): Promise<PhotoRow[]> {
-  if (useMock) return mockRows;
-  return invoke("list_photos");
+function filterMockRows(filter: PhotoFilter): PhotoRow[] {
+  const search = filter.search?.trim().toLowerCase();
+  const from = filter.from ? Date.parse(filter.from) : null;
+  const to = filter.to ? Date.parse(filter.to) : null;
+  return mockRows.filter(
+    (r) =>
+      filter.jobId == null &&
+      (from == null || (!!r.createdAt && Date.parse(r.createdAt) >= from)) &&
+      (to == null || (!!r.createdAt && Date.parse(r.createdAt) <= to)) &&
+      (filter.status == null || r.exportStatus === filter.status) &&
+      (filter.category == null || r.category === filter.category) &&
+      (filter.valuable == null || r.isValuable === filter.valuable) &&
+      (!search ||
+        r.fileName.toLowerCase().includes(search) ||
+        (r.tags ?? []).some((t) => t.toLowerCase().includes(search)))
+  );
+}
+
+// Newest first. Without a limit the backend returns its default page (500 rows); a limit of 0
+// returns every row.
+export async function listPhotos(
+  filter: PhotoFilter = {},
+  page: { limit?: number; offset?: number } = {}
+): Promise<PhotoRow[]> {
+  if (useMock) {
+    const rows = filterMockRows(filter);
+    const offset = page.offset ?? 0;
+    return page.limit ? rows.slice(offset, offset + page.limit) : rows.slice(offset);
+  }
+  return invoke("list_photos", {
+    filter,
+    limit: page.limit ?? null,
+    offset: page.offset ?? null,
+  });
+}
+
+export async function countPhotos(filter: PhotoFilter = {}): Promise<number> {
+  if (useMock) return filterMockRows(filter).length;
+  return invoke("count_photos", { filter });
+}
+
+// Rows analyzed at or after `isoTimestamp` (UTC unless it carries an offset).
+export async function listPhotosSince(isoTimestamp: string): Promise<PhotoRow[]> {
+  if (useMock) {
+    const since = Date.parse(isoTimestamp);
+    if (Number.isNaN(since)) throw new Error(`invalid timestamp: ${isoTimestamp}`);
+    return mockRows.filter((r) => !r.createdAt || Date.parse(r.createdAt) >= since);
+  }
+  return invoke("list_photos_since", { isoTimestamp });
 }
 
 export async function getPhotoDetail(id: string): Promise<PhotoDetail> {
@@ -330,8 +680,98 @@ export async function getPhotoDetail(id: string): Promise<PhotoDetail> {
   return invoke("get_photo_detail", { id });
 }
 
+// Moves a photo to a hand-picked category; its exported file follows into the new folder.
+export async function setPhotoCategory(
+  id: string,
+  category: CategoryKey
+): Promise<RecategorizeResult> {
+  if (useMock) {
+    const found = mockRows.find((r) => r.id === id);
+    if (!found) throw new Error("Photo not found");
+    found.category = category;
+    return { photo: { ...found, manualOverride: true } };
+  }
+  return invoke("set_photo_category", { id, category });
+}
+
+// Removes result rows. With `deleteExportedFile` the exported copy is deleted too; source files
+// are never touched.
+export async function deletePhotos(
+  ids: string[],
+  deleteExportedFile = false
+): Promise<DeletePhotoResult[]> {
+  if (useMock) {
+    const found = new Set(mockRows.filter((r) => ids.includes(r.id)).map((r) => r.id));
+    mockRows = mockRows.filter((r) => !found.has(r.id));
+    return ids.map((id) => ({
+      id,
+      deleted: found.has(id),
+      fileRemoved: false,
+      error: found.has(id) ? null : "not found",
+    }));
+  }
+  return invoke("delete_photos", { ids, deleteExportedFile });
+}
+
+// Moves the last job's exported files to the trash and removes its rows. `dryRun` only counts
+// them, for the confirmation prompt.
+export async function undoLastExport(dryRun = false): Promise<UndoExportReport> {
+  if (useMock) {
+    const rows = mockRows.length;
+    if (!dryRun) mockRows = [];
+    return { jobId: "mock-job", files: rows, rows, failed: [] };
+  }
+  return invoke("undo_last_export", { dryRun });
+}
+
+// Shows the photo's file selected in Finder / Explorer / the desktop file manager.
+export async function revealInFileManager(id: string): Promise<void> {
+  if (useMock) return;
+  await invoke("reveal_in_file_manager", { id });
+}
+
+// Path to a small cached JPEG for the gallery; regenerated on demand if it was deleted.
+export async function getThumbnail(id: string): Promise<string> {
+  if (useMock) {
+    const found = mockRows.find((r) => r.id === id);
+    if (!found) throw new Error("Photo not found");
+    return found.thumbnailPath ?? found.path;
+  }
+  return invoke("get_thumbnail", { id });
+}
+
+export async function reanalyzePhoto(id: string): Promise<PhotoDetail> {
+  if (useMock) {
+    const found = mockRows.find((r) => r.id === id);
+    if (!found) throw new Error("Photo not found");
+    return found;
+  }
+  return invoke("reanalyze_photo", { id });
+}
+
+// Classifies an in-memory image with the configured engine; nothing is exported or stored.
+export async function classifyBytes(
+  bytes: Uint8Array,
+  ext: string
+): Promise<ClassificationOutput> {
+  if (useMock) {
+    const scores = normalizeScores(CATEGORY_KEYS.map(() => Math.random() + 0.2));
+    const category = deriveCategory(scores);
+    return {
+      model: "mock",
+      scores,
+      category,
+      tags: [],
+      analysisLog: `mock: ${bytes.length} bytes (.${ext})`,
+    };
+  }
+  return invoke("classify_bytes", { bytes: Array.from(bytes), ext });
+}
+
+// With `jobId` only that job's rows count; mock rows are never filtered.
 export async function getDistribution(
-  mode: "avg_score" | "count_ratio"
+  mode: "avg_score" | "count_ratio",
+  jobId?: string
 ): Promise<Distribution> {
   if (useMock) {
     const byCategory: Record<CategoryKey, number> = Object.fromEntries(
@@ -357,7 +797,7 @@ export async function getDistribution(
     });
     return { mode, byCategory };
   }
-  return invoke("get_distribution", { mode });
+  return invoke("get_distribution", { mode, jobId: jobId ?? null });
 }
 
 export const categories = CATEGORY_KEYS;
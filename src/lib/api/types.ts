@@ -10,26 +10,110 @@ export type CategoryKey =
 
 export type ScoreVector = Record<CategoryKey, number>;
 
+export interface CategoryScore {
+  category: CategoryKey;
+  score: number;
+}
+
+export type AnalysisEngine = "clip" | "ollama" | "hybrid";
+// Which photos the hybrid engine sends to Ollama for a caption after CLIP has classified them.
+export type HybridCaptionScope = "all" | "valuable_only" | "none";
+export type AnalysisImageFormat = "auto" | "jpeg" | "png";
+export type OutputLanguage = "ko" | "en" | "ja";
+
+// "openai_compatible" talks to LM Studio, llama.cpp server, etc. via /v1/chat/completions.
+export type OllamaBackend = "ollama" | "openai_compatible";
+export type ScanSort = "none" | "name_asc" | "name_desc" | "mtime_asc" | "mtime_desc";
+
+export interface ClipTagPrompt {
+  prompt: string;
+  label: string;
+}
+
+export interface ClipSubcategory {
+  label: string;
+  prompts: string[];
+}
+
+export interface ValueBucket {
+  name: string;
+  prompts: string[];
+  keep?: boolean;
+}
+
 export interface Settings {
   ollamaBaseUrl: string;
   ollamaModel: string;
+  ollamaBackend?: OllamaBackend;
   ollamaThink: boolean;
   ollamaStream: boolean;
+  ollamaMaxResponseBytes?: number;
+  ollamaTimeoutSecs?: number;
+  // Ollama requests in flight at once, independent of analysisConcurrency.
+  ollamaMaxParallel?: number;
+  ollamaMaxTags?: number;
+  ollamaMaxTagChars?: number;
+  outputLanguage?: OutputLanguage;
+  // Off keeps tags/caption/text-in-image exactly as the model wrote them.
+  sanitizeOutput?: boolean;
+  // Category for model answers whose category and scores pick out none.
+  fallbackCategory?: CategoryKey;
+  ollamaSystemPrompt?: string | null;
+  ollamaUserPrompt?: string | null;
+  ollamaOptions?: Record<string, unknown>;
+  ollamaKeepAlive?: string;
+  ollamaApiKey?: string | null;
+  ollamaExtraHeaders?: Record<string, string>;
+  preflightChecks?: boolean;
+  debugCaptureRawResponses?: boolean;
   analysisResizeEnabled: boolean;
   analysisMaxEdge: number;
+  analysisResizePercent?: number | null;
   analysisJpegQuality: number;
+  analysisImageFormat?: AnalysisImageFormat;
   analysisValueEnabled: boolean;
+  valueAsNestedFolders?: boolean;
+  // Writes `<exported file>.json` with the analysis result next to each export.
+  writeSidecar?: boolean;
+  // Keep results in memory only; they are gone after a restart or when this is turned off.
+  ephemeralDb?: boolean;
+  // RGB color transparent pixels are composited over before analysis.
+  alphaBackground?: [number, number, number];
   analysisConcurrency: number;
-  analysisEngine: "clip" | "ollama";
+  // Decode/preprocess workers feeding the `analysisConcurrency` inference workers.
+  decodeConcurrency?: number;
+  analysisEngine: AnalysisEngine;
+  hybridCaptionScope?: HybridCaptionScope;
   clipModelDir?: string | null;
   clipModelFile: string;
   clipFallbackToOllama: boolean;
   clipEpAuto: boolean;
   clipEpCoreml: boolean;
+  clipEpTensorrt: boolean;
   clipEpCuda: boolean;
   clipEpRocm: boolean;
   clipEpDirectml: boolean;
   clipEpOpenvino: boolean;
+  clipGpuDeviceId?: number;
+  clipSessionPoolSize?: number | null;
+  clipIntraThreads?: number | null;
+  clipInterThreads?: number | null;
+  clipParallelExecution?: boolean;
+  clipDisableCpuArena?: boolean;
+  clipAutoUnload?: boolean;
+  clipMaxLoadedModels?: number;
+  clipTagsEnabled?: boolean;
+  clipTagPrompts?: ClipTagPrompt[];
+  clipTagTopN?: number;
+  clipTagThreshold?: number;
+  clipSubcategoriesEnabled?: boolean;
+  clipSubcategories?: Partial<Record<CategoryKey, ClipSubcategory[]>>;
+  clipValueBuckets?: ValueBucket[];
+  categoryBias?: Partial<Record<CategoryKey, number>>;
+  screenshotHeuristic?: boolean;
+  scanExcludeGlobs?: string[];
+  scanMaxDepth?: number | null;
+  scanSort?: ScanSort;
 }
 
 export interface ClipProviderCapability {
@@ -38,18 +122,180 @@ export interface ClipProviderCapability {
   name: string;
 }
 
+export interface ClipGpuDevice {
+  id: number;
+  name: string;
+}
+
 export interface ClipAccelCapabilities {
   cpu: ClipProviderCapability;
   coreml: ClipProviderCapability;
+  tensorrt: ClipProviderCapability;
   cuda: ClipProviderCapability;
   rocm: ClipProviderCapability;
   directml: ClipProviderCapability;
   openvino: ClipProviderCapability;
+  gpuDevices?: ClipGpuDevice[];
+}
+
+export interface ClipEngineStatus {
+  loaded: boolean;
+  // Most recently used first; the other fields describe the first entry.
+  loadedModels?: string[];
+  cacheKey?: string | null;
+  modelPath?: string | null;
+  epsLog?: string | null;
+  // Providers that actually ran nodes, e.g. "coreml(3 nodes)+cpu(12 nodes)".
+  effectiveEps?: string | null;
+  sessionPoolSize?: number | null;
+  intraThreads?: number | null;
+  modelLoadMs?: number | null;
+  textCacheMs?: number | null;
+  inputIdsName?: string | null;
+  attentionMaskName?: string | null;
+  pixelValuesName?: string | null;
+  outputImageEmbeds?: string | null;
+  outputTextEmbeds?: string | null;
+  // Latest warmup event; a `loading_model`..`building_pool` phase means a load is in progress.
+  warmup?: ClipWarmupEvent | null;
+}
+
+export interface ClipReloadResult {
+  elapsedMs: number;
+  status: ClipEngineStatus;
+}
+
+// `embedMs` covers embedding the trial prompts, `inferMs` the image run and scoring.
+export interface ClipPromptTestResult {
+  scores: ScoreVector;
+  category: CategoryKey;
+  embedMs: number;
+  inferMs: number;
+}
+
+export interface SelfTestStage {
+  ok: boolean;
+  elapsedMs: number;
+  detail?: string | null;
+  error?: string | null;
+}
+
+export interface SelfTestReport {
+  decode: SelfTestStage;
+  clip: SelfTestStage;
+  ollama?: SelfTestStage | null;
+  clipModelDir?: string | null;
+  clipEps?: string | null;
+}
+
+export type ClipWarmupPhase =
+  | "loading_model"
+  | "caching_text"
+  | "smoke_test"
+  | "building_pool"
+  | "ready"
+  | "failed";
+
+export interface ClipWarmupEvent {
+  phase: ClipWarmupPhase;
+  elapsedMs: number;
+  error?: string | null;
+  poolIndex?: number | null;
+  poolSize?: number | null;
+}
+
+export interface OllamaModelInfo {
+  name: string;
+  size: number;
+  family?: string | null;
+  parameterSize?: string | null;
+  quantization?: string | null;
+  likelyVision: boolean;
+}
+
+export interface OllamaModelCheck {
+  name: string;
+  capabilitiesReported: boolean;
+  capabilities: string[];
+  supportsVision: boolean;
+  contextLength?: number | null;
+  family?: string | null;
+  parameterSize?: string | null;
+  quantization?: string | null;
+}
+
+export type JobStatus = "idle" | "running" | "completed" | "canceled" | "error" | "aborted";
+
+// A persisted analysis job; `done` is the number of photo rows already written for it.
+export interface JobRecord {
+  id: string;
+  sourceRoot: string;
+  exportRoot: string;
+  status: JobStatus;
+  startedAt?: string | null;
+  done: number;
+  summary?: JobSummary | null;
+  // When the job's first and last photo rows were written.
+  firstPhotoAt?: string | null;
+  lastPhotoAt?: string | null;
+}
+
+export interface JobSummary {
+  jobId: string;
+  status: JobStatus;
+  engine: AnalysisEngine;
+  total: number;
+  processed: number;
+  errors: number;
+  skipped: number;
+  elapsedMs: number;
+  imagesPerSec: number;
+  avgVisionInferMs?: number | null;
+  avgEvalTokensPerSec?: number | null;
+  // True for canceled or failed jobs; the counts cover only what finished.
+  partial?: boolean;
+  categoryCounts?: Partial<Record<CategoryKey, number>>;
+  valuable?: number;
+  notValuable?: number;
+  totalDurationMs?: number;
+  avgDurationMs?: number | null;
+  slowest?: JobSummaryPhoto[];
+  // Capped list; `errors` has the full count.
+  failures?: JobSummaryFailure[];
+  // Retry jobs only: failed photos whose source file is gone.
+  missing?: string[];
+}
+
+export interface JobSummaryPhoto {
+  path: string;
+  durationMs: number;
+}
+
+export interface JobSummaryFailure {
+  path: string;
+  error: string;
+}
+
+export interface ScanProgressEvent {
+  jobId: string;
+  scanned: number;
+  found: number;
+  done: boolean;
+}
+
+export interface ModelPullEvent {
+  model: string;
+  status: string;
+  completed?: number | null;
+  total?: number | null;
+  done: boolean;
+  error?: string | null;
 }
 
 export interface StreamChunk {
   jobId: string;
   fileName: string;
+  slot?: number;
   delta: string;
   done: boolean;
   reset?: boolean;
@@ -68,37 +314,134 @@ export interface ModelOut {
   textInImage?: string;
 }
 
+export type FailureKind =
+  | "network"
+  | "timeout"
+  | "model_refused"
+  | "parse_failed"
+  | "canceled"
+  | "other";
+
 export interface PhotoRow {
   id: string;
   fileName: string;
   path: string;
   category: CategoryKey;
   topScore: number;
+  topK?: CategoryScore[];
   scores: ScoreVector;
   tags?: string[];
   exportStatus: "pending" | "success" | "error";
   errorMessage?: string;
+  failureKind?: FailureKind | null;
   analysisDurationMs?: number;
   model?: string | null;
   isValuable?: boolean | null;
   valuableScore?: number | null;
+  thumbnailPath?: string | null;
+  createdAt?: string | null;
+}
+
+// `listPhotos` filters, combined with AND. `search` matches file name, tags or caption.
+export interface PhotoFilter {
+  status?: PhotoRow["exportStatus"];
+  category?: CategoryKey;
+  valuable?: boolean;
+  search?: string;
+  jobId?: string;
+  // Inclusive `createdAt` bounds; ISO 8601, converted to UTC by the backend.
+  from?: string;
+  to?: string;
+}
+
+// Rows a batch job just wrote, in completion order; several per event when photos finish quickly.
+export interface PhotoEvent {
+  jobId: string;
+  rows: PhotoRow[];
 }
 
 export interface PhotoDetail extends PhotoRow {
   caption?: string;
   textInImage?: string;
   analysisLog?: string;
+  sourcePath?: string | null;
+  exportRoot?: string | null;
+  subcategory?: string | null;
+  valueBucket?: string | null;
+  promptTokens?: number | null;
+  evalTokens?: number | null;
+  jobId?: string | null;
+  exif?: PhotoExif | null;
+  // The category was picked by hand; scores are still the model's.
+  manualOverride?: boolean;
 }
 
-export interface ValueStats {
+// `warning` is set when only the database changed, e.g. the exported file was missing.
+export interface RecategorizeResult {
+  photo: PhotoDetail;
+  warning?: string | null;
+}
+
+// `failed` lists files that could not be moved to the trash, as "path: error".
+export interface UndoExportReport {
+  jobId: string;
+  files: number;
+  rows: number;
+  failed: string[];
+}
+
+// `dateTimeOriginal` is the camera's local time (YYYY-MM-DDTHH:MM:SS, no zone).
+export interface PhotoExif {
+  make?: string | null;
+  model?: string | null;
+  dateTimeOriginal?: string | null;
+  focalLengthMm?: number | null;
+  gpsLatitude?: number | null;
+  gpsLongitude?: number | null;
+}
+
+export interface ClassificationOutput {
+  model: string;
+  scores: ScoreVector;
+  category: CategoryKey;
+  tags: string[];
+  caption?: string | null;
+  textInImage?: string | null;
+  analysisLog: string;
+  isValuable?: boolean | null;
+  valuableScore?: number | null;
+  subcategory?: string | null;
+  valueBucket?: string | null;
+}
+
+// Per-id outcome of `deletePhotos`; `error` can be set even when the row was deleted.
+export interface DeletePhotoResult {
+  id: string;
+  deleted: boolean;
+  fileRemoved: boolean;
+  error?: string | null;
+}
+
+export interface ValueCounts {
   valuable: number;
   notValuable: number;
   unknown: number;
 }
 
+// Totals over every row; `byCategory` has the same counts per category key, zeros included.
+export interface ValueStats extends ValueCounts {
+  byCategory?: Record<string, ValueCounts>;
+}
+
+// `count` covers every spelling of the tag that differs only in case or spacing.
+export interface TagCount {
+  tag: string;
+  count: number;
+}
+
 export interface Progress {
   jobId: string;
-  status: "idle" | "running" | "completed" | "canceled" | "error";
+  status: JobStatus;
   currentFile?: string;
   processed: number;
   total: number;
@@ -113,8 +456,39 @@ export interface Distribution {
 export interface StartAnalysisInput {
   sourceRoot: string;
   exportRoot: string;
+  maxImages?: number | null;
+  sampleRandom?: boolean;
+  sampleSeed?: number | null;
+  // This job only; stored settings are left alone.
+  settingsOverride?: SettingsOverride | null;
+}
+
+// Settings one job can change; omitted fields keep the stored value.
+export interface SettingsOverride {
+  analysisEngine?: AnalysisEngine;
+  analysisValueEnabled?: boolean;
+  analysisConcurrency?: number;
+  analysisResizeEnabled?: boolean;
+  analysisMaxEdge?: number;
+  analysisResizePercent?: number;
+  analysisJpegQuality?: number;
+  ollamaModel?: string;
 }
 
 export interface StartAnalysisResult {
   jobId: string;
+  // 1-based place in the queue; null when the job started right away.
+  queuePosition?: number | null;
+}
+
+export interface QueuedJobInfo {
+  jobId: string;
+  sourceRoot: string;
+  exportRoot: string;
+  position: number;
+}
+
+export interface JobQueueEvent {
+  promoted?: string | null;
+  queue: QueuedJobInfo[];
 }
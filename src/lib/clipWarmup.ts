@@ -0,0 +1,17 @@
+import { ClipWarmupEvent, ClipWarmupPhase } from "./api/types";
+
+export const clipWarmupLabel: Record<ClipWarmupPhase, string> = {
+  loading_model: "모델 로드",
+  caching_text: "텍스트 임베딩 캐시",
+  smoke_test: "동작 확인",
+  building_pool: "세션 풀 구성",
+  ready: "완료",
+  failed: "실패",
+};
+
+export function describeClipWarmup(e: ClipWarmupEvent): string {
+  if (e.phase === "failed") return `모델 준비 실패: ${e.error ?? ""}`;
+  const pool =
+    e.phase === "building_pool" && e.poolIndex && e.poolSize ? ` ${e.poolIndex}/${e.poolSize}` : "";
+  return `모델 준비 중 (${clipWarmupLabel[e.phase]}${pool}, ${(e.elapsedMs / 1000).toFixed(1)}s)`;
+}
@@ -6,27 +6,64 @@ import { useAnalysis } from "../features/analysis/store";
 import { toast } from "../components/toast/toastBus";
 import { platform } from "../lib/platform";
 import { pickDirectory } from "../lib/pickDirectory";
+import { describeClipWarmup } from "../lib/clipWarmup";
+import { listJobs, undoLastExport } from "../lib/api";
+import { AnalysisEngine, JobRecord, SettingsOverride } from "../lib/api/types";
 
 function MainPage() {
   const {
     sourceRoot,
     exportRoot,
     jobElapsedMs,
+    jobSummary,
     updateSourceRoot,
     updateExportRoot,
     startAnalysisNow,
+    resumeJobNow,
+    retryFailedNow,
     cancelCurrent,
+    removeFromQueue,
+    jobQueue,
     progress,
     starting,
     settingsStream,
     settingsEngine,
     settingsClipFallback,
     streamPanel,
+    clipWarmup,
+    scanProgress,
   } = useAnalysis();
 
   const isRunning = progress.status === "running";
   const canStart = Boolean(sourceRoot.trim()) && Boolean(exportRoot.trim());
   const processedPct = progress.total ? progress.processed / progress.total : 0;
+  const warmingUp =
+    isRunning && clipWarmup != null && clipWarmup.phase !== "ready" && clipWarmup.phase !== "failed";
+
+  const [sampleSize, setSampleSize] = useState<string>("20");
+  const [sampleRandom, setSampleRandom] = useState(false);
+  const sampleCount = Math.floor(Number(sampleSize));
+  const canSample = canStart && Number.isFinite(sampleCount) && sampleCount > 0;
+
+  // Per-job overrides; "" keeps the stored setting.
+  const [engineOverride, setEngineOverride] = useState<AnalysisEngine | "">("");
+  const [valueOverride, setValueOverride] = useState<"" | "on" | "off">("");
+  const settingsOverride: SettingsOverride | undefined =
+    engineOverride || valueOverride
+      ? {
+          analysisEngine: engineOverride || undefined,
+          analysisValueEnabled: valueOverride ? valueOverride === "on" : undefined,
+        }
+      : undefined;
+
+  // Jobs left running by a quit or crash; the backend marks them aborted on startup.
+  const [abortedJobs, setAbortedJobs] = useState<JobRecord[]>([]);
+  useEffect(() => {
+    if (isRunning) return;
+    listJobs()
+      .then((jobs) => setAbortedJobs(jobs.filter((j) => j.status === "aborted")))
+      .catch(() => setAbortedJobs([]));
+  }, [isRunning]);
 
   const [detailLoadingId, setDetailLoadingId] = useState<string | null>(null);
   useEffect(() => {
@@ -57,6 +94,29 @@ function MainPage() {
     if (value) setter(value);
   };
 
+  // Sends the last job's exports to the trash; the dry run supplies the counts for the prompt.
+  const [undoing, setUndoing] = useState(false);
+  const onUndoExport = async () => {
+    setUndoing(true);
+    try {
+      const preview = await undoLastExport(true);
+      const ok = window.confirm(
+        `마지막 작업의 내보낸 파일 ${preview.files}개를 휴지통으로 옮기고 결과 ${preview.rows}개를 삭제할까요? (원본은 그대로 둡니다)`
+      );
+      if (!ok) return;
+      const report = await undoLastExport();
+      if (report.failed.length) {
+        toast.error(`${report.failed.length}개 파일을 옮기지 못했습니다: ${report.failed[0]}`);
+      } else {
+        toast.success(`내보낸 파일 ${report.files}개를 휴지통으로 옮겼습니다`);
+      }
+    } catch (e) {
+      toast.error(`되돌리기 실패: ${e}`);
+    } finally {
+      setUndoing(false);
+    }
+  };
+
   return (
     <div className="page">
       <h1>이미지 분류</h1>
@@ -99,16 +159,104 @@ function MainPage() {
 
       <div className="section flex-between">
         <div style={{ display: "flex", gap: 8 }}>
-          <PrimaryButton onClick={startAnalysisNow} loading={starting} disabled={isRunning || !canStart}>
-            분석 시작
+          <PrimaryButton onClick={() => startAnalysisNow(undefined, settingsOverride)} loading={starting} disabled={!canStart}>
+            {isRunning ? "대기열에 추가" : "분석 시작"}
           </PrimaryButton>
-          <PrimaryButton variant="ghost" onClick={cancelCurrent} disabled={!isRunning}>
+          <PrimaryButton variant="ghost" onClick={() => cancelCurrent()} disabled={!isRunning}>
             중지
           </PrimaryButton>
+          {jobQueue.length > 0 && (
+            <PrimaryButton variant="ghost" onClick={() => cancelCurrent(true)} disabled={!isRunning}>
+              모두 중지
+            </PrimaryButton>
+          )}
+        </div>
+        <div style={{ display: "flex", gap: 8, alignItems: "center" }}>
+          <input
+            className="pager-input"
+            inputMode="numeric"
+            value={sampleSize}
+            onChange={(e) => setSampleSize(e.target.value)}
+            aria-label="샘플 개수"
+          />
+          <label className="muted" style={{ display: "flex", gap: 4, alignItems: "center" }}>
+            <input
+              type="checkbox"
+              checked={sampleRandom}
+              onChange={(e) => setSampleRandom(e.target.checked)}
+            />
+            무작위
+          </label>
+          <PrimaryButton
+            variant="secondary"
+            onClick={() => startAnalysisNow({ maxImages: sampleCount, sampleRandom }, settingsOverride)}
+            loading={starting}
+            disabled={!canSample}
+          >
+            샘플 분석
+          </PrimaryButton>
         </div>
         <div className="pill">상태: {progress.status}</div>
       </div>
 
+      <div className="section" style={{ display: "flex", gap: 8, alignItems: "center" }}>
+        <span className="muted">이번 작업만</span>
+        <select
+          className="select"
+          value={engineOverride}
+          onChange={(e) => setEngineOverride(e.target.value as AnalysisEngine | "")}
+          aria-label="엔진"
+        >
+          <option value="">엔진: 설정대로</option>
+          <option value="clip">CLIP</option>
+          <option value="ollama">Ollama</option>
+          <option value="hybrid">Hybrid</option>
+        </select>
+        <select
+          className="select"
+          value={valueOverride}
+          onChange={(e) => setValueOverride(e.target.value as "" | "on" | "off")}
+          aria-label="가치 분류"
+        >
+          <option value="">가치 분류: 설정대로</option>
+          <option value="on">가치 분류 켜기</option>
+          <option value="off">가치 분류 끄기</option>
+        </select>
+      </div>
+
+      {jobQueue.length > 0 && (
+        <div className="section card">
+          <div className="section-title">대기열</div>
+          {jobQueue.map((job) => (
+            <div key={job.jobId} className="flex-between" style={{ marginTop: 8 }}>
+              <div className="muted">
+                {job.position}. {job.sourceRoot} → {job.exportRoot}
+              </div>
+              <PrimaryButton variant="ghost" onClick={() => removeFromQueue(job.jobId)}>
+                제거
+              </PrimaryButton>
+            </div>
+          ))}
+        </div>
+      )}
+
+      {!isRunning && abortedJobs.length > 0 && (
+        <div className="section card">
+          <div className="section-title">중단된 작업</div>
+          {abortedJobs.map((job) => (
+            <div key={job.id} className="flex-between" style={{ marginTop: 8 }}>
+              <div className="muted">
+                {job.sourceRoot} → {job.exportRoot} | 완료 {job.done}장
+                {job.startedAt && ` | 시작 ${new Date(job.startedAt).toLocaleString()}`}
+              </div>
+              <PrimaryButton variant="secondary" onClick={() => resumeJobNow(job.id)} loading={starting}>
+                이어하기
+              </PrimaryButton>
+            </div>
+          ))}
+        </div>
+      )}
+
       <div
         className={`section card progress-panel ${
           progress.status === "running" || progress.status === "completed" ? "visible" : ""
@@ -121,6 +269,76 @@ function MainPage() {
         {progress.status === "completed" && typeof jobElapsedMs === "number" && (
           <div className="muted" style={{ marginTop: 8 }}>
             총 소요 시간: {(jobElapsedMs / 1000).toFixed(1)}초
+            {jobSummary?.jobId === progress.jobId && (
+              <>
+                {" "}| {jobSummary.imagesPerSec.toFixed(2)}장/초
+                {jobSummary.avgVisionInferMs != null &&
+                  ` | CLIP 추론 평균 ${jobSummary.avgVisionInferMs.toFixed(1)}ms`}
+                {jobSummary.avgEvalTokensPerSec != null &&
+                  ` | Ollama 생성 평균 ${jobSummary.avgEvalTokensPerSec.toFixed(1)} tok/s`}
+                {(jobSummary.valuable ?? 0) + (jobSummary.notValuable ?? 0) > 0 &&
+                  ` | 가치 있음 ${jobSummary.valuable ?? 0} / 없음 ${jobSummary.notValuable ?? 0}`}
+              </>
+            )}
+          </div>
+        )}
+        {progress.status === "completed" && (
+          <div style={{ marginTop: 8 }}>
+            <PrimaryButton variant="secondary" onClick={onUndoExport} loading={undoing}>
+              내보내기 되돌리기
+            </PrimaryButton>
+          </div>
+        )}
+        {progress.status === "completed" && progress.errors > 0 && (
+          <div style={{ marginTop: 8, display: "flex", gap: 8 }}>
+            <PrimaryButton
+              variant="secondary"
+              onClick={() => retryFailedNow(progress.jobId)}
+              loading={starting}
+            >
+              실패 {progress.errors}장 재시도
+            </PrimaryButton>
+            <PrimaryButton
+              variant="secondary"
+              onClick={() => retryFailedNow(progress.jobId, ["network", "timeout"])}
+              loading={starting}
+            >
+              네트워크 실패만 재시도
+            </PrimaryButton>
+          </div>
+        )}
+        {progress.status === "completed" &&
+          jobSummary?.jobId === progress.jobId &&
+          !!(
+            jobSummary.slowest?.length ||
+            jobSummary.failures?.length ||
+            jobSummary.missing?.length
+          ) && (
+            <details className="muted" style={{ marginTop: 8 }}>
+              <summary>작업 요약</summary>
+              {jobSummary.slowest?.map((p) => (
+                <div key={p.path}>
+                  느림: {p.path} ({p.durationMs}ms)
+                </div>
+              ))}
+              {jobSummary.failures?.map((f, i) => (
+                <div key={i}>
+                  실패: {f.path} — {f.error}
+                </div>
+              ))}
+              {jobSummary.missing?.map((path) => (
+                <div key={path}>없는 파일: {path}</div>
+              ))}
+            </details>
+          )}
+        {scanProgress && (
+          <div className="muted" style={{ marginTop: 8 }}>
+            스캔 중… {scanProgress.found.toLocaleString()}장 발견 ({scanProgress.scanned.toLocaleString()}개 항목 확인)
+          </div>
+        )}
+        {warmingUp && clipWarmup && (
+          <div className="muted" style={{ marginTop: 8 }}>
+            {describeClipWarmup(clipWarmup)}
           </div>
         )}
         {progress.currentFile && (
@@ -132,16 +350,22 @@ function MainPage() {
 
       {isRunning &&
         settingsStream &&
-        (settingsEngine === "ollama" || settingsClipFallback) &&
+        (settingsEngine !== "clip" || settingsClipFallback) &&
         streamPanel.isOpen && (
         <div className="section card stream-panel">
-          <div className="flex-between">
-            <div className="section-title">Stream</div>
-            <div className="muted" style={{ fontSize: 12 }}>
-              {streamPanel.fileName ? `파일: ${streamPanel.fileName}` : "대기 중…"}
-            </div>
+          <div className="section-title">Stream</div>
+          <div className="stream-panes">
+            {Object.entries(streamPanel.panes)
+              .sort(([a], [b]) => Number(a) - Number(b))
+              .map(([slot, pane]) => (
+                <div key={slot}>
+                  <div className="muted" style={{ fontSize: 12 }}>
+                    {pane.fileName ? `파일: ${pane.fileName}` : "대기 중…"}
+                  </div>
+                  <pre className="stream-pre">{pane.text || "…"}</pre>
+                </div>
+              ))}
           </div>
-          <pre className="stream-pre">{streamPanel.text || "…"}</pre>
         </div>
       )}
     </div>
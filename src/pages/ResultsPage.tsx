@@ -1,12 +1,37 @@
-import { useEffect, useMemo, useState } from "react";
+import { useEffect, useMemo, useRef, useState } from "react";
 import DataTable from "../components/table/DataTable";
 import Badge from "../components/status/Badge";
 import RadarChart from "../components/charts/RadarChart";
 import Modal from "../components/dialog/Modal";
 import { categoryLabelMap, categoryOrder } from "../lib/categories";
 import { useAnalysis } from "../features/analysis/store";
-import { PhotoDetail, PhotoRow } from "../lib/api/types";
-import { getPhotoDetail } from "../lib/api";
+import {
+  CategoryKey,
+  JobRecord,
+  PhotoDetail,
+  PhotoFilter,
+  PhotoRow,
+  TagCount,
+  ValueCounts,
+} from "../lib/api/types";
+import {
+  countPhotos,
+  deletePhotos,
+  getPhotoDetail,
+  getTagStats,
+  listJobs,
+  listPhotos,
+  revealInFileManager,
+  setPhotoCategory,
+} from "../lib/api";
+import { toast } from "../components/toast/toastBus";
+
+// Share of judged photos marked valuable; photos without a judgment are left out.
+function formatKeepRatio(counts?: ValueCounts): string {
+  const judged = (counts?.valuable ?? 0) + (counts?.notValuable ?? 0);
+  if (!counts || judged === 0) return "-";
+  return `${((counts.valuable / judged) * 100).toFixed(1)}% (${counts.valuable}/${judged})`;
+}
 
 function ResultsPage() {
   const {
@@ -23,11 +48,14 @@ function ResultsPage() {
     loadingDetail,
   } = useAnalysis();
 
-  const filteredPhotos = useMemo(() => {
-    if (categoryFilter === "all") return photos;
-    return photos.filter((p) => p.category === categoryFilter);
-  }, [photos, categoryFilter]);
-
+  const [failuresOnly, setFailuresOnly] = useState(false);
+  const [jobFilter, setJobFilter] = useState("");
+  const [jobs, setJobs] = useState<JobRecord[]>([]);
+  // Local calendar days from the date inputs; the filter sends them as UTC instants.
+  const [fromDate, setFromDate] = useState("");
+  const [toDate, setToDate] = useState("");
+  const [search, setSearch] = useState("");
+  const [debouncedSearch, setDebouncedSearch] = useState("");
   const [rowLimit, setRowLimit] = useState<number>(10);
   const [page, setPage] = useState<number>(1);
   const [pageInput, setPageInput] = useState<string>("1");
@@ -36,13 +64,87 @@ function ResultsPage() {
   const [logLoading, setLogLoading] = useState(false);
   const [logDetail, setLogDetail] = useState<PhotoDetail | null>(null);
 
-  const pageCount = Math.max(1, Math.ceil((filteredPhotos.length || 1) / (rowLimit || 1)));
-  const pageStart = (page - 1) * rowLimit;
-  const limitedPhotos = useMemo(
-    () => filteredPhotos.slice(pageStart, pageStart + rowLimit),
-    [filteredPhotos, pageStart, rowLimit]
+  // The table shows one page queried from the DB, so a large library never crosses IPC whole.
+  const [pagePhotos, setPagePhotos] = useState<PhotoRow[]>([]);
+  const [totalCount, setTotalCount] = useState(0);
+  // Rows arriving from a running job refetch the page at most once a second.
+  const [photosVersion, setPhotosVersion] = useState(0);
+  const refetchTimer = useRef<number | null>(null);
+
+  useEffect(() => {
+    listJobs()
+      .then((all) => setJobs(all.filter((j) => j.done > 0)))
+      .catch(() => setJobs([]));
+  }, []);
+
+  useEffect(() => {
+    const timer = setTimeout(() => setDebouncedSearch(search.trim()), 250);
+    return () => clearTimeout(timer);
+  }, [search]);
+
+  useEffect(() => {
+    if (refetchTimer.current != null) return;
+    refetchTimer.current = window.setTimeout(() => {
+      refetchTimer.current = null;
+      setPhotosVersion((v) => v + 1);
+    }, 1000);
+  }, [photos]);
+
+  useEffect(
+    () => () => {
+      if (refetchTimer.current != null) clearTimeout(refetchTimer.current);
+    },
+    []
   );
 
+  // Follows the category and job filters; clicking a tag searches for it.
+  const [tagStats, setTagStats] = useState<TagCount[]>([]);
+  useEffect(() => {
+    let stale = false;
+    getTagStats(50, categoryFilter === "all" ? undefined : categoryFilter, jobFilter || undefined)
+      .then((tags) => !stale && setTagStats(tags))
+      .catch(() => !stale && setTagStats([]));
+    return () => {
+      stale = true;
+    };
+  }, [categoryFilter, jobFilter, photosVersion]);
+
+  const filter = useMemo<PhotoFilter>(
+    () => ({
+      status: failuresOnly ? "error" : undefined,
+      category: categoryFilter === "all" ? undefined : categoryFilter,
+      search: debouncedSearch || undefined,
+      jobId: jobFilter || undefined,
+      from: fromDate ? new Date(`${fromDate}T00:00:00`).toISOString() : undefined,
+      to: toDate ? new Date(`${toDate}T23:59:59`).toISOString() : undefined,
+    }),
+    [failuresOnly, categoryFilter, debouncedSearch, jobFilter, fromDate, toDate]
+  );
+
+  useEffect(() => {
+    let stale = false;
+    Promise.all([
+      listPhotos(filter, { limit: rowLimit, offset: (page - 1) * rowLimit }),
+      countPhotos(filter),
+    ])
+      .then(([rows, count]) => {
+        if (stale) return;
+        setPagePhotos(rows);
+        setTotalCount(count);
+      })
+      .catch(() => {
+        if (stale) return;
+        setPagePhotos([]);
+        setTotalCount(0);
+      });
+    return () => {
+      stale = true;
+    };
+  }, [filter, page, rowLimit, photosVersion]);
+
+  // A row limit of 0 shows every row on one page.
+  const pageCount = rowLimit ? Math.max(1, Math.ceil(totalCount / rowLimit)) : 1;
+
   useEffect(() => {
     if (page > pageCount) {
       setPage(pageCount);
@@ -53,7 +155,7 @@ function ResultsPage() {
   useEffect(() => {
     setPage(1);
     setPageInput("1");
-  }, [categoryFilter, rowLimit, filteredPhotos.length]);
+  }, [filter, rowLimit]);
 
   useEffect(() => {
     setPageInput(String(page));
@@ -77,6 +179,34 @@ function ResultsPage() {
     }
   };
 
+  const deleteDetail = async (detail: PhotoDetail) => {
+    if (!window.confirm(`${detail.fileName} 결과를 삭제할까요?`)) return;
+    const withFile = window.confirm("내보낸 파일도 함께 삭제할까요? (원본은 삭제되지 않습니다)");
+    try {
+      const [result] = await deletePhotos([detail.id], withFile);
+      if (result?.error) toast.error(`삭제 중 오류: ${result.error}`);
+      else toast.success("삭제되었습니다");
+      if (result?.deleted) {
+        setLogDetail(null);
+        setPhotosVersion((v) => v + 1);
+      }
+    } catch (e) {
+      toast.error(`삭제 실패: ${e}`);
+    }
+  };
+
+  const recategorize = async (detail: PhotoDetail, category: CategoryKey) => {
+    try {
+      const result = await setPhotoCategory(detail.id, category);
+      setLogDetail(result.photo);
+      setPhotosVersion((v) => v + 1);
+      if (result.warning) toast.warning(result.warning);
+      else toast.success(`${categoryLabelMap[category]}(으)로 옮겼습니다`);
+    } catch (e) {
+      toast.error(`카테고리 변경 실패: ${e}`);
+    }
+  };
+
   const columns = [
     {
       key: "fileName",
@@ -110,7 +240,15 @@ function ResultsPage() {
     {
       key: "topScore",
       header: "Top score",
-      render: (r: PhotoRow) => `${(r.topScore * 100).toFixed(1)}%`,
+      render: (r: PhotoRow) => (
+        <span
+          title={(r.topK ?? [])
+            .map((t) => `${categoryLabelMap[t.category]} ${t.score.toFixed(2)}`)
+            .join(" / ")}
+        >
+          {`${(r.topScore * 100).toFixed(1)}%`}
+        </span>
+      ),
     },
     {
       key: "exportStatus",
@@ -168,10 +306,79 @@ function ResultsPage() {
         </div>
       </div>
 
+      {tagStats.length > 0 && (
+        <div className="section card">
+          <div className="section-title">자주 쓰인 태그</div>
+          <div className="chips">
+            {tagStats.map((t) => (
+              <button
+                key={t.tag}
+                className={`chip ${debouncedSearch === t.tag ? "active" : ""}`}
+                style={{
+                  fontSize: `${0.8 + 0.6 * (t.count / tagStats[0].count)}em`,
+                }}
+                onClick={() => setSearch(debouncedSearch === t.tag ? "" : t.tag)}
+                title={`${t.count}장`}
+              >
+                {t.tag} {t.count}
+              </button>
+            ))}
+          </div>
+        </div>
+      )}
+
       <div className="section card">
         <div className="flex-between" style={{ marginBottom: 10 }}>
           <div className="section-title">결과 테이블</div>
           <div style={{ display: "flex", alignItems: "center", gap: 8 }}>
+            <input
+              className="pager-input"
+              style={{ width: 160 }}
+              placeholder="파일명/태그/캡션 검색"
+              value={search}
+              onChange={(e) => setSearch(e.target.value)}
+              aria-label="검색"
+            />
+            {jobs.length > 0 && (
+              <select
+                className="select"
+                value={jobFilter}
+                onChange={(e) => setJobFilter(e.target.value)}
+                aria-label="작업"
+              >
+                <option value="">모든 작업</option>
+                {jobs.map((job) => (
+                  <option key={job.id} value={job.id}>
+                    {`${job.startedAt ? new Date(job.startedAt).toLocaleString() : job.id} (${job.done}장)`}
+                  </option>
+                ))}
+              </select>
+            )}
+            <input
+              type="date"
+              className="pager-input"
+              value={fromDate}
+              max={toDate || undefined}
+              onChange={(e) => setFromDate(e.target.value)}
+              aria-label="분석 시작일"
+            />
+            <span className="muted">~</span>
+            <input
+              type="date"
+              className="pager-input"
+              value={toDate}
+              min={fromDate || undefined}
+              onChange={(e) => setToDate(e.target.value)}
+              aria-label="분석 종료일"
+            />
+            <label className="muted" style={{ display: "flex", gap: 4, alignItems: "center" }}>
+              <input
+                type="checkbox"
+                checked={failuresOnly}
+                onChange={(e) => setFailuresOnly(e.target.checked)}
+              />
+              실패만
+            </label>
             <label className="muted" htmlFor="row-limit">
               표시 개수
             </label>
@@ -190,13 +397,13 @@ function ResultsPage() {
                   {n}
                 </option>
               ))}
-              <option value={filteredPhotos.length}>전체</option>
+              <option value={0}>전체</option>
             </select>
           </div>
         </div>
         <DataTable
           columns={columns}
-          data={limitedPhotos}
+          data={pagePhotos}
           onRowClick={handleRowClick}
           emptyText="결과가 없습니다. 분석을 실행하세요."
         />
@@ -216,12 +423,43 @@ function ResultsPage() {
               </div>
               {logDetail ? (
                 <>
-                  <div className="muted" style={{ marginTop: 8 }}>
-                    파일: {logDetail.fileName} · 상태: {logDetail.exportStatus}
+                  <div className="flex-between" style={{ marginTop: 8 }}>
+                    <div className="muted">
+                      파일: {logDetail.fileName} · 상태: {logDetail.exportStatus}
+                      {logDetail.manualOverride && " · 수동 분류"}
+                    </div>
+                    <div style={{ display: "flex", gap: 8 }}>
+                      <select
+                        className="select"
+                        value={logDetail.category}
+                        onChange={(e) => recategorize(logDetail, e.target.value as CategoryKey)}
+                        aria-label="카테고리 변경"
+                      >
+                        {categoryOrder.map((c) => (
+                          <option key={c} value={c}>
+                            {categoryLabelMap[c]}
+                          </option>
+                        ))}
+                      </select>
+                      <button
+                        className="pager-btn"
+                        onClick={() =>
+                          revealInFileManager(logDetail.id).catch((e) =>
+                            toast.error(`폴더를 열 수 없습니다: ${e}`)
+                          )
+                        }
+                      >
+                        폴더에서 보기
+                      </button>
+                      <button className="pager-btn" onClick={() => deleteDetail(logDetail)}>
+                        삭제
+                      </button>
+                    </div>
                   </div>
                   {logDetail.errorMessage && (
                     <div className="muted" style={{ marginTop: 8 }}>
                       에러: {logDetail.errorMessage}
+                      {logDetail.failureKind && ` (${logDetail.failureKind})`}
                     </div>
                   )}
                   <pre className="log-pre">{logDetail.analysisLog ?? "(로그 없음)"}</pre>
@@ -273,7 +511,7 @@ function ResultsPage() {
             />
             <span className="muted"> / {pageCount}</span>
           </div>
-          <span className="muted">(총 {filteredPhotos.length}개)</span>
+          <span className="muted">(총 {totalCount}개)</span>
           <button
             className="pager-btn"
             onClick={() => setPage((p) => Math.min(pageCount, p + 1))}
@@ -347,6 +585,7 @@ function ResultsPage() {
                     <th>카테고리</th>
                     <th>평균 점수</th>
                     <th>개수 비율</th>
+                    {settingsValueEnabled && valueStats?.byCategory && <th>가치 있음</th>}
                   </tr>
                 </thead>
                 <tbody>
@@ -363,6 +602,9 @@ function ResultsPage() {
                           ? `${(distributionCount.byCategory[c] * 100).toFixed(1)}%`
                           : "-"}
                       </td>
+                      {settingsValueEnabled && valueStats?.byCategory && (
+                        <td>{formatKeepRatio(valueStats.byCategory[c])}</td>
+                      )}
                     </tr>
                   ))}
                 </tbody>
@@ -389,8 +631,35 @@ function ResultsPage() {
             </div>
             <div>
               <div className="muted">카테고리</div>
-              <div>{categoryLabelMap[selectedPhoto.category]}</div>
+              <div>
+                {categoryLabelMap[selectedPhoto.category]}
+                {selectedPhoto.subcategory ? ` / ${selectedPhoto.subcategory}` : ""}
+              </div>
             </div>
+            {selectedPhoto.valueBucket && (
+              <div>
+                <div className="muted">저장 가치</div>
+                <div>{selectedPhoto.valueBucket}</div>
+              </div>
+            )}
+            {selectedPhoto.exif && (
+              <div>
+                <div className="muted">촬영 정보</div>
+                <div>
+                  {[selectedPhoto.exif.make, selectedPhoto.exif.model].filter(Boolean).join(" ")}
+                  {selectedPhoto.exif.focalLengthMm != null &&
+                    ` | ${selectedPhoto.exif.focalLengthMm.toFixed(0)}mm`}
+                  {selectedPhoto.exif.dateTimeOriginal &&
+                    ` | ${selectedPhoto.exif.dateTimeOriginal.replace("T", " ")}`}
+                </div>
+                {selectedPhoto.exif.gpsLatitude != null && selectedPhoto.exif.gpsLongitude != null && (
+                  <div>
+                    GPS {selectedPhoto.exif.gpsLatitude.toFixed(5)},{" "}
+                    {selectedPhoto.exif.gpsLongitude.toFixed(5)}
+                  </div>
+                )}
+              </div>
+            )}
             <div className="radar-wrap">
               <RadarChart scores={selectedPhoto.scores} />
             </div>
@@ -4,6 +4,15 @@ import { useAnalysis } from "../features/analysis/store";
 import SettingsHome from "./settings/SettingsHome";
 import OllamaSettings from "./settings/OllamaSettings";
 import ClipSettings from "./settings/ClipSettings";
+import ScanSettings from "./settings/ScanSettings";
+import OllamaAdvancedSettings from "./settings/OllamaAdvancedSettings";
+import HybridSettings from "./settings/HybridSettings";
+import CategoryBiasSettings from "./settings/CategoryBiasSettings";
+import ClipPromptTester from "./settings/ClipPromptTester";
+import ValueExportSettings from "./settings/ValueExportSettings";
+import DecodeSettings from "./settings/DecodeSettings";
+import StorageSettings from "./settings/StorageSettings";
+import { AnalysisEngine } from "../lib/api/types";
 
 interface SettingsPageProps {
   theme: "light" | "dark";
@@ -18,9 +27,13 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
     settingsModel,
     settingsThink,
     settingsStream,
+    settingsOllamaTimeoutSecs,
+    settingsOutputLanguage,
     settingsResizeEnabled,
     settingsMaxEdge,
+    settingsResizePercent,
     settingsJpegQuality,
+    settingsImageFormat,
     settingsValueEnabled,
     settingsConcurrency,
     settingsEngine,
@@ -29,10 +42,12 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
     settingsClipFallback,
     settingsClipEpAuto,
     settingsClipEpCoreml,
+    settingsClipEpTensorrt,
     settingsClipEpCuda,
     settingsClipEpRocm,
     settingsClipEpDirectml,
     settingsClipEpOpenvino,
+    settingsClipGpuDeviceId,
     resetResults,
     saveSettings,
   } = useAnalysis();
@@ -43,30 +58,40 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
   const [model, setModel] = useState(settingsModel);
   const [think, setThink] = useState(settingsThink);
   const [stream, setStream] = useState(settingsStream);
+  const [ollamaTimeoutSecs, setOllamaTimeoutSecs] = useState(settingsOllamaTimeoutSecs);
+  const [outputLanguage, setOutputLanguage] = useState(settingsOutputLanguage);
   const [resizeEnabled, setResizeEnabled] = useState(settingsResizeEnabled);
   const [maxEdge, setMaxEdge] = useState(settingsMaxEdge);
+  const [resizePercent, setResizePercent] = useState(settingsResizePercent);
   const [jpegQuality, setJpegQuality] = useState(settingsJpegQuality);
+  const [imageFormat, setImageFormat] = useState(settingsImageFormat);
   const [valueEnabled, setValueEnabled] = useState(settingsValueEnabled);
   const [concurrency, setConcurrency] = useState(settingsConcurrency);
-  const [engine, setEngine] = useState<"clip" | "ollama">(settingsEngine);
+  const [engine, setEngine] = useState<AnalysisEngine>(settingsEngine);
   const [clipModelDir, setClipModelDir] = useState(settingsClipModelDir);
   const [clipModelFile, setClipModelFile] = useState(settingsClipModelFile);
   const [clipFallbackToOllama, setClipFallbackToOllama] =
     useState(settingsClipFallback);
   const [clipEpAuto, setClipEpAuto] = useState(settingsClipEpAuto);
   const [clipEpCoreml, setClipEpCoreml] = useState(settingsClipEpCoreml);
+  const [clipEpTensorrt, setClipEpTensorrt] = useState(settingsClipEpTensorrt);
   const [clipEpCuda, setClipEpCuda] = useState(settingsClipEpCuda);
   const [clipEpRocm, setClipEpRocm] = useState(settingsClipEpRocm);
   const [clipEpDirectml, setClipEpDirectml] = useState(settingsClipEpDirectml);
   const [clipEpOpenvino, setClipEpOpenvino] = useState(settingsClipEpOpenvino);
+  const [clipGpuDeviceId, setClipGpuDeviceId] = useState(settingsClipGpuDeviceId);
 
   useEffect(() => setBaseUrl(settingsBaseUrl), [settingsBaseUrl]);
   useEffect(() => setModel(settingsModel), [settingsModel]);
   useEffect(() => setThink(settingsThink), [settingsThink]);
   useEffect(() => setStream(settingsStream), [settingsStream]);
+  useEffect(() => setOllamaTimeoutSecs(settingsOllamaTimeoutSecs), [settingsOllamaTimeoutSecs]);
+  useEffect(() => setOutputLanguage(settingsOutputLanguage), [settingsOutputLanguage]);
   useEffect(() => setResizeEnabled(settingsResizeEnabled), [settingsResizeEnabled]);
   useEffect(() => setMaxEdge(settingsMaxEdge), [settingsMaxEdge]);
+  useEffect(() => setResizePercent(settingsResizePercent), [settingsResizePercent]);
   useEffect(() => setJpegQuality(settingsJpegQuality), [settingsJpegQuality]);
+  useEffect(() => setImageFormat(settingsImageFormat), [settingsImageFormat]);
   useEffect(() => setValueEnabled(settingsValueEnabled), [settingsValueEnabled]);
   useEffect(() => setConcurrency(settingsConcurrency), [settingsConcurrency]);
   useEffect(() => setEngine(settingsEngine), [settingsEngine]);
@@ -75,10 +100,12 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
   useEffect(() => setClipFallbackToOllama(settingsClipFallback), [settingsClipFallback]);
   useEffect(() => setClipEpAuto(settingsClipEpAuto), [settingsClipEpAuto]);
   useEffect(() => setClipEpCoreml(settingsClipEpCoreml), [settingsClipEpCoreml]);
+  useEffect(() => setClipEpTensorrt(settingsClipEpTensorrt), [settingsClipEpTensorrt]);
   useEffect(() => setClipEpCuda(settingsClipEpCuda), [settingsClipEpCuda]);
   useEffect(() => setClipEpRocm(settingsClipEpRocm), [settingsClipEpRocm]);
   useEffect(() => setClipEpDirectml(settingsClipEpDirectml), [settingsClipEpDirectml]);
   useEffect(() => setClipEpOpenvino(settingsClipEpOpenvino), [settingsClipEpOpenvino]);
+  useEffect(() => setClipGpuDeviceId(settingsClipGpuDeviceId), [settingsClipGpuDeviceId]);
 
   const onSaveAll = () =>
     saveSettings({
@@ -86,9 +113,13 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
       model,
       think,
       stream,
+      ollamaTimeoutSecs,
+      outputLanguage,
       resizeEnabled,
       maxEdge,
+      resizePercent,
       jpegQuality,
+      imageFormat,
       valueEnabled,
       concurrency,
       engine,
@@ -97,10 +128,12 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
       clipFallbackToOllama,
       clipEpAuto,
       clipEpCoreml,
+      clipEpTensorrt,
       clipEpCuda,
       clipEpRocm,
       clipEpDirectml,
       clipEpOpenvino,
+      clipGpuDeviceId,
     });
 
   const ollamaDraft = useMemo(
@@ -109,12 +142,29 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
       model,
       think,
       stream,
+      ollamaTimeoutSecs,
+      outputLanguage,
       resizeEnabled,
       maxEdge,
+      resizePercent,
       jpegQuality,
+      imageFormat,
       concurrency,
     }),
-    [baseUrl, model, think, stream, resizeEnabled, maxEdge, jpegQuality, concurrency]
+    [
+      baseUrl,
+      model,
+      think,
+      stream,
+      ollamaTimeoutSecs,
+      outputLanguage,
+      resizeEnabled,
+      maxEdge,
+      resizePercent,
+      jpegQuality,
+      imageFormat,
+      concurrency,
+    ]
   );
 
   const clipDraft = useMemo(
@@ -124,19 +174,23 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
       clipFallbackToOllama,
       clipEpAuto,
       clipEpCoreml,
+      clipEpTensorrt,
       clipEpCuda,
       clipEpRocm,
       clipEpDirectml,
       clipEpOpenvino,
+      clipGpuDeviceId,
       valueEnabled,
       concurrency,
     }),
     [
       clipEpAuto,
       clipEpCoreml,
+      clipEpTensorrt,
       clipEpCuda,
       clipEpDirectml,
       clipEpOpenvino,
+      clipGpuDeviceId,
       clipEpRocm,
       clipFallbackToOllama,
       clipModelDir,
@@ -200,9 +254,13 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
                 model,
                 think,
                 stream,
+                ollamaTimeoutSecs,
+                outputLanguage,
                 resizeEnabled,
                 maxEdge,
+                resizePercent,
                 jpegQuality,
+                imageFormat,
                 valueEnabled,
                 concurrency,
                 engine: next,
@@ -211,16 +269,28 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
                 clipFallbackToOllama,
                 clipEpAuto,
                 clipEpCoreml,
+                clipEpTensorrt,
                 clipEpCuda,
                 clipEpRocm,
                 clipEpDirectml,
                 clipEpOpenvino,
+                clipGpuDeviceId,
               });
             }}
             onOpenOllama={() => setView("ollama")}
             onOpenClip={() => setView("clip")}
           />
 
+          {engine === "hybrid" && <HybridSettings />}
+
+          <ScanSettings />
+
+          <ValueExportSettings />
+
+          <DecodeSettings />
+
+          <StorageSettings />
+
           <div className="section card" style={{ borderTop: "none" }}>
             <div className="section-title">결과 초기화</div>
             <p className="muted" style={{ marginTop: 6 }}>
@@ -241,41 +311,54 @@ function SettingsPage({ theme, onToggleTheme }: SettingsPageProps) {
           </div>
         </>
       ) : view === "ollama" ? (
-        <OllamaSettings
-          disabled={engine !== "ollama"}
-          draft={ollamaDraft}
-          onChange={(next) => {
-            if (next.baseUrl !== undefined) setBaseUrl(next.baseUrl);
-            if (next.model !== undefined) setModel(next.model);
-            if (next.think !== undefined) setThink(next.think);
-            if (next.stream !== undefined) setStream(next.stream);
-            if (next.resizeEnabled !== undefined) setResizeEnabled(next.resizeEnabled);
-            if (next.maxEdge !== undefined) setMaxEdge(next.maxEdge);
-            if (next.jpegQuality !== undefined) setJpegQuality(next.jpegQuality);
-            if (next.concurrency !== undefined) setConcurrency(next.concurrency);
-          }}
-          onSave={onSaveAll}
-        />
+        <>
+          <OllamaSettings
+            disabled={engine === "clip"}
+            draft={ollamaDraft}
+            onChange={(next) => {
+              if (next.baseUrl !== undefined) setBaseUrl(next.baseUrl);
+              if (next.model !== undefined) setModel(next.model);
+              if (next.think !== undefined) setThink(next.think);
+              if (next.stream !== undefined) setStream(next.stream);
+              if (next.ollamaTimeoutSecs !== undefined) setOllamaTimeoutSecs(next.ollamaTimeoutSecs);
+              if (next.outputLanguage !== undefined) setOutputLanguage(next.outputLanguage);
+              if (next.resizeEnabled !== undefined) setResizeEnabled(next.resizeEnabled);
+              if (next.maxEdge !== undefined) setMaxEdge(next.maxEdge);
+              if (next.resizePercent !== undefined) setResizePercent(next.resizePercent);
+              if (next.jpegQuality !== undefined) setJpegQuality(next.jpegQuality);
+              if (next.imageFormat !== undefined) setImageFormat(next.imageFormat);
+              if (next.concurrency !== undefined) setConcurrency(next.concurrency);
+            }}
+            onSave={onSaveAll}
+          />
+          <OllamaAdvancedSettings />
+        </>
       ) : (
-        <ClipSettings
-          disabled={engine !== "clip"}
-          draft={clipDraft}
-          onChange={(next) => {
-            if (next.clipModelDir !== undefined) setClipModelDir(next.clipModelDir);
-            if (next.clipModelFile !== undefined) setClipModelFile(next.clipModelFile);
-            if (next.clipFallbackToOllama !== undefined)
-              setClipFallbackToOllama(next.clipFallbackToOllama);
-            if (next.clipEpAuto !== undefined) setClipEpAuto(next.clipEpAuto);
-            if (next.clipEpCoreml !== undefined) setClipEpCoreml(next.clipEpCoreml);
-            if (next.clipEpCuda !== undefined) setClipEpCuda(next.clipEpCuda);
-            if (next.clipEpRocm !== undefined) setClipEpRocm(next.clipEpRocm);
-            if (next.clipEpDirectml !== undefined) setClipEpDirectml(next.clipEpDirectml);
-            if (next.clipEpOpenvino !== undefined) setClipEpOpenvino(next.clipEpOpenvino);
-            if (next.valueEnabled !== undefined) setValueEnabled(next.valueEnabled);
-            if (next.concurrency !== undefined) setConcurrency(next.concurrency);
-          }}
-          onSave={onSaveAll}
-        />
+        <>
+          <ClipSettings
+            disabled={engine === "ollama"}
+            draft={clipDraft}
+            onChange={(next) => {
+              if (next.clipModelDir !== undefined) setClipModelDir(next.clipModelDir);
+              if (next.clipModelFile !== undefined) setClipModelFile(next.clipModelFile);
+              if (next.clipFallbackToOllama !== undefined)
+                setClipFallbackToOllama(next.clipFallbackToOllama);
+              if (next.clipEpAuto !== undefined) setClipEpAuto(next.clipEpAuto);
+              if (next.clipEpCoreml !== undefined) setClipEpCoreml(next.clipEpCoreml);
+              if (next.clipEpTensorrt !== undefined) setClipEpTensorrt(next.clipEpTensorrt);
+              if (next.clipEpCuda !== undefined) setClipEpCuda(next.clipEpCuda);
+              if (next.clipEpRocm !== undefined) setClipEpRocm(next.clipEpRocm);
+              if (next.clipEpDirectml !== undefined) setClipEpDirectml(next.clipEpDirectml);
+              if (next.clipEpOpenvino !== undefined) setClipEpOpenvino(next.clipEpOpenvino);
+              if (next.clipGpuDeviceId !== undefined) setClipGpuDeviceId(next.clipGpuDeviceId);
+              if (next.valueEnabled !== undefined) setValueEnabled(next.valueEnabled);
+              if (next.concurrency !== undefined) setConcurrency(next.concurrency);
+            }}
+            onSave={onSaveAll}
+          />
+          <CategoryBiasSettings />
+          <ClipPromptTester />
+        </>
       )}
     </div>
   );
@@ -0,0 +1,87 @@
+import { useEffect, useState } from "react";
+import TextField from "../../components/forms/TextField";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { toast } from "../../components/toast/toastBus";
+import { getSettings, setSettings } from "../../lib/api";
+import { CategoryKey } from "../../lib/api/types";
+import { categoryLabelMap, categoryOrder } from "../../lib/categories";
+
+// Applied to an already loaded engine without a reload, so it saves on its own like the scan
+// options.
+function CategoryBiasSettings() {
+  const [bias, setBias] = useState<Partial<Record<CategoryKey, string>>>({});
+  const [screenshotHeuristic, setScreenshotHeuristic] = useState(false);
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    getSettings()
+      .then((s) => {
+        const next: Partial<Record<CategoryKey, string>> = {};
+        for (const key of categoryOrder) {
+          const value = s.categoryBias?.[key];
+          if (value) next[key] = String(value);
+        }
+        setBias(next);
+        setScreenshotHeuristic(s.screenshotHeuristic ?? false);
+      })
+      .catch(() => {});
+  }, []);
+
+  const onSave = async () => {
+    const categoryBias: Partial<Record<CategoryKey, number>> = {};
+    for (const key of categoryOrder) {
+      const value = Number(bias[key]);
+      if (bias[key]?.trim() && Number.isFinite(value) && value !== 0) categoryBias[key] = value;
+    }
+    setSaving(true);
+    try {
+      const current = await getSettings();
+      await setSettings({ ...current, categoryBias, screenshotHeuristic });
+      toast.success("저장되었습니다");
+    } catch (e) {
+      toast.error(`저장 실패: ${e}`);
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  return (
+    <div className="section card">
+      <div className="section-title">카테고리 보정</div>
+      <p className="muted" style={{ marginTop: 6 }}>
+        CLIP이 특정 카테고리를 너무 자주(또는 드물게) 고를 때 점수에 더할 값입니다. 양수는 해당
+        카테고리를 밀어주고 음수는 억제합니다. 0.01~0.05 정도부터 시작해 보세요.
+      </p>
+      <div className="grid two" style={{ marginTop: 12 }}>
+        {categoryOrder.map((key) => (
+          <TextField
+            key={key}
+            label={categoryLabelMap[key]}
+            inputMode="decimal"
+            value={bias[key] ?? ""}
+            onChange={(e) => setBias((prev) => ({ ...prev, [key]: e.target.value }))}
+            placeholder="0"
+          />
+        ))}
+      </div>
+      <label
+        className="muted"
+        style={{ display: "flex", gap: 6, alignItems: "center", marginTop: 12 }}
+      >
+        <input
+          type="checkbox"
+          checked={screenshotHeuristic}
+          onChange={(e) => setScreenshotHeuristic(e.target.checked)}
+        />
+        단색 영역이 넓고 화면 비율인 이미지는 스크린샷_문서 쪽으로 보정
+      </label>
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onSave} loading={saving}>
+          Save
+        </PrimaryButton>
+      </div>
+    </div>
+  );
+}
+
+export default CategoryBiasSettings;
@@ -0,0 +1,105 @@
+import { useState } from "react";
+import TextField from "../../components/forms/TextField";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { toast } from "../../components/toast/toastBus";
+import { testClipPrompts } from "../../lib/api";
+import { CategoryKey, ClipPromptTestResult } from "../../lib/api/types";
+import { categoryLabelMap, categoryOrder } from "../../lib/categories";
+
+// "a photo of a receipt | 2" gives the prompt weight 2; without a valid weight it is 1.
+function parsePromptLine(line: string): [string, number] {
+  const bar = line.lastIndexOf("|");
+  if (bar > 0) {
+    const weight = Number(line.slice(bar + 1).trim());
+    if (Number.isFinite(weight) && weight > 0) return [line.slice(0, bar).trim(), weight];
+  }
+  return [line, 1];
+}
+
+// Nothing here is saved: it only compares trial prompt sets on one image. Results stay listed so
+// two sets can be read side by side.
+function ClipPromptTester() {
+  const [imagePath, setImagePath] = useState("");
+  const [prompts, setPrompts] = useState<Partial<Record<CategoryKey, string>>>({});
+  const [results, setResults] = useState<ClipPromptTestResult[]>([]);
+  const [running, setRunning] = useState(false);
+
+  const onTest = async () => {
+    const promptSets: Partial<Record<CategoryKey, [string, number][]>> = {};
+    for (const key of categoryOrder) {
+      const lines = (prompts[key] ?? "")
+        .split("\n")
+        .map((s) => s.trim())
+        .filter(Boolean)
+        .map(parsePromptLine);
+      if (lines.length) promptSets[key] = lines;
+    }
+    setRunning(true);
+    try {
+      const result = await testClipPrompts(imagePath.trim(), promptSets);
+      setResults((prev) => [result, ...prev].slice(0, 2));
+    } catch (e) {
+      toast.error(`테스트 실패: ${e}`);
+    } finally {
+      setRunning(false);
+    }
+  };
+
+  return (
+    <div className="section card">
+      <div className="section-title">프롬프트 테스트</div>
+      <p className="muted" style={{ marginTop: 6 }}>
+        카테고리별 프롬프트를 한 줄에 하나씩 입력하고 이미지 하나로 점수를 확인합니다. 비워 둔
+        카테고리는 기본 프롬프트를 씁니다. 줄 끝에 "| 2"처럼 가중치를 붙이면 그 프롬프트의 비중이
+        커집니다 (기본 1). 최근 두 결과를 나란히 보여줍니다.
+      </p>
+      <div style={{ marginTop: 12 }}>
+        <TextField
+          label="이미지 경로"
+          value={imagePath}
+          onChange={(e) => setImagePath(e.target.value)}
+          placeholder="/path/to/image.jpg"
+          fullWidth
+        />
+      </div>
+      <div className="grid two" style={{ marginTop: 12 }}>
+        {categoryOrder.map((key) => (
+          <label key={key} className="textfield fullWidth">
+            <span className="textfield-label">{categoryLabelMap[key]}</span>
+            <textarea
+              className="textfield-input"
+              rows={2}
+              value={prompts[key] ?? ""}
+              onChange={(e) => setPrompts((prev) => ({ ...prev, [key]: e.target.value }))}
+              placeholder="기본 프롬프트"
+            />
+          </label>
+        ))}
+      </div>
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onTest} loading={running} disabled={!imagePath.trim()}>
+          테스트
+        </PrimaryButton>
+      </div>
+      {results.length > 0 && (
+        <div className="grid two" style={{ marginTop: 12 }}>
+          {results.map((r, i) => (
+            <div key={i} className="muted">
+              <div>
+                {i === 0 ? "최근" : "이전"}: {categoryLabelMap[r.category]} (임베딩 {r.embedMs}ms, 추론{" "}
+                {r.inferMs}ms)
+              </div>
+              {categoryOrder.map((key) => (
+                <div key={key}>
+                  {categoryLabelMap[key]}: {r.scores[key].toFixed(4)}
+                </div>
+              ))}
+            </div>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+}
+
+export default ClipPromptTester;
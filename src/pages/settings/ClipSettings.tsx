@@ -1,6 +1,7 @@
 import TextField from "../../components/forms/TextField";
 import PrimaryButton from "../../components/buttons/PrimaryButton";
 import { useAnalysis } from "../../features/analysis/store";
+import { describeClipWarmup } from "../../lib/clipWarmup";
 
 interface ClipSettingsProps {
   disabled?: boolean;
@@ -10,10 +11,12 @@ interface ClipSettingsProps {
     clipFallbackToOllama: boolean;
     clipEpAuto: boolean;
     clipEpCoreml: boolean;
+    clipEpTensorrt: boolean;
     clipEpCuda: boolean;
     clipEpRocm: boolean;
     clipEpDirectml: boolean;
     clipEpOpenvino: boolean;
+    clipGpuDeviceId: number;
     valueEnabled: boolean;
     concurrency: number;
   };
@@ -22,10 +25,14 @@ interface ClipSettingsProps {
 }
 
 function ClipSettings({ disabled = false, draft, onChange, onSave }: ClipSettingsProps) {
-  const { clipAccelCaps, clipModelFiles } = useAnalysis();
+  const { clipAccelCaps, clipModelFiles, clipWarmup } = useAnalysis();
   const files = clipModelFiles.length ? clipModelFiles : [draft.clipModelFile].filter(Boolean);
   const hasCurrent = files.includes(draft.clipModelFile);
   const options = hasCurrent ? files : [draft.clipModelFile, ...files];
+  const gpuDevices = clipAccelCaps?.gpuDevices ?? [];
+  const gpuOptions = gpuDevices.some((d) => d.id === draft.clipGpuDeviceId)
+    ? gpuDevices
+    : [{ id: draft.clipGpuDeviceId, name: "" }, ...gpuDevices];
 
   return (
     <div
@@ -40,6 +47,11 @@ function ClipSettings({ disabled = false, draft, onChange, onSave }: ClipSetting
         <p className="muted" style={{ marginTop: 6 }}>
           모델 디렉터리를 지정하지 않으면 models/clip-vit-b32-onnx를 자동 탐색합니다.
         </p>
+        {clipWarmup && clipWarmup.phase !== "ready" && (
+          <p className="muted" style={{ marginTop: 6, fontSize: 12 }}>
+            {describeClipWarmup(clipWarmup)}
+          </p>
+        )}
         <div className="grid" style={{ marginTop: 12 }}>
           <TextField
             label="CLIP 모델 디렉터리(선택)"
@@ -178,6 +190,33 @@ function ClipSettings({ disabled = false, draft, onChange, onSave }: ClipSetting
             )}
           </div>
 
+          <div className="section card" style={{ border: "none", padding: 0 }}>
+            <div className="section-title" style={{ fontSize: 13 }}>
+              NVIDIA TensorRT
+            </div>
+            <div className="toggle-group" style={{ marginTop: 10 }}>
+              <button
+                className={draft.clipEpTensorrt ? "active" : ""}
+                onClick={() => onChange({ clipEpTensorrt: true })}
+                disabled={clipAccelCaps ? !clipAccelCaps.tensorrt.available : false}
+              >
+                ON
+              </button>
+              <button
+                className={!draft.clipEpTensorrt ? "active" : ""}
+                onClick={() => onChange({ clipEpTensorrt: false })}
+                disabled={clipAccelCaps ? !clipAccelCaps.tensorrt.supported : false}
+              >
+                OFF
+              </button>
+            </div>
+            {clipAccelCaps && !clipAccelCaps.tensorrt.available && (
+              <p className="muted" style={{ marginTop: 6, fontSize: 12 }}>
+                사용 불가: {clipAccelCaps.tensorrt.name}
+              </p>
+            )}
+          </div>
+
           <div className="section card" style={{ border: "none", padding: 0 }}>
             <div className="section-title" style={{ fontSize: 13 }}>
               NVIDIA CUDA
@@ -285,6 +324,29 @@ function ClipSettings({ disabled = false, draft, onChange, onSave }: ClipSetting
               </p>
             )}
           </div>
+
+          <div className="textfield">
+            <label className="muted" htmlFor="clip-gpu-device">
+              GPU 장치 (CUDA/TensorRT/DirectML)
+            </label>
+            <select
+              id="clip-gpu-device"
+              className="select"
+              value={draft.clipGpuDeviceId}
+              onChange={(e) => onChange({ clipGpuDeviceId: Number(e.target.value) })}
+            >
+              {gpuOptions.map((d) => (
+                <option key={d.id} value={d.id}>
+                  {d.name ? `${d.id}: ${d.name}` : `장치 ${d.id}`}
+                </option>
+              ))}
+            </select>
+            {gpuDevices.length === 0 && (
+              <div className="muted" style={{ marginTop: 6, fontSize: 12 }}>
+                장치 목록을 확인할 수 없습니다. 기본 장치(0)를 사용합니다.
+              </div>
+            )}
+          </div>
         </div>
       </div>
 
@@ -0,0 +1,81 @@
+import { useEffect, useState } from "react";
+import TextField from "../../components/forms/TextField";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { toast } from "../../components/toast/toastBus";
+import { getSettings, setSettings } from "../../lib/api";
+
+const toHex = (rgb: [number, number, number]) =>
+  `#${rgb.map((c) => c.toString(16).padStart(2, "0")).join("")}`;
+
+const fromHex = (hex: string): [number, number, number] => [
+  parseInt(hex.slice(1, 3), 16),
+  parseInt(hex.slice(3, 5), 16),
+  parseInt(hex.slice(5, 7), 16),
+];
+
+// Applies to both engines, so like the scan options it loads and saves on its own.
+function DecodeSettings() {
+  const [background, setBackground] = useState("#ffffff");
+  const [decodeWorkers, setDecodeWorkers] = useState("2");
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    getSettings()
+      .then((s) => {
+        setBackground(toHex(s.alphaBackground ?? [255, 255, 255]));
+        setDecodeWorkers(String(s.decodeConcurrency ?? 2));
+      })
+      .catch(() => {});
+  }, []);
+
+  const onSave = async () => {
+    setSaving(true);
+    try {
+      const current = await getSettings();
+      const workers = Number.parseInt(decodeWorkers, 10);
+      await setSettings({
+        ...current,
+        alphaBackground: fromHex(background),
+        decodeConcurrency: Number.isFinite(workers) && workers > 0 ? workers : 2,
+      });
+      toast.success("저장되었습니다");
+    } catch (e) {
+      toast.error(`저장 실패: ${e}`);
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  return (
+    <div className="section card">
+      <div className="section-title">디코딩</div>
+      <p className="muted" style={{ marginTop: 6 }}>
+        투명한 PNG는 이 색 위에 합성한 뒤 분석합니다. 기본값은 흰색입니다.
+      </p>
+      <div style={{ display: "flex", gap: 10, alignItems: "center", marginTop: 12 }}>
+        <input type="color" value={background} onChange={(e) => setBackground(e.target.value)} />
+        <span className="muted">{background}</span>
+      </div>
+      <p className="muted" style={{ marginTop: 12 }}>
+        이미지 디코딩과 전처리는 분석과 별도의 작업자가 미리 진행합니다. HEIC나 고해상도 사진이 많으면
+        늘려 보세요.
+      </p>
+      <div style={{ marginTop: 8 }}>
+        <TextField
+          label="디코딩 작업자 수"
+          inputMode="numeric"
+          value={decodeWorkers}
+          onChange={(e) => setDecodeWorkers(e.target.value)}
+          placeholder="예: 2"
+        />
+      </div>
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onSave} loading={saving}>
+          Save
+        </PrimaryButton>
+      </div>
+    </div>
+  );
+}
+
+export default DecodeSettings;
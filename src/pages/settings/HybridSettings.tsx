@@ -0,0 +1,62 @@
+import { useEffect, useState } from "react";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { toast } from "../../components/toast/toastBus";
+import { getSettings, setSettings } from "../../lib/api";
+import { HybridCaptionScope } from "../../lib/api/types";
+
+// Only the hybrid engine reads this, so it loads and saves on its own like the scan options.
+function HybridSettings() {
+  const [scope, setScope] = useState<HybridCaptionScope>("all");
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    getSettings()
+      .then((s) => setScope(s.hybridCaptionScope ?? "all"))
+      .catch(() => {});
+  }, []);
+
+  const onSave = async () => {
+    setSaving(true);
+    try {
+      const current = await getSettings();
+      await setSettings({ ...current, hybridCaptionScope: scope });
+      toast.success("저장되었습니다");
+    } catch (e) {
+      toast.error(`저장 실패: ${e}`);
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  return (
+    <div className="section card">
+      <div className="section-title">하이브리드 캡션</div>
+      <p className="muted" style={{ marginTop: 6 }}>
+        모든 사진을 CLIP으로 분류·내보낸 다음 두 번째 단계에서 Ollama가 캡션을 채웁니다. 중간에
+        취소해도 분류 결과는 유지되고 남은 사진의 캡션만 비어 있게 됩니다.
+      </p>
+      <div className="textfield" style={{ marginTop: 12 }}>
+        <label className="muted" htmlFor="hybrid-caption-scope">
+          캡션 대상
+        </label>
+        <select
+          id="hybrid-caption-scope"
+          className="select"
+          value={scope}
+          onChange={(e) => setScope(e.target.value as HybridCaptionScope)}
+        >
+          <option value="all">모든 사진</option>
+          <option value="valuable_only">가치 있는 사진만 (가치 판별이 꺼져 있으면 모두)</option>
+          <option value="none">캡션 없음 (CLIP만)</option>
+        </select>
+      </div>
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onSave} loading={saving}>
+          Save
+        </PrimaryButton>
+      </div>
+    </div>
+  );
+}
+
+export default HybridSettings;
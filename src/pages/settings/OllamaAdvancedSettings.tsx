@@ -0,0 +1,297 @@
+import { useEffect, useState } from "react";
+import TextField from "../../components/forms/TextField";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { toast } from "../../components/toast/toastBus";
+import { clearDebugCaptures, getSettings, setSettings } from "../../lib/api";
+import { CategoryKey, OllamaBackend } from "../../lib/api/types";
+import { categoryLabelMap, categoryOrder } from "../../lib/categories";
+
+// Prompt overrides and raw request options are rarely touched, so they load and save on their own
+// instead of going through the Ollama draft.
+function OllamaAdvancedSettings() {
+  const [backend, setBackend] = useState<OllamaBackend>("ollama");
+  const [systemPrompt, setSystemPrompt] = useState("");
+  const [userPrompt, setUserPrompt] = useState("");
+  const [keepAlive, setKeepAlive] = useState("");
+  const [optionsJson, setOptionsJson] = useState("");
+  const [preflightChecks, setPreflightChecks] = useState(true);
+  const [captureRaw, setCaptureRaw] = useState(false);
+  const [sanitizeOutput, setSanitizeOutput] = useState(true);
+  const [fallbackCategory, setFallbackCategory] = useState<CategoryKey>("other");
+  const [apiKey, setApiKey] = useState("");
+  const [headersJson, setHeadersJson] = useState("");
+  const [maxTags, setMaxTags] = useState("8");
+  const [maxTagChars, setMaxTagChars] = useState("20");
+  const [maxParallel, setMaxParallel] = useState("2");
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    getSettings()
+      .then((s) => {
+        setBackend(s.ollamaBackend ?? "ollama");
+        setSystemPrompt(s.ollamaSystemPrompt ?? "");
+        setUserPrompt(s.ollamaUserPrompt ?? "");
+        setKeepAlive(s.ollamaKeepAlive ?? "");
+        setPreflightChecks(s.preflightChecks ?? true);
+        setCaptureRaw(s.debugCaptureRawResponses ?? false);
+        setSanitizeOutput(s.sanitizeOutput ?? true);
+        setFallbackCategory(s.fallbackCategory ?? "other");
+        setApiKey(s.ollamaApiKey ?? "");
+        setMaxTags(String(s.ollamaMaxTags ?? 8));
+        setMaxTagChars(String(s.ollamaMaxTagChars ?? 20));
+        setMaxParallel(String(s.ollamaMaxParallel ?? 2));
+        const headers = s.ollamaExtraHeaders ?? {};
+        setHeadersJson(Object.keys(headers).length ? JSON.stringify(headers, null, 2) : "");
+        const options = s.ollamaOptions ?? {};
+        setOptionsJson(Object.keys(options).length ? JSON.stringify(options, null, 2) : "");
+      })
+      .catch(() => {});
+  }, []);
+
+  const onSave = async () => {
+    let options: Record<string, unknown> = {};
+    if (optionsJson.trim()) {
+      try {
+        const parsed = JSON.parse(optionsJson);
+        if (!parsed || typeof parsed !== "object" || Array.isArray(parsed)) throw new Error();
+        options = parsed;
+      } catch {
+        toast.error("옵션은 JSON 객체여야 합니다 (예: {\"num_ctx\": 4096})");
+        return;
+      }
+    }
+    let headers: Record<string, string> = {};
+    if (headersJson.trim()) {
+      try {
+        const parsed = JSON.parse(headersJson);
+        if (!parsed || typeof parsed !== "object" || Array.isArray(parsed)) throw new Error();
+        if (Object.values(parsed).some((v) => typeof v !== "string")) throw new Error();
+        headers = parsed;
+      } catch {
+        toast.error("추가 헤더는 문자열 값만 가진 JSON 객체여야 합니다");
+        return;
+      }
+    }
+    setSaving(true);
+    try {
+      const current = await getSettings();
+      await setSettings({
+        ...current,
+        ollamaBackend: backend,
+        ollamaSystemPrompt: systemPrompt.trim() ? systemPrompt : null,
+        ollamaUserPrompt: userPrompt.trim() ? userPrompt : null,
+        ollamaKeepAlive: keepAlive.trim(),
+        ollamaOptions: options,
+        preflightChecks,
+        debugCaptureRawResponses: captureRaw,
+        sanitizeOutput,
+        fallbackCategory,
+        ollamaApiKey: apiKey.trim() ? apiKey.trim() : null,
+        ollamaExtraHeaders: headers,
+        ollamaMaxTags: Math.min(12, Math.max(1, Math.floor(Number(maxTags)) || 8)),
+        ollamaMaxTagChars: Math.max(0, Math.floor(Number(maxTagChars)) || 0),
+        ollamaMaxParallel: Math.max(1, Math.floor(Number(maxParallel)) || 2),
+      });
+      toast.success("저장되었습니다");
+    } catch (e) {
+      toast.error(`저장 실패: ${e}`);
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  const onClearCaptures = async () => {
+    try {
+      await clearDebugCaptures();
+      toast.success("응답 기록을 삭제했습니다");
+    } catch (e) {
+      toast.error(`삭제 실패: ${e}`);
+    }
+  };
+
+  return (
+    <div className="section card" style={{ marginTop: 12 }}>
+      <div className="section-title">고급 설정</div>
+      <div className="textfield" style={{ marginTop: 12 }}>
+        <label className="muted" htmlFor="ollama-backend">
+          서버 종류
+        </label>
+        <select
+          id="ollama-backend"
+          className="select"
+          value={backend}
+          onChange={(e) => setBackend(e.target.value as OllamaBackend)}
+        >
+          <option value="ollama">Ollama</option>
+          <option value="openai_compatible">OpenAI 호환 (LM Studio, llama.cpp server)</option>
+        </select>
+        <span className="muted">
+          OpenAI 호환 서버는 Base URL에 http://127.0.0.1:1234 처럼 입력합니다 (/v1은 생략 가능).
+          저장 후 Test Connection과 모델 목록에 적용되며, think·keep_alive·모델 받기·사전 확인은
+          Ollama에서만 동작합니다.
+        </span>
+      </div>
+      <div className="textfield" style={{ marginTop: 12 }}>
+        <label className="muted" htmlFor="fallback-category">
+          애매한 응답의 카테고리
+        </label>
+        <select
+          id="fallback-category"
+          className="select"
+          value={fallbackCategory}
+          onChange={(e) => setFallbackCategory(e.target.value as CategoryKey)}
+        >
+          {categoryOrder.map((key) => (
+            <option key={key} value={key}>
+              {categoryLabelMap[key]}
+            </option>
+          ))}
+        </select>
+        <span className="muted">
+          모델이 알 수 없는 카테고리를 답하고 점수도 동점(또는 전부 0)이라 하나를 고를 수 없을 때
+          쓰입니다.
+        </span>
+      </div>
+      <div className="grid two" style={{ marginTop: 12 }}>
+        <TextField
+          label="API Key"
+          type="password"
+          value={apiKey}
+          onChange={(e) => setApiKey(e.target.value)}
+          placeholder="프록시 뒤의 Ollama용 (Bearer 토큰)"
+          helperText="저장 후 Test Connection에도 적용됩니다. 분석 로그와 오류에는 표시되지 않습니다."
+          fullWidth
+        />
+        <TextField
+          label="keep_alive"
+          value={keepAlive}
+          onChange={(e) => setKeepAlive(e.target.value)}
+          placeholder="예: 10m, -1 (계속 유지), 0 (바로 해제)"
+          helperText="비우면 Ollama 기본값(5분). 모델을 메모리에 유지하면 연속 분석이 빨라집니다."
+          fullWidth
+        />
+      </div>
+      <div className="grid two" style={{ marginTop: 12 }}>
+        <TextField
+          label="최대 태그 수"
+          inputMode="numeric"
+          value={maxTags}
+          onChange={(e) => setMaxTags(e.target.value)}
+          helperText="중복(대소문자·띄어쓰기만 다른 태그)을 합친 뒤 앞에서부터 남깁니다."
+          fullWidth
+        />
+        <TextField
+          label="태그 최대 글자 수"
+          inputMode="numeric"
+          value={maxTagChars}
+          onChange={(e) => setMaxTagChars(e.target.value)}
+          helperText="이보다 긴 태그는 버립니다. 0이면 제한 없음."
+          fullWidth
+        />
+      </div>
+      <div className="grid two" style={{ marginTop: 12 }}>
+        <TextField
+          label="동시 요청 수"
+          inputMode="numeric"
+          value={maxParallel}
+          onChange={(e) => setMaxParallel(e.target.value)}
+          helperText="병렬 처리 수와 별개로 서버에 동시에 보내는 요청을 제한합니다. GPU 하나면 1~2가 적당합니다."
+          fullWidth
+        />
+      </div>
+      <label
+        className="muted"
+        style={{ display: "flex", gap: 6, alignItems: "center", marginTop: 12 }}
+      >
+        <input
+          type="checkbox"
+          checked={preflightChecks}
+          onChange={(e) => setPreflightChecks(e.target.checked)}
+        />
+        분석 시작 전에 모델이 이미지를 지원하는지 확인
+      </label>
+      <label
+        className="muted"
+        style={{ display: "flex", gap: 6, alignItems: "center", marginTop: 8 }}
+      >
+        <input
+          type="checkbox"
+          checked={captureRaw}
+          onChange={(e) => setCaptureRaw(e.target.checked)}
+        />
+        디버그: 모델 응답 원문을 사진별 파일(앱 데이터/debug)로 저장하고 로그에는 경로만 표시
+      </label>
+      <label
+        className="muted"
+        style={{ display: "flex", gap: 6, alignItems: "center", marginTop: 8 }}
+      >
+        <input
+          type="checkbox"
+          checked={!sanitizeOutput}
+          onChange={(e) => setSanitizeOutput(!e.target.checked)}
+        />
+        디버그: 태그/캡션을 정리하지 않고 모델 출력 그대로 저장 (한국어 출력의 다른 문자 제거 안 함)
+      </label>
+      <div className="grid" style={{ marginTop: 12 }}>
+        <label className="textfield fullWidth">
+          <span className="textfield-label">추가 헤더 (JSON)</span>
+          <textarea
+            className="textfield-input"
+            rows={2}
+            value={headersJson}
+            onChange={(e) => setHeadersJson(e.target.value)}
+            placeholder={'{"CF-Access-Client-Id": "..."}'}
+          />
+        </label>
+        <label className="textfield fullWidth">
+          <span className="textfield-label">요청 options (JSON)</span>
+          <textarea
+            className="textfield-input"
+            rows={3}
+            value={optionsJson}
+            onChange={(e) => setOptionsJson(e.target.value)}
+            placeholder={'{"num_ctx": 4096, "num_predict": 512}'}
+          />
+        </label>
+      </div>
+      <p className="muted" style={{ marginTop: 12 }}>
+        비워 두면 기본 프롬프트를 사용합니다. {"{schema}"}는 응답 JSON 형식, {"{categories}"}는
+        카테고리 목록, {"{language}"}는 출력 언어로 바뀝니다. 결과를 읽으려면 두 메시지 중 하나에
+        {" {schema}"}가 있거나 category, scores, tags, caption, text_in_image 키가 모두 적혀 있어야
+        합니다.
+      </p>
+      <div className="grid" style={{ marginTop: 12 }}>
+        <label className="textfield fullWidth">
+          <span className="textfield-label">System 프롬프트</span>
+          <textarea
+            className="textfield-input"
+            rows={4}
+            value={systemPrompt}
+            onChange={(e) => setSystemPrompt(e.target.value)}
+            placeholder="You are a strict JSON generator..."
+          />
+        </label>
+        <label className="textfield fullWidth">
+          <span className="textfield-label">User 프롬프트</span>
+          <textarea
+            className="textfield-input"
+            rows={4}
+            value={userPrompt}
+            onChange={(e) => setUserPrompt(e.target.value)}
+            placeholder="Analyze the image and output JSON with EXACT keys: {schema}."
+          />
+        </label>
+      </div>
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onSave} loading={saving}>
+          Save
+        </PrimaryButton>
+        <PrimaryButton variant="secondary" onClick={onClearCaptures}>
+          응답 기록 삭제
+        </PrimaryButton>
+      </div>
+    </div>
+  );
+}
+
+export default OllamaAdvancedSettings;
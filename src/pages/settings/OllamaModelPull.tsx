@@ -0,0 +1,85 @@
+import { useEffect, useState } from "react";
+import TextField from "../../components/forms/TextField";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import ProgressBar from "../../components/status/ProgressBar";
+import { toast } from "../../components/toast/toastBus";
+import { cancelModelPull, onModelPull, pullOllamaModel } from "../../lib/api";
+import { ModelPullEvent } from "../../lib/api/types";
+
+interface OllamaModelPullProps {
+  baseUrl: string;
+  onPulled: (model: string) => void;
+}
+
+function formatBytes(bytes: number) {
+  if (bytes >= 1024 ** 3) return `${(bytes / 1024 ** 3).toFixed(1)} GB`;
+  return `${(bytes / 1024 ** 2).toFixed(0)} MB`;
+}
+
+function OllamaModelPull({ baseUrl, onPulled }: OllamaModelPullProps) {
+  const [name, setName] = useState("");
+  const [pulling, setPulling] = useState(false);
+  const [progress, setProgress] = useState<ModelPullEvent | null>(null);
+
+  useEffect(() => onModelPull(setProgress), []);
+
+  const onPull = async () => {
+    const model = name.trim();
+    if (!model) return;
+    setPulling(true);
+    setProgress(null);
+    try {
+      await pullOllamaModel(baseUrl, model);
+      toast.success(`${model} 다운로드 완료`);
+      onPulled(model);
+    } catch (e) {
+      if (!String(e).includes("canceled")) toast.error(`다운로드 실패: ${e}`);
+    } finally {
+      setPulling(false);
+    }
+  };
+
+  const total = progress?.total ?? 0;
+  const completed = progress?.completed ?? 0;
+  const status =
+    total > 0 ? `${formatBytes(completed)} / ${formatBytes(total)}` : progress?.status ?? "";
+
+  return (
+    <div className="section card" style={{ marginTop: 12 }}>
+      <div className="section-title">모델 다운로드</div>
+      <p className="muted" style={{ marginTop: 6 }}>
+        목록에 없는 모델은 이름을 입력해 Ollama로 바로 받을 수 있습니다.
+      </p>
+      <div className="grid two" style={{ marginTop: 12 }}>
+        <TextField
+          label="모델 이름"
+          value={name}
+          onChange={(e) => setName(e.target.value)}
+          placeholder="예: qwen2.5vl:7b"
+          disabled={pulling}
+          fullWidth
+        />
+      </div>
+      {pulling && progress && (
+        <div style={{ marginTop: 12 }}>
+          <ProgressBar value={total > 0 ? completed / total : 0} status={status} />
+          <p className="muted" style={{ marginTop: 6, fontSize: 12 }}>
+            {progress.status}
+          </p>
+        </div>
+      )}
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onPull} loading={pulling} disabled={!name.trim()}>
+          Pull
+        </PrimaryButton>
+        {pulling && (
+          <PrimaryButton variant="secondary" onClick={() => cancelModelPull()}>
+            취소
+          </PrimaryButton>
+        )}
+      </div>
+    </div>
+  );
+}
+
+export default OllamaModelPull;
@@ -2,6 +2,8 @@ import { useEffect, useState } from "react";
 import TextField from "../../components/forms/TextField";
 import PrimaryButton from "../../components/buttons/PrimaryButton";
 import { useAnalysis } from "../../features/analysis/store";
+import OllamaModelPull from "./OllamaModelPull";
+import { AnalysisImageFormat, OutputLanguage } from "../../lib/api/types";
 
 interface OllamaSettingsProps {
   disabled?: boolean;
@@ -10,9 +12,13 @@ interface OllamaSettingsProps {
     model: string;
     think: boolean;
     stream: boolean;
+    ollamaTimeoutSecs: number;
+    outputLanguage: OutputLanguage;
     resizeEnabled: boolean;
     maxEdge: number;
+    resizePercent: number;
     jpegQuality: number;
+    imageFormat: AnalysisImageFormat;
     concurrency: number;
   };
   onChange: (next: Partial<OllamaSettingsProps["draft"]>) => void;
@@ -22,7 +28,6 @@ interface OllamaSettingsProps {
 function OllamaSettings({ disabled = false, draft, onChange, onSave }: OllamaSettingsProps) {
   const { availableModels, testConnection } = useAnalysis();
   const [testing, setTesting] = useState(false);
-  const streamLockedByConcurrency = (draft.concurrency || 1) > 1;
 
   useEffect(() => {
     if (!availableModels.length) return;
@@ -31,10 +36,6 @@ function OllamaSettings({ disabled = false, draft, onChange, onSave }: OllamaSet
     }
   }, [availableModels, draft.model, onChange]);
 
-  useEffect(() => {
-    if (streamLockedByConcurrency && draft.stream) onChange({ stream: false });
-  }, [draft.stream, streamLockedByConcurrency, onChange]);
-
   return (
     <div
       className="section"
@@ -92,6 +93,14 @@ function OllamaSettings({ disabled = false, draft, onChange, onSave }: OllamaSet
         </PrimaryButton>
       </div>
 
+      <OllamaModelPull
+        baseUrl={draft.baseUrl}
+        onPulled={async (model) => {
+          await testConnection(draft.baseUrl);
+          onChange({ model });
+        }}
+      />
+
       <div className="section card" style={{ marginTop: 12 }}>
         <div className="section-title">Reasoning(Think)</div>
         <p className="muted" style={{ marginTop: 6 }}>
@@ -112,29 +121,70 @@ function OllamaSettings({ disabled = false, draft, onChange, onSave }: OllamaSet
         <p className="muted" style={{ marginTop: 6 }}>
           Ollama 응답을 스트리밍으로 받아 현재 생성 중인 내용을 실시간으로 표시합니다.
         </p>
-        {streamLockedByConcurrency && (
-          <p className="muted" style={{ marginTop: 6, fontSize: 12 }}>
-            병렬 처리(동시 처리 수 2 이상)에서는 출력이 섞이기 때문에 Stream은 자동으로 OFF로 고정됩니다.
-          </p>
-        )}
+        <p className="muted" style={{ marginTop: 6, fontSize: 12 }}>
+          동시 처리 수가 2 이상이면 작업마다 별도의 창에 표시됩니다.
+        </p>
         <div className="toggle-group" style={{ marginTop: 10 }}>
           <button
             className={!draft.stream ? "active" : ""}
             onClick={() => onChange({ stream: false })}
-            disabled={streamLockedByConcurrency}
           >
             OFF
           </button>
           <button
             className={draft.stream ? "active" : ""}
             onClick={() => onChange({ stream: true })}
-            disabled={streamLockedByConcurrency}
           >
             ON
           </button>
         </div>
       </div>
 
+      <div className="section card" style={{ marginTop: 12 }}>
+        <div className="section-title">출력 언어</div>
+        <p className="muted" style={{ marginTop: 6 }}>
+          태그, 설명, 이미지 속 텍스트를 작성할 언어입니다. 한국어는 다른 문자(한자 등)를 걸러냅니다.
+        </p>
+        <div className="toggle-group" style={{ marginTop: 10 }}>
+          {(
+            [
+              ["ko", "한국어"],
+              ["en", "English"],
+              ["ja", "日本語"],
+            ] as [OutputLanguage, string][]
+          ).map(([value, label]) => (
+            <button
+              key={value}
+              className={draft.outputLanguage === value ? "active" : ""}
+              onClick={() => onChange({ outputLanguage: value })}
+            >
+              {label}
+            </button>
+          ))}
+        </div>
+      </div>
+
+      <div className="section card" style={{ marginTop: 12 }}>
+        <div className="section-title">요청 타임아웃</div>
+        <p className="muted" style={{ marginTop: 6 }}>
+          Ollama가 응답하지 않으면 해당 사진을 오류로 처리하고 다음 사진으로 넘어갑니다. Stream
+          ON에서는 응답 조각 사이의 대기 시간에 적용됩니다.
+        </p>
+        <div className="grid two" style={{ marginTop: 12 }}>
+          <TextField
+            label="타임아웃(초)"
+            type="number"
+            inputMode="numeric"
+            value={String(draft.ollamaTimeoutSecs)}
+            min={0}
+            step={10}
+            onChange={(e) => onChange({ ollamaTimeoutSecs: Number(e.target.value) })}
+            helperText="0이면 제한 없음 (느린 모델은 값을 늘리세요)"
+            fullWidth
+          />
+        </div>
+      </div>
+
       <div className="section card" style={{ marginTop: 12 }}>
         <div className="section-title">분석 속도(이미지 최적화)</div>
         <p className="muted" style={{ marginTop: 6 }}>
@@ -166,6 +216,20 @@ function OllamaSettings({ disabled = false, draft, onChange, onSave }: OllamaSet
             onChange={(e) => onChange({ maxEdge: Number(e.target.value) })}
             helperText="예: 512~1024 권장 (작을수록 빠름)"
             fullWidth
+            disabled={!draft.resizeEnabled || draft.resizePercent > 0}
+          />
+          <TextField
+            label="축소 비율(%)"
+            type="number"
+            inputMode="numeric"
+            value={draft.resizePercent > 0 ? String(draft.resizePercent) : ""}
+            min={1}
+            max={100}
+            step={5}
+            onChange={(e) => onChange({ resizePercent: Number(e.target.value) || 0 })}
+            placeholder="비우면 최대 변 기준"
+            helperText="원본 크기와 상관없이 이 비율로 줄입니다 (예: 50). 확대는 하지 않습니다."
+            fullWidth
             disabled={!draft.resizeEnabled}
           />
           <TextField
@@ -179,8 +243,26 @@ function OllamaSettings({ disabled = false, draft, onChange, onSave }: OllamaSet
             onChange={(e) => onChange({ jpegQuality: Number(e.target.value) })}
             helperText="예: 50~70 권장 (낮을수록 빠름/작음)"
             fullWidth
-            disabled={!draft.resizeEnabled}
+            disabled={!draft.resizeEnabled || draft.imageFormat === "png"}
           />
+          <div className="textfield">
+            <label className="muted" htmlFor="ollama-image-format">
+              전송 이미지 형식
+            </label>
+            <select
+              id="ollama-image-format"
+              className="select"
+              value={draft.imageFormat}
+              onChange={(e) => onChange({ imageFormat: e.target.value as AnalysisImageFormat })}
+            >
+              <option value="auto">자동 (스크린샷은 PNG)</option>
+              <option value="jpeg">JPEG</option>
+              <option value="png">PNG (무손실)</option>
+            </select>
+            <div className="muted" style={{ marginTop: 6, fontSize: 12 }}>
+              PNG는 글자가 선명하지만 용량이 커서 전송/분석이 느려질 수 있습니다.
+            </div>
+          </div>
         </div>
       </div>
 
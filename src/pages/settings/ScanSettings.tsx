@@ -0,0 +1,104 @@
+import { useEffect, useState } from "react";
+import TextField from "../../components/forms/TextField";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { toast } from "../../components/toast/toastBus";
+import { getSettings, setSettings } from "../../lib/api";
+import { ScanSort } from "../../lib/api/types";
+
+// Scan options are engine-independent, so they load and save on their own instead of going
+// through the engine drafts.
+function ScanSettings() {
+  const [excludeGlobs, setExcludeGlobs] = useState("");
+  const [maxDepth, setMaxDepth] = useState("");
+  const [sort, setSort] = useState<ScanSort>("name_asc");
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    getSettings()
+      .then((s) => {
+        setExcludeGlobs((s.scanExcludeGlobs ?? []).join("\n"));
+        setMaxDepth(s.scanMaxDepth != null ? String(s.scanMaxDepth) : "");
+        setSort(s.scanSort ?? "name_asc");
+      })
+      .catch(() => {});
+  }, []);
+
+  const onSave = async () => {
+    const depth = Math.floor(Number(maxDepth));
+    setSaving(true);
+    try {
+      const current = await getSettings();
+      await setSettings({
+        ...current,
+        scanExcludeGlobs: excludeGlobs
+          .split("\n")
+          .map((s) => s.trim())
+          .filter(Boolean),
+        scanMaxDepth: maxDepth.trim() && Number.isFinite(depth) && depth > 0 ? depth : null,
+        scanSort: sort,
+      });
+      toast.success("저장되었습니다");
+    } catch (e) {
+      toast.error(`저장 실패: ${e}`);
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  return (
+    <div className="section card">
+      <div className="section-title">스캔 설정</div>
+      <p className="muted" style={{ marginTop: 6 }}>
+        제외할 폴더/파일 패턴을 한 줄에 하나씩 입력하세요 (예: @eaDir, .thumbnails, **/sorted/**).
+        소스 폴더 안에 있는 Export 폴더는 자동으로 제외됩니다.
+        소스 폴더 최상위에 .imgsortignore 파일을 두면 그 안의 패턴(.gitignore 형식)도 함께 적용됩니다.
+      </p>
+      <div className="grid" style={{ marginTop: 12 }}>
+        <label className="textfield fullWidth">
+          <span className="textfield-label">제외 패턴</span>
+          <textarea
+            className="textfield-input"
+            rows={4}
+            value={excludeGlobs}
+            onChange={(e) => setExcludeGlobs(e.target.value)}
+            placeholder="@eaDir"
+          />
+        </label>
+        <TextField
+          label="최대 탐색 깊이 (비우면 제한 없음)"
+          inputMode="numeric"
+          value={maxDepth}
+          onChange={(e) => setMaxDepth(e.target.value)}
+          placeholder="예: 3"
+        />
+        <div className="textfield">
+          <label className="muted" htmlFor="scan-sort">
+            처리 순서
+          </label>
+          <select
+            id="scan-sort"
+            className="select"
+            value={sort}
+            onChange={(e) => setSort(e.target.value as ScanSort)}
+          >
+            <option value="name_asc">파일 이름 오름차순</option>
+            <option value="name_desc">파일 이름 내림차순</option>
+            <option value="mtime_asc">수정 시각 (오래된 순)</option>
+            <option value="mtime_desc">수정 시각 (최신 순)</option>
+            <option value="none">정렬 안 함 (폴더 탐색 순서)</option>
+          </select>
+          <div className="muted" style={{ marginTop: 6, fontSize: 12 }}>
+            순서를 고정하면 다시 실행해도 중복 파일 이름(_1, _2)이 같게 붙습니다.
+          </div>
+        </div>
+      </div>
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onSave} loading={saving}>
+          Save
+        </PrimaryButton>
+      </div>
+    </div>
+  );
+}
+
+export default ScanSettings;
@@ -1,8 +1,9 @@
 import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { AnalysisEngine } from "../../lib/api/types";
 
 interface SettingsHomeProps {
-  activeEngine: "clip" | "ollama";
-  onSelectEngine: (engine: "clip" | "ollama") => void;
+  activeEngine: AnalysisEngine;
+  onSelectEngine: (engine: AnalysisEngine) => void;
   onOpenOllama: () => void;
   onOpenClip: () => void;
 }
@@ -15,6 +16,7 @@ function SettingsHome({
 }: SettingsHomeProps) {
   const isOllamaActive = activeEngine === "ollama";
   const isClipActive = activeEngine === "clip";
+  const isHybridActive = activeEngine === "hybrid";
 
   return (
     <div className="section">
@@ -35,7 +37,7 @@ function SettingsHome({
             Base URL / 모델 선택 / Think / Stream 등 Ollama 관련 설정을 관리합니다.
           </p>
           <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
-            <PrimaryButton onClick={onOpenOllama} disabled={!isOllamaActive}>
+            <PrimaryButton onClick={onOpenOllama} disabled={isClipActive}>
               열기
             </PrimaryButton>
           </div>
@@ -56,11 +58,28 @@ function SettingsHome({
             분류 엔진 / 모델 경로 / 가속(EP) / 병렬 처리 등 CLIP 관련 설정을 관리합니다.
           </p>
           <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
-            <PrimaryButton onClick={onOpenClip} disabled={!isClipActive}>
+            <PrimaryButton onClick={onOpenClip} disabled={isOllamaActive}>
               열기
             </PrimaryButton>
           </div>
         </div>
+        <div className="section card">
+          <div className="flex-between" style={{ gap: 12 }}>
+            <div className="section-title">하이브리드</div>
+            <div className="toggle-group">
+              <button className={isHybridActive ? "active" : ""} onClick={() => onSelectEngine("hybrid")}>
+                ON
+              </button>
+              <button className={!isHybridActive ? "active" : ""} onClick={() => onSelectEngine("clip")}>
+                OFF
+              </button>
+            </div>
+          </div>
+          <p className="muted" style={{ marginTop: 6 }}>
+            CLIP으로 빠르게 분류·내보내기한 뒤, Ollama로 캡션/태그/이미지 속 텍스트를 채웁니다. CLIP과
+            Ollama 설정을 모두 사용합니다.
+          </p>
+        </div>
       </div>
     </div>
   );
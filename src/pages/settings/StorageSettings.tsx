@@ -0,0 +1,54 @@
+import { useEffect, useState } from "react";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { toast } from "../../components/toast/toastBus";
+import { getSettings, setSettings } from "../../lib/api";
+
+// Switching takes effect immediately, so the backend refuses it while a job runs.
+function StorageSettings() {
+  const [ephemeral, setEphemeral] = useState(false);
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    getSettings()
+      .then((s) => setEphemeral(s.ephemeralDb ?? false))
+      .catch(() => {});
+  }, []);
+
+  const onSave = async () => {
+    setSaving(true);
+    try {
+      const current = await getSettings();
+      await setSettings({ ...current, ephemeralDb: ephemeral });
+      toast.success("저장되었습니다");
+    } catch (e) {
+      toast.error(`저장 실패: ${e}`);
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  return (
+    <div className="section card">
+      <div className="section-title">임시 결과 DB</div>
+      <p className="muted" style={{ marginTop: 6 }}>
+        ON이면 분석 결과를 메모리에만 기록해 기존 결과 DB를 건드리지 않습니다. 앱을 종료하거나 다시
+        OFF로 바꾸면 임시 결과는 사라집니다. 내보낸 파일과 썸네일은 그대로 남습니다.
+      </p>
+      <label className="muted" style={{ display: "flex", gap: 6, alignItems: "center", marginTop: 12 }}>
+        <input
+          type="checkbox"
+          checked={ephemeral}
+          onChange={(e) => setEphemeral(e.target.checked)}
+        />
+        임시 DB 사용
+      </label>
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onSave} loading={saving}>
+          Save
+        </PrimaryButton>
+      </div>
+    </div>
+  );
+}
+
+export default StorageSettings;
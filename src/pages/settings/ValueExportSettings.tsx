@@ -0,0 +1,63 @@
+import { useEffect, useState } from "react";
+import PrimaryButton from "../../components/buttons/PrimaryButton";
+import { toast } from "../../components/toast/toastBus";
+import { getSettings, setSettings } from "../../lib/api";
+
+// Export layout only matters once value detection is on; like the scan options it loads and
+// saves on its own.
+function ValueExportSettings() {
+  const [nested, setNested] = useState(true);
+  const [sidecar, setSidecar] = useState(false);
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    getSettings()
+      .then((s) => {
+        setNested(s.valueAsNestedFolders ?? true);
+        setSidecar(s.writeSidecar ?? false);
+      })
+      .catch(() => {});
+  }, []);
+
+  const onSave = async () => {
+    setSaving(true);
+    try {
+      const current = await getSettings();
+      await setSettings({ ...current, valueAsNestedFolders: nested, writeSidecar: sidecar });
+      toast.success("저장되었습니다");
+    } catch (e) {
+      toast.error(`저장 실패: ${e}`);
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  return (
+    <div className="section card">
+      <div className="section-title">가치 판별 폴더</div>
+      <p className="muted" style={{ marginTop: 6 }}>
+        ON이면 가치있음/가치없음 폴더 아래에 카테고리 폴더를 만듭니다. OFF이면 카테고리 폴더에 바로
+        내보내고 가치 판별 결과는 결과 DB에만 기록합니다.
+      </p>
+      <div className="toggle-group" style={{ marginTop: 10 }}>
+        <button className={nested ? "active" : ""} onClick={() => setNested(true)}>
+          ON
+        </button>
+        <button className={!nested ? "active" : ""} onClick={() => setNested(false)}>
+          OFF
+        </button>
+      </div>
+      <label className="muted" style={{ display: "flex", gap: 6, alignItems: "center", marginTop: 12 }}>
+        <input type="checkbox" checked={sidecar} onChange={(e) => setSidecar(e.target.checked)} />
+        내보낸 파일 옆에 분석 결과 JSON(사이드카) 저장
+      </label>
+      <div style={{ display: "flex", gap: 10, marginTop: 12 }}>
+        <PrimaryButton onClick={onSave} loading={saving}>
+          Save
+        </PrimaryButton>
+      </div>
+    </div>
+  );
+}
+
+export default ValueExportSettings;